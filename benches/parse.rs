@@ -0,0 +1,88 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+// fetch has no dependencies on the rest of the binary, so the bench builds it on its own
+#[allow(dead_code)]
+#[path = "../src/fetch.rs"]
+mod fetch;
+
+const SIZES: [usize; 3] = [10, 100, 1000];
+
+fn rss(entries: usize) -> String {
+    let items: String = (0..entries)
+        .map(|i| {
+            format!(
+                "<item><title>Article {i}</title><link>https://bench.invalid/{i}</link><guid>https://bench.invalid/{i}</guid><pubDate>Mon, 02 Feb 2026 10:00:00 GMT</pubDate><description>&lt;p&gt;{}&lt;/p&gt;</description></item>",
+                "lorem ipsum dolor sit amet ".repeat(20)
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><rss version="2.0"><channel><title>Bench</title><link>https://bench.invalid</link>{items}</channel></rss>"#
+    )
+}
+
+fn atom(entries: usize) -> String {
+    let items: String = (0..entries)
+        .map(|i| {
+            format!(
+                r#"<entry><title>Article {i}</title><link href="https://bench.invalid/{i}"/><id>https://bench.invalid/{i}</id><updated>2026-02-02T10:00:00Z</updated><content type="html">{}</content></entry>"#,
+                "lorem ipsum dolor sit amet ".repeat(20)
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><feed xmlns="http://www.w3.org/2005/Atom"><title>Bench</title><id>https://bench.invalid</id><updated>2026-02-02T10:00:00Z</updated>{items}</feed>"#
+    )
+}
+
+fn fetched(body: Vec<u8>, content_type: &str) -> fetch::Fetched {
+    fetch::Fetched {
+        url: "https://bench.invalid/feed.xml".to_string(),
+        status: 200,
+        headers: vec![],
+        content_type: Some(content_type.to_string()),
+        body: fetch::Body::Memory(body),
+    }
+}
+
+// parse goes through feed_content and fetch::parse like a refresh does, including the hop to the blocking pool
+fn parse(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("parse");
+    for (format, generate) in [("rss", rss as fn(usize) -> String), ("atom", atom)] {
+        for entries in SIZES {
+            let body = generate(entries).into_bytes();
+            group.throughput(Throughput::Bytes(body.len() as u64));
+            group.bench_with_input(BenchmarkId::new(format, entries), &body, |b, body| {
+                b.iter(|| {
+                    let content = fetched(body.clone(), "application/rss+xml")
+                        .feed_content()
+                        .unwrap();
+                    let url = "https://example.com/feed.xml".to_string();
+                    runtime.block_on(fetch::parse(content, url)).unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+// transcode covers feeds declared in another charset, they are decoded to utf-8 before parsing
+fn transcode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transcode");
+    for entries in SIZES {
+        let body = rss(entries).replace("utf-8", "iso-8859-1").into_bytes();
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        group.bench_with_input(BenchmarkId::new("latin1", entries), &body, |b, body| {
+            b.iter(|| {
+                fetched(body.clone(), "application/rss+xml; charset=iso-8859-1")
+                    .feed_content()
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, transcode);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+use std::env;
+use std::process::Command;
+
+// build.rs embeds the commit the binary was built from, GIT_SHA wins for builds without a checkout
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let sha = env::var("GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())
+        .or_else(git_sha)
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=FEEDREADER_GIT_SHA={}", sha);
+    println!(
+        "cargo:rustc-env=FEEDREADER_PROFILE={}",
+        env::var("PROFILE").unwrap_or_default()
+    );
+}
+
+fn git_sha() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let sha = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    match sha.is_empty() {
+        true => None,
+        false => Some(sha),
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use clap::Parser;
+use futures::{stream, StreamExt};
+use std::time::{Duration, Instant};
+use tokio_postgres::{Config, NoTls};
+
+// seeded articles share this prefix and feeds this site so --clean can remove them again
+const PREFIX: &str = "loadtest-";
+const SITE: &str = "https://loadtest.invalid/";
+
+/// Seeds feeds and articles into a feedreader database, then measures how long a running
+/// instance on that database takes to serve article lists
+#[derive(Parser)]
+struct Args {
+    /// Instance to request pages from, it has to use the seeded database
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    url: String,
+    #[arg(long, default_value_t = 50)]
+    feeds: i32,
+    /// Articles seeded per feed
+    #[arg(long, default_value_t = 200)]
+    articles: i32,
+    /// Requests sent to each path
+    #[arg(long, default_value_t = 200)]
+    requests: usize,
+    #[arg(long, default_value_t = 8)]
+    concurrency: usize,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "/,/favorites.html,/history.html,/articles/more?filter=unread,/search?q=loadtest"
+    )]
+    paths: Vec<String>,
+    /// Skip seeding and measure the data already there
+    #[arg(long)]
+    no_seed: bool,
+    /// Remove the seeded feeds and articles and exit
+    #[arg(long)]
+    clean: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let env = |key: &str, default: &str| std::env::var(key).unwrap_or_else(|_| default.to_string());
+    let (client, connection) = Config::new()
+        .user(env("POSTGRES_USERNAME", "feedreader").as_str())
+        .password(env("POSTGRES_PASSWORD", "feedreader").as_str())
+        .host(env("POSTGRES_HOST", "127.0.0.1").as_str())
+        .port(env("POSTGRES_PORT", "5432").parse()?)
+        .dbname("feedreader")
+        .connect(NoTls)
+        .await?;
+    tokio::spawn(connection);
+
+    if args.clean {
+        let pattern = format!("{}%", PREFIX);
+        let articles = client
+            .execute("DELETE FROM articles WHERE id LIKE $1", &[&pattern])
+            .await?;
+        let feeds = client
+            .execute(
+                "DELETE FROM feeds WHERE feed_url LIKE $1",
+                &[&format!("{}%", SITE)],
+            )
+            .await?;
+        println!("removed {} feeds and {} articles", feeds, articles);
+        return Ok(());
+    }
+
+    // the schema comes from the instance, run it or `feedreader migrate` against the database first
+    if !args.no_seed {
+        let started = Instant::now();
+        client
+            .execute(
+                "INSERT INTO feeds (id, name, site_url, feed_url, date_added, last_updated)
+                 SELECT md5($1 || f)::uuid::text, 'Load test ' || f, 'https://loadtest.invalid/' || f, 'https://loadtest.invalid/' || f || '/feed.xml', to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS.MS\"Z\"'), '-1'
+                 FROM generate_series(1, $2) f
+                 ON CONFLICT DO NOTHING",
+                &[&PREFIX, &args.feeds],
+            )
+            .await?;
+        let seeded = client
+            .execute(
+                "INSERT INTO articles (id, feed, title, link, author, published, read, favorited, read_date, feed_id, guid)
+                 SELECT $1 || f || '-' || a, 'Load test ' || f, 'Load test article ' || a, 'https://loadtest.invalid/' || f || '/' || a, '', p, a % 3 = 0, a % 10 = 0, CASE WHEN a % 3 = 0 THEN p ELSE '-1' END, md5($1 || f)::uuid::text, 'https://loadtest.invalid/' || f || '/' || a
+                 FROM generate_series(1, $2) f, generate_series(1, $3) a,
+                 LATERAL (SELECT to_char((now() - (a * 97 + f) * interval '1 minute') AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS.MS\"Z\"') AS p) published
+                 ON CONFLICT DO NOTHING",
+                &[&PREFIX, &args.feeds, &args.articles],
+            )
+            .await?;
+        println!(
+            "seeded {} feeds and {} articles in {:?}",
+            args.feeds,
+            seeded,
+            started.elapsed()
+        );
+    }
+
+    let http = reqwest::Client::new();
+    println!(
+        "{:<40} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "path", "errors", "p50", "p95", "p99", "max"
+    );
+    for path in args.paths.iter() {
+        let url = format!("{}{}", args.url.trim_end_matches('/'), path);
+        let results: Vec<Result<Duration>> = stream::iter(0..args.requests)
+            .map(|_| {
+                let (http, url) = (http.clone(), url.clone());
+                async move {
+                    let started = Instant::now();
+                    let response = http.get(url).send().await?.error_for_status()?;
+                    response.bytes().await?;
+                    Ok(started.elapsed())
+                }
+            })
+            .buffer_unordered(args.concurrency)
+            .collect()
+            .await;
+
+        let errors = results.iter().filter(|r| r.is_err()).count();
+        let mut latencies: Vec<Duration> = results.into_iter().filter_map(|r| r.ok()).collect();
+        latencies.sort();
+        let percentile = |p: usize| match latencies.is_empty() {
+            true => Duration::ZERO,
+            false => latencies[((latencies.len() - 1) * p) / 100],
+        };
+        println!(
+            "{:<40} {:>8} {:>10.1?} {:>10.1?} {:>10.1?} {:>10.1?}",
+            path,
+            errors,
+            percentile(50),
+            percentile(95),
+            percentile(99),
+            percentile(100)
+        );
+    }
+
+    Ok(())
+}
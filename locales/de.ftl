@@ -0,0 +1,258 @@
+language-name = Deutsch
+
+## Datumsangaben, beide Formate sind strftime-Muster
+date-format = %d.%m.%Y
+day-format = %d.%m.%Y
+date-just-now = gerade eben
+date-minutes-ago = vor { $count } Min.
+date-hours-ago = vor { $count } Std.
+date-days-ago = { $count ->
+    [one] vor einem Tag
+   *[other] vor { $count } Tagen
+}
+
+## Navigation
+nav-unread = Ungelesen
+nav-favorites = Favoriten
+nav-history = Verlauf
+nav-trash = Papierkorb
+nav-folders = Ordner
+nav-feeds = Feeds
+nav-add-feed = Feed hinzufügen
+nav-notifications = Benachrichtigungen
+nav-settings = Einstellungen
+search-placeholder = Suchen
+theme-label = Design
+theme-system = System
+theme-light = hell
+theme-dark = dunkel
+apply = Anwenden
+
+## Artikellisten
+page-unread = Ungelesen
+page-favorites = Favoriten
+page-history = Verlauf
+mark-read = als gelesen markieren
+mark-favorite = als Favorit markieren
+save-to-wallabag = in Wallabag speichern
+wallabag-saved = Gespeichert
+share = teilen
+share-button = Teilen
+share-post = Senden
+shared = Geteilt:
+read-on = Gelesen { $date }
+article-details = Details
+related-articles = Ähnliche Artikel
+no-related-articles = Kein anderer Artikel teilt Wörter mit diesem.
+related-favorited = Favorit
+published-estimated = Der Feed hat für diesen Artikel kein Datum angegeben, angezeigt wird der Abrufzeitpunkt
+ai-summary-title = Zusammenfassung vom eingerichteten Sprachmodell
+previous-page = vorherige Seite
+next-page = nächste Seite
+previous = Zurück
+next = Weiter
+loading-more = Weitere Artikel werden geladen
+no-more-articles = Keine weiteren Artikel
+empty-no-feeds = Du hast noch keine Feeds abonniert, füge einen hinzu, um mit dem Lesen zu beginnen.
+empty-no-articles = Es sind noch keine Artikel eingetroffen, sie erscheinen hier, sobald die Feeds aktualisiert wurden.
+empty-unread = Du bist auf dem neuesten Stand, es gibt nichts mehr zu lesen.
+empty-favorite = Noch keine Favoriten, markiere einen Artikel als Favorit, um ihn hier aufzubewahren.
+empty-read = Gelesene Artikel werden hier aufgelistet.
+empty-folder = Zurzeit passen keine Artikel zu diesem Ordner.
+empty-feed = Dieser Feed hat noch keine Artikel.
+add-first-feed = Füge deinen ersten Feed hinzu
+
+## Suche
+search-title = Suche
+search-titles-placeholder = Artikeltitel durchsuchen
+search-no-results = Keine Artikel gefunden für { $query }
+
+## Feeds
+feeds-title = Feeds
+delete-feed = Feed löschen
+refresh-feed = Artikel des Feeds aktualisieren
+fetched-through-socks = Über SOCKS5-Proxy abgerufen
+last-refresh-failed = Letzte Aktualisierung fehlgeschlagen: { $error }
+feed-article-count = { $count ->
+    [one] ein Artikel
+   *[other] { $count } Artikel
+}
+feed-unread-count = { $count } ungelesen
+last-fetched = Zuletzt abgerufen { $date }
+never-fetched = Noch nicht abgerufen
+fetch-latency = Abrufdauer
+no-fetches = Noch keine erfolgreichen Abrufe.
+samples = Messungen
+average-size = Durchschnittliche Größe
+recent-fetches = Letzte Abrufe
+fetched = Abgerufen
+duration = Dauer
+size = Größe
+error = Fehler
+bytes = { $count } Bytes
+feed-settings = Einstellungen
+feed-rules = Benachrichtigungsregeln
+no-feed-rules = Keine Regeln gelten nur für diesen Feed.
+feed-stages = Verarbeitung
+feed-stages-help = Schritte, die neue Einträge dieses Feeds vor dem Speichern durchlaufen, Bereinigung und Regeln laufen immer.
+stage-on = an
+stage-off = aus
+stage-turn-on = Einschalten
+stage-turn-off = Ausschalten
+manage-rules = Benachrichtigungsregeln verwalten
+
+## Feed hinzufügen
+add-feed-title = Feed hinzufügen
+feed-name = Name
+site-url = Website-URL
+feed-url = Feed-URL
+use-socks = Über SOCKS5-Proxy abrufen
+auto-read-days = Als gelesen markieren nach
+auto-read-days-help = Tage, nach denen ungelesene Artikel dieses Feeds als gelesen markiert werden, sie bleiben in der Liste der gelesenen. 0 markiert sie nie
+category = Kategorie
+add-feed-button = Feed hinzufügen
+invalid-feed-name-empty = Gib dem Feed einen Namen.
+invalid-feed-name-long = Der Name darf höchstens 200 Zeichen lang sein.
+invalid-url-empty = Gib die Adresse des Feeds ein.
+save-page-title = Seite speichern
+save-page-help = Behält eine Seite, die in keinem Feed steht, als ungelesenen Artikel unter Gespeicherte Seiten.
+page-url = Seiten-URL
+page-title = Titel
+page-title-help = Optional, ohne Angabe wird der Titel der Seite verwendet
+save-page-button = Seite speichern
+invalid-page-url-empty = Gib die Adresse der Seite ein.
+invalid-url = Gib eine vollständige http- oder https-Adresse ein.
+invalid-url-long = Die Adresse darf höchstens 2048 Zeichen lang sein.
+invalid-category-long = Die Kategorie darf höchstens 100 Zeichen lang sein.
+invalid-auto-read-days = Gib eine Anzahl Tage von 0 bis 3650 ein.
+
+## Seitenleiste
+sidebar-title = Feeds
+sidebar-folders = Ordner
+sidebar-uncategorized = Ohne Kategorie
+sidebar-toggle = { $category } ein- oder ausklappen
+
+## Intelligente Ordner
+folders-title = Intelligente Ordner
+no-folders = Noch keine intelligenten Ordner.
+delete-folder = Ordner löschen
+add-folder-title = Intelligenten Ordner hinzufügen
+folder-name = Name
+folder-expression = Filter
+folder-expression-help = Kombiniere unread, read, favorited, feed:"Name", category:"Name", title:~"Teil", author:~"Teil" und published > 7d oder published < 2024-01-01 mit AND, OR, NOT und Klammern. ~ trifft einen Teil des Textes.
+add-folder-button = Ordner hinzufügen
+folder-name-required = Ein Ordner braucht einen Namen.
+folder-expression-invalid = Der Filter konnte nicht gelesen werden: { $error }
+
+## Benachrichtigungen
+notifications-title = Benachrichtigungen
+add-rule-title = Benachrichtigungsregel hinzufügen
+rule-action = Aktion
+action-notify = Benachrichtigen
+action-read = Als gelesen markieren
+action-favorite = Favorisieren
+action-skip = Überspringen
+rule-action-help = Dienst, Ziel und Token gelten nur für Benachrichtigungsregeln
+rule-service = Dienst
+rule-target = Ziel
+rule-token = Token
+rule-feed = Feed
+any-feed = Beliebiger Feed
+rule-keyword = Stichwort im Titel
+add-rule-button = Regel hinzufügen
+no-rules = Noch keine Benachrichtigungsregeln.
+delete-rule = Regel löschen
+delete = Löschen
+rule-any-feed = beliebiger Feed
+rule-feed-id = Feed { $id }
+rule-any-title = beliebiger Titel
+rule-keyword-match = Titel mit „{ $keyword }“
+
+quiet-hours-title = Ruhezeiten
+quiet-hours-help = Artikel eines Feeds kommen während seiner Ruhezeiten weiter an, bleiben aber bis zu deren Ende aus der Liste der ungelesenen Artikel und den Benachrichtigungen heraus.
+no-quiet-hours = Noch keine Ruhezeiten.
+add-quiet-hours-title = Ruhezeiten hinzufügen
+mute-target = Feed oder Kategorie
+mute-feeds = Feeds
+mute-categories = Kategorien
+mute-days = Tage
+mute-days-daily = Jeden Tag
+mute-days-weekdays = Werktags
+mute-days-weekend = Wochenende
+mute-days-mon = Montag
+mute-days-tue = Dienstag
+mute-days-wed = Mittwoch
+mute-days-thu = Donnerstag
+mute-days-fri = Freitag
+mute-days-sat = Samstag
+mute-days-sun = Sonntag
+mute-starts = Von
+mute-ends = Bis
+mute-window-help = Ein Ende vor dem Beginn reicht in den nächsten Tag, dieselbe Uhrzeit für beide gilt für den ganzen Tag
+add-quiet-hours-button = Ruhezeiten hinzufügen
+delete-quiet-hours = Ruhezeiten löschen
+
+## Papierkorb
+trash-title = Papierkorb
+trash-help = Gelesene Artikel, die durch die Aufbewahrung entfernt wurden, bleiben hier { $days } Tage, bevor sie endgültig gelöscht werden.
+no-trash = Der Papierkorb ist leer.
+trash-shown = Die zuletzt entfernten von { $total } Artikeln werden angezeigt.
+trashed-on = entfernt { $date }
+restore = Wiederherstellen
+restore-article = Artikel zurücklegen
+
+## Einstellungen
+settings-title = Einstellungen
+settings-saved = Einstellungen gespeichert
+refresh-interval = Aktualisierungsintervall
+refresh-interval-help = Sekunden zwischen Aktualisierungen, ersetzt nach dem Speichern den konfigurierten Zeitplan
+retention = Aufbewahrung
+retention-help = Tage, die gelesene und nicht favorisierte Artikel aufbewahrt werden, bevor sie in den Papierkorb kommen, 0 behält sie für immer
+page-size = Seitengröße
+display-density = Darstellung
+display-density-help = Karten zeigen eine Zusammenfassung und ein Vorschaubild, kompakt zeigt jeden Artikel in einer Zeile
+density-cards = Karten
+density-compact = kompakt
+group-by-day = Artikel nach Tag gruppieren
+group-by-day-help = Trennt Artikellisten nach Veröffentlichungstag
+language = Sprache
+language-help = Übersetzungen liegen im Verzeichnis locales, fehlende Texte werden auf Englisch angezeigt
+custom-css = Eigenes CSS
+custom-css-help = Wird auf jeder Seite nach den eingebauten Styles geladen, damit lässt sich die Instanz anpassen
+save = Speichern
+
+## Einrichtung
+setup-title = Willkommen bei Feedreader
+setup-intro = Es ist noch nichts abonniert. Übernimm deine Feeds aus einem anderen Reader und wähle, wie oft sie aktualisiert werden, alles hier lässt sich später ändern.
+setup-opml = OPML importieren
+setup-opml-help = Die meisten Reader können ihre Abonnements als OPML-Datei exportieren, Ordner werden zu Kategorien
+setup-opml-invalid = Die Datei konnte nicht als OPML gelesen werden: { $error }
+setup-finish = Einrichtung abschließen
+setup-add-by-hand = Lieber einzelne Feeds hinzufügen?
+setup-skip = Einrichtung überspringen
+setup-done = Die Einrichtung ist abgeschlossen, { $count ->
+    [one] ein Feed wurde importiert.
+   *[other] { $count } Feeds wurden importiert.
+}
+setup-skipped = Übersprungen
+setup-next = Artikel erscheinen, sobald die Feeds aktualisiert wurden.
+setup-start-reading = Mit dem Lesen beginnen
+
+## Offline
+offline-title = Offline
+offline-body = Feedreader ist gerade nicht erreichbar. Bereits geöffnete Seiten sind weiterhin verfügbar, alles andere ist wieder da, sobald du online bist.
+try-again = Erneut versuchen
+
+## Fehler
+error-title = Fehler { $status }
+error-not-found = Unter dieser Adresse gibt es nichts, vielleicht wurde es verschoben oder gelöscht.
+error-method-not-allowed = Diese Adresse akzeptiert diese Art von Anfrage nicht.
+error-bad-request = Die Anfrage konnte nicht verstanden werden, prüfe das Formular und versuche es erneut.
+error-forbidden = Diese Anfrage ist von hier aus nicht erlaubt.
+error-unauthorized = Diese Adresse braucht ein gültiges Token.
+error-idempotency-in-flight = Dieselbe Anfrage wird noch bearbeitet.
+error-idempotency-reused = Dieser Idempotenzschlüssel wurde bereits für eine andere Anfrage verwendet.
+error-conflict = Das gibt es schon, es muss nichts hinzugefügt werden.
+error-stale = Das wurde inzwischen geändert, lade die Seite neu, um den aktuellen Stand zu sehen.
+error-internal = Bei uns ist etwas schiefgelaufen.
+back-home = Zurück zu den Artikeln
@@ -0,0 +1,261 @@
+# English is the fallback, a key missing from another language is taken from here
+language-name = English
+
+
+## dates, both formats are strftime patterns
+date-format = %m/%d/%Y
+day-format = %A, %B %-d, %Y
+date-just-now = just now
+date-minutes-ago = { $count }m ago
+date-hours-ago = { $count }h ago
+date-days-ago = { $count }d ago
+
+## navigation
+nav-unread = Unread
+nav-favorites = Favorites
+nav-history = History
+nav-trash = Trash
+nav-folders = Folders
+nav-feeds = Feeds
+nav-add-feed = Add Feed
+nav-notifications = Notifications
+nav-settings = Settings
+nav-epub = EPUB
+search-placeholder = Search
+theme-label = Theme
+theme-system = system
+theme-light = light
+theme-dark = dark
+apply = Apply
+
+## article lists
+page-unread = Unread
+page-favorites = Favorites
+page-history = History
+mark-read = mark read
+mark-favorite = mark favorite
+save-to-wallabag = save to wallabag
+wallabag = Wallabag
+wallabag-saved = Saved
+share = share
+share-button = Share
+share-post = Post
+shared = Shared:
+read-on = Read { $date }
+article-details = Details
+related-articles = Related
+no-related-articles = No other article shares words with this one.
+related-favorited = favorite
+published-estimated = The feed gave no date for this article, it is shown with the time it was fetched
+ai-summary-title = Summary written by the configured language model
+previous-page = previous page
+next-page = next page
+previous = Previous
+next = Next
+loading-more = Loading more articles
+no-more-articles = No more articles
+empty-no-feeds = You aren't subscribed to any feeds yet, add one to start reading.
+empty-no-articles = No articles have come in yet, they show up here once the feeds are refreshed.
+empty-unread = You're all caught up, there is nothing left to read.
+empty-favorite = Nothing is favorited yet, mark an article as favorite to keep it here.
+empty-read = Articles you read are listed here.
+empty-folder = No articles match this folder right now.
+empty-feed = This feed has no articles yet.
+add-first-feed = Add your first feed
+
+## search
+search-title = Search
+search-titles-placeholder = Search article titles
+search-no-results = No articles match { $query }
+
+## feeds
+feeds-title = Feeds
+delete-feed = delete feed
+refresh-feed = refresh feed articles
+fetched-through-socks = Fetched through SOCKS5 proxy
+last-refresh-failed = Last refresh failed: { $error }
+feed-article-count = { $count ->
+    [one] one article
+   *[other] { $count } articles
+}
+feed-unread-count = { $count } unread
+last-fetched = Last fetched { $date }
+never-fetched = Not fetched yet
+fetch-latency = Fetch latency
+no-fetches = No successful fetches recorded yet.
+samples = Samples
+average-size = Average size
+recent-fetches = Recent fetches
+fetched = Fetched
+duration = Duration
+size = Size
+error = Error
+milliseconds = { $count } ms
+bytes = { $count } bytes
+feed-settings = Settings
+feed-rules = Notification rules
+no-feed-rules = No rules apply only to this feed.
+feed-stages = Processing
+feed-stages-help = Steps new entries of this feed go through before they are stored, sanitizing and rules always run.
+stage-on = on
+stage-off = off
+stage-turn-on = Turn on
+stage-turn-off = Turn off
+manage-rules = Manage notification rules
+
+## add feed
+add-feed-title = Add feed
+feed-name = Name
+site-url = Site URL
+feed-url = Feed URL
+use-socks = Fetch through SOCKS5 proxy
+auto-read-days = Mark read after
+auto-read-days-help = Days after which unread articles of this feed are marked read, they stay in the read list. 0 never marks them
+category = Category
+add-feed-button = Add Feed
+invalid-feed-name-empty = Give the feed a name.
+invalid-feed-name-long = The name can be at most 200 characters.
+invalid-url-empty = Enter the address of the feed.
+save-page-title = Save a page
+save-page-help = Keeps a page that isn't in any feed as an unread article under Saved pages.
+page-url = Page URL
+page-title = Title
+page-title-help = Optional, the title of the page is used when left empty
+save-page-button = Save page
+invalid-page-url-empty = Enter the address of the page.
+invalid-url = Enter a full http or https address.
+invalid-url-long = The address can be at most 2048 characters.
+invalid-category-long = The category can be at most 100 characters.
+invalid-auto-read-days = Enter a number of days from 0 to 3650.
+
+## sidebar
+sidebar-title = Feeds
+sidebar-folders = Folders
+sidebar-uncategorized = Uncategorized
+sidebar-toggle = Collapse or expand { $category }
+
+## smart folders
+folders-title = Smart folders
+no-folders = No smart folders yet.
+delete-folder = delete folder
+add-folder-title = Add a smart folder
+folder-name = Name
+folder-expression = Filter
+folder-expression-help = Combine unread, read, favorited, feed:"name", category:"name", title:~"part", author:~"part" and published > 7d or published < 2024-01-01 with AND, OR, NOT and parentheses. ~ matches a part of the text.
+add-folder-button = Add folder
+folder-name-required = A folder needs a name.
+folder-expression-invalid = The filter could not be read: { $error }
+
+## notifications
+notifications-title = Notifications
+add-rule-title = Add notification rule
+rule-action = Action
+action-notify = Notify
+action-read = Mark read
+action-favorite = Favorite
+action-skip = Skip
+rule-action-help = Service, target and token only apply to notify rules
+rule-service = Service
+rule-target = Target
+rule-target-help = Topic or server URL for ntfy, Gotify and webhooks, chat id for Telegram, homeserver URL and room id for Matrix (https://matrix.example.org/!room:example.org), address for email
+rule-token = Token
+rule-feed = Feed
+any-feed = Any feed
+rule-keyword = Title keyword
+add-rule-button = Add Rule
+no-rules = No notification rules yet.
+delete-rule = delete rule
+delete = Delete
+rule-any-feed = any feed
+rule-feed-id = feed { $id }
+rule-any-title = any title
+rule-keyword-match = titles containing "{ $keyword }"
+
+quiet-hours-title = Quiet hours
+quiet-hours-help = Articles of a feed keep coming in during its quiet hours, but they stay out of the unread list and notifications until the quiet hours end.
+no-quiet-hours = No quiet hours yet.
+add-quiet-hours-title = Add quiet hours
+mute-target = Feed or category
+mute-feeds = Feeds
+mute-categories = Categories
+mute-days = Days
+mute-days-daily = Every day
+mute-days-weekdays = Weekdays
+mute-days-weekend = Weekend
+mute-days-mon = Monday
+mute-days-tue = Tuesday
+mute-days-wed = Wednesday
+mute-days-thu = Thursday
+mute-days-fri = Friday
+mute-days-sat = Saturday
+mute-days-sun = Sunday
+mute-starts = From
+mute-ends = Until
+mute-window-help = An end before the start runs into the next day, the same time for both mutes the whole day
+add-quiet-hours-button = Add Quiet Hours
+delete-quiet-hours = delete quiet hours
+
+## trash
+trash-title = Trash
+trash-help = Read articles removed by the retention are kept here for { $days } days before they are deleted for good.
+no-trash = The trash is empty.
+trash-shown = Showing the most recently removed of { $total } articles.
+trashed-on = removed { $date }
+restore = Restore
+restore-article = put the article back
+
+## settings
+settings-title = Settings
+settings-saved = Settings saved
+refresh-interval = Refresh interval
+refresh-interval-help = Seconds between refreshes, overrides the configured schedule once saved
+retention = Retention
+retention-help = Days to keep read articles that are not favorited before they go to the trash, 0 keeps them forever
+page-size = Page size
+display-density = Display density
+display-density-help = Cards show a summary and thumbnail, compact fits each article on one line
+density-cards = cards
+density-compact = compact
+group-by-day = Group articles by day
+group-by-day-help = Separates article lists by the day they were published
+language = Language
+language-help = Translations live in the locales directory, a missing message falls back to English
+custom-css = Custom CSS
+custom-css-help = Loaded after the built in styles on every page, use it to theme the instance
+save = Save
+
+## setup
+setup-title = Welcome to Feedreader
+setup-intro = Nothing is subscribed yet. Bring your feeds over from another reader and pick how often they are refreshed, everything here can be changed later.
+setup-opml = Import OPML
+setup-opml-help = Most readers can export their subscriptions as an OPML file, folders become categories
+setup-opml-invalid = The file couldn't be read as OPML: { $error }
+setup-finish = Finish setup
+setup-add-by-hand = Rather add feeds one at a time?
+setup-skip = Skip setup
+setup-done = Setup is done, { $count ->
+    [one] one feed was imported.
+   *[other] { $count } feeds were imported.
+}
+setup-skipped = Skipped
+setup-next = Articles show up once the feeds are refreshed.
+setup-start-reading = Start reading
+
+## offline
+offline-title = Offline
+offline-body = Feedreader can't be reached right now. Pages you opened before are still available, everything else comes back once you're online again.
+try-again = Try again
+
+## errors
+error-title = Error { $status }
+error-not-found = There is nothing at this address, it may have been moved or deleted.
+error-method-not-allowed = This address doesn't accept that kind of request.
+error-bad-request = The request couldn't be understood, check the form and try again.
+error-forbidden = This request isn't allowed from here.
+error-unauthorized = This address needs a valid token.
+error-idempotency-in-flight = The same request is still being handled.
+error-idempotency-reused = This idempotency key was already used for a different request.
+error-conflict = That already exists, there is nothing to add.
+error-stale = That changed in the meantime, reload to see the latest version.
+error-internal = Something went wrong on our side.
+back-home = Back to articles
@@ -0,0 +1,59 @@
+use rust_embed::{EmbeddedFile, RustEmbed};
+
+// Assets are the files under static/, compiled into release builds and read from disk in debug builds
+#[derive(RustEmbed)]
+#[folder = "static/"]
+struct Assets;
+
+pub struct Asset {
+    pub content_type: String,
+    pub body: Vec<u8>,
+    // requested under its current fingerprint, so it can be cached for good
+    pub immutable: bool,
+}
+
+// get serves files under their plain and their fingerprinted names
+pub fn get(path: &str) -> Option<Asset> {
+    if let Some(file) = Assets::get(path) {
+        return Some(asset(file, false));
+    }
+
+    let (stem, extension) = path.rsplit_once('.')?;
+    let (name, hash) = stem.rsplit_once('.')?;
+    let file = Assets::get(format!("{}.{}", name, extension).as_str())?;
+    let immutable = fingerprint(&file) == hash;
+    Some(asset(file, immutable))
+}
+
+fn asset(file: EmbeddedFile, immutable: bool) -> Asset {
+    Asset {
+        content_type: file.metadata.mimetype().to_string(),
+        body: file.data.into_owned(),
+        immutable,
+    }
+}
+
+// fingerprint is the start of the file's sha256, a changed file gets a new url
+fn fingerprint(file: &EmbeddedFile) -> String {
+    file.metadata.sha256_hash()[..4]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// fingerprinted puts the fingerprint before the extension, style.css becomes style.1a2b3c4d.css
+pub fn fingerprinted(name: &str) -> String {
+    let file = match Assets::get(name) {
+        Some(file) => file,
+        None => return name.to_string(),
+    };
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}.{}.{}", stem, fingerprint(&file), extension),
+        None => name.to_string(),
+    }
+}
+
+// names lists every embedded file by its fingerprinted name, the service worker precaches them
+pub fn names() -> Vec<String> {
+    Assets::iter().map(|name| fingerprinted(&name)).collect()
+}
@@ -0,0 +1,21 @@
+use rweb::http::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use rweb::reply::Response;
+
+// apply keeps html out of every cache, pages and fragments carry unread counts that go stale
+// responses that set their own policy, like fingerprinted assets, are left alone
+pub fn apply(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    if headers.contains_key(CACHE_CONTROL) {
+        return response;
+    }
+
+    let is_html = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with("text/html"))
+        .unwrap_or(false);
+    if is_html {
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+    response
+}
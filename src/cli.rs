@@ -0,0 +1,170 @@
+use crate::models::{AddFeed, Feed};
+use crate::{db, fetch, import};
+use anyhow::Result;
+use chrono::{Duration, SecondsFormat, Utc};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(
+    name = "feedreader",
+    version = concat!(env!("CARGO_PKG_VERSION"), " (", env!("FEEDREADER_GIT_SHA"), ")"),
+    about = "A self hosted feed reader"
+)]
+pub struct Cli {
+    /// Config file, FEEDREADER_CONFIG or ./feedreader.toml are read otherwise
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Subscribe to a few well known feeds and fetch their articles before serving, to try the reader out
+    #[arg(long)]
+    pub seed_demo: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the web server and the refresh schedule, the default when no command is given
+    Serve,
+    /// Subscribe to a feed, the name and site url are read from the feed unless given
+    AddFeed {
+        url: String,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        site_url: Option<String>,
+        /// Fetch the feed through FEED_SOCKS_PROXY
+        #[arg(long)]
+        socks: bool,
+        /// List the feed under this category in the sidebar
+        #[arg(long, default_value = "")]
+        category: String,
+    },
+    /// Subscribe to every feed listed in an OPML file, folders become categories
+    ImportOpml { file: PathBuf },
+    /// Favorite the starred items of a Google Reader Takeout or The Old Reader export, feeds that are not subscribed are not added
+    ImportStarred { file: PathBuf },
+    /// Move read articles that are not favorited to the trash once they are older than the given number of days, they are deleted a week later
+    Prune {
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u16).range(1..))]
+        days: u16,
+    },
+    /// Create or upgrade the database schema and exit
+    Migrate,
+}
+
+// run handles every command but serve, the schema has already been migrated by the time it is called
+pub async fn run(command: Command, store: &db::Storage, fetcher: &fetch::Fetcher) -> Result<()> {
+    match command {
+        Command::Serve => Ok(()),
+        Command::AddFeed {
+            url,
+            name,
+            site_url,
+            socks,
+            category,
+        } => {
+            let feed = add_feed(store, fetcher, url, name, site_url, socks, category).await?;
+            println!("added {} ({})", feed.name, feed.feed_url);
+            Ok(())
+        }
+        Command::ImportOpml { file } => import_opml(store, file).await,
+        Command::ImportStarred { file } => import_starred(store, file).await,
+        Command::Prune { days } => {
+            let before = (Utc::now() - Duration::days(days.into()))
+                .to_rfc3339_opts(SecondsFormat::Millis, true);
+            let trashed = store.prune_articles(before).await?;
+            println!("moved {} articles to the trash", trashed);
+            Ok(())
+        }
+        Command::Migrate => {
+            println!("database schema is up to date");
+            Ok(())
+        }
+    }
+}
+
+async fn add_feed(
+    store: &db::Storage,
+    fetcher: &fetch::Fetcher,
+    url: String,
+    name: Option<String>,
+    site_url: Option<String>,
+    use_socks: bool,
+    category: String,
+) -> Result<Feed> {
+    let (name, site_url) = match (name, site_url) {
+        (Some(name), Some(site_url)) => (name, site_url),
+        (name, site_url) => {
+            let fetched = fetcher.get(url.as_str(), use_socks).await?;
+            let fetched_url = fetched.url.clone();
+            let parsed_feed = fetch::parse(fetched.feed_content()?, fetched_url).await?;
+            let title = parsed_feed.title.map(|t| t.content);
+            let link = parsed_feed
+                .links
+                .into_iter()
+                .find(|l| l.rel.as_deref() != Some("self"))
+                .map(|l| l.href);
+            (
+                name.or(title).unwrap_or_else(|| url.clone()),
+                site_url.or(link).unwrap_or_default(),
+            )
+        }
+    };
+
+    let mut feed = AddFeed {
+        feed_name: name,
+        site_url,
+        feed_url: url,
+        use_socks: use_socks.then(|| "on".to_string()),
+        category,
+    };
+    import::invalid(feed.validate())?;
+    store.add_feed(feed).await
+}
+
+// import_opml lists the feeds that were skipped, an import is not undone by one that fails
+async fn import_opml(store: &db::Storage, file: PathBuf) -> Result<()> {
+    let imported = import::opml(store, std::fs::read_to_string(file)?.as_str()).await?;
+    for (feed_url, e) in imported.skipped {
+        println!("skipped {}: {}", feed_url, e);
+    }
+    println!("imported {} feeds", imported.added);
+    Ok(())
+}
+
+async fn import_starred(store: &db::Storage, file: PathBuf) -> Result<()> {
+    let imported = import::starred(store, std::fs::read_to_string(file)?.as_str()).await?;
+    for (id, e) in imported.skipped {
+        println!("skipped {}: {}", id, e);
+    }
+    println!("imported {} starred items", imported.added);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_and_prune_days_are_parsed() {
+        for args in [
+            ["feedreader", "--config=/etc/feedreader.toml", "migrate"],
+            ["feedreader", "migrate", "--config=/etc/feedreader.toml"],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert_eq!(cli.config, Some(PathBuf::from("/etc/feedreader.toml")));
+        }
+        let days = |days: &str| match Cli::try_parse_from(["feedreader", "prune", "--days", days]) {
+            Ok(Cli {
+                command: Some(Command::Prune { days }),
+                ..
+            }) => Some(days),
+            _ => None,
+        };
+        assert_eq!(days("7"), Some(7));
+        assert_eq!(days("-1"), None);
+        assert_eq!(days("0"), None);
+        assert_eq!(days("9999999"), None);
+    }
+}
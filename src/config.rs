@@ -0,0 +1,591 @@
+use crate::integrations::{bluesky, bookmarks, mastodon, pocket, readwise, summarizer, wallabag};
+use crate::{dates, mail, mqtt, scheduler, security};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+// path used when neither --config nor FEEDREADER_CONFIG is given, it is fine for it not to exist
+pub const DEFAULT_PATH: &str = "feedreader.toml";
+
+const DEFAULT_ADDRESS: &str = "0.0.0.0:8080";
+const DEFAULT_POSTGRES_HOST: &str = "0.0.0.0";
+const DEFAULT_POSTGRES_PORT: u16 = 5432;
+const DEFAULT_REFRESH_SECONDS: u64 = 3 * 60;
+const DEFAULT_FETCH_RETRIES: u32 = 2;
+const DEFAULT_SMTP_PORT: u16 = 587;
+const DEFAULT_DIGEST_CRON: &str = "0 7 * * *";
+const DEFAULT_KINDLE_CRON: &str = "0 6 * * 6";
+const DEFAULT_POCKET_SYNC_SECONDS: u64 = 5 * 60;
+const DEFAULT_READWISE_SYNC_SECONDS: u64 = 5 * 60;
+const DEFAULT_SHUTDOWN_GRACE_SECONDS: u64 = 30;
+const DEFAULT_MQTT_PORT: u16 = 1883;
+const DEFAULT_MQTT_TOPIC: &str = "feedreader";
+
+// Config resolves settings from the environment first and the config file second
+// each env var maps to a key in the table named by its first segment, e.g. FEED_REFRESH_SECONDS is refresh_seconds under [feed]
+#[derive(Clone, Default)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    // load reads the file given with --config, FEEDREADER_CONFIG or the default path
+    pub fn load(flag: Option<&Path>) -> Result<Config> {
+        let flag = flag.map(|path| path.display().to_string());
+        let path = match flag.or(env::var("FEEDREADER_CONFIG").ok()) {
+            Some(path) => path,
+            None if Path::new(DEFAULT_PATH).exists() => DEFAULT_PATH.to_string(),
+            None => return Ok(Config::default()),
+        };
+
+        let content = std::fs::read_to_string(path.as_str())
+            .map_err(|e| anyhow::Error::msg(format!("could not read config {}: {}", path, e)))?;
+        Config::parse(content.as_str())
+            .map_err(|e| anyhow::Error::msg(format!("could not parse config {}: {}", path, e)))
+    }
+
+    pub fn parse(content: &str) -> Result<Config> {
+        let table: toml::value::Table = toml::from_str(content)?;
+        let mut values = HashMap::new();
+        for (section, value) in table {
+            let keys = match value {
+                toml::Value::Table(keys) => keys,
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "{} should be a table, e.g. [{}]",
+                        section, section
+                    )))
+                }
+            };
+
+            for (key, value) in keys {
+                let name = format!("{}_{}", section, key).to_uppercase();
+                let value = match value {
+                    toml::Value::String(s) => s,
+                    toml::Value::Array(items) => items
+                        .iter()
+                        .map(|item| match item {
+                            toml::Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        })
+                        .collect::<Vec<String>>()
+                        .join(","),
+                    other => other.to_string(),
+                };
+                values.insert(name, value);
+            }
+        }
+        Ok(Config { values })
+    }
+
+    // var looks up NAME and then NAME_FILE, in the environment before the config file
+    // NAME_FILE holds a path whose contents are the value, the way docker and kubernetes mount secrets
+    pub fn var(&self, name: &str) -> Result<Option<String>> {
+        if let Some(value) = self.lookup(name)? {
+            return Ok(Some(value));
+        }
+
+        let file = format!("{}_FILE", name);
+        match self.lookup(file.as_str())? {
+            Some(path) => match std::fs::read_to_string(path.as_str()) {
+                Ok(value) => Ok(Some(value.trim_end_matches(['\r', '\n']).to_string())),
+                Err(e) => Err(anyhow::Error::msg(format!(
+                    "{} could not read {}: {}",
+                    file, path, e
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Option<String>> {
+        match env::var(name) {
+            Ok(value) => Ok(Some(value)),
+            Err(env::VarError::NotPresent) => Ok(self.values.get(name).cloned()),
+            Err(e) => Err(anyhow::Error::msg(format!("{}: {}", name, e))),
+        }
+    }
+}
+
+pub struct Postgres {
+    pub username: String,
+    pub password: String,
+    pub host: String,
+    pub port: u16,
+}
+
+pub struct Fetch {
+    pub proxy: Option<String>,
+    pub socks_proxy: Option<String>,
+    pub allowed_hosts: Option<String>,
+    pub retries: u32,
+}
+
+pub struct Mailing {
+    pub to: String,
+    pub schedule: Option<scheduler::Schedule>,
+}
+
+// Shutdown is how long to keep serving after a shutdown signal, then how long in-flight work gets to finish
+#[derive(Clone, Copy)]
+pub struct Shutdown {
+    pub delay_seconds: u64,
+    pub grace_seconds: u64,
+}
+
+// Settings is every startup setting, validated up front so a bad deployment fails with all of its problems at once
+pub struct Settings {
+    pub address: SocketAddr,
+    pub base_path: String,
+    // scheme and host the reader is reached at, absolute urls are built from the request headers without it
+    pub public_url: Option<String>,
+    pub shutdown: Shutdown,
+    pub dates: dates::Format,
+    pub postgres: Postgres,
+    pub fetch: Fetch,
+    pub schedule: scheduler::Schedule,
+    pub refresh_on_startup: bool,
+    pub smtp: Option<mail::Config>,
+    pub digest: Option<Mailing>,
+    pub kindle: Option<Mailing>,
+    pub mqtt: Option<mqtt::Config>,
+    pub mqtt_topic: String,
+    pub wallabag: Option<wallabag::Config>,
+    pub pocket: Option<pocket::Config>,
+    pub pocket_sync_seconds: u64,
+    pub bookmarks: Option<bookmarks::Config>,
+    pub readwise: Option<readwise::Config>,
+    pub readwise_sync_seconds: u64,
+    pub mastodon: Option<mastodon::Config>,
+    pub bluesky: Option<bluesky::Config>,
+    pub summarizer: Option<summarizer::Config>,
+    pub trigger_token: Option<String>,
+    pub extension_token: Option<String>,
+    pub mailgun_signing_key: Option<String>,
+    pub image_proxy_secret: Option<String>,
+    pub security: security::Headers,
+    pub sentry: Option<sentry::types::Dsn>,
+}
+
+impl Settings {
+    // load returns every problem found rather than stopping at the first, config is the path given with --config
+    pub fn load(config: Option<&Path>) -> Result<Settings, Vec<String>> {
+        let config = Config::load(config).map_err(|e| vec![e.to_string()])?;
+        Settings::from_config(&config)
+    }
+
+    // from_config validates settings resolved from config, a program embedding the reader can pass Config::parse of its own
+    pub fn from_config(config: &Config) -> Result<Settings, Vec<String>> {
+        let mut c = Checker {
+            config,
+            errors: vec![],
+        };
+
+        let address = c
+            .parse("SERVER_ADDRESS")
+            .unwrap_or_else(|| DEFAULT_ADDRESS.parse().unwrap());
+
+        // stored as /prefix without a trailing slash, or empty when served from the root
+        let base_path = match c.optional("BASE_PATH") {
+            Some(path) => match path.trim_matches('/') {
+                "" => "".to_string(),
+                trimmed => format!("/{}", trimmed),
+            },
+            None => "".to_string(),
+        };
+        if base_path.contains(['?', '#', ' ']) {
+            c.errors.push(format!(
+                "BASE_PATH has a bad value {:?}: it should be a plain path like /feedreader",
+                base_path
+            ));
+        }
+
+        // kept as the origin alone, the prefix is BASE_PATH
+        let public_url = c.optional("SERVER_PUBLIC_URL").and_then(|raw| {
+            match reqwest::Url::parse(raw.as_str()) {
+                Ok(url)
+                    if matches!(url.scheme(), "http" | "https")
+                        && url.has_host()
+                        && url.path() == "/" =>
+                {
+                    Some(url.origin().ascii_serialization())
+                }
+                _ => {
+                    c.errors.push(format!(
+                        "SERVER_PUBLIC_URL has a bad value {:?}: it should be a scheme and host like https://reader.example.com",
+                        raw
+                    ));
+                    None
+                }
+            }
+        });
+
+        let timezone = c.parse("DATE_TIMEZONE").unwrap_or(chrono_tz::UTC);
+        let format = c.optional("DATE_FORMAT");
+        let dates = match dates::Format::new(timezone, format.as_deref()) {
+            Ok(dates) => dates,
+            Err(e) => {
+                c.errors.push(format!(
+                    "DATE_FORMAT has a bad value {:?}: {}",
+                    format.unwrap_or_default(),
+                    e
+                ));
+                dates::Format::default()
+            }
+        };
+
+        let shutdown = Shutdown {
+            delay_seconds: c.parse("SHUTDOWN_DELAY_SECONDS").unwrap_or(0),
+            grace_seconds: c
+                .parse("SHUTDOWN_GRACE_SECONDS")
+                .unwrap_or(DEFAULT_SHUTDOWN_GRACE_SECONDS),
+        };
+
+        let postgres = Postgres {
+            username: c.required("POSTGRES_USERNAME", ""),
+            password: c.required("POSTGRES_PASSWORD", ""),
+            host: c
+                .optional("POSTGRES_HOST")
+                .unwrap_or(DEFAULT_POSTGRES_HOST.to_string()),
+            port: c.parse("POSTGRES_PORT").unwrap_or(DEFAULT_POSTGRES_PORT),
+        };
+
+        let fetch = Fetch {
+            proxy: c.proxy("FEED_PROXY"),
+            socks_proxy: c.proxy("FEED_SOCKS_PROXY"),
+            allowed_hosts: c.optional("FEED_ALLOWED_HOSTS"),
+            retries: c
+                .parse("FEED_FETCH_RETRIES")
+                .unwrap_or(DEFAULT_FETCH_RETRIES),
+        };
+
+        let refresh_seconds = c
+            .interval("FEED_REFRESH_SECONDS")
+            .unwrap_or(DEFAULT_REFRESH_SECONDS);
+        let schedule = c
+            .parse("FEED_REFRESH_CRON")
+            .unwrap_or(scheduler::Schedule::Interval(refresh_seconds));
+        let refresh_on_startup = c.parse("FEED_REFRESH_ON_STARTUP").unwrap_or(true);
+
+        let smtp = c.optional("SMTP_HOST").map(|host| mail::Config {
+            host,
+            port: c.parse("SMTP_PORT").unwrap_or(DEFAULT_SMTP_PORT),
+            username: c.optional("SMTP_USERNAME"),
+            password: c.optional("SMTP_PASSWORD"),
+            from: c.mailbox("SMTP_FROM"),
+        });
+
+        let digest = c.optional("DIGEST_TO").map(|to| Mailing {
+            to,
+            schedule: Some(
+                c.parse("DIGEST_CRON")
+                    .unwrap_or_else(|| DEFAULT_DIGEST_CRON.parse().unwrap()),
+            ),
+        });
+
+        // the scheduled kindle job only runs when KINDLE_CRON is set, sending on demand works regardless
+        let kindle = c.optional("KINDLE_TO").map(|to| Mailing {
+            to,
+            schedule: match c.optional("KINDLE_CRON") {
+                Some(cron) if cron.is_empty() => Some(DEFAULT_KINDLE_CRON.parse().unwrap()),
+                Some(_) => c.parse("KINDLE_CRON"),
+                None => None,
+            },
+        });
+
+        if smtp.is_none() {
+            for name in ["DIGEST_TO", "KINDLE_TO"] {
+                if c.optional(name).is_some() {
+                    c.errors.push(format!(
+                        "{} is set but SMTP_HOST is not, mail can not be sent",
+                        name
+                    ));
+                }
+            }
+        }
+
+        let mqtt = c.optional("MQTT_HOST").map(|host| mqtt::Config {
+            host,
+            port: c.parse("MQTT_PORT").unwrap_or(DEFAULT_MQTT_PORT),
+            username: c.optional("MQTT_USERNAME"),
+            password: c.optional("MQTT_PASSWORD"),
+            client_id: c
+                .optional("MQTT_CLIENT_ID")
+                .unwrap_or(DEFAULT_MQTT_TOPIC.to_string()),
+        });
+        if mqtt
+            .as_ref()
+            .is_some_and(|mqtt| mqtt.password.is_some() && mqtt.username.is_none())
+        {
+            c.errors.push(
+                "MQTT_PASSWORD is set but MQTT_USERNAME is not, mqtt only sends a password with a user name"
+                    .to_string(),
+            );
+        }
+        let mqtt_topic = c
+            .optional("MQTT_TOPIC")
+            .unwrap_or(DEFAULT_MQTT_TOPIC.to_string());
+
+        let wallabag = c.optional("WALLABAG_URL").map(|url| wallabag::Config {
+            url,
+            client_id: c.required("WALLABAG_CLIENT_ID", " when WALLABAG_URL is set"),
+            client_secret: c.required("WALLABAG_CLIENT_SECRET", " when WALLABAG_URL is set"),
+            username: c.required("WALLABAG_USERNAME", " when WALLABAG_URL is set"),
+            password: c.required("WALLABAG_PASSWORD", " when WALLABAG_URL is set"),
+        });
+
+        let pocket = c
+            .optional("POCKET_CONSUMER_KEY")
+            .map(|consumer_key| pocket::Config {
+                consumer_key,
+                access_token: c.required("POCKET_ACCESS_TOKEN", " when POCKET_CONSUMER_KEY is set"),
+            });
+        let pocket_sync_seconds = c
+            .interval("POCKET_SYNC_SECONDS")
+            .unwrap_or(DEFAULT_POCKET_SYNC_SECONDS);
+
+        let bookmarks = c
+            .parse::<bookmarks::Service>("BOOKMARKS_SERVICE")
+            .map(|service| bookmarks::Config {
+                service,
+                url: c.optional("BOOKMARKS_URL"),
+                token: c.required("BOOKMARKS_TOKEN", " when BOOKMARKS_SERVICE is set"),
+            });
+
+        let readwise = c
+            .optional("READWISE_TOKEN")
+            .map(|token| readwise::Config { token });
+        let readwise_sync_seconds = c
+            .interval("READWISE_SYNC_SECONDS")
+            .unwrap_or(DEFAULT_READWISE_SYNC_SECONDS);
+
+        let mastodon = c.optional("MASTODON_URL").map(|url| mastodon::Config {
+            url,
+            token: c.required("MASTODON_TOKEN", " when MASTODON_URL is set"),
+        });
+
+        let bluesky = c.optional("BLUESKY_HANDLE").map(|handle| bluesky::Config {
+            url: c
+                .optional("BLUESKY_URL")
+                .unwrap_or(bluesky::DEFAULT_URL.to_string()),
+            handle,
+            app_password: c.required("BLUESKY_APP_PASSWORD", " when BLUESKY_HANDLE is set"),
+        });
+
+        // nothing is sent anywhere until SUMMARIZER_URL is set and a feed turns the stage on
+        let summarizer = c.optional("SUMMARIZER_URL").map(|url| summarizer::Config {
+            url,
+            model: c.required("SUMMARIZER_MODEL", " when SUMMARIZER_URL is set"),
+            token: c.optional("SUMMARIZER_TOKEN"),
+        });
+
+        let trigger_token = c.optional("TRIGGER_TOKEN");
+        let extension_token = c.optional("EXTENSION_TOKEN");
+        let mailgun_signing_key = c.optional("MAILGUN_SIGNING_KEY");
+        let image_proxy_secret = c.optional("IMAGE_PROXY_SECRET");
+
+        let image_sources = c.optional("CSP_IMAGE_SOURCES").unwrap_or_default();
+        let static_sources = c
+            .optional("CSP_STATIC_SOURCES")
+            .unwrap_or(security::DEFAULT_STATIC_SOURCES.to_string());
+        let security = security::Headers::new(image_sources.as_str(), static_sources.as_str())
+            .map_err(|e| {
+                c.errors.push(format!(
+                    "CSP_IMAGE_SOURCES or CSP_STATIC_SOURCES has a bad value: {}",
+                    e
+                ))
+            })
+            .ok();
+        let sentry = c.parse("SENTRY_DSN");
+
+        if !c.errors.is_empty() {
+            return Err(c.errors);
+        }
+
+        Ok(Settings {
+            address,
+            base_path,
+            public_url,
+            shutdown,
+            dates,
+            postgres,
+            fetch,
+            schedule,
+            refresh_on_startup,
+            smtp,
+            digest,
+            kindle,
+            mqtt,
+            mqtt_topic,
+            wallabag,
+            pocket,
+            pocket_sync_seconds,
+            bookmarks,
+            readwise,
+            readwise_sync_seconds,
+            mastodon,
+            bluesky,
+            summarizer,
+            trigger_token,
+            extension_token,
+            mailgun_signing_key,
+            image_proxy_secret,
+            // only None when an error was recorded above
+            security: security.unwrap(),
+            sentry,
+        })
+    }
+}
+
+// Checker reads settings while collecting every missing or malformed value
+struct Checker<'a> {
+    config: &'a Config,
+    errors: Vec<String>,
+}
+
+impl<'a> Checker<'a> {
+    fn optional(&mut self, name: &str) -> Option<String> {
+        match self.config.var(name) {
+            Ok(value) => value,
+            Err(e) => {
+                self.errors.push(e.to_string());
+                None
+            }
+        }
+    }
+
+    fn required(&mut self, name: &str, when: &str) -> String {
+        let errors = self.errors.len();
+        match self.optional(name) {
+            Some(value) => value,
+            // an unreadable value has already been reported
+            None if self.errors.len() > errors => "".to_string(),
+            None => {
+                self.errors.push(format!("{} is required{}", name, when));
+                "".to_string()
+            }
+        }
+    }
+
+    fn parse<T>(&mut self, name: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.optional(name)?;
+        match value.parse() {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                self.errors
+                    .push(format!("{} has a bad value {:?}: {}", name, value, e));
+                None
+            }
+        }
+    }
+
+    // interval is a number of seconds between runs, 0 is refused since a schedule can't tick without a pause
+    fn interval(&mut self, name: &str) -> Option<u64> {
+        match self.parse(name)? {
+            0 => {
+                self.errors.push(format!(
+                    "{} has a bad value \"0\": it should be 1 or more",
+                    name
+                ));
+                None
+            }
+            seconds => Some(seconds),
+        }
+    }
+
+    fn proxy(&mut self, name: &str) -> Option<String> {
+        let url = self.optional(name)?;
+        if let Err(e) = reqwest::Proxy::all(url.as_str()) {
+            self.errors
+                .push(format!("{} has a bad proxy url {:?}: {}", name, url, e));
+        }
+        Some(url)
+    }
+
+    fn mailbox(&mut self, name: &str) -> String {
+        let address = self.required(name, " when SMTP_HOST is set");
+        if !address.is_empty() {
+            if let Err(e) = address.parse::<lettre::message::Mailbox>() {
+                self.errors
+                    .push(format!("{} has a bad address {:?}: {}", name, address, e));
+            }
+        }
+        address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(toml: &str) -> Vec<String> {
+        let config = Config::parse(toml).unwrap();
+        match Settings::from_config(&config) {
+            Ok(_) => vec![],
+            Err(errors) => errors,
+        }
+    }
+
+    #[test]
+    fn public_url_is_an_origin() {
+        let postgres = "[postgres]\nusername = \"feedreader\"\npassword = \"feedreader\"\n";
+        let settings = |url: &str| {
+            let config =
+                Config::parse(format!("{}[server]\npublic_url = {:?}\n", postgres, url).as_str())
+                    .unwrap();
+            Settings::from_config(&config).map(|settings| settings.public_url)
+        };
+        assert_eq!(
+            settings("https://reader.example.com/").ok().flatten(),
+            Some("https://reader.example.com".to_string())
+        );
+        assert!(settings("javascript:alert(1)").is_err());
+        assert!(settings("https://reader.example.com/feedreader").is_err());
+    }
+
+    #[test]
+    fn intervals_are_not_zero() {
+        let postgres = "[postgres]\nusername = \"feedreader\"\npassword = \"feedreader\"\n";
+        let refused = |section: &str, seconds: u64| {
+            let errors =
+                errors(format!("{}[{}]\nsync_seconds = {}\n", postgres, section, seconds).as_str());
+            errors.iter().any(|e| e.ends_with("it should be 1 or more"))
+        };
+        assert!(refused("pocket", 0));
+        assert!(!refused("pocket", 60));
+        assert!(refused("readwise", 0));
+        assert!(
+            errors(format!("{}[feed]\nrefresh_seconds = 0\n", postgres).as_str())
+                .iter()
+                .any(|e| e.starts_with("FEED_REFRESH_SECONDS"))
+        );
+    }
+
+    #[test]
+    fn mqtt_password_needs_a_user_name() {
+        let postgres = "[postgres]\nusername = \"feedreader\"\npassword = \"feedreader\"\n";
+        let refused = |errors: Vec<String>| errors.iter().any(|e| e.starts_with("MQTT_PASSWORD"));
+        assert!(refused(errors(
+            format!(
+                "{}[mqtt]\nhost = \"broker\"\npassword = \"secret\"\n",
+                postgres
+            )
+            .as_str()
+        )));
+        assert!(!refused(errors(
+            format!(
+                "{}[mqtt]\nhost = \"broker\"\nusername = \"reader\"\npassword = \"secret\"\n",
+                postgres
+            )
+            .as_str()
+        )));
+    }
+}
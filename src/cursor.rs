@@ -0,0 +1,175 @@
+use crate::db;
+use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+
+// the setting the signing key is kept in, it is made on first start so cursors survive restarts
+pub const SECRET_SETTING: &str = "cursor_secret";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    // towards older articles
+    Next,
+    // back towards newer articles
+    Prev,
+}
+
+// Position is where a page of a list starts, clients only ever see it signed and encoded
+// sort names the column key is a value of, so a cursor handed out before the order of a list changes is refused
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Position {
+    pub filter: String,
+    pub sort: String,
+    pub key: String,
+    pub direction: Direction,
+}
+
+// Invalid is a cursor that was not made by this reader, or not for the list it was sent to
+#[derive(Debug, PartialEq)]
+pub struct Invalid;
+
+impl std::fmt::Display for Invalid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid cursor")
+    }
+}
+
+impl std::error::Error for Invalid {}
+
+// Signer turns positions into opaque tokens and back, a token is the position as json and its hmac
+#[derive(Clone)]
+pub struct Signer {
+    key: Arc<Vec<u8>>,
+}
+
+impl Signer {
+    pub fn new(key: Vec<u8>) -> Signer {
+        Signer { key: Arc::new(key) }
+    }
+
+    // load reads the signing key from the settings, a new one is made and saved the first time
+    pub async fn load(settings: &db::Settings) -> Result<Signer> {
+        if let Some(secret) = settings.get(SECRET_SETTING) {
+            return Ok(Signer::new(general_purpose::STANDARD.decode(secret)?));
+        }
+        let mut key = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        settings
+            .set(SECRET_SETTING, general_purpose::STANDARD.encode(&key))
+            .await?;
+        Ok(Signer::new(key))
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.as_slice())
+            .expect("hmac accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+
+    pub fn sign(&self, position: &Position) -> String {
+        let payload = serde_json::to_vec(position).expect("positions serialize");
+        let signature = self.mac(payload.as_slice()).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            general_purpose::URL_SAFE_NO_PAD.encode(payload),
+            general_purpose::URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    // verify returns the position of a token this signer made, anything else is invalid
+    pub fn verify(&self, token: &str) -> Result<Position, Invalid> {
+        let (payload, signature) = token.split_once('.').ok_or(Invalid)?;
+        let payload = general_purpose::URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| Invalid)?;
+        let signature = general_purpose::URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| Invalid)?;
+        self.mac(payload.as_slice())
+            .verify_slice(signature.as_slice())
+            .map_err(|_| Invalid)?;
+        serde_json::from_slice(payload.as_slice()).map_err(|_| Invalid)
+    }
+
+    // resume is the key a page starts from, a cursor must be for the filter and sort the list uses now
+    pub fn resume(&self, token: &str, filter: &db::Filter) -> Result<String, Invalid> {
+        let position = self.verify(token)?;
+        match position.filter == filter.to_string() && position.sort == filter.sort() {
+            true => Ok(position.key),
+            false => Err(Invalid),
+        }
+    }
+
+    // cursors are the tokens of the pages before and after a page of a list, None where there is none
+    pub fn cursors(
+        &self,
+        filter: &db::Filter,
+        cursor: &db::Cursor,
+    ) -> (Option<String>, Option<String>) {
+        let token = |key: &str, direction| {
+            self.sign(&Position {
+                filter: filter.to_string(),
+                sort: filter.sort().to_string(),
+                key: key.to_string(),
+                direction,
+            })
+        };
+        (
+            cursor
+                .has_next
+                .then(|| token(cursor.next.as_str(), Direction::Next)),
+            cursor
+                .has_prev
+                .then(|| token(cursor.prev.as_str(), Direction::Prev)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_are_signed_for_their_list() {
+        let signer = Signer::new(b"key".to_vec());
+        let position = Position {
+            filter: "unread".to_string(),
+            sort: "published".to_string(),
+            key: "2026-02-02T10:00:00.000Z".to_string(),
+            direction: Direction::Next,
+        };
+        let token = signer.sign(&position);
+        assert!(!token.contains("2026"));
+        assert_eq!(signer.verify(token.as_str()), Ok(position));
+        assert_eq!(
+            signer.resume(token.as_str(), &db::Filter::Unread),
+            Ok("2026-02-02T10:00:00.000Z".to_string())
+        );
+        assert_eq!(
+            signer.resume(token.as_str(), &db::Filter::Favorite),
+            Err(Invalid)
+        );
+
+        assert_eq!(
+            Signer::new(b"other".to_vec()).verify(token.as_str()),
+            Err(Invalid)
+        );
+        let (payload, signature) = token.split_once('.').unwrap();
+        let forged = general_purpose::URL_SAFE_NO_PAD.encode(
+            String::from_utf8(general_purpose::URL_SAFE_NO_PAD.decode(payload).unwrap())
+                .unwrap()
+                .replace("2026", "2025"),
+        );
+        assert_eq!(
+            signer.verify(format!("{}.{}", forged, signature).as_str()),
+            Err(Invalid)
+        );
+        assert_eq!(signer.verify("2026-02-02"), Err(Invalid));
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::Result;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone,
+    Utc,
+};
+use chrono_tz::Tz;
+
+// RELATIVE renders recent dates as "2h ago" and falls back to the language's format after a week
+pub const RELATIVE: &str = "relative";
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    // the language's date-format message, used unless a format is configured
+    Localized,
+    Relative,
+    Fixed(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Format {
+    timezone: Tz,
+    pattern: Pattern,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            timezone: Tz::UTC,
+            pattern: Pattern::Localized,
+        }
+    }
+}
+
+impl Format {
+    pub fn new(timezone: Tz, format: Option<&str>) -> Result<Format> {
+        let pattern = match format {
+            None => Pattern::Localized,
+            Some(RELATIVE) => Pattern::Relative,
+            Some(pattern) if StrftimeItems::new(pattern).any(|item| item == Item::Error) => {
+                return Err(anyhow::Error::msg(
+                    "it should be a strftime pattern like %m/%d/%Y or relative",
+                ))
+            }
+            Some(pattern) => Pattern::Fixed(pattern.to_string()),
+        };
+
+        Ok(Format { timezone, pattern })
+    }
+
+    pub fn render(&self, dt: DateTime<FixedOffset>) -> String {
+        let local = dt.with_timezone(&self.timezone);
+        let localized = || local.format(crate::tr("date-format").as_str()).to_string();
+        match &self.pattern {
+            Pattern::Fixed(pattern) => local.format(pattern.as_str()).to_string(),
+            Pattern::Localized => localized(),
+            Pattern::Relative => match relative(Utc::now().signed_duration_since(dt)) {
+                Some(ago) => ago,
+                None => localized(),
+            },
+        }
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    // day is the calendar day in the configured timezone, always in the language's day format
+    pub fn day(&self, dt: DateTime<FixedOffset>) -> String {
+        dt.with_timezone(&self.timezone)
+            .format(crate::tr("day-format").as_str())
+            .to_string()
+    }
+}
+
+fn relative(elapsed: Duration) -> Option<String> {
+    if elapsed < Duration::zero() || elapsed > Duration::days(7) {
+        return None;
+    }
+
+    let ago = match elapsed {
+        e if e < Duration::minutes(1) => crate::tr("date-just-now"),
+        e if e < Duration::hours(1) => crate::tr_with("date-minutes-ago", "count", e.num_minutes()),
+        e if e < Duration::days(1) => crate::tr_with("date-hours-ago", "count", e.num_hours()),
+        e => crate::tr_with("date-days-ago", "count", e.num_days()),
+    };
+    Some(ago)
+}
+
+// formats seen in feeds besides rfc 3339 and rfc 2822, tried in order
+const ZONED: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%Y-%m-%d %H:%M:%S%.f%z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    "%a, %d %b %Y %H:%M %z",
+];
+const NAIVE: [&str; 9] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%a, %d %b %Y %H:%M:%S",
+    "%a, %d %b %Y %H:%M",
+    "%d %b %Y %H:%M:%S",
+    "%A, %d-%b-%y %H:%M:%S",
+];
+const DAYS: [&str; 5] = ["%Y-%m-%d", "%Y/%m/%d", "%d %b %Y", "%B %d, %Y", "%b %d, %Y"];
+// years a stored date can be in, four digits and not before unix time
+const STORED_YEARS: std::ops::RangeInclusive<i32> = 1970..=9999;
+
+// stored is the form timestamps are kept in, utc to the millisecond, so the text sorts in time order
+// whatever offset the feed wrote the date with
+pub fn stored(dt: DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// day_start is the instant day begins in timezone, on a day that begins inside a daylight saving gap it is the first
+// local time that exists, days outside the years stored dates can hold have none
+pub fn day_start(day: NaiveDate, timezone: Tz) -> Option<DateTime<Utc>> {
+    if !STORED_YEARS.contains(&day.year()) {
+        return None;
+    }
+    (0..24)
+        .filter_map(|hour| day.and_hms_opt(hour, 0, 0))
+        .find_map(|local| timezone.from_local_datetime(&local).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+// parse_published reads an entry date whatever shape the feed wrote it in, times without an offset are taken as utc
+// a zone name chrono does not know, like CEST, is dropped rather than failing the whole date
+// dates outside the years stored dates can hold are taken as missing, so the article gets an estimated date
+pub fn parse_published(raw: &str) -> Option<DateTime<Utc>> {
+    parse(raw).filter(|dt| STORED_YEARS.contains(&dt.year()))
+}
+
+fn parse(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw).or_else(|_| DateTime::parse_from_rfc2822(raw))
+    {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    if let Some(dt) = ZONED
+        .iter()
+        .find_map(|f| DateTime::parse_from_str(raw, f).ok())
+    {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    let naive = match raw.rsplit_once(' ') {
+        Some((rest, zone)) if zone.chars().all(|c| c.is_ascii_alphabetic()) => rest,
+        _ => raw,
+    };
+    if let Some(dt) = NAIVE
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(naive, f).ok())
+    {
+        return Some(Utc.from_utc_datetime(&dt));
+    }
+
+    if let Some(day) = DAYS
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(naive, f).ok())
+    {
+        return day
+            .and_hms_opt(0, 0, 0)
+            .map(|dt| Utc.from_utc_datetime(&dt));
+    }
+
+    // unix seconds, only accepted when long enough to not be mistaken for a year, or milliseconds as javascript writes them
+    match raw.parse::<i64>() {
+        Ok(seconds) if (9..=12).contains(&raw.len()) => Utc.timestamp_opt(seconds, 0).single(),
+        Ok(millis) if raw.len() == 13 => Utc.timestamp_millis_opt(millis).single(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the same few instants written the way feeds in different zones write them
+    const MIXED_OFFSETS: [(&str, &str); 6] = [
+        (
+            "Mon, 01 Jan 2024 10:00:00 +0200",
+            "2024-01-01T08:00:00.000Z",
+        ),
+        ("2024-01-01T09:30:00+00:00", "2024-01-01T09:30:00.000Z"),
+        ("2024-01-01T03:45:00-05:00", "2024-01-01T08:45:00.000Z"),
+        ("Mon, 01 Jan 2024 00:15:00 PST", "2024-01-01T08:15:00.000Z"),
+        ("2024-01-01 18:00:00+09:00", "2024-01-01T09:00:00.000Z"),
+        ("2023-12-31T23:59:59.5-10:00", "2024-01-01T09:59:59.500Z"),
+    ];
+
+    #[test]
+    fn published_dates_are_stored_in_utc() {
+        for (raw, expected) in MIXED_OFFSETS {
+            let dt = parse_published(raw).unwrap_or_else(|| panic!("{} did not parse", raw));
+            assert_eq!(stored(dt), expected, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn days_start_at_local_midnight() {
+        let start = |raw: &str, timezone: Tz| {
+            day_start(
+                NaiveDate::parse_from_str(raw, "%Y-%m-%d").unwrap(),
+                timezone,
+            )
+            .map(stored)
+        };
+        assert_eq!(
+            start("2024-01-01", chrono_tz::Europe::Berlin),
+            Some("2023-12-31T23:00:00.000Z".to_string())
+        );
+        assert_eq!(
+            start("2024-07-01", chrono_tz::America::New_York),
+            Some("2024-07-01T04:00:00.000Z".to_string())
+        );
+        // midnight does not exist on the day santiago moves its clocks forward
+        assert_eq!(
+            start("2024-09-08", chrono_tz::America::Santiago),
+            Some("2024-09-08T04:00:00.000Z".to_string())
+        );
+        assert_eq!(start("1969-12-31", chrono_tz::UTC), None);
+    }
+
+    #[test]
+    fn unix_times_are_read_in_seconds_or_milliseconds() {
+        let expected = Some("2023-11-14T22:13:20.000Z".to_string());
+        assert_eq!(parse_published("1700000000").map(stored), expected);
+        assert_eq!(parse_published("1700000000000").map(stored), expected);
+        // anything else would be stored with a year of more than four digits, the article gets an estimated date
+        assert_eq!(parse_published("1700000000000000"), None);
+        assert_eq!(parse_published("999999999999"), None);
+        assert_eq!(parse_published("0001-01-01T00:00:00Z"), None);
+        assert_eq!(parse_published("2024"), None);
+    }
+
+    #[test]
+    fn stored_dates_sort_in_time_order() {
+        // the lists order by the stored text, that has to agree with ordering the instants
+        let mut by_text: Vec<String> = MIXED_OFFSETS
+            .iter()
+            .filter_map(|(raw, _)| parse_published(raw).map(stored))
+            .collect();
+        by_text.sort();
+
+        let mut by_time: Vec<DateTime<Utc>> = MIXED_OFFSETS
+            .iter()
+            .filter_map(|(raw, _)| parse_published(raw))
+            .collect();
+        by_time.sort();
+
+        assert_eq!(by_text, by_time.into_iter().map(stored).collect::<Vec<_>>());
+        // written as text the offsets would have put the -10:00 date from the previous day first
+        assert_eq!(
+            by_text.first().map(String::as_str),
+            Some("2024-01-01T08:00:00.000Z")
+        );
+    }
+
+    #[test]
+    fn dates_render_in_the_configured_zone() {
+        let dt = DateTime::parse_from_rfc3339("2024-01-01T23:30:00.000Z").unwrap();
+        let render = |zone: Tz| {
+            Format::new(zone, Some("%Y-%m-%d %H:%M"))
+                .unwrap()
+                .render(dt)
+        };
+        assert_eq!(render(Tz::UTC), "2024-01-01 23:30");
+        assert_eq!(render(chrono_tz::Europe::Berlin), "2024-01-02 00:30");
+        assert_eq!(render(chrono_tz::America::New_York), "2024-01-01 18:30");
+        assert_eq!(render(chrono_tz::Asia::Kolkata), "2024-01-02 05:00");
+    }
+}
@@ -1,29 +1,111 @@
-use super::{AddFeed, Article, Feed};
+use crate::models::{
+    AddFeed, AddNotificationRule, AddSmartFolder, Article, Feed, FeedSettings, FetchLatency,
+    FetchLog, IdempotentResponse, MuteSchedule, NewArticle, NotificationRule, SmartFolder,
+    TrashedArticle, MAX_AUTO_READ_DAYS,
+};
+use crate::{dates, history, query, sync};
 use anyhow::Result;
-use futures::lock::Mutex;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering as Atomic};
+use std::sync::{Arc, Mutex, RwLock};
 
-use tokio_postgres::{Client, Config, NoTls, Row};
+use tokio::sync::RwLockWriteGuard;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, Config, NoTls, Row, Statement};
 
 pub static MAX_DATE: &str = "9999-12-31";
 
-const LIMIT: usize = 4;
-const LIMIT_UPPER_BOUND: usize = LIMIT + 1;
-const LIMIT_LOWER_BOUND: usize = LIMIT - 1;
+// default number of items per page, the settings page can change it at runtime
+pub const LIMIT: usize = 4;
+// days pruned articles stay in the trash before the scheduler deletes them for good
+pub const TRASH_DAYS: i64 = 7;
+pub const MAX_LIMIT: usize = 100;
 
+// search headlines mark matches with control characters so the title can be escaped before they become tags
+pub const HIGHLIGHT_START: char = '\u{1}';
+pub const HIGHLIGHT_STOP: char = '\u{2}';
+
+// number of fetch log entries kept per feed
+const FETCH_LOG_LIMIT: i64 = 100;
+
+// Error is what a caller can answer differently from a failed query, it travels inside the anyhow error
+#[derive(Debug)]
+pub enum Error {
+    // the kind of row that was looked for
+    NotFound(&'static str),
+    // the kind of row that already exists
+    Conflict(&'static str),
+    // the kind of row that was changed since the version the caller saw
+    Stale(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound(kind) => write!(f, "{} not found", kind),
+            Error::Conflict(kind) => write!(f, "{} already exists", kind),
+            Error::Stale(kind) => write!(f, "{} changed since it was loaded", kind),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// conflict turns a unique violation into Error::Conflict and leaves other errors as they are
+fn conflict(kind: &'static str) -> impl Fn(tokio_postgres::Error) -> anyhow::Error {
+    move |e| match e.code() {
+        Some(&SqlState::UNIQUE_VIOLATION) => Error::Conflict(kind).into(),
+        _ => e.into(),
+    }
+}
+
+// marked tells a missing article from one whose version moved on when a mark updated nothing
+async fn marked(conn: &Client, row: Option<Row>, id: String) -> Result<Article> {
+    if let Some(row) = row {
+        return Ok(Article::from(&row));
+    }
+    match conn
+        .query_opt("SELECT 1 FROM articles WHERE id = $1", &[&id])
+        .await?
+    {
+        Some(_) => Err(Error::Stale("article").into()),
+        None => Err(Error::NotFound("article").into()),
+    }
+}
+
+#[derive(Clone)]
 pub enum Filter {
     Unread,
     Favorite,
     Read,
+    // the articles a smart folder matches, by the folder id
+    Folder(String),
+    // the articles of one feed, by the feed id
+    Feed(String),
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::Read => write!(f, "read"),
+            Filter::Favorite => write!(f, "favorite"),
+            Filter::Unread => write!(f, "unread"),
+            Filter::Folder(id) => write!(f, "folder:{}", id),
+            Filter::Feed(id) => write!(f, "feed:{}", id),
+        }
+    }
 }
 
-impl ToString for Filter {
-    fn to_string(&self) -> String {
+impl Filter {
+    // sort is the column a list is paged by, cursors carry a value of it
+    pub fn sort(&self) -> &'static str {
         match self {
-            Filter::Read => "read".to_string(),
-            Filter::Favorite => "favorite".to_string(),
-            Filter::Unread => "unread".to_string(),
+            Filter::Read => "read_date",
+            _ => "published",
         }
     }
 }
@@ -35,7 +117,11 @@ impl FromStr for Filter {
             "unread" => Ok(Filter::Unread),
             "favorite" => Ok(Filter::Favorite),
             "read" => Ok(Filter::Read),
-            _ => Err(anyhow::Error::msg(format!("bad filter type: {}", s))),
+            _ => match s.split_once(':') {
+                Some(("folder", id)) if !id.is_empty() => Ok(Filter::Folder(id.to_string())),
+                Some(("feed", id)) if !id.is_empty() => Ok(Filter::Feed(id.to_string())),
+                _ => Err(anyhow::Error::msg(format!("bad filter type: {}", s))),
+            },
         }
     }
 }
@@ -45,17 +131,17 @@ enum Ordering {
     Descending,
 }
 
-impl ToString for Ordering {
-    fn to_string(&self) -> String {
+impl fmt::Display for Ordering {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Ordering::Ascending => "ASC".to_string(),
-            Ordering::Descending => "DESC".to_string(),
+            Ordering::Ascending => write!(f, "ASC"),
+            Ordering::Descending => write!(f, "DESC"),
         }
     }
 }
 
 enum PaginationField {
-    Id,
+    DateAdded,
     Published,
     ReadDate,
 }
@@ -63,7 +149,7 @@ enum PaginationField {
 impl PaginationField {
     fn index(self) -> usize {
         match self {
-            PaginationField::Id => 0,
+            PaginationField::DateAdded => 4,
             PaginationField::Published => 5,
             PaginationField::ReadDate => 8,
         }
@@ -76,10 +162,63 @@ pub struct Page {
 }
 
 impl Page {
-    fn new(next: Vec<Row>, prev: Vec<Row>, curr: String, paginated_field: PaginationField) -> Page {
+    fn new(
+        next: Vec<Row>,
+        prev: Vec<Row>,
+        curr: String,
+        paginated_field: PaginationField,
+        limit: usize,
+    ) -> Page {
         Page {
-            cursor: Cursor::new(next.as_slice(), prev, curr, paginated_field.index()),
-            items: Cursor::items(next),
+            cursor: Cursor::new(next.as_slice(), prev, curr, paginated_field.index(), limit),
+            items: Cursor::items(next, limit),
+        }
+    }
+}
+
+// Counts are shown next to the navigation links on every page
+#[derive(Default, Clone)]
+pub struct Counts {
+    pub unread: i64,
+    pub favorites: i64,
+    // the sidebar replaces the navigation tabs once any feed has a category
+    pub categories: i64,
+    // smart folders show the sidebar too
+    pub folders: i64,
+    // empty lists tell a first run apart from a filter that matches nothing
+    pub feeds: i64,
+    pub articles: i64,
+}
+
+// SidebarFeed is a feed with its unread count, listed under its category in the sidebar
+#[derive(Clone)]
+pub struct SidebarFeed {
+    pub id: String,
+    pub name: String,
+    pub category: String,
+    pub unread: i64,
+}
+
+// FeedStats is how a feed is doing, shown next to it on the feeds page
+#[derive(Default, Clone)]
+pub struct FeedStats {
+    pub articles: i64,
+    pub unread: i64,
+    // when the feed was last fetched, successful or not, empty when it never was
+    pub last_fetched: String,
+}
+
+impl From<&Row> for FeedStats {
+    // the stats are selected after every feed column
+    fn from(row: &Row) -> Self {
+        let last_fetched: &str = row.get(row.len() - 1);
+        FeedStats {
+            articles: row.get(row.len() - 3),
+            unread: row.get(row.len() - 2),
+            last_fetched: match last_fetched {
+                "" => "".to_string(),
+                fetched_at => Article::rfc3339_timestamp_to_human(fetched_at),
+            },
         }
     }
 }
@@ -94,17 +233,19 @@ pub struct Cursor {
 }
 
 impl Cursor {
-    fn new(next: &[Row], prev: Vec<Row>, curr: String, index: usize) -> Self {
+    // queries fetch one row past the limit, its presence is what tells another page exists
+    fn new(next: &[Row], prev: Vec<Row>, curr: String, index: usize, limit: usize) -> Self {
         let (hn, n) = match next.len() {
             // next contains the elements for the next page, we only need elements up to the limit as the last is used to confirm there is another page
-            LIMIT_UPPER_BOUND => (true, next[next.len() - 1 - 1].get(index)),
-            1..=LIMIT_LOWER_BOUND => (false, next[next.len() - 1].get(index)),
+            len if len == limit + 1 => (true, next[next.len() - 1 - 1].get(index)),
+            len if len >= 1 && len < limit => (false, next[next.len() - 1].get(index)),
             _ => (false, "".to_string()),
         };
 
         let (hp, p) = match prev.len() {
-            LIMIT_UPPER_BOUND => (true, prev[0 + 1].get(index)),
-            1..=LIMIT_LOWER_BOUND => (true, MAX_DATE.to_string()),
+            len if len == limit + 1 => (true, prev[1].get(index)),
+            // a short previous page is the first page, which starts from the top again
+            len if len >= 1 && len <= limit => (true, MAX_DATE.to_string()),
             _ => (false, "".to_string()),
         };
 
@@ -113,14 +254,14 @@ impl Cursor {
             has_prev: hp,
             next: n,
             prev: p,
-            curr: curr,
+            curr,
         }
     }
 
-    fn items(next: Vec<Row>) -> Vec<Row> {
+    fn items(next: Vec<Row>, limit: usize) -> Vec<Row> {
         let mut items = next;
         match items.len() {
-            LIMIT_UPPER_BOUND => {
+            len if len == limit + 1 => {
                 // if we have more elements than the limit, another page exists and we only need the len of LIMIT elements
                 items.pop();
                 items
@@ -130,14 +271,69 @@ impl Cursor {
     }
 }
 
+// Storage is handed to every route, cloning it only bumps the count on the shared state
 #[derive(Clone)]
 pub struct Storage {
-    client: Arc<Mutex<Client>>,
+    inner: Arc<Shared>,
+}
+
+struct Shared {
+    // statements outside a transaction share the connection and are pipelined, a transaction holds it alone
+    client: tokio::sync::RwLock<Client>,
+    page_size: AtomicUsize,
+    // generation moves whenever something article lists render changes, cached pages are keyed on it
+    generation: AtomicU64,
+    // hot queries are prepared once on the connection and reused, keyed by their text
+    statements: Mutex<HashMap<String, Statement>>,
 }
 
 impl Storage {
+    fn limit(&self) -> usize {
+        self.inner.page_size.load(Atomic::Relaxed)
+    }
+
+    pub(crate) fn set_page_size(&self, page_size: usize) {
+        self.inner.page_size.store(page_size, Atomic::Relaxed);
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.inner.generation.load(Atomic::SeqCst)
+    }
+
+    // prepared returns the statement for query, it is only sent to postgres to be prepared the first time
+    // listings put the page size in their text, so a changed page size prepares a new statement
+    async fn prepared(&self, conn: &Client, query: &str) -> Result<Statement> {
+        let cached = self.inner.statements.lock().unwrap().get(query).cloned();
+        if let Some(statement) = cached {
+            return Ok(statement);
+        }
+
+        let statement = conn.prepare(query).await?;
+        self.inner
+            .statements
+            .lock()
+            .unwrap()
+            .insert(query.to_string(), statement.clone());
+        Ok(statement)
+    }
+
+    // change takes the connection for a write that changes what article lists render
+    // the generation moves while the lock is held, so a page rendered from the old rows keeps the old generation
+    async fn change(&self) -> RwLockWriteGuard<'_, Client> {
+        let conn = self.inner.client.write().await;
+        self.inner.generation.fetch_add(1, Atomic::SeqCst);
+        conn
+    }
+
+    // batch_execute runs statements as they are, tests use it to set up rows the app would not write
+    #[cfg(test)]
+    pub(crate) async fn batch_execute(&self, statements: &str) -> Result<()> {
+        let conn = &self.change().await;
+        Ok(conn.batch_execute(statements).await?)
+    }
+
     pub(crate) async fn init(&self) -> Result<()> {
-        let conn = self.client.lock().await;
+        let conn = self.inner.client.write().await;
         let query = r#"
 CREATE TABLE IF NOT EXISTS feeds (
     id TEXT NOT NULL,
@@ -152,23 +348,564 @@ CREATE TABLE IF NOT EXISTS articles (
     id TEXT NOT NULL,
     feed TEXT NOT NULL,
     title TEXT NOT NULL,
-    link TEXT NOT NULL UNIQUE,
+    link TEXT NOT NULL,
     author TEXT NOT NULL,
     published TEXT NOT NULL,
     read BOOLEAN NOT NULL,
     favorited BOOLEAN NOT NULL,
     read_date TEXT NOT NULL
-);"#;
+);
+
+ALTER TABLE feeds ADD COLUMN IF NOT EXISTS use_socks BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE feeds ADD COLUMN IF NOT EXISTS last_error TEXT NOT NULL DEFAULT '';
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS wallabag_saved TEXT NOT NULL DEFAULT '-1';
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS added TEXT NOT NULL DEFAULT '-1';
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS summary TEXT NOT NULL DEFAULT '';
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS thumbnail TEXT NOT NULL DEFAULT '';
+
+CREATE TABLE IF NOT EXISTS fetch_log (
+    feed_id TEXT NOT NULL,
+    fetched_at TEXT NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    bytes BIGINT NOT NULL,
+    error TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS fetch_log_feed_id_fetched_at ON fetch_log (feed_id, fetched_at);
+
+ALTER TABLE fetch_log ADD COLUMN IF NOT EXISTS warning TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX IF NOT EXISTS articles_title_search ON articles USING GIN (to_tsvector('english', title));
+
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT NOT NULL PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS pocket_items (
+    article_id TEXT NOT NULL PRIMARY KEY,
+    item_id TEXT NOT NULL,
+    synced_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS readwise_documents (
+    article_id TEXT NOT NULL PRIMARY KEY,
+    document_id TEXT NOT NULL,
+    synced_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS notification_rules (
+    id TEXT NOT NULL PRIMARY KEY,
+    feed_id TEXT NOT NULL,
+    keyword TEXT NOT NULL,
+    service TEXT NOT NULL,
+    url TEXT NOT NULL,
+    token TEXT NOT NULL
+);
+
+ALTER TABLE notification_rules ADD COLUMN IF NOT EXISTS action TEXT NOT NULL DEFAULT 'notify';
+
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS updated_at TEXT NOT NULL DEFAULT '-1';
+ALTER TABLE feeds ADD COLUMN IF NOT EXISTS updated_at TEXT NOT NULL DEFAULT '-1';
+
+CREATE INDEX IF NOT EXISTS articles_updated_at ON articles (updated_at);
+CREATE INDEX IF NOT EXISTS feeds_updated_at ON feeds (updated_at);
+
+ALTER TABLE feeds ADD COLUMN IF NOT EXISTS category TEXT NOT NULL DEFAULT '';
+
+-- the paging queries read limit + 1 rows on each side of the cursor, these keep that to an index range scan
+CREATE INDEX IF NOT EXISTS articles_unread_published ON articles (published) WHERE read = false;
+CREATE INDEX IF NOT EXISTS articles_favorited_published ON articles (published) WHERE favorited = true;
+CREATE INDEX IF NOT EXISTS articles_read_date ON articles (read_date) WHERE read = true;
+
+CREATE TABLE IF NOT EXISTS tombstones (
+    kind TEXT NOT NULL,
+    id TEXT NOT NULL,
+    deleted_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS tombstones_deleted_at ON tombstones (deleted_at);
+
+-- set when an entry had no date that could be read, published is then the time it was fetched
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS published_estimated BOOLEAN NOT NULL DEFAULT false;
+
+-- an article is its feed and the entry guid, or the link when the entry has none, so feeds sharing a link keep their own copy
+-- rows from before take the link as guid and adopt the real one the next time their feed is refreshed
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS feed_id TEXT NOT NULL DEFAULT '';
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS guid TEXT NOT NULL DEFAULT '';
+UPDATE articles a SET feed_id = f.id FROM feeds f WHERE a.feed_id = '' AND a.feed = f.name;
+UPDATE articles SET guid = link WHERE guid = '' AND link <> '';
+ALTER TABLE articles DROP CONSTRAINT IF EXISTS articles_link_key;
+CREATE UNIQUE INDEX IF NOT EXISTS articles_feed_id_guid ON articles (feed_id, guid);
+CREATE UNIQUE INDEX IF NOT EXISTS articles_id ON articles (id);
+
+-- feed text is stored on one line since ingest strips control characters, rows from before are cleaned the same way
+UPDATE articles SET title = btrim(regexp_replace(title, '[[:space:][:cntrl:]]+', ' ', 'g')), author = btrim(regexp_replace(author, '[[:space:][:cntrl:]]+', ' ', 'g'))
+    WHERE title ~ '[[:cntrl:]]|\s\s|^\s|\s$' OR author ~ '[[:cntrl:]]|\s\s|^\s|\s$';
+UPDATE articles SET link = '' WHERE link <> '' AND link !~* '^https?://';
+UPDATE articles SET title = rtrim(left(title, 300)) || '…' WHERE char_length(title) > 300;
+UPDATE articles SET author = rtrim(left(author, 100)) || '…' WHERE char_length(author) > 100;
+
+-- sync timestamps are kept by triggers so every write path, including pruning, shows up in /api/v1/sync
+CREATE OR REPLACE FUNCTION sync_timestamp() RETURNS TEXT AS $$
+    SELECT to_char(clock_timestamp() AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.MS"Z"')
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION touch_updated_at() RETURNS trigger AS $$
+BEGIN
+    NEW.updated_at := sync_timestamp();
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION record_tombstone() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO tombstones (kind, id, deleted_at) VALUES (TG_ARGV[0], OLD.id, sync_timestamp());
+    RETURN OLD;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS articles_updated_at ON articles;
+CREATE TRIGGER articles_updated_at BEFORE INSERT OR UPDATE ON articles FOR EACH ROW EXECUTE FUNCTION touch_updated_at();
+DROP TRIGGER IF EXISTS feeds_updated_at ON feeds;
+CREATE TRIGGER feeds_updated_at BEFORE INSERT OR UPDATE ON feeds FOR EACH ROW EXECUTE FUNCTION touch_updated_at();
+DROP TRIGGER IF EXISTS articles_tombstone ON articles;
+CREATE TRIGGER articles_tombstone AFTER DELETE ON articles FOR EACH ROW EXECUTE FUNCTION record_tombstone('article');
+DROP TRIGGER IF EXISTS feeds_tombstone ON feeds;
+CREATE TRIGGER feeds_tombstone AFTER DELETE ON feeds FOR EACH ROW EXECUTE FUNCTION record_tombstone('feed');
+
+-- feed ids used to be the base64 feed url, they are replaced by uuids everywhere they are referenced
+-- sync clients get a tombstone for the old id and see the feed again under its new one
+CREATE TEMP TABLE feed_ids ON COMMIT DROP AS
+    SELECT id AS old, gen_random_uuid()::text AS new FROM feeds
+    WHERE id !~ '^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$';
+UPDATE feeds f SET id = m.new FROM feed_ids m WHERE f.id = m.old;
+UPDATE articles a SET feed_id = m.new FROM feed_ids m WHERE a.feed_id = m.old;
+UPDATE fetch_log l SET feed_id = m.new FROM feed_ids m WHERE l.feed_id = m.old;
+UPDATE notification_rules r SET feed_id = m.new FROM feed_ids m WHERE r.feed_id = m.old;
+INSERT INTO tombstones (kind, id, deleted_at) SELECT 'feed', old, sync_timestamp() FROM feed_ids;
+CREATE UNIQUE INDEX IF NOT EXISTS feeds_id ON feeds (id);
+
+-- smart folders list the articles their filter expression matches
+CREATE TABLE IF NOT EXISTS smart_folders (
+    id TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL,
+    expression TEXT NOT NULL,
+    created TEXT NOT NULL
+);
+
+-- unread articles of a feed with auto read days are marked read by the scheduler once they are that old, 0 leaves them unread
+ALTER TABLE feeds ADD COLUMN IF NOT EXISTS auto_read_days INTEGER NOT NULL DEFAULT 0;
+
+-- the pipeline stages a feed turned on or off, a stage in neither runs as it was registered
+ALTER TABLE feeds ADD COLUMN IF NOT EXISTS stages_on TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE feeds ADD COLUMN IF NOT EXISTS stages_off TEXT[] NOT NULL DEFAULT '{}';
+
+-- the summary the ai-summary stage wrote when the article was added, empty when the stage is off
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS ai_summary TEXT NOT NULL DEFAULT '';
+
+-- the text of newsletters received by mail, they have no page of their own to link to
+CREATE TABLE IF NOT EXISTS newsletter_bodies (
+    article_id TEXT NOT NULL PRIMARY KEY REFERENCES articles (id) ON DELETE CASCADE,
+    body TEXT NOT NULL
+);
+
+-- quiet hours of a feed, or of every feed in a category, see mute.rs for how the window is read
+CREATE TABLE IF NOT EXISTS mute_schedules (
+    id TEXT NOT NULL PRIMARY KEY,
+    feed_id TEXT NOT NULL,
+    category TEXT NOT NULL,
+    days TEXT NOT NULL,
+    starts TEXT NOT NULL,
+    ends TEXT NOT NULL
+);
+
+-- articles added while their feed was muted stay out of the unread list until then, empty sorts before every timestamp
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS muted_until TEXT NOT NULL DEFAULT '';
+CREATE INDEX IF NOT EXISTS articles_muted_until ON articles (muted_until) WHERE muted_until <> '';
+
+-- responses to requests sent with an idempotency key, a retry with the key gets the stored one instead of running again
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key TEXT NOT NULL PRIMARY KEY,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    headers TEXT NOT NULL,
+    body BYTEA NOT NULL,
+    created TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idempotency_keys_created ON idempotency_keys (created);
+-- the hash of the query and body a key was first sent with, the key sent with another payload is refused
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS fingerprint TEXT NOT NULL DEFAULT '';
+
+-- pruned articles are moved here whole, as json so the row survives columns added to articles later, with the text of a newsletter
+CREATE TABLE IF NOT EXISTS trashed_articles (
+    id TEXT NOT NULL PRIMARY KEY,
+    article JSONB NOT NULL,
+    newsletter_body TEXT,
+    trashed_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS trashed_articles_trashed_at ON trashed_articles (trashed_at);"#;
         conn.batch_execute(query).await?;
+
+        // lists sort on the published text, so rows stored before dates were normalized are rewritten into the same format
+        let rows = conn
+            .query(
+                r"SELECT id, published, added FROM articles WHERE published !~ '^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d{3}Z$'",
+                &[],
+            )
+            .await?;
+        for row in rows.iter() {
+            let (published, estimated) = match dates::parse_published(row.get(1)) {
+                Some(dt) => (dates::stored(dt), false),
+                None => match row.get::<_, &str>(2) {
+                    "-1" => (Article::rfc3339_timestamp(), true),
+                    added => (added.to_string(), true),
+                },
+            };
+            conn.execute(
+                "UPDATE articles SET published = $1, published_estimated = $2 WHERE id = $3",
+                &[&published, &estimated, &row.get::<_, &str>(0)],
+            )
+            .await?;
+        }
+
+        // from here on a published value in any other form is refused, it would sort out of time order
+        conn.batch_execute(
+            r"DO $$ BEGIN
+                ALTER TABLE articles ADD CONSTRAINT articles_published_stored CHECK (published ~ '^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d{3}Z$');
+            EXCEPTION WHEN duplicate_object THEN NULL;
+            END $$;",
+        )
+        .await?;
+
+        // auto read days past the limit of the feed settings form are cut to it before the limit is enforced
+        conn.batch_execute(&format!(
+            r"UPDATE feeds SET auto_read_days = {MAX_AUTO_READ_DAYS} WHERE auto_read_days > {MAX_AUTO_READ_DAYS};
+            DO $$ BEGIN
+                ALTER TABLE feeds ADD CONSTRAINT feeds_auto_read_days CHECK (auto_read_days BETWEEN 0 AND {MAX_AUTO_READ_DAYS});
+            EXCEPTION WHEN duplicate_object THEN NULL;
+            END $$;",
+        ))
+        .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn add_notification_rule(&self, r: AddNotificationRule) -> Result<()> {
+        let conn = &mut self.inner.client.write().await;
+        let query = "INSERT INTO notification_rules (id, feed_id, keyword, service, url, token, action) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (id) DO NOTHING";
+        let rule = NotificationRule::new(r.feed_id, r.keyword, r.service, r.url, r.token, r.action);
+        let tx = conn.transaction().await?;
+        tx.execute(
+            query,
+            &[
+                &rule.id,
+                &rule.feed_id,
+                &rule.keyword,
+                &rule.service,
+                &rule.url,
+                &rule.token,
+                &rule.action,
+            ],
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn get_notification_rules(&self) -> Result<Vec<NotificationRule>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT * FROM notification_rules ORDER BY action, service, url";
+        let rows = conn.query(query, &[]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn get_feed_notification_rules(
+        &self,
+        feed_id: String,
+    ) -> Result<Vec<NotificationRule>> {
+        let conn = &self.inner.client.read().await;
+        let query =
+            "SELECT * FROM notification_rules WHERE feed_id = $1 ORDER BY action, service, url";
+        let rows = conn.query(query, &[&feed_id]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn delete_notification_rule(&self, id: String) -> Result<()> {
+        let conn = &mut self.inner.client.write().await;
+        let query = "DELETE FROM notification_rules WHERE id = $1";
+        let tx = conn.transaction().await?;
+        tx.execute(query, &[&id]).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn add_mute_schedule(
+        &self,
+        feed_id: String,
+        category: String,
+        days: String,
+        starts: String,
+        ends: String,
+    ) -> Result<()> {
+        let conn = &self.inner.client.write().await;
+        let query = "INSERT INTO mute_schedules (id, feed_id, category, days, starts, ends) VALUES ($1, $2, $3, $4, $5, $6)";
+        conn.execute(
+            query,
+            &[
+                &uuid::Uuid::new_v4().to_string(),
+                &feed_id,
+                &category,
+                &days,
+                &starts,
+                &ends,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn get_mute_schedules(&self) -> Result<Vec<MuteSchedule>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT id, feed_id, category, days, starts, ends FROM mute_schedules ORDER BY category, feed_id, starts";
+        let rows = conn.query(query, &[]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn delete_mute_schedule(&self, id: String) -> Result<()> {
+        let conn = &self.inner.client.write().await;
+        conn.execute("DELETE FROM mute_schedules WHERE id = $1", &[&id])
+            .await?;
+        Ok(())
+    }
+
+    // get_muted_feeds returns the feeds that have articles held back past now
+    pub(crate) async fn get_muted_feeds(&self, now: String) -> Result<Vec<Feed>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT * FROM feeds WHERE id IN (SELECT DISTINCT feed_id FROM articles WHERE muted_until > $1)";
+        let rows = conn.query(query, &[&now]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    // remute_articles moves the end of the mute of a feed's articles that are still held back at now to until
+    pub(crate) async fn remute_articles(
+        &self,
+        feed_id: String,
+        until: String,
+        now: String,
+    ) -> Result<()> {
+        let conn = &mut self.change().await;
+        let query = "UPDATE articles SET muted_until = $2 WHERE feed_id = $1 AND muted_until > $3";
+        conn.execute(query, &[&feed_id, &until, &now]).await?;
+        Ok(())
+    }
+
+    // release_muted_articles clears the mute of articles whose window ended before now and returns them
+    // the unread list shows them from then on anyway, this is for the notifications that were held back
+    pub(crate) async fn release_muted_articles(&self, now: String) -> Result<Vec<Article>> {
+        let conn = &mut self.change().await;
+        let query = "UPDATE articles SET muted_until = '' WHERE muted_until <> '' AND muted_until <= $1 RETURNING *";
+        let rows = conn.query(query, &[&now]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    // claim_idempotency_key takes a key for a request that is about to run, a key older than expired is taken over
+    // and so is one that is still running since abandoned, its request died with the server
+    // None means the key is claimed, otherwise it returns what the request that claimed it first was answered
+    pub(crate) async fn claim_idempotency_key(
+        &self,
+        key: String,
+        method: String,
+        path: String,
+        fingerprint: String,
+        expired: String,
+        abandoned: String,
+    ) -> Result<Option<IdempotentResponse>> {
+        let conn = &self.inner.client.write().await;
+        let claim = "INSERT INTO idempotency_keys (key, method, path, fingerprint, status, headers, body, created) VALUES ($1, $2, $3, $4, 0, '[]', '', $5) ON CONFLICT (key) DO UPDATE SET method = EXCLUDED.method, path = EXCLUDED.path, fingerprint = EXCLUDED.fingerprint, status = 0, headers = '[]', body = '', created = EXCLUDED.created WHERE idempotency_keys.created < $6 OR (idempotency_keys.status = 0 AND idempotency_keys.created < $7)";
+        let claim = self.prepared(conn, claim).await?;
+        let created = Article::rfc3339_timestamp();
+        if conn
+            .execute(
+                &claim,
+                &[
+                    &key,
+                    &method,
+                    &path,
+                    &fingerprint,
+                    &created,
+                    &expired,
+                    &abandoned,
+                ],
+            )
+            .await?
+            == 1
+        {
+            return Ok(None);
+        }
+        let stored = self
+            .prepared(
+                conn,
+                "SELECT method, path, fingerprint, status, headers, body FROM idempotency_keys WHERE key = $1",
+            )
+            .await?;
+        Ok(conn
+            .query_opt(&stored, &[&key])
+            .await?
+            .map(|row| IdempotentResponse::from(&row)))
+    }
+
+    pub(crate) async fn save_idempotent_response(
+        &self,
+        key: String,
+        status: i32,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> Result<()> {
+        let conn = &self.inner.client.write().await;
+        let query =
+            "UPDATE idempotency_keys SET status = $2, headers = $3, body = $4 WHERE key = $1";
+        conn.execute(
+            query,
+            &[&key, &status, &serde_json::to_string(&headers)?, &body],
+        )
+        .await?;
+        Ok(())
+    }
+
+    // release_idempotency_key forgets a key whose response is not kept, a retry with it runs again
+    pub(crate) async fn release_idempotency_key(&self, key: String) -> Result<()> {
+        let conn = &self.inner.client.write().await;
+        conn.execute("DELETE FROM idempotency_keys WHERE key = $1", &[&key])
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn prune_idempotency_keys(&self, before: String) -> Result<u64> {
+        let conn = &self.inner.client.write().await;
+        let query = "DELETE FROM idempotency_keys WHERE created < $1";
+        Ok(conn.execute(query, &[&before]).await?)
+    }
+
+    pub(crate) async fn add_smart_folder(&self, f: AddSmartFolder) -> Result<SmartFolder> {
+        let conn = &mut self.change().await;
+        let query = "INSERT INTO smart_folders (id, name, expression, created) VALUES ($1, $2, $3, $4) RETURNING id, name, expression";
+        let row = conn
+            .query_one(
+                query,
+                &[
+                    &uuid::Uuid::new_v4().to_string(),
+                    &f.name,
+                    &f.expression,
+                    &Article::rfc3339_timestamp(),
+                ],
+            )
+            .await?;
+        Ok(SmartFolder::from(&row))
+    }
+
+    pub(crate) async fn get_smart_folders(&self) -> Result<Vec<SmartFolder>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT id, name, expression FROM smart_folders ORDER BY name";
+        let rows = conn.query(query, &[]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn get_smart_folder(&self, id: String) -> Result<SmartFolder> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT id, name, expression FROM smart_folders WHERE id = $1";
+        match conn.query_opt(query, &[&id]).await? {
+            Some(row) => Ok(SmartFolder::from(&row)),
+            None => Err(Error::NotFound("smart folder").into()),
+        }
+    }
+
+    pub(crate) async fn delete_smart_folder(&self, id: String) -> Result<()> {
+        let conn = &mut self.change().await;
+        match conn
+            .execute("DELETE FROM smart_folders WHERE id = $1", &[&id])
+            .await?
+        {
+            0 => Err(Error::NotFound("smart folder").into()),
+            _ => Ok(()),
+        }
+    }
+
+    // get_folder_articles pages through the articles an expression matches, newest first
+    // every folder is its own query, they are not kept with the prepared statements
+    pub(crate) async fn get_folder_articles(
+        &self,
+        expr: &query::Expr,
+        pagination: String,
+    ) -> Result<Page> {
+        let conn = &self.inner.client.read().await;
+        let sql = expr.sql(2, Utc::now());
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&pagination];
+        params.extend(sql.params.iter().map(|p| p as &(dyn ToSql + Sync)));
+
+        let next_query = format!(
+            "SELECT * FROM articles WHERE {} AND published < $1 ORDER BY published {} LIMIT {}",
+            sql.clause,
+            Ordering::Descending,
+            self.limit() + 1
+        );
+        let next = conn.query(next_query.as_str(), &params).await?;
+
+        let prev_query = format!("SELECT * FROM ( SELECT * FROM articles WHERE {} AND published > $1 ORDER BY published {} LIMIT {} ) AS data ORDER BY published {}", sql.clause, Ordering::Ascending, self.limit() + 1, Ordering::Descending);
+        let prev = conn.query(prev_query.as_str(), &params).await?;
+
+        Ok(Page::new(
+            next,
+            prev,
+            pagination,
+            PaginationField::Published,
+            self.limit(),
+        ))
+    }
+
+    // count_folder_unread counts the unread articles an expression matches, for the badge in the sidebar
+    pub(crate) async fn count_folder_unread(&self, expr: &query::Expr) -> Result<i64> {
+        let conn = &self.inner.client.read().await;
+        let sql = expr.sql(1, Utc::now());
+        let params: Vec<&(dyn ToSql + Sync)> = sql
+            .params
+            .iter()
+            .map(|p| p as &(dyn ToSql + Sync))
+            .collect();
+        let query = format!(
+            "SELECT COUNT(*) FROM articles WHERE {} AND read = false",
+            sql.clause
+        );
+        Ok(conn.query_one(query.as_str(), &params).await?.get(0))
+    }
+
+    pub(crate) async fn get_settings(&self) -> Result<Vec<(String, String)>> {
+        let conn = &self.inner.client.read().await;
+        let rows = conn.query("SELECT key, value FROM settings", &[]).await?;
+        Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+    }
+
+    pub(crate) async fn set_setting(&self, key: &str, value: String) -> Result<()> {
+        let conn = &mut self.change().await;
+        let query = "INSERT INTO settings (key, value) VALUES ($1, $2) ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value";
+        let tx = conn.transaction().await?;
+        tx.execute(query, &[&key, &value]).await?;
+        tx.commit().await?;
         Ok(())
     }
 
     pub(crate) async fn add_feed(&self, f: AddFeed) -> Result<Feed> {
-        let conn = &mut self.client.lock().await;
-        let query = "INSERT INTO FEEDS (id, name, site_url, feed_url, date_added, last_updated) VALUES ($1, $2, $3, $4, $5, $6)";
+        let conn = &mut self.change().await;
+        let query = "INSERT INTO FEEDS (id, name, site_url, feed_url, date_added, last_updated, use_socks, category) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)";
         let tx = conn.transaction().await?;
         let stmt = tx.prepare(query).await?;
-        let fta = Feed::new(f.feed_name, f.site_url, f.feed_url);
+        let fta = Feed::new(
+            f.feed_name,
+            f.site_url,
+            f.feed_url,
+            f.use_socks.is_some(),
+            f.category.trim().to_string(),
+        );
         tx.execute(
             &stmt,
             &[
@@ -178,41 +915,71 @@ CREATE TABLE IF NOT EXISTS articles (
                 &fta.feed_url,
                 &fta.date_added,
                 &fta.last_updated,
+                &fta.use_socks,
+                &fta.category,
             ],
         )
-        .await?;
+        .await
+        .map_err(conflict("feed"))?;
         tx.commit().await?;
 
         Ok(fta)
     }
 
     pub(crate) async fn get_feed_by_id(&self, id: String) -> Result<Feed> {
-        let conn = &mut self.client.lock().await;
+        let conn = &self.inner.client.read().await;
         let query = "SELECT * FROM feeds WHERE id = $1";
-        let result = conn.query_one(query, &[&id]).await?;
-        Ok(Feed::from(&result))
+        match conn.query_opt(query, &[&id]).await? {
+            Some(row) => Ok(Feed::from(&row)),
+            None => Err(Error::NotFound("feed").into()),
+        }
     }
 
+    pub(crate) async fn get_all_feeds(&self) -> Result<Vec<Feed>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT * FROM feeds ORDER BY name ASC";
+        let rows = conn.query(query, &[]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    // get_feeds pages through the feeds, each row ends with the columns of its FeedStats
+    // the stats are counted per feed of the page only, the lateral joins run once for each row returned
     pub(crate) async fn get_feeds(&self, pagination: String) -> Result<Page> {
-        let conn = &mut self.client.lock().await;
+        let conn = &self.inner.client.read().await;
+        let stats = "COALESCE(c.articles, 0) AS articles, COALESCE(c.unread, 0) AS unread, COALESCE(l.fetched_at, '') AS last_fetched FROM feeds f LEFT JOIN LATERAL (SELECT COUNT(*) AS articles, COUNT(*) FILTER (WHERE a.read = false) AS unread FROM articles a WHERE a.feed_id = f.id) c ON true LEFT JOIN LATERAL (SELECT fetched_at FROM fetch_log WHERE feed_id = f.id ORDER BY fetched_at DESC LIMIT 1) l ON true";
         let next_query = format!(
-            "SELECT * FROM feeds WHERE date_added < $1 ORDER BY id {} LIMIT {}",
-            Ordering::Descending.to_string(),
-            LIMIT_UPPER_BOUND
+            "SELECT f.*, {} WHERE f.date_added < $1 ORDER BY f.date_added {} LIMIT {}",
+            stats,
+            Ordering::Descending,
+            self.limit() + 1
         );
-        let next = conn.query(next_query.as_str(), &[&pagination]).await?;
+        let next_query = self.prepared(conn, next_query.as_str()).await?;
+        let next = conn.query(&next_query, &[&pagination]).await?;
 
-        let prev_query = format!("SELECT * FROM ( SELECT * FROM feeds WHERE date_added > $1 ORDER BY id {} LIMIT {} ) AS data ORDER BY date_added {}", Ordering::Ascending.to_string(), LIMIT_UPPER_BOUND, Ordering::Descending.to_string());
-        let prev = conn.query(prev_query.as_str(), &[&pagination]).await?;
+        let prev_query = format!("SELECT * FROM ( SELECT f.*, {} WHERE f.date_added > $1 ORDER BY f.date_added {} LIMIT {} ) AS data ORDER BY date_added {}", stats, Ordering::Ascending, self.limit() + 1, Ordering::Descending);
+        let prev_query = self.prepared(conn, prev_query.as_str()).await?;
+        let prev = conn.query(&prev_query, &[&pagination]).await?;
 
-        Ok(Page::new(next, prev, pagination, PaginationField::Id))
+        Ok(Page::new(
+            next,
+            prev,
+            pagination,
+            PaginationField::DateAdded,
+            self.limit(),
+        ))
     }
 
     pub(crate) async fn delete_feed(&self, id: String) -> Result<()> {
-        let conn = &mut self.client.lock().await;
+        let conn = &mut self.change().await;
         let query = "DELETE FROM feeds WHERE id = $1";
         let tx = conn.transaction().await?;
-        tx.execute(query, &[&id]).await?;
+        if tx.execute(query, &[&id]).await? == 0 {
+            return Err(Error::NotFound("feed").into());
+        }
+        tx.execute("DELETE FROM fetch_log WHERE feed_id = $1", &[&id])
+            .await?;
+        tx.execute("DELETE FROM mute_schedules WHERE feed_id = $1", &[&id])
+            .await?;
         tx.commit().await?;
         Ok(())
     }
@@ -222,129 +989,761 @@ CREATE TABLE IF NOT EXISTS articles (
         timestamp: String,
         id: String,
     ) -> Result<()> {
-        let conn = &mut self.client.lock().await;
+        let conn = &mut self.inner.client.write().await;
         let tx = conn.transaction().await?;
-        let query = "UPDATE feeds SET last_updated = $1 WHERE id = $2";
+        let query = "UPDATE feeds SET last_updated = $1, last_error = '' WHERE id = $2";
         tx.query(query, &[&timestamp, &id]).await?;
         tx.commit().await?;
         Ok(())
     }
 
-    pub(crate) async fn add_articles<T>(&self, articles: T) -> Result<()>
+    // toggle_feed_stage adds the stage to stages_on or stages_off of the feed, or removes it when it is there
+    pub(crate) async fn toggle_feed_stage(
+        &self,
+        id: String,
+        stage: String,
+        off: bool,
+    ) -> Result<()> {
+        let conn = &self.change().await;
+        let column = match off {
+            true => "stages_off",
+            false => "stages_on",
+        };
+        let query = format!(
+            "UPDATE feeds SET {0} = CASE WHEN $1 = ANY({0}) THEN array_remove({0}, $1) ELSE array_append({0}, $1) END WHERE id = $2",
+            column
+        );
+        if conn.execute(query.as_str(), &[&stage, &id]).await? == 0 {
+            return Err(Error::NotFound("feed").into());
+        }
+        Ok(())
+    }
+
+    // articles carry the name of their feed for display, a rename relabels the ones of this feed id, other feeds can share the name
+    pub(crate) async fn update_feed_settings(
+        &self,
+        id: String,
+        s: FeedSettings,
+        auto_read_days: i32,
+    ) -> Result<()> {
+        let conn = &mut self.change().await;
+        let tx = conn.transaction().await?;
+        let feed = match tx
+            .query_opt("SELECT * FROM feeds WHERE id = $1", &[&id])
+            .await?
+        {
+            Some(row) => Feed::from(&row),
+            None => return Err(Error::NotFound("feed").into()),
+        };
+        let name = s.feed_name.trim().to_string();
+        tx.execute(
+            "UPDATE feeds SET name = $1, site_url = $2, category = $3, use_socks = $4, auto_read_days = $5 WHERE id = $6",
+            &[
+                &name,
+                &s.site_url.trim(),
+                &s.category.trim(),
+                &s.use_socks.is_some(),
+                &auto_read_days,
+                &id,
+            ],
+        )
+        .await?;
+        if name != feed.name {
+            tx.execute(
+                "UPDATE articles SET feed = $1 WHERE feed_id = $2",
+                &[&name, &id],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn update_feed_error(&self, error: String, id: String) -> Result<()> {
+        let conn = &mut self.inner.client.write().await;
+        let tx = conn.transaction().await?;
+        let query = "UPDATE feeds SET last_error = $1 WHERE id = $2";
+        tx.query(query, &[&error, &id]).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn add_fetch_log(&self, log: FetchLog) -> Result<()> {
+        let conn = &mut self.inner.client.write().await;
+        let tx = conn.transaction().await?;
+        let query = "INSERT INTO fetch_log (feed_id, fetched_at, duration_ms, bytes, error, warning) VALUES ($1, $2, $3, $4, $5, $6)";
+        tx.execute(
+            query,
+            &[
+                &log.feed_id,
+                &log.fetched_at,
+                &log.duration_ms,
+                &log.bytes,
+                &log.error,
+                &log.warning,
+            ],
+        )
+        .await?;
+
+        let prune_query = "DELETE FROM fetch_log WHERE feed_id = $1 AND fetched_at < ( SELECT fetched_at FROM fetch_log WHERE feed_id = $1 ORDER BY fetched_at DESC OFFSET $2 LIMIT 1 )";
+        tx.execute(prune_query, &[&log.feed_id, &(FETCH_LOG_LIMIT - 1)])
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn get_fetch_log(&self, feed_id: String, limit: i64) -> Result<Vec<FetchLog>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT * FROM fetch_log WHERE feed_id = $1 ORDER BY fetched_at DESC LIMIT $2";
+        let rows = conn.query(query, &[&feed_id, &limit]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn get_fetch_latency(&self, feed_id: String) -> Result<FetchLatency> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT count(*), COALESCE(percentile_cont(0.5) WITHIN GROUP (ORDER BY duration_ms), 0), COALESCE(percentile_cont(0.95) WITHIN GROUP (ORDER BY duration_ms), 0), COALESCE(percentile_cont(0.99) WITHIN GROUP (ORDER BY duration_ms), 0), COALESCE(avg(bytes)::float8, 0) FROM fetch_log WHERE feed_id = $1 AND error = ''";
+        let row = conn.query_one(query, &[&feed_id]).await?;
+        Ok(FetchLatency::from(&row))
+    }
+
+    // add_articles returns the articles that were not already stored, the title, link and summary of stored ones are updated
+    // while their ai summary is kept, the stage only writes one for new entries
+    pub(crate) async fn add_articles<T>(&self, articles: T) -> Result<Vec<Article>>
     where
         T: Iterator<Item = Article>,
     {
-        let conn = &mut self.client.lock().await;
+        self.add_muted_articles(articles, String::new()).await
+    }
+
+    // add_muted_articles adds articles like add_articles, the ones it inserts are held back until muted_until
+    // in the same statement, so a muted article is never visible as unread
+    pub(crate) async fn add_muted_articles<T>(
+        &self,
+        articles: T,
+        muted_until: String,
+    ) -> Result<Vec<Article>>
+    where
+        T: Iterator<Item = Article>,
+    {
+        // the whole batch goes in one statement as arrays, unnest turns them back into rows
+        // a statement can only update a row once, so of a guid repeated within the batch the first entry wins
+        let mut seen = HashSet::new();
+        let articles: Vec<Article> = articles.filter(|a| seen.insert(a.id.clone())).collect();
+        let column =
+            |f: fn(&Article) -> &String| -> Vec<&String> { articles.iter().map(f).collect() };
+        let ids = column(|a| &a.id);
+        let feeds = column(|a| &a.feed);
+        let feed_ids = column(|a| &a.feed_id);
+        let guids = column(|a| &a.guid);
+        let titles = column(|a| &a.title);
+        let links = column(|a| &a.link);
+        let authors = column(|a| &a.author);
+        let published = column(|a| &a.published);
+        let read: Vec<bool> = articles.iter().map(|a| a.read).collect();
+        let favorited: Vec<bool> = articles.iter().map(|a| a.favorited).collect();
+        let estimated: Vec<bool> = articles.iter().map(|a| a.published_estimated).collect();
+        let read_dates = column(|a| &a.read_date);
+        let summaries = column(|a| &a.summary);
+        let thumbnails = column(|a| &a.thumbnail);
+        let ai_summaries = column(|a| &a.ai_summary);
+        let added_at = Article::rfc3339_timestamp();
+
+        let conn = &mut self.change().await;
+        let adopt = "UPDATE articles a SET guid = n.guid FROM UNNEST($1::text[], $2::text[], $3::text[]) AS n (feed_id, guid, link) WHERE a.feed_id = n.feed_id AND a.guid = n.link AND a.link = n.link AND n.guid <> n.link AND NOT EXISTS (SELECT 1 FROM articles b WHERE b.feed_id = n.feed_id AND b.guid = n.guid)";
+        let adopt = self.prepared(conn, adopt).await?;
+        let query = "INSERT INTO articles (id, feed, title, link, author, published, read, favorited, read_date, added, summary, thumbnail, published_estimated, feed_id, guid, ai_summary, muted_until) SELECT id, feed, title, link, author, published, read, favorited, read_date, $10::text, summary, thumbnail, published_estimated, feed_id, guid, ai_summary, $17::text FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[], $6::text[], $7::bool[], $8::bool[], $9::text[], $11::text[], $12::text[], $13::bool[], $14::text[], $15::text[], $16::text[]) AS a (id, feed, title, link, author, published, read, favorited, read_date, summary, thumbnail, published_estimated, feed_id, guid, ai_summary) ON CONFLICT (feed_id, guid) DO UPDATE SET title = EXCLUDED.title, link = EXCLUDED.link, author = EXCLUDED.author, summary = EXCLUDED.summary, thumbnail = EXCLUDED.thumbnail WHERE (articles.title, articles.link, articles.author, articles.summary, articles.thumbnail) IS DISTINCT FROM (EXCLUDED.title, EXCLUDED.link, EXCLUDED.author, EXCLUDED.summary, EXCLUDED.thumbnail) RETURNING id, xmax = 0";
+        let query = self.prepared(conn, query).await?;
+
+        // rows stored before guids were kept take the guid of the entry with their link instead of being added again
         let tx = conn.transaction().await?;
-        let query = "INSERT INTO articles (id, feed, title, link, author, published, read, favorited, read_date) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT (link) DO NOTHING";
-        let stmt = tx.prepare(query).await?;
-        for article in articles {
-            tx.execute(
-                &stmt,
+        tx.execute(&adopt, &[&feed_ids, &guids, &links]).await?;
+        let rows = tx
+            .query(
+                &query,
                 &[
-                    &article.id,
-                    &article.feed,
-                    &article.title,
-                    &article.link,
-                    &article.author,
-                    &article.published,
-                    &article.read,
-                    &article.favorited,
-                    &article.read_date,
+                    &ids,
+                    &feeds,
+                    &titles,
+                    &links,
+                    &authors,
+                    &published,
+                    &read,
+                    &favorited,
+                    &read_dates,
+                    &added_at,
+                    &summaries,
+                    &thumbnails,
+                    &estimated,
+                    &feed_ids,
+                    &guids,
+                    &ai_summaries,
+                    &muted_until,
                 ],
             )
             .await?;
+        tx.commit().await?;
+
+        // an entry edited under the same guid updates its article in place, only rows with xmax = 0 were inserted
+        let inserted: HashSet<String> =
+            rows.iter().filter(|r| r.get(1)).map(|r| r.get(0)).collect();
+        Ok(articles
+            .into_iter()
+            .filter(|a| inserted.contains(&a.id))
+            .collect())
+    }
+
+    // existing_guids returns which of the feed's entries are already stored, matching the link of rows that have no guid yet
+    pub(crate) async fn existing_guids(
+        &self,
+        feed_id: String,
+        guids: Vec<String>,
+        links: Vec<String>,
+    ) -> Result<Vec<String>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT n.guid FROM UNNEST($2::text[], $3::text[]) AS n (guid, link) WHERE EXISTS (SELECT 1 FROM articles a WHERE a.feed_id = $1 AND (a.guid = n.guid OR (a.guid = a.link AND a.link = n.link)))";
+        let query = self.prepared(conn, query).await?;
+        let rows = conn.query(&query, &[&feed_id, &guids, &links]).await?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+
+    pub(crate) async fn get_article_by_id(&self, id: String) -> Result<Article> {
+        let conn = &self.inner.client.read().await;
+        let query = self
+            .prepared(conn, "SELECT * FROM articles WHERE id = $1")
+            .await?;
+        match conn.query_opt(&query, &[&id]).await? {
+            Some(row) => Ok(Article::from(&row)),
+            None => Err(Error::NotFound("article").into()),
         }
+    }
 
-        tx.commit().await?;
+    pub(crate) async fn add_newsletter_body(&self, article_id: String, body: String) -> Result<()> {
+        let conn = &self.inner.client.write().await;
+        let query = "INSERT INTO newsletter_bodies (article_id, body) VALUES ($1, $2) ON CONFLICT (article_id) DO NOTHING";
+        conn.execute(query, &[&article_id, &body]).await?;
         Ok(())
     }
 
-    pub(crate) async fn get_article_by_id(&self, id: String) -> Result<Article> {
-        let conn = &mut self.client.lock().await;
-        let query = "SELECT * FROM articles WHERE id = $1";
-        let row = conn.query_one(query, &[&id]).await?;
-        Ok(Article::from(&row))
+    // get_newsletter_body is empty for articles that did not come in by mail
+    pub(crate) async fn get_newsletter_body(&self, article_id: String) -> Result<String> {
+        let conn = &self.inner.client.read().await;
+        let query = self
+            .prepared(
+                conn,
+                "SELECT body FROM newsletter_bodies WHERE article_id = $1",
+            )
+            .await?;
+        Ok(conn
+            .query_opt(&query, &[&article_id])
+            .await?
+            .map(|row| row.get(0))
+            .unwrap_or_default())
     }
 
+    // get_related_articles ranks other articles by the words of their title they share with the title and summary of this one
+    // the words are matched as stemmed, so posts and posting are the same word, and favorites win ties so saved ones resurface
+    pub(crate) async fn get_related_articles(
+        &self,
+        id: String,
+        limit: i64,
+    ) -> Result<Vec<Article>> {
+        let conn = &self.inner.client.read().await;
+        let query = self
+            .prepared(
+                conn,
+                "WITH source AS (SELECT a.id, websearch_to_tsquery('simple', string_agg(w.lexeme, ' or ')) AS q FROM articles a, unnest(to_tsvector('english', a.title || ' ' || a.summary)) w WHERE a.id = $1 GROUP BY a.id) SELECT a.* FROM articles a, source s WHERE a.id <> s.id AND to_tsvector('english', a.title) @@ s.q ORDER BY ts_rank(to_tsvector('english', a.title), s.q) DESC, a.favorited DESC, a.published DESC LIMIT $2",
+            )
+            .await?;
+        let rows = conn.query(&query, &[&id, &limit]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    // get_unread_articles leaves out articles of muted feeds until their mute ends
     pub(crate) async fn get_unread_articles(&self, pagination: String) -> Result<Page> {
-        let conn = &mut self.client.lock().await;
+        let conn = &self.inner.client.read().await;
+        let now = Article::rfc3339_timestamp();
 
-        let next_query =format!("SELECT * FROM articles WHERE read = false AND published < $1 ORDER BY published {} LIMIT {}", Ordering::Descending.to_string(), LIMIT_UPPER_BOUND);
-        let next = conn.query(next_query.as_str(), &[&pagination]).await?;
+        let next_query =format!("SELECT * FROM articles WHERE read = false AND muted_until < $2 AND published < $1 ORDER BY published {} LIMIT {}", Ordering::Descending, self.limit() + 1);
+        let next_query = self.prepared(conn, next_query.as_str()).await?;
+        let next = conn.query(&next_query, &[&pagination, &now]).await?;
 
-        let prev_query = format!("SELECT * FROM ( SELECT * FROM articles WHERE read = false AND published > $1 ORDER BY published {} LIMIT {} ) AS data ORDER BY published {}", Ordering::Ascending.to_string(), LIMIT_UPPER_BOUND, Ordering::Descending.to_string());
-        let prev = conn.query(prev_query.as_str(), &[&pagination]).await?;
+        let prev_query = format!("SELECT * FROM ( SELECT * FROM articles WHERE read = false AND muted_until < $2 AND published > $1 ORDER BY published {} LIMIT {} ) AS data ORDER BY published {}", Ordering::Ascending, self.limit() + 1, Ordering::Descending);
+        let prev_query = self.prepared(conn, prev_query.as_str()).await?;
+        let prev = conn.query(&prev_query, &[&pagination, &now]).await?;
 
         Ok(Page::new(
             next,
             prev,
             pagination,
             PaginationField::Published,
+            self.limit(),
         ))
     }
 
-    pub(crate) async fn get_read_articles(&self, pagination: String) -> Result<Page> {
-        let conn = &mut self.client.lock().await;
+    // get_new_articles returns the articles stored after since, newest first, articles held back by quiet hours are left out
+    pub(crate) async fn get_new_articles(
+        &self,
+        since: String,
+        limit: i64,
+    ) -> Result<Vec<NewArticle>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT * FROM articles WHERE added > $1 AND muted_until < $3 ORDER BY added DESC, published DESC LIMIT $2";
+        let rows = conn
+            .query(query, &[&since, &limit, &Article::rfc3339_timestamp()])
+            .await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn count_unread_articles(&self) -> Result<i64> {
+        let conn = &self.inner.client.read().await;
+        let query = self
+            .prepared(
+                conn,
+                "SELECT COUNT(*) FROM articles WHERE read = false AND muted_until < $1",
+            )
+            .await?;
+        let row = conn
+            .query_one(&query, &[&Article::rfc3339_timestamp()])
+            .await?;
+        Ok(row.get(0))
+    }
 
-        let next_query = format!("SELECT * FROM articles WHERE read = true AND read_date < $1 ORDER BY read_date {} LIMIT {}", Ordering::Descending.to_string(), LIMIT_UPPER_BOUND);
+    pub(crate) async fn count_feed_articles(&self, feed_id: String) -> Result<i64> {
+        let conn = &self.inner.client.read().await;
+        let query = self
+            .prepared(conn, "SELECT COUNT(*) FROM articles WHERE feed_id = $1")
+            .await?;
+        let row = conn.query_one(&query, &[&feed_id]).await?;
+        Ok(row.get(0))
+    }
+
+    pub(crate) async fn get_counts(&self) -> Result<Counts> {
+        let conn = &self.inner.client.read().await;
+        let query = self.prepared(conn, "SELECT COUNT(*) FILTER (WHERE read = false AND muted_until < $1), COUNT(*) FILTER (WHERE favorited = true), (SELECT COUNT(DISTINCT category) FROM feeds WHERE category <> ''), (SELECT COUNT(*) FROM feeds), COUNT(*), (SELECT COUNT(*) FROM smart_folders) FROM articles").await?;
+        let row = conn
+            .query_one(&query, &[&Article::rfc3339_timestamp()])
+            .await?;
+        Ok(Counts {
+            unread: row.get(0),
+            favorites: row.get(1),
+            categories: row.get(2),
+            feeds: row.get(3),
+            articles: row.get(4),
+            folders: row.get(5),
+        })
+    }
+
+    // unread counts are joined on the feed id, feeds and pseudo-feeds can share a name
+    pub(crate) async fn get_sidebar_feeds(&self) -> Result<Vec<SidebarFeed>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT f.id, f.name, f.category, COUNT(a.id) FILTER (WHERE a.read = false AND a.muted_until < $1) FROM feeds f LEFT JOIN articles a ON a.feed_id = f.id GROUP BY f.id, f.name, f.category ORDER BY f.category, f.name";
+        let query = self.prepared(conn, query).await?;
+        let rows = conn.query(&query, &[&Article::rfc3339_timestamp()]).await?;
+        Ok(rows
+            .iter()
+            .map(|row| SidebarFeed {
+                id: row.get(0),
+                name: row.get(1),
+                category: row.get(2),
+                unread: row.get(3),
+            })
+            .collect())
+    }
+
+    // get_read_history returns read articles from the given time on that were read before the cursor, newest first
+    // the cursor is the read_date and id of the last entry of the previous chunk, the id breaks ties of a millisecond
+    pub(crate) async fn get_read_history(
+        &self,
+        from: String,
+        before: String,
+        before_id: String,
+        limit: i64,
+    ) -> Result<Vec<history::Entry>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT * FROM articles WHERE read = true AND read_date <> '-1' AND read_date >= $1 AND (read_date, id) < ($2, $3) ORDER BY read_date DESC, id DESC LIMIT $4";
+        let rows = conn
+            .query(query, &[&from, &before, &before_id, &limit])
+            .await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    // get_read_activity counts the articles read per day since the given time, days are dates in the timezone
+    // only days with reads are returned, pruned articles no longer count
+    pub(crate) async fn get_read_activity(
+        &self,
+        since: String,
+        timezone: String,
+    ) -> Result<Vec<(String, i64)>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT to_char((read_date::timestamptz AT TIME ZONE $2)::date, 'YYYY-MM-DD') AS day, COUNT(*) FROM articles WHERE read = true AND read_date <> '-1' AND read_date >= $1 GROUP BY day ORDER BY day";
+        let rows = conn.query(query, &[&since, &timezone]).await?;
+        Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+    }
+
+    pub(crate) async fn get_digest_articles(&self, limit: i64) -> Result<Vec<Article>> {
+        let conn = &self.inner.client.read().await;
+        let query =
+            "SELECT * FROM articles WHERE read = false AND muted_until < $2 ORDER BY feed ASC, published DESC LIMIT $1";
+        let rows = conn
+            .query(query, &[&limit, &Article::rfc3339_timestamp()])
+            .await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn get_articles_by_ids(&self, ids: Vec<String>) -> Result<Vec<Article>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT * FROM articles WHERE id = ANY($1) ORDER BY feed ASC, published DESC";
+        let rows = conn.query(query, &[&ids]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    // search_articles pages through articles whose title matches a web search style query, each row ends with the highlighted title
+    pub(crate) async fn search_articles(&self, query: String, pagination: String) -> Result<Page> {
+        let conn = &self.inner.client.read().await;
+        let options = format!(
+            "StartSel={}, StopSel={}, HighlightAll=true",
+            HIGHLIGHT_START, HIGHLIGHT_STOP
+        );
+
+        let next_query = format!("SELECT a.*, ts_headline('english', a.title, q, $3) FROM articles a, websearch_to_tsquery('english', $1) q WHERE to_tsvector('english', a.title) @@ q AND a.published < $2 ORDER BY a.published {} LIMIT {}", Ordering::Descending, self.limit() + 1);
+        let next_query = self.prepared(conn, next_query.as_str()).await?;
         let next = conn
-            .query(next_query.as_str(), &[&pagination.clone()])
+            .query(&next_query, &[&query, &pagination, &options])
             .await?;
 
-        let prev_query = format!("SELECT * FROM ( SELECT * FROM articles WHERE read = true AND read_date > $1 ORDER BY read_date {} LIMIT {} ) AS data ORDER BY read_date {}", Ordering::Ascending.to_string(), LIMIT_UPPER_BOUND, Ordering::Descending.to_string());
+        let prev_query = format!("SELECT * FROM ( SELECT a.*, ts_headline('english', a.title, q, $3) FROM articles a, websearch_to_tsquery('english', $1) q WHERE to_tsvector('english', a.title) @@ q AND a.published > $2 ORDER BY a.published {} LIMIT {} ) AS data ORDER BY published {}", Ordering::Ascending, self.limit() + 1, Ordering::Descending);
+        let prev_query = self.prepared(conn, prev_query.as_str()).await?;
         let prev = conn
-            .query(prev_query.as_str(), &[&pagination.clone()])
+            .query(&prev_query, &[&query, &pagination, &options])
             .await?;
 
-        Ok(Page::new(next, prev, pagination, PaginationField::ReadDate))
+        Ok(Page::new(
+            next,
+            prev,
+            pagination,
+            PaginationField::Published,
+            self.limit(),
+        ))
+    }
+
+    pub(crate) async fn get_feed_articles(
+        &self,
+        feed_id: String,
+        pagination: String,
+    ) -> Result<Page> {
+        let conn = &self.inner.client.read().await;
+
+        let next_query = format!("SELECT * FROM articles WHERE feed_id = $2 AND published < $1 ORDER BY published {} LIMIT {}", Ordering::Descending, self.limit() + 1);
+        let next_query = self.prepared(conn, next_query.as_str()).await?;
+        let next = conn.query(&next_query, &[&pagination, &feed_id]).await?;
+
+        let prev_query = format!("SELECT * FROM ( SELECT * FROM articles WHERE feed_id = $2 AND published > $1 ORDER BY published {} LIMIT {} ) AS data ORDER BY published {}", Ordering::Ascending, self.limit() + 1, Ordering::Descending);
+        let prev_query = self.prepared(conn, prev_query.as_str()).await?;
+        let prev = conn.query(&prev_query, &[&pagination, &feed_id]).await?;
+
+        Ok(Page::new(
+            next,
+            prev,
+            pagination,
+            PaginationField::Published,
+            self.limit(),
+        ))
+    }
+
+    pub(crate) async fn get_read_articles(&self, pagination: String) -> Result<Page> {
+        let conn = &self.inner.client.read().await;
+
+        let next_query = format!("SELECT * FROM articles WHERE read = true AND read_date < $1 ORDER BY read_date {} LIMIT {}", Ordering::Descending, self.limit() + 1);
+        let next_query = self.prepared(conn, next_query.as_str()).await?;
+        let next = conn.query(&next_query, &[&pagination]).await?;
+
+        let prev_query = format!("SELECT * FROM ( SELECT * FROM articles WHERE read = true AND read_date > $1 ORDER BY read_date {} LIMIT {} ) AS data ORDER BY read_date {}", Ordering::Ascending, self.limit() + 1, Ordering::Descending);
+        let prev_query = self.prepared(conn, prev_query.as_str()).await?;
+        let prev = conn.query(&prev_query, &[&pagination]).await?;
+
+        Ok(Page::new(
+            next,
+            prev,
+            pagination,
+            PaginationField::ReadDate,
+            self.limit(),
+        ))
     }
 
     pub(crate) async fn get_favorited_articles(&self, pagination: String) -> Result<Page> {
-        let conn = &mut self.client.lock().await;
+        let conn = &self.inner.client.read().await;
 
-        let next_query = format!("SELECT * FROM articles WHERE favorited = true AND published < $1 ORDER BY published {} LIMIT {}", Ordering::Descending.to_string(), LIMIT_UPPER_BOUND);
-        let next = conn.query(next_query.as_str(), &[&pagination]).await?;
+        let next_query = format!("SELECT * FROM articles WHERE favorited = true AND published < $1 ORDER BY published {} LIMIT {}", Ordering::Descending, self.limit() + 1);
+        let next_query = self.prepared(conn, next_query.as_str()).await?;
+        let next = conn.query(&next_query, &[&pagination]).await?;
 
-        let prev_query = format!("SELECT * FROM ( SELECT * FROM articles WHERE favorited = true AND published > $1 ORDER BY published {} LIMIT {} ) AS data ORDER BY published {}", Ordering::Ascending.to_string(), LIMIT_UPPER_BOUND, Ordering::Descending.to_string());
-        let prev = conn.query(prev_query.as_str(), &[&pagination]).await?;
+        let prev_query = format!("SELECT * FROM ( SELECT * FROM articles WHERE favorited = true AND published > $1 ORDER BY published {} LIMIT {} ) AS data ORDER BY published {}", Ordering::Ascending, self.limit() + 1, Ordering::Descending);
+        let prev_query = self.prepared(conn, prev_query.as_str()).await?;
+        let prev = conn.query(&prev_query, &[&pagination]).await?;
 
         Ok(Page::new(
             next,
             prev,
             pagination,
             PaginationField::Published,
+            self.limit(),
         ))
     }
 
-    pub(crate) async fn mark_article_read(&self, a: Article) -> Result<()> {
-        let conn = &mut self.client.lock().await;
-        let timestamp = match a.read {
-            true => "-1".to_string(),
-            false => Article::rfc3339_timestamp(),
+    // next_unread_article marks the newest unread article read in the same statement so two clients never get the same one
+    pub(crate) async fn next_unread_article(&self) -> Result<Option<Article>> {
+        let conn = &mut self.change().await;
+        let query = "UPDATE articles SET read = true, read_date = $1 WHERE id = (SELECT id FROM articles WHERE read = false AND muted_until < $1 ORDER BY published DESC LIMIT 1 FOR UPDATE SKIP LOCKED) RETURNING *";
+        let query = self.prepared(conn, query).await?;
+        let row = conn
+            .query_opt(&query, &[&Article::rfc3339_timestamp()])
+            .await?;
+        Ok(row.as_ref().map(Article::from))
+    }
+
+    // favorite_links favorites the articles of a feed that have one of the links, it returns the links that were found
+    pub(crate) async fn favorite_links(
+        &self,
+        feed_id: &str,
+        links: &[String],
+    ) -> Result<Vec<String>> {
+        let conn = &mut self.change().await;
+        let query = self
+            .prepared(
+                conn,
+                "UPDATE articles SET favorited = true WHERE feed_id = $1 AND link = ANY($2) RETURNING link",
+            )
+            .await?;
+        let rows = conn.query(&query, &[&feed_id, &links]).await?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+
+    // mark_article_read toggles read and returns the row as the update left it
+    // a version is the updated_at the caller saw, the toggle only applies when nobody changed the article since
+    pub(crate) async fn mark_article_read(&self, id: String, version: &str) -> Result<Article> {
+        let conn = &mut self.change().await;
+        let query = self
+            .prepared(
+                conn,
+                "UPDATE articles SET read = NOT read, read_date = CASE WHEN read THEN '-1' ELSE $1 END WHERE id = $2 AND ($3 = '' OR updated_at = $3) RETURNING *",
+            )
+            .await?;
+        let row = conn
+            .query_opt(&query, &[&Article::rfc3339_timestamp(), &id, &version])
+            .await?;
+        marked(conn, row, id).await
+    }
+
+    // get_changes returns every row written at or after since, a full sync passes no since and gets no tombstones
+    pub(crate) async fn get_changes(
+        &self,
+        since: Option<String>,
+        checkpoint: String,
+    ) -> Result<sync::Changes> {
+        let conn = &self.inner.client.read().await;
+        let since = since.unwrap_or_default();
+
+        let articles = conn
+            .query(
+                "SELECT * FROM articles WHERE updated_at >= $1 ORDER BY updated_at",
+                &[&since],
+            )
+            .await?;
+        let feeds = conn
+            .query(
+                "SELECT * FROM feeds WHERE updated_at >= $1 ORDER BY updated_at",
+                &[&since],
+            )
+            .await?;
+        let deleted = match since.is_empty() {
+            true => vec![],
+            false => {
+                conn.query(
+                    "SELECT kind, id, deleted_at FROM tombstones WHERE deleted_at >= $1 ORDER BY deleted_at",
+                    &[&since],
+                )
+                .await?
+            }
         };
 
-        let query = "UPDATE articles SET read = NOT read, read_date = $1 WHERE id = $2";
+        Ok(sync::Changes {
+            checkpoint,
+            articles: articles.iter().map(|r| r.into()).collect(),
+            feeds: feeds.iter().map(|r| r.into()).collect(),
+            deleted: deleted.iter().map(|r| r.into()).collect(),
+        })
+    }
+
+    // auto_read_articles marks unread articles read once they were published more than the auto read days of their feed before now
+    // unlike pruning the articles are kept, they show up in the read list from now on
+    pub(crate) async fn auto_read_articles(&self, now: String) -> Result<u64> {
+        let conn = &mut self.change().await;
+        let query = "UPDATE articles a SET read = true, read_date = $1::text FROM feeds f WHERE a.feed_id = f.id AND f.auto_read_days > 0 AND a.read = false AND a.published::timestamptz < $1::text::timestamptz - make_interval(days => f.auto_read_days)";
+        Ok(conn.execute(query, &[&now]).await?)
+    }
+
+    // prune_articles moves read articles that are not favorited to the trash, only once they were read and published before the cutoff so they have usually left the feed and will not be ingested again
+    // the statement sees newsletter bodies as they were before the delete cascades to them, so they go to the trash with their article
+    pub(crate) async fn prune_articles(&self, before: String) -> Result<u64> {
+        let conn = &mut self.change().await;
+        let query = "WITH pruned AS (DELETE FROM articles WHERE read AND NOT favorited AND read_date <> '-1' AND read_date < $1 AND published < $1 RETURNING *) INSERT INTO trashed_articles (id, article, newsletter_body, trashed_at) SELECT p.id, to_jsonb(p), b.body, $2 FROM pruned p LEFT JOIN newsletter_bodies b ON b.article_id = p.id ON CONFLICT (id) DO UPDATE SET article = EXCLUDED.article, newsletter_body = EXCLUDED.newsletter_body, trashed_at = EXCLUDED.trashed_at";
         let tx = conn.transaction().await?;
-        tx.execute(query, &[&timestamp, &a.clone().id]).await?;
+        let deleted = tx
+            .execute(query, &[&before, &Article::rfc3339_timestamp()])
+            .await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    // get_trash lists the articles in the trash as they were when they were pruned, most recently pruned first
+    pub(crate) async fn get_trash(&self, limit: i64) -> Result<Vec<TrashedArticle>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT (jsonb_populate_record(NULL::articles, article)).*, trashed_at FROM trashed_articles ORDER BY trashed_at DESC, id LIMIT $1";
+        let rows = conn.query(query, &[&limit]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn count_trash(&self) -> Result<i64> {
+        let conn = &self.inner.client.read().await;
+        let row = conn
+            .query_one("SELECT COUNT(*) FROM trashed_articles", &[])
+            .await?;
+        Ok(row.get(0))
+    }
+
+    // restore_article puts an article from the trash back where it was, sync clients that saw it deleted get it as changed
+    // an article its feed brought back in the meantime is a conflict, the trashed copy is kept
+    pub(crate) async fn restore_article(&self, id: String) -> Result<Article> {
+        let conn = &mut self.change().await;
+        let tx = conn.transaction().await?;
+        let trashed = tx
+            .query_opt(
+                "DELETE FROM trashed_articles WHERE id = $1 RETURNING article::text, newsletter_body",
+                &[&id],
+            )
+            .await?
+            .ok_or(Error::NotFound("article"))?;
+        // only the columns the article was trashed with are inserted, columns added since get their defaults
+        let article: &str = trashed.get(0);
+        let columns: String = tx
+            .query_one(
+                "SELECT string_agg(quote_ident(a.attname), ', ') FROM pg_attribute a WHERE a.attrelid = 'articles'::regclass AND a.attnum > 0 AND NOT a.attisdropped AND $1::text::jsonb ? a.attname",
+                &[&article],
+            )
+            .await?
+            .get(0);
+        let restore = format!(
+            "INSERT INTO articles ({0}) SELECT {0} FROM jsonb_populate_record(NULL::articles, $1::text::jsonb) RETURNING *",
+            columns
+        );
+        let row = tx
+            .query_one(restore.as_str(), &[&article])
+            .await
+            .map_err(conflict("article"))?;
+        if let Some(body) = trashed.get::<_, Option<&str>>(1) {
+            tx.execute(
+                "INSERT INTO newsletter_bodies (article_id, body) VALUES ($1, $2)",
+                &[&id, &body],
+            )
+            .await?;
+        }
+        tx.execute(
+            "DELETE FROM tombstones WHERE kind = 'article' AND id = $1",
+            &[&id],
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(Article::from(&row))
+    }
+
+    // empty_trash deletes the articles that were put in the trash before the given time for good
+    pub(crate) async fn empty_trash(&self, before: String) -> Result<u64> {
+        let conn = &mut self.change().await;
+        let query = "DELETE FROM trashed_articles WHERE trashed_at < $1";
+        Ok(conn.execute(query, &[&before]).await?)
+    }
+
+    pub(crate) async fn mark_article_wallabag_saved(
+        &self,
+        timestamp: String,
+        id: String,
+    ) -> Result<()> {
+        let conn = &mut self.change().await;
+        let query = "UPDATE articles SET wallabag_saved = $1 WHERE id = $2";
+        let tx = conn.transaction().await?;
+        tx.execute(query, &[&timestamp, &id]).await?;
         tx.commit().await?;
         Ok(())
     }
 
-    pub(crate) async fn mark_article_favorite(&self, id: String) -> Result<()> {
-        let conn = &mut self.client.lock().await;
-        let query = "UPDATE articles SET favorited = NOT favorited WHERE id = $1";
+    pub(crate) async fn get_unsynced_pocket_favorites(&self, limit: i64) -> Result<Vec<Article>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT a.* FROM articles a LEFT JOIN pocket_items p ON p.article_id = a.id WHERE a.favorited = true AND p.article_id IS NULL ORDER BY a.published ASC LIMIT $1";
+        let rows = conn.query(query, &[&limit]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn add_pocket_item(
+        &self,
+        article_id: String,
+        item_id: String,
+        timestamp: String,
+    ) -> Result<()> {
+        let conn = &mut self.inner.client.write().await;
+        let query = "INSERT INTO pocket_items (article_id, item_id, synced_at) VALUES ($1, $2, $3) ON CONFLICT (article_id) DO NOTHING";
         let tx = conn.transaction().await?;
-        tx.execute(query, &[&id]).await?;
+        tx.execute(query, &[&article_id, &item_id, &timestamp])
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn get_unsynced_readwise_favorites(&self, limit: i64) -> Result<Vec<Article>> {
+        let conn = &self.inner.client.read().await;
+        let query = "SELECT a.* FROM articles a LEFT JOIN readwise_documents r ON r.article_id = a.id WHERE a.favorited = true AND r.article_id IS NULL ORDER BY a.published ASC LIMIT $1";
+        let rows = conn.query(query, &[&limit]).await?;
+        Ok(rows.iter().map(|r| r.into()).collect())
+    }
+
+    pub(crate) async fn add_readwise_document(
+        &self,
+        article_id: String,
+        document_id: String,
+        timestamp: String,
+    ) -> Result<()> {
+        let conn = &mut self.inner.client.write().await;
+        let query = "INSERT INTO readwise_documents (article_id, document_id, synced_at) VALUES ($1, $2, $3) ON CONFLICT (article_id) DO NOTHING";
+        let tx = conn.transaction().await?;
+        tx.execute(query, &[&article_id, &document_id, &timestamp])
+            .await?;
         tx.commit().await?;
         Ok(())
     }
 
+    // mark_article_favorite toggles favorited with the same version check as mark_article_read
+    pub(crate) async fn mark_article_favorite(&self, id: String, version: &str) -> Result<Article> {
+        let conn = &mut self.change().await;
+        let query = self
+            .prepared(
+                conn,
+                "UPDATE articles SET favorited = NOT favorited WHERE id = $1 AND ($2 = '' OR updated_at = $2) RETURNING *",
+            )
+            .await?;
+        let row = conn.query_opt(&query, &[&id, &version]).await?;
+        marked(conn, row, id).await
+    }
+
     pub(crate) async fn filter(self, filter: Filter, pagination: String) -> Result<Page> {
         match filter {
             Filter::Unread => return self.get_unread_articles(pagination).await,
             Filter::Favorite => return self.get_favorited_articles(pagination).await,
             Filter::Read => return self.get_read_articles(pagination).await,
+            Filter::Folder(id) => {
+                let folder = self.get_smart_folder(id).await?;
+                let expr = query::parse(folder.expression.as_str())?;
+                return self.get_folder_articles(&expr, pagination).await;
+            }
+            Filter::Feed(id) => return self.get_feed_articles(id, pagination).await,
         }
     }
 }
@@ -366,6 +1765,120 @@ pub async fn connection(username: &str, password: &str, host: &str, port: u16) -
     });
 
     Ok(Storage {
-        client: Arc::new(Mutex::new(client)),
+        inner: Arc::new(Shared {
+            client: tokio::sync::RwLock::new(client),
+            page_size: AtomicUsize::new(LIMIT),
+            generation: AtomicU64::new(0),
+            statements: Mutex::new(HashMap::new()),
+        }),
     })
 }
+
+// keys of the settings that can be changed from the settings page
+pub const REFRESH_SECONDS: &str = "refresh_seconds";
+pub const RETENTION_DAYS: &str = "retention_days";
+pub const PAGE_SIZE: &str = "page_size";
+pub const THEME: &str = "theme";
+pub const GROUP_BY_DAY: &str = "group_by_day";
+pub const DENSITY: &str = "density";
+pub const LANGUAGE: &str = "language";
+pub const COLLAPSED_CATEGORIES: &str = "collapsed_categories";
+pub const CUSTOM_CSS: &str = "custom_css";
+// set once the setup page is finished or skipped, until then an instance without feeds opens on it
+pub const SETUP_COMPLETE: &str = "setup_complete";
+
+// custom css is kept in the settings table, this keeps a paste gone wrong from bloating every page load
+pub const MAX_CUSTOM_CSS: usize = 64 * 1024;
+
+pub const THEMES: [&str; 3] = ["system", "light", "dark"];
+// cards show the summary and thumbnail, compact fits an article on one line
+pub const DENSITIES: [&str; 2] = ["cards", "compact"];
+
+// Settings caches the settings table so pages can read it without a query, writes go through to the table
+#[derive(Clone)]
+pub struct Settings {
+    store: Storage,
+    cache: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl Settings {
+    pub async fn load(store: Storage) -> Result<Settings> {
+        let cache = store.get_settings().await?.into_iter().collect();
+        let settings = Settings {
+            store,
+            cache: Arc::new(RwLock::new(cache)),
+        };
+        settings.store.set_page_size(settings.page_size());
+        Ok(settings)
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.cache.read().unwrap().get(key).cloned()
+    }
+
+    pub async fn set(&self, key: &str, value: String) -> Result<()> {
+        self.store.set_setting(key, value.clone()).await?;
+        self.cache.write().unwrap().insert(key.to_string(), value);
+        if key == PAGE_SIZE {
+            self.store.set_page_size(self.page_size());
+        }
+        Ok(())
+    }
+
+    fn parse<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|value| value.parse().ok())
+    }
+
+    // refresh_seconds overrides the configured schedule once set
+    pub fn refresh_seconds(&self) -> Option<u64> {
+        self.parse(REFRESH_SECONDS)
+    }
+
+    // retention_days of zero keeps read articles forever
+    pub fn retention_days(&self) -> i64 {
+        self.parse(RETENTION_DAYS).unwrap_or(0)
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.parse(PAGE_SIZE)
+            .filter(|size| (1..=MAX_LIMIT).contains(size))
+            .unwrap_or(LIMIT)
+    }
+
+    pub fn group_by_day(&self) -> bool {
+        self.parse(GROUP_BY_DAY).unwrap_or(false)
+    }
+
+    pub fn density(&self) -> String {
+        self.get(DENSITY)
+            .filter(|density| DENSITIES.contains(&density.as_str()))
+            .unwrap_or(DENSITIES[0].to_string())
+    }
+
+    // language is only checked against the catalog when saved, messages fall back on their own
+    pub fn language(&self) -> String {
+        self.get(LANGUAGE)
+            .unwrap_or(crate::i18n::DEFAULT_LANGUAGE.to_string())
+    }
+
+    // collapsed_categories is kept as a json array, category names are free text
+    pub fn collapsed_categories(&self) -> Vec<String> {
+        self.get(COLLAPSED_CATEGORIES)
+            .and_then(|value| serde_json::from_str(value.as_str()).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn setup_complete(&self) -> bool {
+        self.parse(SETUP_COMPLETE).unwrap_or(false)
+    }
+
+    pub fn custom_css(&self) -> String {
+        self.get(CUSTOM_CSS).unwrap_or_default()
+    }
+
+    pub fn theme(&self) -> String {
+        self.get(THEME)
+            .filter(|theme| THEMES.contains(&theme.as_str()))
+            .unwrap_or(THEMES[0].to_string())
+    }
+}
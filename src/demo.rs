@@ -0,0 +1,92 @@
+use crate::models::AddFeed;
+use crate::scheduler::refresh;
+use crate::{db, import, report, App};
+
+// FEEDS are what --seed-demo subscribes to, each is (name, site url, feed url, category)
+const FEEDS: [(&str, &str, &str, &str); 6] = [
+    (
+        "Rust Blog",
+        "https://blog.rust-lang.org",
+        "https://blog.rust-lang.org/feed.xml",
+        "Rust",
+    ),
+    (
+        "This Week in Rust",
+        "https://this-week-in-rust.org",
+        "https://this-week-in-rust.org/rss.xml",
+        "Rust",
+    ),
+    (
+        "Hacker News",
+        "https://news.ycombinator.com",
+        "https://hnrss.org/frontpage",
+        "News",
+    ),
+    (
+        "Julia Evans",
+        "https://jvns.ca",
+        "https://jvns.ca/atom.xml",
+        "Blogs",
+    ),
+    (
+        "Simon Willison",
+        "https://simonwillison.net",
+        "https://simonwillison.net/atom/everything/",
+        "Blogs",
+    ),
+    (
+        "xkcd",
+        "https://xkcd.com",
+        "https://xkcd.com/atom.xml",
+        "Comics",
+    ),
+];
+
+// seed subscribes to the demo feeds and refreshes the ones it added, so there is something to read right away
+// feeds that are already subscribed are left as they are, seeding twice adds nothing
+pub async fn seed(app: &App) {
+    let mut added = vec![];
+    for (name, site_url, feed_url, category) in FEEDS {
+        let mut feed = AddFeed {
+            feed_name: name.to_string(),
+            site_url: site_url.to_string(),
+            feed_url: feed_url.to_string(),
+            use_socks: None,
+            category: category.to_string(),
+        };
+        if let Err(e) = import::invalid(feed.validate()) {
+            println!("skipped demo feed {}: {}", feed_url, e);
+            continue;
+        }
+        match app.store.add_feed(feed).await {
+            Ok(feed) => added.push(feed),
+            Err(e) if matches!(e.downcast_ref(), Some(db::Error::Conflict(_))) => {}
+            Err(e) => {
+                println!("could not add demo feed {}: {}", feed_url, e);
+                report::error(&e);
+            }
+        }
+    }
+
+    let mut refreshed = 0;
+    for f in added.iter() {
+        match refresh(
+            app.store.clone(),
+            &app.fetcher,
+            &app.pipeline,
+            app.dispatcher.clone(),
+            app.events.clone(),
+            app.images.clone(),
+            f.clone(),
+        )
+        .await
+        {
+            Ok(_) => refreshed += 1,
+            Err(e) => {
+                println!("error updating feed {}: {}", f.feed_url, e);
+                report::feed_error(&e, f);
+            }
+        }
+    }
+    println!("seeded {} demo feeds, {} refreshed", added.len(), refreshed);
+}
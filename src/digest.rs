@@ -0,0 +1,67 @@
+use crate::models::Article;
+use crate::{db, mail, scheduler};
+use anyhow::Result;
+use askama::Template;
+use futures::stream::StreamExt;
+
+// most articles included in a single digest
+const DIGEST_LIMIT: i64 = 200;
+
+struct Group {
+    feed: String,
+    articles: Vec<Article>,
+}
+
+#[derive(Template)]
+#[template(path = "digest.html")]
+struct DigestTemplate {
+    total: usize,
+    groups: Vec<Group>,
+}
+
+// send_digest emails the unread articles grouped by feed, returning how many were included
+pub async fn send_digest(mailer: &mail::Mailer, store: &db::Storage, to: &str) -> Result<usize> {
+    let articles = store.get_digest_articles(DIGEST_LIMIT).await?;
+    if articles.is_empty() {
+        return Ok(0);
+    }
+
+    let total = articles.len();
+    let mut groups: Vec<Group> = vec![];
+    for article in articles {
+        match groups.last_mut() {
+            Some(group) if group.feed == article.feed => group.articles.push(article),
+            _ => groups.push(Group {
+                feed: article.feed.clone(),
+                articles: vec![article],
+            }),
+        }
+    }
+
+    let body = DigestTemplate { total, groups }.render()?;
+    mailer
+        .send_html(
+            to,
+            format!("Feedreader digest: {} unread articles", total),
+            body,
+        )
+        .await?;
+    Ok(total)
+}
+
+pub async fn run(
+    mailer: mail::Mailer,
+    store: db::Storage,
+    schedule: scheduler::Schedule,
+    to: String,
+) {
+    schedule
+        .ticks(false)
+        .for_each(|_| async {
+            match send_digest(&mailer, &store, to.as_str()).await {
+                Ok(count) => println!("sent digest with {} articles", count),
+                Err(e) => println!("could not send digest: {}", e),
+            }
+        })
+        .await;
+}
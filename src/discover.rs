@@ -0,0 +1,199 @@
+use crate::fetch::{self, Fetched};
+use crate::models;
+use anyhow::Result;
+use reqwest::Url;
+use serde::Serialize;
+use std::io::Read;
+
+// bytes of a page read while looking for its feed links, they sit in the head so the start of the page is enough
+const MAX_PAGE_BYTES: u64 = 512 * 1024;
+// the link types that point at a feed, json feeds are often announced as plain json
+const FEED_TYPES: [&str; 4] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+    "application/json",
+];
+
+// Found is a feed a page leads to, feed_id is set when it is subscribed already
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Found {
+    pub url: String,
+    pub title: String,
+    pub feed_id: Option<String>,
+}
+
+// feeds returns the feeds an html page links to, or the page itself when it is a feed
+pub async fn feeds(fetched: Fetched) -> Result<Vec<Found>> {
+    let url = fetched.url.clone();
+    let base = Url::parse(url.as_str())?;
+    let content_type = fetched.content_type.clone().unwrap_or_default();
+    let content = fetched.feed_content()?;
+
+    if !is_html(content_type.as_str()) {
+        return match fetch::parse(content, url.clone()).await {
+            Ok(feed) => Ok(vec![Found {
+                title: feed
+                    .title
+                    .map(|t| models::plain_text(t.content.as_str()))
+                    .unwrap_or_default(),
+                url,
+                feed_id: None,
+            }]),
+            Err(_) => Ok(vec![]),
+        };
+    }
+
+    let page = page(content).await?;
+    Ok(links(page.as_str(), &base))
+}
+
+// page reads the start of an html page on the blocking pool, a page can be as large as a feed
+pub(crate) async fn page(content: fetch::Content) -> Result<String> {
+    let page = tokio::task::spawn_blocking(move || -> std::io::Result<Vec<u8>> {
+        let mut page = vec![];
+        content.take(MAX_PAGE_BYTES).read_to_end(&mut page)?;
+        Ok(page)
+    })
+    .await??;
+    Ok(String::from_utf8_lossy(&page).into_owned())
+}
+
+pub(crate) fn is_html(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    mime.eq_ignore_ascii_case("text/html") || mime.eq_ignore_ascii_case("application/xhtml+xml")
+}
+
+// links reads the link tags of a page and keeps the alternates of a feed type, relative hrefs are resolved against the page
+pub fn links(html: &str, base: &Url) -> Vec<Found> {
+    let mut found: Vec<Found> = vec![];
+    for attributes in tags(html, "link") {
+        let get = |name: &str| attribute(&attributes, name);
+        let alternate = get("rel")
+            .split_whitespace()
+            .any(|rel| rel.eq_ignore_ascii_case("alternate"));
+        let kind = get("type").trim().to_ascii_lowercase();
+        if !alternate || !FEED_TYPES.contains(&kind.as_str()) {
+            continue;
+        }
+        let url = match base.join(unescape(get("href")).as_str()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => url.to_string(),
+            _ => continue,
+        };
+        if found.iter().any(|f| f.url == url) {
+            continue;
+        }
+        found.push(Found {
+            url,
+            title: models::plain_text(unescape(get("title")).as_str()),
+            feed_id: None,
+        });
+    }
+    found
+}
+
+// tags returns the attributes of every start tag with the name, the tags are found by scanning rather than parsing the page
+pub(crate) fn tags(html: &str, name: &str) -> Vec<Vec<(String, String)>> {
+    // ascii lowercasing keeps every byte where it was, so offsets found in lower index html as well
+    let lower = html.to_ascii_lowercase();
+    let open = format!("<{}", name);
+    let mut tags = vec![];
+    let mut at = 0;
+    while let Some(start) = lower[at..].find(open.as_str()) {
+        let start = at + start + open.len();
+        let end = match lower[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        at = end;
+        if html[start..].starts_with(|c: char| c.is_whitespace() || c == '/') {
+            tags.push(attributes(&html[start..end]));
+        }
+    }
+    tags
+}
+
+// attribute is the value of a named attribute, empty when the tag does not have it
+pub(crate) fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> &'a str {
+    attributes
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, value)| value.as_str())
+        .unwrap_or_default()
+}
+
+// attributes splits the inside of a tag into lowercase names and their values, quoted or not
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    let mut chars = tag.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == '/').is_some() {}
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=' && *c != '/') {
+            name.push(c.to_ascii_lowercase());
+        }
+        if name.is_empty() {
+            return attributes;
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next_if(|c| *c == '"' || *c == '\'') {
+                Some(quote) => value.extend(chars.by_ref().take_while(|c| *c != quote)),
+                None => {
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        value.push(c);
+                    }
+                }
+            }
+        }
+        attributes.push((name, value));
+    }
+}
+
+// unescape decodes the entities an attribute value is likely to hold
+pub(crate) fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_feed_links_of_a_page() {
+        let base = Url::parse("https://example.com/blog/post").unwrap();
+        let html = r#"<!doctype html><html><head>
+            <link rel="stylesheet" href="/style.css">
+            <LINK REL="alternate" TYPE="application/rss+xml" TITLE="Posts &amp; notes" HREF="/feed.xml">
+            <link type='application/atom+xml' rel='alternate home' href=atom.xml />
+            <link rel="alternate" type="application/rss+xml" href="https://example.com/feed.xml">
+            <link rel="alternate" hreflang="de" href="/de/">
+            <link rel="alternate" type="application/rss+xml" href="javascript:alert(1)">
+            <linked rel="alternate" type="application/rss+xml" href="/not-a-link.xml">
+            </head><body><p>link rel="alternate"</p></body></html>"#;
+        let found: Vec<(String, String)> = links(html, &base)
+            .into_iter()
+            .map(|f| (f.url, f.title))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "https://example.com/feed.xml".to_string(),
+                    "Posts & notes".to_string()
+                ),
+                (
+                    "https://example.com/blog/atom.xml".to_string(),
+                    "".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::models::Article;
+use crate::{db, mail, scheduler};
+use anyhow::Result;
+use askama::Template;
+use chrono::Utc;
+use futures::stream::StreamExt;
+use std::io::{Cursor, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+// most articles bundled into a single book
+pub const EPUB_LIMIT: i64 = 200;
+
+pub const CONTENT_TYPE: &str = "application/epub+zip";
+
+struct Chapter {
+    id: String,
+    article: Article,
+}
+
+#[derive(Template)]
+#[template(path = "epub/container.xml")]
+struct ContainerTemplate {}
+
+#[derive(Template)]
+#[template(path = "epub/content.opf", escape = "html")]
+struct ContentTemplate<'a> {
+    identifier: &'a str,
+    title: &'a str,
+    modified: &'a str,
+    chapters: &'a [Chapter],
+}
+
+#[derive(Template)]
+#[template(path = "epub/nav.xhtml", escape = "html")]
+struct NavTemplate<'a> {
+    title: &'a str,
+    chapters: &'a [Chapter],
+}
+
+#[derive(Template)]
+#[template(path = "epub/toc.ncx", escape = "html")]
+struct TocTemplate<'a> {
+    identifier: &'a str,
+    title: &'a str,
+    chapters: &'a [Chapter],
+}
+
+#[derive(Template)]
+#[template(path = "epub/chapter.xhtml", escape = "html")]
+struct ChapterTemplate<'a> {
+    article: &'a Article,
+}
+
+// bundle builds an epub with a chapter per article
+// article content is not stored, so each chapter holds the article details and a link to the original
+pub fn bundle(title: &str, articles: Vec<Article>) -> Result<Vec<u8>> {
+    let now = Utc::now();
+    let identifier = format!("urn:feedreader:{}", now.timestamp_millis());
+    let modified = now.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let chapters: Vec<Chapter> = articles
+        .into_iter()
+        .enumerate()
+        .map(|(i, article)| Chapter {
+            id: format!("article{}", i + 1),
+            article,
+        })
+        .collect();
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    // the mimetype has to be the first entry and stored uncompressed
+    zip.start_file(
+        "mimetype",
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(CONTENT_TYPE.as_bytes())?;
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("META-INF/container.xml", options)?;
+    zip.write_all(ContainerTemplate {}.render()?.as_bytes())?;
+
+    zip.start_file("OEBPS/content.opf", options)?;
+    let content = ContentTemplate {
+        identifier: identifier.as_str(),
+        title,
+        modified: modified.as_str(),
+        chapters: &chapters,
+    };
+    zip.write_all(content.render()?.as_bytes())?;
+
+    zip.start_file("OEBPS/nav.xhtml", options)?;
+    let nav = NavTemplate {
+        title,
+        chapters: &chapters,
+    };
+    zip.write_all(nav.render()?.as_bytes())?;
+
+    // kindle and older readers still look for the epub 2 table of contents
+    zip.start_file("OEBPS/toc.ncx", options)?;
+    let toc = TocTemplate {
+        identifier: identifier.as_str(),
+        title,
+        chapters: &chapters,
+    };
+    zip.write_all(toc.render()?.as_bytes())?;
+
+    for chapter in chapters.iter() {
+        zip.start_file(format!("OEBPS/{}.xhtml", chapter.id), options)?;
+        let page = ChapterTemplate {
+            article: &chapter.article,
+        };
+        zip.write_all(page.render()?.as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+pub fn title() -> String {
+    format!("Feedreader {}", Utc::now().format("%Y-%m-%d"))
+}
+
+// Kindle emails bundles to a send-to-kindle address
+#[derive(Clone)]
+pub struct Kindle {
+    pub mailer: mail::Mailer,
+    pub to: String,
+}
+
+impl Kindle {
+    // send mails the given articles, or all unread articles when there are none, returning how many were included
+    pub async fn send(&self, store: &db::Storage, articles: Vec<Article>) -> Result<usize> {
+        let articles = match articles.is_empty() {
+            true => store.get_digest_articles(EPUB_LIMIT).await?,
+            false => articles,
+        };
+        if articles.is_empty() {
+            return Ok(0);
+        }
+
+        let total = articles.len();
+        let title = title();
+        let book = bundle(title.as_str(), articles)?;
+        self.mailer
+            .send_attachment(
+                self.to.as_str(),
+                title.clone(),
+                format!("{} articles attached.", total),
+                format!("{}.epub", title.replace(' ', "-").to_lowercase()),
+                CONTENT_TYPE,
+                book,
+            )
+            .await?;
+        Ok(total)
+    }
+
+    pub async fn run(&self, store: db::Storage, schedule: scheduler::Schedule) {
+        schedule
+            .ticks(false)
+            .for_each(|_| async {
+                match self.send(&store, vec![]).await {
+                    Ok(count) => println!("sent {} articles to kindle", count),
+                    Err(e) => println!("could not send to kindle: {}", e),
+                }
+            })
+            .await;
+    }
+}
@@ -0,0 +1,90 @@
+use crate::models::{Article, Feed};
+use crate::{db, mqtt};
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+// most events kept for /api/events
+const EVENT_LIMIT: usize = 50;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Event {
+    pub feed_id: String,
+    pub feed: String,
+    pub count: usize,
+    pub titles: Vec<String>,
+    pub at: String,
+}
+
+// Events keeps the recent new article events and publishes them to mqtt when a broker is configured
+#[derive(Clone, Default)]
+pub struct Events {
+    recent: Arc<Mutex<VecDeque<Event>>>,
+    mqtt: Option<Arc<mqtt::Publisher>>,
+    topic: Arc<str>,
+}
+
+impl Events {
+    pub fn new(mqtt: Option<mqtt::Publisher>, topic: String) -> Self {
+        Events {
+            recent: Arc::new(Mutex::new(VecDeque::new())),
+            mqtt: mqtt.map(Arc::new),
+            topic: topic.into(),
+        }
+    }
+
+    pub async fn recent(&self) -> Vec<Event> {
+        self.recent.lock().await.iter().cloned().collect()
+    }
+
+    // record takes the titles of the articles a refresh added
+    pub async fn record(&self, store: &db::Storage, feed: &Feed, titles: Vec<String>) {
+        let event = Event {
+            feed_id: feed.id.clone(),
+            feed: feed.name.clone(),
+            count: titles.len(),
+            titles,
+            at: Article::rfc3339_timestamp(),
+        };
+
+        {
+            let mut recent = self.recent.lock().await;
+            recent.push_front(event.clone());
+            recent.truncate(EVENT_LIMIT);
+        }
+
+        let publisher = match self.mqtt.clone() {
+            Some(publisher) => publisher,
+            None => return,
+        };
+
+        let unread = match store.count_unread_articles().await {
+            Ok(unread) => unread,
+            Err(e) => {
+                println!("could not count unread articles: {}", e);
+                return;
+            }
+        };
+
+        let messages = vec![
+            mqtt::Publish {
+                topic: format!("{}/unread", self.topic),
+                payload: unread.to_string().into_bytes(),
+                retain: true,
+            },
+            mqtt::Publish {
+                topic: format!("{}/new_articles", self.topic),
+                payload: serde_json::to_vec(&event).unwrap_or_default(),
+                retain: false,
+            },
+        ];
+
+        // a slow or missing broker should not hold up the refresh
+        tokio::spawn(async move {
+            if let Err(e) = publisher.publish(messages).await {
+                println!("could not publish to mqtt: {}", e);
+            }
+        });
+    }
+}
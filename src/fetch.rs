@@ -0,0 +1,959 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use encoding_rs::{Encoding, UTF_8};
+use feed_rs::parser::{self, ParseErrorKind, ParseFeedError};
+use ipnet::IpNet;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE, LOCATION};
+use reqwest::{redirect, Client, NoProxy, Proxy, Response, Url};
+use rweb::hyper::client::connect::dns::Name;
+use siphasher::sip128::{Hasher128, SipHasher};
+use std::fmt;
+use std::hash::Hasher;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::time;
+
+const MAX_REDIRECTS: usize = 5;
+const REQUEST_TIMEOUT_SECONDS: u64 = 30;
+const CONNECT_TIMEOUT_SECONDS: u64 = 10;
+const RETRY_BACKOFF_MILLIS: u64 = 500;
+// bodies past this size are spooled to a temporary file as they arrive instead of being held in memory
+const SPOOL_THRESHOLD_BYTES: usize = 1024 * 1024;
+// bodies larger than this are refused, so a broken or hostile server can't fill the disk through the spool
+pub const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+// the start of the body is kept in memory to tell feeds from html pages
+const HEAD_BYTES: usize = 512;
+// the keys feed_rs hashes made up entry ids with
+const GENERATED_ID_KEYS: (u64, u64) = (0x5d78_4074_2887_2d60, 0x90ee_ca4c_90a5_e228);
+
+#[derive(Debug)]
+pub enum FetchError {
+    NotAFeed(String),
+    UnknownCharset(String),
+    Parse(String),
+    Spool(String),
+    TooLarge(usize),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FetchError::NotAFeed(received) => {
+                write!(f, "not a feed: received {} content", received)
+            }
+            FetchError::UnknownCharset(charset) => write!(f, "unknown charset: {}", charset),
+            FetchError::Parse(e) => write!(f, "could not parse feed: {}", e),
+            FetchError::Spool(e) => write!(f, "could not read spooled feed: {}", e),
+            FetchError::TooLarge(max) => write!(f, "response is larger than {} bytes", max),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+pub struct Fetched {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub content_type: Option<String>,
+    pub body: Body,
+}
+
+// Content is a feed ready to be parsed, read from memory or from the spool file
+pub type Content = Box<dyn Read + Send>;
+
+pub enum Body {
+    Memory(Vec<u8>),
+    // the file is unlinked as soon as it is created, it goes away when dropped
+    File {
+        file: std::fs::File,
+        len: usize,
+        head: Vec<u8>,
+    },
+}
+
+impl Body {
+    // read stops at max_bytes, a response that says it is larger is not read at all
+    async fn read(mut response: Response, max_bytes: usize) -> Result<Body> {
+        let announced = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse::<u64>().ok());
+        if announced.is_some_and(|len| len > max_bytes as u64) {
+            return Err(FetchError::TooLarge(max_bytes).into());
+        }
+        let mut memory = vec![];
+        let mut spool: Option<(tokio::fs::File, usize)> = None;
+        let mut read = 0;
+        while let Some(chunk) = response.chunk().await? {
+            read += chunk.len();
+            if read > max_bytes {
+                return Err(FetchError::TooLarge(max_bytes).into());
+            }
+            match spool.as_mut() {
+                Some((file, len)) => {
+                    file.write_all(&chunk).await?;
+                    *len += chunk.len();
+                }
+                None if memory.len() + chunk.len() > SPOOL_THRESHOLD_BYTES => {
+                    let mut file = tokio::fs::File::from_std(tempfile::tempfile()?);
+                    file.write_all(&memory).await?;
+                    file.write_all(&chunk).await?;
+                    spool = Some((file, memory.len() + chunk.len()));
+                    memory.truncate(HEAD_BYTES);
+                }
+                None => memory.extend_from_slice(&chunk),
+            }
+        }
+
+        match spool {
+            Some((mut file, len)) => {
+                file.flush().await?;
+                let mut file = file.into_std().await;
+                file.seek(SeekFrom::Start(0))?;
+                Ok(Body::File {
+                    file,
+                    len,
+                    head: memory,
+                })
+            }
+            None => Ok(Body::Memory(memory)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Body::Memory(body) => body.len(),
+            Body::File { len, .. } => *len,
+        }
+    }
+
+    fn head(&self) -> &[u8] {
+        match self {
+            Body::Memory(body) => &body[..body.len().min(HEAD_BYTES)],
+            Body::File { head, .. } => head.as_slice(),
+        }
+    }
+
+    pub fn into_vec(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Body::Memory(body) => Ok(body),
+            Body::File { mut file, len, .. } => {
+                let mut body = Vec::with_capacity(len);
+                file.read_to_end(&mut body)?;
+                Ok(body)
+            }
+        }
+    }
+
+    fn into_content(self) -> Content {
+        match self {
+            Body::Memory(body) => Box::new(Cursor::new(body)),
+            Body::File { file, .. } => Box::new(std::io::BufReader::new(file)),
+        }
+    }
+}
+
+// parse runs on the blocking pool, a feed several megabytes long would otherwise stall the runtime thread
+// url is where the feed was fetched from, relative entry links are resolved against it
+pub async fn parse(content: Content, url: String) -> Result<feed_rs::model::Feed, FetchError> {
+    tokio::task::spawn_blocking(move || {
+        let mut content = BufReader::new(content);
+        let start = content
+            .fill_buf()
+            .map(|head| String::from_utf8_lossy(&head[..head.len().min(HEAD_BYTES)]).to_string())
+            .unwrap_or_default();
+        let mut feed = parser::parse_with_uri(content, Some(url.as_str()))
+            .map_err(|e| parse_error(start.as_str(), e))?;
+        clear_generated_ids(&mut feed);
+        if let Ok(base) = Url::parse(url.as_str()) {
+            resolve_links(&mut feed, &base);
+        }
+        Ok(feed)
+    })
+    .await
+    .map_err(|e| FetchError::Parse(e.to_string()))?
+}
+
+// parse_error tells a body that was never a feed from a feed that is broken
+// a document without a feed root, an html page or something that is neither xml nor json is not a feed
+fn parse_error(start: &str, e: ParseFeedError) -> FetchError {
+    let start = start
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .to_lowercase();
+    let received = if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        "html"
+    } else if start.starts_with('<') {
+        "xml"
+    } else if start.starts_with('{') {
+        "json"
+    } else {
+        "unrecognized"
+    };
+    match e {
+        ParseFeedError::ParseError(ParseErrorKind::NoFeedRoot) => {
+            FetchError::NotAFeed(received.to_string())
+        }
+        _ if received == "html" || received == "unrecognized" => {
+            FetchError::NotAFeed(received.to_string())
+        }
+        e => FetchError::Parse(e.to_string()),
+    }
+}
+
+// clear_generated_ids empties the entry ids feed_rs made up for entries without a guid
+// they hash the link and title, so an edited title would look like a new entry, articles fall back to their link instead
+// an entry without a link keeps its made up id, it has nothing else to tell it from the other entries
+// the keys and what is hashed are feed_rs internals, Cargo.toml pins its version and missing_guids.xml checks them
+fn clear_generated_ids(feed: &mut feed_rs::model::Feed) {
+    for entry in feed.entries.iter_mut() {
+        let Some(link) = entry.links.first() else {
+            continue;
+        };
+        let mut hasher = SipHasher::new_with_keys(GENERATED_ID_KEYS.0, GENERATED_ID_KEYS.1);
+        hasher.write(link.href.as_bytes());
+        if let Some(title) = &entry.title {
+            hasher.write(title.content.as_bytes());
+        }
+        let hash = hasher.finish128();
+        if entry.id == format!("{:x}{:x}", hash.h1, hash.h2) {
+            entry.id.clear();
+        }
+    }
+}
+
+// resolve_links makes the remaining relative entry links absolute
+// feed_rs already resolves xml feeds against the url and xml:base, json feeds are left as written
+fn resolve_links(feed: &mut feed_rs::model::Feed, base: &Url) {
+    for link in feed.entries.iter_mut().flat_map(|e| e.links.iter_mut()) {
+        if link.href.is_empty() || Url::parse(link.href.as_str()).is_ok() {
+            continue;
+        }
+        if let Ok(resolved) = base.join(link.href.as_str()) {
+            link.href = resolved.to_string();
+        }
+    }
+}
+
+impl Fetched {
+    // content_warning notes a body served with a content type that is not a feed type
+    // servers get this wrong often enough that the body is parsed anyway, only a failed parse rejects it
+    pub fn content_warning(&self) -> Option<String> {
+        let content_type = self.content_type.clone().unwrap_or_default().to_lowercase();
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+        match mime {
+            "" => Some("served without a content type".to_string()),
+            _ if mime.ends_with("xml") || mime.ends_with("json") => None,
+            _ => Some(format!("served as {} instead of a feed type", mime)),
+        }
+    }
+
+    // feed_content transcodes the body to utf-8, whether it is a feed is left to the parser
+    // the encoding comes from a byte order mark, then the charset of the content type, then the xml declaration
+    pub fn feed_content(self) -> Result<Content, FetchError> {
+        let content_type = self.content_type.unwrap_or_default().to_lowercase();
+
+        // utf-16 feeds only look like xml once decoded, decoding also drops the byte order mark
+        let bom = Encoding::for_bom(self.body.head()).map(|(encoding, _)| encoding);
+        let (head, _, _) = bom.unwrap_or(UTF_8).decode(self.body.head());
+        let start = head.trim_start().to_lowercase();
+
+        let charset = content_type
+            .split(';')
+            .skip(1)
+            .filter_map(|param| param.trim().strip_prefix("charset="))
+            .map(|charset| charset.trim_matches('"').to_string())
+            .next();
+
+        let encoding = match (bom, charset) {
+            (Some(encoding), _) => encoding,
+            (None, Some(charset)) => match Encoding::for_label(charset.as_bytes()) {
+                Some(encoding) => encoding,
+                None => return Err(FetchError::UnknownCharset(charset)),
+            },
+            (None, None) => match prolog_encoding(start.as_str()) {
+                Some(encoding) => encoding,
+                None => return Ok(self.body.into_content()),
+            },
+        };
+
+        if encoding == UTF_8 {
+            return Ok(self.body.into_content());
+        }
+
+        // transcoding needs the whole body, other charsets are rare enough to read a spooled feed back
+        let body = self
+            .body
+            .into_vec()
+            .map_err(|e| FetchError::Spool(e.to_string()))?;
+        let (decoded, _, _) = encoding.decode(body.as_slice());
+        Ok(Box::new(Cursor::new(
+            declare_utf8(decoded.as_ref()).into_bytes(),
+        )))
+    }
+}
+
+// prolog_encoding is the encoding named by the xml declaration, unknown names are left for the parser to reject
+fn prolog_encoding(head: &str) -> Option<&'static Encoding> {
+    let prolog = head.strip_prefix("<?xml")?;
+    let prolog = &prolog[..prolog.find("?>")?];
+    let value = prolog[prolog.find("encoding=")? + "encoding=".len()..].trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let label = value[1..].split(quote).next()?;
+    Encoding::for_label(label.as_bytes())
+}
+
+// declare_utf8 rewrites the encoding in the xml prolog so the parser does not decode the transcoded body a second time
+fn declare_utf8(content: &str) -> String {
+    let prolog_end = match content.trim_start().starts_with("<?xml") {
+        true => content.find("?>"),
+        false => None,
+    };
+
+    let end = match prolog_end {
+        Some(end) => end,
+        None => return content.to_string(),
+    };
+
+    let prolog = &content[..end];
+    let rewritten = match prolog.find("encoding=") {
+        Some(start) => {
+            let value_start = start + "encoding=".len();
+            let quote = prolog[value_start..].chars().next().unwrap_or('"');
+            let value_end = prolog[value_start + 1..]
+                .find(quote)
+                .map(|i| value_start + 1 + i + 1)
+                .unwrap_or(prolog.len());
+            format!(
+                "{}encoding=\"utf-8\"{}",
+                &prolog[..start],
+                &prolog[value_end..]
+            )
+        }
+        None => prolog.to_string(),
+    };
+
+    format!("{}{}", rewritten, &content[end..])
+}
+
+enum Allowed {
+    Host(String),
+    Network(IpNet),
+}
+
+impl Allowed {
+    fn parse(s: &str) -> Allowed {
+        match s.parse::<IpNet>() {
+            Ok(network) => Allowed::Network(network),
+            Err(_) => match s.parse::<IpAddr>() {
+                Ok(ip) => Allowed::Network(IpNet::from(ip)),
+                Err(_) => Allowed::Host(s.to_lowercase()),
+            },
+        }
+    }
+}
+
+// Refused is the error of a connection Guarded would not make
+#[derive(Debug)]
+struct Refused(String);
+
+impl fmt::Display for Refused {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+impl std::error::Error for Refused {}
+
+// Guarded resolves the hosts the client connects to and refuses internal addresses, validate checks the url before
+// the request but the client resolves again when it connects, a host that answers differently the second time is caught here
+// proxies are the hosts of the configured http proxies, the client resolves those instead of feed hosts and they may be internal
+struct Guarded {
+    allowed: Arc<Vec<Allowed>>,
+    proxies: Vec<String>,
+}
+
+impl Resolve for Guarded {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = self.allowed.clone();
+        let host = name.as_str().to_lowercase();
+        let trusted = self.proxies.contains(&host);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if !trusted && !is_allowed_host(&allowed, host.as_str()) {
+                if let Some(addr) = addrs
+                    .iter()
+                    .find(|addr| is_internal(addr.ip()) && !is_allowed_ip(&allowed, addr.ip()))
+                {
+                    return Err(Box::new(Refused(format!(
+                        "refusing to connect to internal address: {} resolves to {}",
+                        host,
+                        addr.ip()
+                    )))
+                        as Box<dyn std::error::Error + Send + Sync>);
+                }
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Proxied tells which urls the client sends through an http proxy the way reqwest picks one, the configured proxy
+// takes every scheme and otherwise HTTP_PROXY or HTTPS_PROXY their own, NO_PROXY exempts hosts from either
+struct Proxied {
+    schemes: Vec<&'static str>,
+    exempt: Vec<String>,
+}
+
+impl Proxied {
+    fn new(proxy: Option<&str>) -> Proxied {
+        let set = |names: [&str; 2]| {
+            names
+                .into_iter()
+                .filter_map(|name| std::env::var(name).ok())
+                .any(|value| !value.trim().is_empty())
+        };
+        let schemes = match proxy {
+            Some(_) => vec!["http", "https"],
+            None => [
+                ("http", set(["HTTP_PROXY", "http_proxy"])),
+                ("https", set(["HTTPS_PROXY", "https_proxy"])),
+            ]
+            .into_iter()
+            .filter_map(|(scheme, set)| set.then_some(scheme))
+            .collect(),
+        };
+        let exempt = std::env::var("NO_PROXY")
+            .or_else(|_| std::env::var("no_proxy"))
+            .unwrap_or_default()
+            .split(',')
+            .map(|host| host.trim().trim_start_matches('.').to_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+        Proxied { schemes, exempt }
+    }
+
+    // applies is whether the proxy is the one that resolves host, a host is exempt by name or as a subdomain
+    fn applies(&self, url: &Url, host: &str) -> bool {
+        self.schemes.contains(&url.scheme())
+            && !self.exempt.iter().any(|exempt| {
+                exempt == "*" || host == exempt || host.ends_with(format!(".{}", exempt).as_str())
+            })
+    }
+}
+
+#[derive(Clone)]
+pub struct Fetcher {
+    client: Client,
+    socks_client: Option<Client>,
+    allowed: Arc<Vec<Allowed>>,
+    proxied: Arc<Proxied>,
+    retries: u32,
+}
+
+impl Fetcher {
+    // without an explicit proxy the client falls back to HTTP_PROXY, HTTPS_PROXY and NO_PROXY from the environment
+    // through a proxy it is the proxy that resolves feed hosts, only the check in validate applies then
+    pub fn new(
+        proxy: Option<String>,
+        socks_proxy: Option<String>,
+        allowed_hosts: Option<String>,
+        retries: u32,
+    ) -> Result<Fetcher> {
+        let timeout = Duration::from_secs(REQUEST_TIMEOUT_SECONDS);
+        let allowed: Arc<Vec<Allowed>> = Arc::new(
+            allowed_hosts
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(Allowed::parse)
+                .collect(),
+        );
+
+        // redirects are followed by hand so every hop goes through the same url checks
+        let mut builder = Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(timeout)
+            .dns_resolver(Arc::new(Guarded {
+                allowed: allowed.clone(),
+                proxies: proxy_hosts(proxy.as_deref()),
+            }));
+        let proxied = Arc::new(Proxied::new(proxy.as_deref()));
+        if let Some(url) = proxy {
+            builder = builder.proxy(Proxy::all(url.as_str())?.no_proxy(NoProxy::from_env()));
+        }
+
+        // feeds marked use_socks are fetched through this client only, e.g. socks5h://127.0.0.1:9050 for tor
+        let socks_client = match socks_proxy {
+            Some(url) => Some(
+                Client::builder()
+                    .redirect(redirect::Policy::none())
+                    .timeout(timeout)
+                    .proxy(Proxy::all(url.as_str())?)
+                    .build()?,
+            ),
+            None => None,
+        };
+
+        Ok(Fetcher {
+            client: builder.build()?,
+            socks_client,
+            allowed,
+            proxied,
+            retries,
+        })
+    }
+
+    // get retries transient failures (timeouts, dropped connections, 5xx and 429) with a short backoff
+    pub async fn get(&self, url: &str, use_socks: bool) -> Result<Fetched> {
+        self.get_at_most(url, use_socks, MAX_BODY_BYTES).await
+    }
+
+    // get_at_most is get for a body that may not be larger than max_bytes, a larger one is not retried
+    pub async fn get_at_most(
+        &self,
+        url: &str,
+        use_socks: bool,
+        max_bytes: usize,
+    ) -> Result<Fetched> {
+        let mut attempt = 0;
+        loop {
+            let (transient, e) = match self.send_at_most(url, use_socks, max_bytes).await {
+                Ok(fetched) if (200..=299).contains(&fetched.status) => return Ok(fetched),
+                Ok(fetched) => (
+                    is_transient_status(fetched.status),
+                    anyhow::Error::msg(format!(
+                        "HTTP status {} fetching {}",
+                        fetched.status, fetched.url
+                    )),
+                ),
+                Err(e) => (is_transient_error(&e), e),
+            };
+
+            if !transient || attempt >= self.retries {
+                return Err(e);
+            }
+
+            println!("retrying {} after transient error: {}", url, e);
+            time::sleep(Duration::from_millis(
+                RETRY_BACKOFF_MILLIS * 2u64.pow(attempt),
+            ))
+            .await;
+            attempt += 1;
+        }
+    }
+
+    // send follows redirects and returns the final response whatever its status
+    pub async fn send(&self, url: &str, use_socks: bool) -> Result<Fetched> {
+        self.send_at_most(url, use_socks, MAX_BODY_BYTES).await
+    }
+
+    async fn send_at_most(&self, url: &str, use_socks: bool, max_bytes: usize) -> Result<Fetched> {
+        let client = match (use_socks, &self.socks_client) {
+            (false, _) => &self.client,
+            (true, Some(socks_client)) => socks_client,
+            (true, None) => {
+                return Err(anyhow::Error::msg(format!(
+                    "feed {} requires a socks proxy but FEED_SOCKS_PROXY is not set",
+                    url
+                )))
+            }
+        };
+
+        let mut url = Url::parse(url)?;
+        for _ in 0..=MAX_REDIRECTS {
+            self.validate(&url, use_socks).await?;
+
+            let response = client.get(url.clone()).send().await?;
+            if response.status().is_redirection() {
+                let location = match response.headers().get(LOCATION) {
+                    Some(location) => location.to_str()?,
+                    None => {
+                        return Err(anyhow::Error::msg(format!(
+                            "redirect from {} has no location",
+                            url
+                        )))
+                    }
+                };
+                url = url.join(location)?;
+                continue;
+            }
+
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string());
+
+            let headers = response
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).to_string(),
+                    )
+                })
+                .collect();
+
+            return Ok(Fetched {
+                url: url.to_string(),
+                status: response.status().as_u16(),
+                headers,
+                content_type,
+                body: Body::read(response, max_bytes).await?,
+            });
+        }
+
+        Err(anyhow::Error::msg(format!(
+            "too many redirects fetching {}",
+            url
+        )))
+    }
+
+    async fn validate(&self, url: &Url, use_socks: bool) -> Result<()> {
+        match url.scheme() {
+            "http" | "https" => {}
+            scheme => {
+                return Err(anyhow::Error::msg(format!(
+                    "unsupported url scheme: {}",
+                    scheme
+                )))
+            }
+        }
+
+        let host = match url.host_str() {
+            Some(host) => host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_lowercase(),
+            None => return Err(anyhow::Error::msg(format!("url has no host: {}", url))),
+        };
+
+        if self.is_allowed_host(host.as_str()) {
+            return Ok(());
+        }
+
+        if host == "localhost" || host.ends_with(".localhost") {
+            return Err(anyhow::Error::msg(format!(
+                "refusing to fetch internal url: {}",
+                url
+            )));
+        }
+
+        // socks feeds are resolved by the proxy, which is trusted with onion addresses since they never resolve locally
+        // other hosts are resolved here as well, one that resolves to an internal address is refused before the proxy sees it
+        // a host that does not resolve here is left to the proxy, on a network that only lets the proxy out it is the one that can
+        let proxied = use_socks || self.proxied.applies(url, host.as_str());
+        let addresses: Vec<IpAddr> = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) if use_socks && host.ends_with(".onion") => vec![],
+            Err(_) if proxied => {
+                let port = url.port_or_known_default().unwrap_or(80);
+                match tokio::net::lookup_host((host.as_str(), port)).await {
+                    Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+                    Err(_) => vec![],
+                }
+            }
+            Err(_) => {
+                let port = url.port_or_known_default().unwrap_or(80);
+                tokio::net::lookup_host((host.as_str(), port))
+                    .await?
+                    .map(|addr| addr.ip())
+                    .collect()
+            }
+        };
+
+        for ip in addresses {
+            if is_internal(ip) && !self.is_allowed_ip(ip) {
+                return Err(anyhow::Error::msg(format!(
+                    "refusing to fetch internal url: {} resolves to {}",
+                    url, ip
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_allowed_host(&self, host: &str) -> bool {
+        is_allowed_host(&self.allowed, host)
+    }
+
+    fn is_allowed_ip(&self, ip: IpAddr) -> bool {
+        is_allowed_ip(&self.allowed, ip)
+    }
+}
+
+// outbound is the client for what the reader sends to integrations and notifiers, it goes through the feed proxy as
+// well so a network that only lets the proxy out reaches them too, their hosts are the user's own and may be internal
+// one worker sends every notification in turn, so a target that never answers is given up on rather than waited for
+pub fn outbound(proxy: Option<&str>) -> Result<Client> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+        .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECONDS));
+    if let Some(url) = proxy {
+        builder = builder.proxy(Proxy::all(url)?.no_proxy(NoProxy::from_env()));
+    }
+    Ok(builder.build()?)
+}
+
+// proxy_hosts are the hosts of the configured proxy and of the proxies the environment names
+fn proxy_hosts(proxy: Option<&str>) -> Vec<String> {
+    let from_env = [
+        "HTTP_PROXY",
+        "http_proxy",
+        "HTTPS_PROXY",
+        "https_proxy",
+        "ALL_PROXY",
+        "all_proxy",
+    ]
+    .into_iter()
+    .filter_map(|name| std::env::var(name).ok());
+    proxy
+        .map(str::to_string)
+        .into_iter()
+        .chain(from_env)
+        .filter_map(|url| {
+            let url = match url.contains("://") {
+                true => url,
+                false => format!("http://{}", url),
+            };
+            Url::parse(url.as_str())
+                .ok()?
+                .host_str()
+                .map(str::to_lowercase)
+        })
+        .collect()
+}
+
+fn is_allowed_host(allowed: &[Allowed], host: &str) -> bool {
+    allowed.iter().any(|allowed| match allowed {
+        Allowed::Host(h) => h == host,
+        Allowed::Network(_) => false,
+    })
+}
+
+fn is_allowed_ip(allowed: &[Allowed], ip: IpAddr) -> bool {
+    allowed.iter().any(|allowed| match allowed {
+        Allowed::Host(_) => false,
+        Allowed::Network(network) => network.contains(&ip),
+    })
+}
+
+// Source supplies the fetch and parse stages of a refresh, the fetcher goes over the network while tests read fixtures
+#[async_trait]
+pub trait Source: Send + Sync {
+    async fn fetch(&self, url: &str, use_socks: bool) -> Result<Fetched>;
+
+    // parse also returns a warning when the body was served with a content type that is not a feed type
+    async fn parse(&self, fetched: Fetched) -> Result<(feed_rs::model::Feed, Option<String>)> {
+        let warning = fetched.content_warning();
+        let url = fetched.url.clone();
+        let feed = parse(fetched.feed_content()?, url).await?;
+        Ok((feed, warning))
+    }
+}
+
+#[async_trait]
+impl Source for Fetcher {
+    async fn fetch(&self, url: &str, use_socks: bool) -> Result<Fetched> {
+        self.get(url, use_socks).await
+    }
+}
+
+fn is_transient_status(status: u16) -> bool {
+    status == 429 || (500..=599).contains(&status)
+}
+
+// a connection the resolver refused fails the same way on every attempt, it is not retried
+fn is_transient_error(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<reqwest::Error>() {
+        Some(e) if refused(e) => false,
+        Some(e) => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+        None => false,
+    }
+}
+
+fn refused(e: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(e) = source {
+        if e.is::<Refused>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+// is_internal covers loopback, link-local, RFC1918 and the other ranges that should never be reachable from a feed url
+// v6 addresses that carry a v4 address, mapped, compatible, 6to4 and nat64, are as internal as the address they carry
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let octets = v4.octets();
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_multicast()
+                // this network, 0.0.0.0/8, the unspecified address included
+                || octets[0] == 0
+                // carrier grade nat, 100.64.0.0/10
+                || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+                // ietf protocol assignments, 192.0.0.0/24
+                || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0)
+                // benchmarking, 198.18.0.0/15
+                || (octets[0] == 198 && (octets[1] & 0xfe) == 18)
+                // reserved, 240.0.0.0/4, the broadcast address included
+                || octets[0] >= 240
+        }
+        IpAddr::V6(v6) => {
+            let segments = v6.segments();
+            let first = segments[0];
+            let embedded = |high: u16, low: u16| {
+                let [a, b] = high.to_be_bytes();
+                let [c, d] = low.to_be_bytes();
+                is_internal(IpAddr::V4(std::net::Ipv4Addr::new(a, b, c, d)))
+            };
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // link local, fe80::/10
+                || (first & 0xffc0) == 0xfe80
+                || v6.to_ipv4_mapped().is_some_and(|v4| is_internal(IpAddr::V4(v4)))
+                // ipv4 compatible, ::a.b.c.d
+                || (segments[..6] == [0; 6] && embedded(segments[6], segments[7]))
+                // 6to4, 2002::/16 carries the v4 address in the next 32 bits
+                || (first == 0x2002 && embedded(segments[1], segments[2]))
+                // nat64, 64:ff9b::/96
+                || (segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] && embedded(segments[6], segments[7]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: Vec<u8>, content_length: Option<usize>) -> Response {
+        let mut response = rweb::http::Response::builder();
+        if let Some(len) = content_length {
+            response = response.header("content-length", len);
+        }
+        Response::from(response.body(body).unwrap())
+    }
+
+    fn too_large(read: Result<Body>) -> bool {
+        matches!(
+            read.err().and_then(|e| e.downcast::<FetchError>().ok()),
+            Some(FetchError::TooLarge(_))
+        )
+    }
+
+    #[tokio::test]
+    async fn connections_to_internal_addresses_are_refused() {
+        let resolve = |allowed: &str, proxies: Vec<String>, host: &str| {
+            Guarded {
+                allowed: Arc::new(vec![Allowed::parse(allowed)]),
+                proxies,
+            }
+            .resolve(host.parse::<Name>().unwrap())
+        };
+        assert!(resolve("", vec![], "localhost").await.is_err());
+        assert!(resolve("127.0.0.0/8", vec![], "localhost").await.is_ok());
+        assert!(resolve("", vec!["localhost".to_string()], "localhost")
+            .await
+            .is_ok());
+        // a refused connection is not worth a retry
+        let client = Client::builder()
+            .dns_resolver(Arc::new(Guarded {
+                allowed: Arc::new(vec![]),
+                proxies: vec![],
+            }))
+            .build()
+            .unwrap();
+        let refusal = client.get("http://localhost:1/").send().await.unwrap_err();
+        assert!(!is_transient_error(&refusal.into()));
+        assert_eq!(
+            proxy_hosts(Some("squid.internal:3128"))
+                .first()
+                .map(String::as_str),
+            Some("squid.internal")
+        );
+    }
+
+    #[test]
+    fn internal_ranges_are_refused() {
+        for ip in [
+            "0.1.2.3",
+            "192.0.0.8",
+            "198.19.0.1",
+            "240.0.0.1",
+            "224.0.0.251",
+            "::127.0.0.1",
+            "::ffff:10.0.0.1",
+            "2002:7f00:1::",
+            "64:ff9b::192.168.1.1",
+            "ff02::1",
+        ] {
+            assert!(is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "93.184.216.34",
+            "2002:5db8:d822::",
+            "64:ff9b::93.184.216.34",
+            "2606:2800::1",
+        ] {
+            assert!(!is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn proxies_resolve_the_hosts_they_fetch() {
+        let proxied = Proxied {
+            schemes: vec!["https"],
+            exempt: vec!["internal.example".to_string(), "10.0.0.1".to_string()],
+        };
+        let applies = |url: &str| {
+            let url = Url::parse(url).unwrap();
+            proxied.applies(&url, url.host_str().unwrap())
+        };
+        assert!(applies("https://example.com/feed"));
+        assert!(!applies("http://example.com/feed"));
+        assert!(!applies("https://internal.example/feed"));
+        assert!(!applies("https://www.internal.example/feed"));
+        assert!(!applies("https://10.0.0.1/feed"));
+    }
+
+    #[tokio::test]
+    async fn bodies_are_read_up_to_the_limit() {
+        let body = Body::read(response(vec![b'x'; 2048], None), 2048)
+            .await
+            .unwrap();
+        assert_eq!(body.len(), 2048);
+        assert!(too_large(
+            Body::read(response(vec![b'x'; 2049], None), 2048).await
+        ));
+        // a server that says up front the body is too large is not read at all
+        assert!(too_large(
+            Body::read(response(vec![], Some(4096)), 2048).await
+        ));
+        // past the spool threshold the body goes to a file and the limit still holds
+        assert!(too_large(
+            Body::read(
+                response(vec![b'x'; SPOOL_THRESHOLD_BYTES + 1], None),
+                SPOOL_THRESHOLD_BYTES
+            )
+            .await
+        ));
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::str::FromStr;
+use tokio_postgres::Row;
+
+// rows read per query while an export streams, each chunk of the response is one such page
+pub const CHUNK_SIZE: i64 = 500;
+
+// Entry is a read article as exported, timestamps stay rfc3339 in utc so scripts can parse them as they are
+#[derive(Serialize)]
+pub struct Entry {
+    pub read_date: String,
+    pub published: String,
+    pub feed: String,
+    pub feed_id: String,
+    pub title: String,
+    pub link: String,
+    pub author: String,
+    pub favorited: bool,
+    pub id: String,
+}
+
+impl From<&Row> for Entry {
+    fn from(row: &Row) -> Self {
+        Entry {
+            id: row.get(0),
+            feed: row.get(1),
+            title: row.get(2),
+            link: row.get(3),
+            author: row.get(4),
+            published: row.get(5),
+            favorited: row.get(7),
+            read_date: row.get(8),
+            feed_id: row.get(15),
+        }
+    }
+}
+
+// the csv columns, in the order Entry::csv writes them
+const CSV_HEADER: &str = "read_date,published,feed,feed_id,title,link,author,favorited,id\r\n";
+
+#[derive(Clone, Copy, Default)]
+pub enum Format {
+    // one json object per line
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Format> {
+        match s {
+            "jsonl" => Ok(Format::Jsonl),
+            "csv" => Ok(Format::Csv),
+            _ => Err(anyhow::Error::msg(format!("bad export format: {}", s))),
+        }
+    }
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Jsonl => "application/x-ndjson",
+            Format::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Jsonl => "jsonl",
+            Format::Csv => "csv",
+        }
+    }
+
+    // header is written once before the first entry
+    pub fn header(self) -> &'static str {
+        match self {
+            Format::Jsonl => "",
+            Format::Csv => CSV_HEADER,
+        }
+    }
+
+    pub fn write(self, entries: &[Entry]) -> Result<String> {
+        let mut out = String::new();
+        for e in entries {
+            match self {
+                Format::Jsonl => {
+                    out.push_str(serde_json::to_string(e)?.as_str());
+                    out.push('\n');
+                }
+                Format::Csv => out.push_str(e.csv().as_str()),
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Entry {
+    fn csv(&self) -> String {
+        let favorited = self.favorited.to_string();
+        let fields = [
+            &self.read_date,
+            &self.published,
+            &self.feed,
+            &self.feed_id,
+            &self.title,
+            &self.link,
+            &self.author,
+            &favorited,
+            &self.id,
+        ];
+        let mut line = fields
+            .iter()
+            .map(|f| quote(f))
+            .collect::<Vec<String>>()
+            .join(",");
+        line.push_str("\r\n");
+        line
+    }
+}
+
+// quote wraps a csv field in quotes when it holds a separator, a quote or a line break, quotes inside are doubled
+// titles and authors come from feeds, one a spreadsheet would take for a formula is prefixed with ' to keep it text
+fn quote(field: &str) -> String {
+    let field = match field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        true => format!("'{}", field),
+        false => field.to_string(),
+    };
+    match field.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_quoted_and_kept_from_formulas() {
+        assert_eq!(quote("A title"), "A title");
+        assert_eq!(quote("Say \"hi\", then"), "\"Say \"\"hi\"\", then\"");
+        assert_eq!(quote("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(quote("-1"), "'-1");
+        assert_eq!(quote("@home"), "'@home");
+    }
+}
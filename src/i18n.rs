@@ -0,0 +1,102 @@
+use anyhow::Result;
+use chrono::format::{Item, StrftimeItems};
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use rust_embed::RustEmbed;
+use std::collections::BTreeMap;
+use unic_langid::LanguageIdentifier;
+
+// DEFAULT_LANGUAGE is complete, other languages fall back to it one message at a time
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+// the date messages are strftime patterns, a bad one would fail at render time so they are checked on load
+const DATE_PATTERNS: [&str; 2] = ["date-format", "day-format"];
+
+// Locales are the fluent files under locales/, one per language named by its language tag
+#[derive(RustEmbed)]
+#[folder = "locales/"]
+struct Locales;
+
+pub struct Catalog {
+    bundles: BTreeMap<String, FluentBundle<FluentResource>>,
+}
+
+impl Catalog {
+    pub fn load() -> Result<Catalog> {
+        let mut bundles = BTreeMap::new();
+        for name in Locales::iter() {
+            let language = match name.strip_suffix(".ftl") {
+                Some(language) => language.to_string(),
+                None => continue,
+            };
+            let file = Locales::get(name.as_ref()).unwrap();
+            let source = String::from_utf8(file.data.into_owned())?;
+            bundles.insert(language.clone(), bundle(language.as_str(), source)?);
+        }
+
+        if !bundles.contains_key(DEFAULT_LANGUAGE) {
+            return Err(anyhow::Error::msg(format!(
+                "locales/{}.ftl is missing",
+                DEFAULT_LANGUAGE
+            )));
+        }
+        Ok(Catalog { bundles })
+    }
+
+    pub fn languages(&self) -> Vec<String> {
+        self.bundles.keys().cloned().collect()
+    }
+
+    pub fn contains(&self, language: &str) -> bool {
+        self.bundles.contains_key(language)
+    }
+
+    // message falls back to the default language and then to the key itself so a gap never breaks a page
+    pub fn message(&self, language: &str, key: &str, args: Option<&FluentArgs>) -> String {
+        for language in [language, DEFAULT_LANGUAGE] {
+            let bundle = match self.bundles.get(language) {
+                Some(bundle) => bundle,
+                None => continue,
+            };
+            if let Some(pattern) = bundle.get_message(key).and_then(|m| m.value()) {
+                let mut errors = vec![];
+                return bundle
+                    .format_pattern(pattern, args, &mut errors)
+                    .into_owned();
+            }
+        }
+        key.to_string()
+    }
+}
+
+fn bundle(language: &str, source: String) -> Result<FluentBundle<FluentResource>> {
+    let id: LanguageIdentifier = language
+        .parse()
+        .map_err(|e| anyhow::Error::msg(format!("locales/{}.ftl: {}", language, e)))?;
+    let resource = FluentResource::try_new(source).map_err(|(_, errors)| {
+        anyhow::Error::msg(format!("locales/{}.ftl: {:?}", language, errors))
+    })?;
+
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // isolation marks would end up inside attributes and form values
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .map_err(|errors| anyhow::Error::msg(format!("locales/{}.ftl: {:?}", language, errors)))?;
+
+    for key in DATE_PATTERNS {
+        let pattern = match bundle.get_message(key).and_then(|m| m.value()) {
+            Some(pattern) => pattern,
+            None => continue,
+        };
+        let mut errors = vec![];
+        let value = bundle.format_pattern(pattern, None, &mut errors);
+        if StrftimeItems::new(value.as_ref()).any(|item| item == Item::Error) {
+            return Err(anyhow::Error::msg(format!(
+                "locales/{}.ftl: {} is not a strftime pattern",
+                language, key
+            )));
+        }
+    }
+    Ok(bundle)
+}
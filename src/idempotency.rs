@@ -0,0 +1,328 @@
+use crate::models::IdempotentResponse;
+use crate::{dates, db, report};
+use chrono::{Duration, Utc};
+use futures::TryStreamExt;
+use rweb::http::header::{HeaderMap, HeaderName, HeaderValue};
+use rweb::http::{Method, Request, StatusCode};
+use rweb::hyper::body::{self, Buf, HttpBody};
+use rweb::hyper::service::Service;
+use rweb::hyper::Body;
+use rweb::reply::Response;
+use rweb::warp::{self, path::FullPath, Filter, Rejection};
+use sha2::{Digest, Sha256};
+
+// https://datatracker.ietf.org/doc/draft-ietf-httpapi-idempotency-key-header/
+pub const HEADER: &str = "idempotency-key";
+// set on a response that was stored for an earlier request with the same key
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+// hours a key is kept, a retry after that runs the request again
+pub const TTL_HOURS: i64 = 24;
+// minutes after which a request that never finished is taken to have died, a retry then runs it again
+const IN_FLIGHT_MINUTES: i64 = 5;
+const MAX_KEY_CHARS: usize = 255;
+// responses larger than this, or streamed ones of unknown size, are not kept and their key is released
+const MAX_STORED_BYTES: u64 = 1024 * 1024;
+// carries the hash of the query and body of a keyed request to claim, it is set by fingerprinted and never taken from the client
+const FINGERPRINT_HEADER: &str = "x-idempotency-fingerprint";
+// the fingerprint of a body that was not read since it is larger than its route accepts, no sha256 hex looks like it
+const TOO_LARGE: &str = "too-large";
+
+// Settled is a request whose key was seen before, it is answered without running the route
+#[derive(Debug)]
+pub enum Settled {
+    // the first request finished, this is what it was answered
+    Replay(IdempotentResponse),
+    // the first request is still running
+    InFlight,
+    // the key was sent with another request, or another payload, before
+    Reused,
+    // the key is empty, too long or not printable, or its body could not be read
+    Invalid,
+    // the body is larger than the route accepts, the same request without a key is refused the same way
+    TooLarge,
+}
+
+impl warp::reject::Reject for Settled {}
+
+// fingerprinted reads the body of a POST or DELETE sent with a key and hands the request on to the routes with the hash
+// of its query and body, so claim can refuse the key sent with another payload, the routes get the body as it was sent
+// limit is the largest body the route at a path accepts, a body past it is not read and its key is refused
+pub fn fingerprinted<F>(
+    routes: F,
+    limit: fn(&str) -> u64,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Response,), Error = Rejection> + Clone + Send + Sync + 'static,
+{
+    let service = warp::service(routes.clone());
+    let keyed = warp::method()
+        .and(warp::header::<String>(HEADER))
+        .and_then(|method: Method, _: String| async move {
+            match method == Method::POST || method == Method::DELETE {
+                true => Ok(method),
+                false => Err(warp::reject()),
+            }
+        })
+        .and(warp::path::full())
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::stream())
+        .and_then(
+            move |method: Method, path: FullPath, query: String, headers: HeaderMap, body| {
+                let mut service = service.clone();
+                async move {
+                    let body = read(body, limit(path.as_str())).await;
+                    let request = rebuilt(method, path, query, headers, body)
+                        .map_err(|_| warp::reject::custom(Settled::Invalid))?;
+                    // the routes run on a task of their own, warp refuses to run them inside the route of this request
+                    let answer = tokio::spawn(async move { service.call(request).await });
+                    match answer.await {
+                        Ok(Ok(response)) => Ok::<_, Rejection>(response),
+                        Ok(Err(never)) => match never {},
+                        Err(e) => std::panic::resume_unwind(e.into_panic()),
+                    }
+                }
+            },
+        );
+    keyed.or(routes).unify()
+}
+
+// read is the whole body, refused as too large once it goes past limit and as invalid when it could not be read
+async fn read<B: Buf>(
+    body: impl futures::Stream<Item = Result<B, warp::Error>>,
+    limit: u64,
+) -> Result<Vec<u8>, Settled> {
+    body.map_err(|_| Settled::Invalid)
+        .try_fold(vec![], |mut bytes, mut chunk| async move {
+            if (bytes.len() + chunk.remaining()) as u64 > limit {
+                return Err(Settled::TooLarge);
+            }
+            while chunk.has_remaining() {
+                let part = chunk.chunk();
+                bytes.extend_from_slice(part);
+                let read = part.len();
+                chunk.advance(read);
+            }
+            Ok(bytes)
+        })
+        .await
+}
+
+// rebuilt is the request again with the body that was read, a body that could not be read leaves the request without
+// a fingerprint and one that is too large is marked as such, claim refuses either so the error page is the routes' own
+fn rebuilt(
+    method: Method,
+    path: FullPath,
+    query: String,
+    mut headers: HeaderMap,
+    body: Result<Vec<u8>, Settled>,
+) -> Result<Request<Body>, rweb::http::Error> {
+    let uri = match query.is_empty() {
+        true => path.as_str().to_string(),
+        false => format!("{}?{}", path.as_str(), query),
+    };
+    headers.remove(FINGERPRINT_HEADER);
+    let marked = match body.as_ref() {
+        Ok(body) => Some(fingerprint(query.as_str(), body)),
+        Err(Settled::TooLarge) => Some(TOO_LARGE.to_string()),
+        Err(_) => None,
+    };
+    if let Some(marked) = marked {
+        headers.insert(
+            FINGERPRINT_HEADER,
+            HeaderValue::from_str(marked.as_str())
+                .expect("hex and the marker are valid header values"),
+        );
+    }
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(Body::from(body.unwrap_or_default()))?;
+    *request.headers_mut() = headers;
+    Ok(request)
+}
+
+pub(crate) fn fingerprint(query: &str, body: &[u8]) -> String {
+    let mut hash = Sha256::new();
+    hash.update(query.as_bytes());
+    hash.update(b"\n");
+    hash.update(body);
+    format!("{:x}", hash.finalize())
+}
+
+// claim takes the idempotency key of a POST or DELETE, it extracts the key when the route should run and record it
+// requests without a key, or of another method, run as they always did
+pub fn claim(
+    store: db::Storage,
+) -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::optional::<String>(HEADER))
+        .and(warp::header::optional::<String>(FINGERPRINT_HEADER))
+        .and_then(
+            move |method: Method, path: FullPath, key: Option<String>, fingerprint| {
+                let store = store.clone();
+                async move {
+                    claim_key(store, method, path.as_str().to_string(), key, fingerprint).await
+                }
+            },
+        )
+}
+
+async fn claim_key(
+    store: db::Storage,
+    method: Method,
+    path: String,
+    key: Option<String>,
+    fingerprint: Option<String>,
+) -> Result<Option<String>, Rejection> {
+    let key = match key {
+        Some(key) if method == Method::POST || method == Method::DELETE => key,
+        _ => return Ok(None),
+    };
+    let fingerprint = match fingerprint {
+        Some(fingerprint) if fingerprint == TOO_LARGE => {
+            return Err(warp::reject::custom(Settled::TooLarge))
+        }
+        Some(fingerprint) if valid(key.as_str()) => fingerprint,
+        _ => return Err(warp::reject::custom(Settled::Invalid)),
+    };
+
+    let expired = dates::stored(Utc::now() - Duration::hours(TTL_HOURS));
+    let abandoned = dates::stored(Utc::now() - Duration::minutes(IN_FLIGHT_MINUTES));
+    match store
+        .claim_idempotency_key(
+            key.clone(),
+            method.to_string(),
+            path.clone(),
+            fingerprint.clone(),
+            expired,
+            abandoned,
+        )
+        .await
+    {
+        Ok(None) => Ok(Some(key)),
+        Ok(Some(stored))
+            if stored.method != method.as_str()
+                || stored.path != path
+                || stored.fingerprint != fingerprint =>
+        {
+            Err(warp::reject::custom(Settled::Reused))
+        }
+        Ok(Some(stored)) if stored.status == 0 => Err(warp::reject::custom(Settled::InFlight)),
+        Ok(Some(stored)) => Err(warp::reject::custom(Settled::Replay(stored))),
+        // the key table is a safety net, a request is not refused because it is unavailable
+        Err(e) => {
+            println!("could not claim idempotency key: {}", e);
+            report::error(&e);
+            Ok(None)
+        }
+    }
+}
+
+fn valid(key: &str) -> bool {
+    !key.is_empty()
+        && key.chars().count() <= MAX_KEY_CHARS
+        && key.chars().all(|c| c.is_ascii_graphic())
+}
+
+// record keeps the response to a request that claimed a key, server errors are not kept so a retry runs again
+pub async fn record(store: &db::Storage, key: Option<String>, response: Response) -> Response {
+    let key = match key {
+        Some(key) => key,
+        None => return response,
+    };
+    let size = response.body().size_hint().exact();
+    if response.status().is_server_error() || size.is_none_or(|size| size > MAX_STORED_BYTES) {
+        release(store, key).await;
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let bytes = match body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            println!(
+                "could not read response to keep for its idempotency key: {}",
+                e
+            );
+            release(store, key).await;
+            return Response::from_parts(parts, Default::default());
+        }
+    };
+    let headers = parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    if let Err(e) = store
+        .save_idempotent_response(key, parts.status.as_u16() as i32, headers, bytes.to_vec())
+        .await
+    {
+        println!("could not keep response for its idempotency key: {}", e);
+        report::error(&e);
+    }
+    Response::from_parts(parts, bytes.into())
+}
+
+async fn release(store: &db::Storage, key: String) {
+    if let Err(e) = store.release_idempotency_key(key).await {
+        println!("could not release idempotency key: {}", e);
+        report::error(&e);
+    }
+}
+
+// replay is the stored response again, marked as a replay
+pub fn replay(stored: &IdempotentResponse) -> Response {
+    let mut response = Response::new(stored.body.clone().into());
+    *response.status_mut() =
+        StatusCode::from_u16(stored.status as u16).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers.iter() {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value.as_str()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_what_was_stored() {
+        let stored = IdempotentResponse {
+            method: "POST".to_string(),
+            path: "/articles/abc/toggle_favorite".to_string(),
+            fingerprint: fingerprint("", b""),
+            status: 303,
+            headers: vec![
+                ("location".to_string(), "/articles/abc".to_string()),
+                ("hx-trigger".to_string(), "changed".to_string()),
+            ],
+            body: b"moved".to_vec(),
+        };
+        let response = replay(&stored);
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()["location"], "/articles/abc");
+        assert_eq!(response.headers()["hx-trigger"], "changed");
+        assert_eq!(response.headers()[REPLAYED_HEADER], "true");
+        assert_eq!(response.body().size_hint().exact(), Some(5));
+
+        assert!(valid("0b7a4c5e-5d1c-4f0e-9a3b-2f6f1b1f8e2d"));
+        assert!(!valid(""));
+        assert!(!valid("two words"));
+        assert!(!valid("x".repeat(MAX_KEY_CHARS + 1).as_str()));
+
+        assert_eq!(fingerprint("a=1", b""), fingerprint("a=1", b""));
+        assert_ne!(
+            fingerprint("", b"category=News"),
+            fingerprint("", b"category=Sports")
+        );
+    }
+}
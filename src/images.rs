@@ -0,0 +1,170 @@
+use crate::{fetch, queue};
+use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
+use futures::lock::Mutex;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use reqwest::Url;
+use sha2::Sha256;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+// larger images are refused rather than cached, they are not read past this
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+// the oldest images are evicted once the cache holds this much
+const MAX_CACHE_BYTES: usize = 64 * 1024 * 1024;
+// thumbnails of new articles waiting to be fetched, and how many are fetched at once
+pub const PREFETCH_QUEUE_SIZE: usize = 512;
+pub const PREFETCH_CONCURRENCY: usize = 2;
+
+#[derive(Clone)]
+pub struct Image {
+    pub content_type: String,
+    pub body: Arc<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct Cache {
+    images: HashMap<String, Image>,
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl Cache {
+    // insert keeps the image fetched first when two requests missed the same url at once
+    fn insert(&mut self, url: String, image: Image) {
+        if self.images.contains_key(&url) {
+            return;
+        }
+        self.bytes += image.body.len();
+        self.order.push_back(url.clone());
+        self.images.insert(url, image);
+
+        while self.bytes > MAX_CACHE_BYTES {
+            let oldest = match self.order.pop_front() {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            if let Some(image) = self.images.remove(&oldest) {
+                self.bytes -= image.body.len();
+            }
+        }
+    }
+}
+
+// Proxy re-serves remote images so the browser never contacts their hosts, only urls signed by url are served
+#[derive(Clone)]
+pub struct Proxy {
+    key: Arc<Vec<u8>>,
+    fetcher: fetch::Fetcher,
+    cache: Arc<Mutex<Cache>>,
+    prefetch: queue::Queue,
+}
+
+impl Proxy {
+    // without a configured secret the key is random, so proxied urls stop working after a restart
+    pub fn new(secret: Option<String>, fetcher: fetch::Fetcher, prefetch: queue::Queue) -> Proxy {
+        let key = match secret {
+            Some(secret) => secret.into_bytes(),
+            None => {
+                let mut key = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                key
+            }
+        };
+
+        Proxy {
+            key: Arc::new(key),
+            fetcher,
+            cache: Arc::new(Mutex::new(Cache::default())),
+            prefetch,
+        }
+    }
+
+    fn mac(&self, url: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.as_slice())
+            .expect("hmac accepts keys of any length");
+        mac.update(url.as_bytes());
+        mac
+    }
+
+    // url is the proxied location of an image for use in rendered article content
+    pub fn url(&self, src: &str) -> String {
+        let sig = general_purpose::URL_SAFE_NO_PAD.encode(self.mac(src).finalize().into_bytes());
+        let mut url = Url::parse("http://feedreader/proxy/image").unwrap();
+        url.query_pairs_mut()
+            .append_pair("url", src)
+            .append_pair("sig", sig.as_str());
+        format!(
+            "{}/proxy/image?{}",
+            crate::base_path(),
+            url.query().unwrap_or_default()
+        )
+    }
+
+    pub fn verify(&self, src: &str, sig: &str) -> bool {
+        match general_purpose::URL_SAFE_NO_PAD.decode(sig) {
+            Ok(sig) => self.mac(src).verify_slice(sig.as_slice()).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    // prefetch queues the image to be fetched into the cache in the background, ingesting articles does not wait on it
+    pub fn prefetch(&self, src: String) {
+        let proxy = self.clone();
+        self.prefetch
+            .push(async move { proxy.get(src.as_str()).await.map(|_| ()) });
+    }
+
+    // get fetches through the feed fetcher so the same internal address checks apply
+    pub async fn get(&self, src: &str) -> Result<Image> {
+        if let Some(image) = self.cache.lock().await.images.get(src) {
+            return Ok(image.clone());
+        }
+
+        let fetched = self
+            .fetcher
+            .get_at_most(src, false, MAX_IMAGE_BYTES)
+            .await?;
+        let content_type = fetched.content_type.unwrap_or_default();
+        if !content_type.starts_with("image/") {
+            return Err(anyhow::Error::msg(format!(
+                "not an image: {} is {}",
+                src, content_type
+            )));
+        }
+        let image = Image {
+            content_type,
+            body: Arc::new(fetched.body.into_vec()?),
+        };
+        self.cache
+            .lock()
+            .await
+            .insert(src.to_string(), image.clone());
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_bytes_count_each_url_once() {
+        let image = |len: usize| Image {
+            content_type: "image/png".to_string(),
+            body: Arc::new(vec![0; len]),
+        };
+        let mut cache = Cache::default();
+        cache.insert("a".to_string(), image(10));
+        cache.insert("a".to_string(), image(10));
+        assert_eq!((cache.bytes, cache.order.len()), (10, 1));
+
+        // evicting the oldest brings the total back under the limit instead of emptying the cache
+        cache.insert("b".to_string(), image(MAX_CACHE_BYTES - 10));
+        cache.insert("c".to_string(), image(5));
+        assert!(!cache.images.contains_key("a"));
+        assert!(cache.images.contains_key("b") && cache.images.contains_key("c"));
+        assert_eq!(cache.bytes, MAX_CACHE_BYTES - 5);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::models::{self, AddFeed, Article, Feed, FieldError};
+use crate::{dates, db};
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use opml::{Outline, OPML};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+// Imported is what an import did, skipped holds the url of every feed that was left out and why
+#[derive(Default, Serialize)]
+pub struct Imported {
+    pub added: usize,
+    pub skipped: Vec<(String, String)>,
+}
+
+// opml subscribes to every feed in the document, it keeps going past feeds that fail, usually because they are already subscribed
+pub async fn opml(store: &db::Storage, document: &str) -> Result<Imported> {
+    let document = OPML::from_str(document)?;
+    let mut outlines = vec![];
+    collect_feeds(document.body.outlines, "", &mut outlines);
+
+    let mut imported = Imported::default();
+    for (category, outline) in outlines {
+        let feed_url = outline.xml_url.unwrap_or_default();
+        let mut feed = AddFeed {
+            feed_name: outline.title.unwrap_or(outline.text),
+            site_url: outline.html_url.unwrap_or_default(),
+            feed_url: feed_url.clone(),
+            use_socks: None,
+            category,
+        };
+        if let Err(e) = invalid(feed.validate()) {
+            imported.skipped.push((feed_url, e.to_string()));
+            continue;
+        }
+        match store.add_feed(feed).await {
+            Ok(_) => imported.added += 1,
+            Err(e) => imported.skipped.push((feed_url, e.to_string())),
+        }
+    }
+    Ok(imported)
+}
+
+// Starred is a list of starred items as google reader takeout archives and the old reader export them, both use the google reader api format
+#[derive(Deserialize)]
+struct Starred {
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+// Item is one starred entry, every field may be missing since exports from different years and services disagree
+// timestamps are unix seconds, some exports write them as strings
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Item {
+    id: String,
+    title: Option<String>,
+    author: Option<String>,
+    published: Option<Value>,
+    updated: Option<Value>,
+    #[serde(rename = "crawlTimeMsec")]
+    crawl_time_msec: Option<Value>,
+    alternate: Vec<Href>,
+    canonical: Vec<Href>,
+    summary: Option<Content>,
+    content: Option<Content>,
+    origin: Option<Origin>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Href {
+    href: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Content {
+    content: String,
+}
+
+// Origin is the feed an item came from, the stream id is the feed url behind a feed/ prefix
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Origin {
+    #[serde(rename = "streamId")]
+    stream_id: String,
+    title: String,
+    #[serde(rename = "htmlUrl")]
+    html_url: String,
+}
+
+// starred stores every item of the export as a read and favorited article, published and read when the item was
+// items of a subscribed feed that are still stored are only favorited, they are matched by their link
+// a feed that is not subscribed is not added, its items are kept under a feed id derived from its url so importing twice adds nothing
+pub async fn starred(store: &db::Storage, document: &str) -> Result<Imported> {
+    let document: Starred = serde_json::from_str(document)?;
+    let subscribed: HashMap<String, Feed> = store
+        .get_all_feeds()
+        .await?
+        .into_iter()
+        .map(|f| (f.feed_url.clone(), f))
+        .collect();
+
+    let mut imported = Imported::default();
+    let mut articles = vec![];
+    for item in document.items {
+        let origin = item.origin.as_ref();
+        let feed_url = origin
+            .and_then(|o| o.stream_id.strip_prefix("feed/"))
+            .unwrap_or_default();
+        let feed = match subscribed.get(feed_url) {
+            Some(feed) => feed.clone(),
+            None => unsubscribed(feed_url, origin),
+        };
+        match item.into_article() {
+            Ok(article) => articles.push(article.in_feed(&feed)),
+            Err((id, reason)) => imported.skipped.push((id, reason.to_string())),
+        }
+    }
+
+    let mut links: HashMap<&str, Vec<String>> = HashMap::new();
+    for a in articles.iter().filter(|a| !a.link.is_empty()) {
+        links.entry(&a.feed_id).or_default().push(a.link.clone());
+    }
+    let mut found = HashMap::new();
+    for (feed_id, links) in links {
+        let favorited = store.favorite_links(feed_id, links.as_slice()).await?;
+        imported.added += favorited.len();
+        found.insert(feed_id.to_string(), favorited);
+    }
+
+    let articles = articles.into_iter().filter(|a| {
+        !found
+            .get(&a.feed_id)
+            .map(|links| links.contains(&a.link))
+            .unwrap_or(false)
+    });
+    imported.added += store.add_articles(articles).await?.len();
+    Ok(imported)
+}
+
+// unsubscribed is the feed the items of a feed that is not subscribed are stored under, it is never saved so it is not refreshed
+fn unsubscribed(feed_url: &str, origin: Option<&Origin>) -> Feed {
+    let name = origin
+        .map(|o| models::plain_text(o.title.as_str()))
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| match feed_url.is_empty() {
+            true => "Imported".to_string(),
+            false => feed_url.to_string(),
+        });
+    Feed {
+        id: format!("imported-{:x}", Sha256::digest(feed_url.as_bytes())),
+        ..Feed::new(
+            models::truncate(name, models::MAX_FEED_NAME_CHARS),
+            origin.map(|o| o.html_url.clone()).unwrap_or_default(),
+            feed_url.to_string(),
+            false,
+            "".to_string(),
+        )
+    }
+}
+
+impl Item {
+    // into_article keeps the link as guid like a feed entry without one, so a later refresh of the feed adopts the row
+    fn into_article(self) -> Result<Article, (String, &'static str)> {
+        let link = self
+            .alternate
+            .iter()
+            .chain(self.canonical.iter())
+            .map(|h| models::web_link(h.href.as_str()))
+            .find(|link| !link.is_empty())
+            .unwrap_or_default();
+        let guid = match link.is_empty() {
+            true => self.id.clone(),
+            false => link.clone(),
+        };
+        if guid.is_empty() {
+            return Err((self.id, "the item has no link or id"));
+        }
+
+        let published = [&self.published, &self.updated]
+            .into_iter()
+            .find_map(|v| v.as_ref().and_then(seconds))
+            .or_else(|| {
+                self.crawl_time_msec
+                    .as_ref()
+                    .and_then(seconds)
+                    .map(|ms| ms / 1000)
+            })
+            .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+            .map(dates::stored)
+            .unwrap_or_default();
+        let title = self
+            .title
+            .map(|t| models::truncate(models::strip_tags(t.as_str()), models::MAX_TITLE_CHARS))
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| link.clone());
+        let author = self
+            .author
+            .map(|a| models::truncate(models::plain_text(a.as_str()), models::MAX_AUTHOR_CHARS))
+            .unwrap_or_default();
+        let summary = self
+            .summary
+            .or(self.content)
+            .map(|c| models::summarize(c.content.as_str()))
+            .unwrap_or_default();
+
+        let article = Article::new(title, link, author, published, true, true);
+        Ok(Article {
+            read_date: article.published.clone(),
+            guid: models::stored_guid(guid.as_str()),
+            summary,
+            ..article
+        })
+    }
+}
+
+// seconds reads a timestamp written as a number or as a string of digits
+fn seconds(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+// invalid turns the problems validation found into one error
+pub(crate) fn invalid(errors: Vec<FieldError>) -> Result<()> {
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow::Error::msg(
+            errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+        )),
+    }
+}
+
+// collect_feeds flattens the outline tree, folders are outlines without a feed url
+// feeds keep the name of the closest folder as their category
+fn collect_feeds(outlines: Vec<Outline>, category: &str, feeds: &mut Vec<(String, Outline)>) {
+    for mut outline in outlines {
+        let children = std::mem::take(&mut outline.outlines);
+        match outline.xml_url.is_some() {
+            true => {
+                feeds.push((category.to_string(), outline));
+                collect_feeds(children, category, feeds);
+            }
+            false => {
+                let folder = outline.title.unwrap_or(outline.text);
+                collect_feeds(children, folder.as_str(), feeds);
+            }
+        }
+    }
+}
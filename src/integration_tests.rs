@@ -0,0 +1,1394 @@
+// the suite boots postgres in a container for every test, run it with `cargo test -- --ignored` where docker is available
+use super::*;
+use crate::models::{self, AddFeed, Article, Feed};
+use rweb::test::request;
+use testcontainers_modules::postgres::Postgres;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+use testcontainers_modules::testcontainers::{ContainerAsync, ImageExt};
+
+const PASSWORD: &str = "feedreader";
+const EXTENSION_TOKEN: &str = "extension";
+const MAILGUN_KEY: &str = "mailgun";
+
+// Scratch is a migrated database with the app built on it, the container is removed when it is dropped
+struct Scratch {
+    _container: ContainerAsync<Postgres>,
+    app: App,
+}
+
+async fn scratch() -> Scratch {
+    let container = Postgres::default()
+        .with_db_name("feedreader")
+        .with_user("feedreader")
+        .with_password(PASSWORD)
+        .with_tag("15-alpine")
+        .start()
+        .await
+        .expect("could not start postgres, is docker running?");
+    let host = container.get_host().await.unwrap().to_string();
+    let port = container.get_host_port_ipv4(5432).await.unwrap();
+
+    let store = db::connection("feedreader", PASSWORD, host.as_str(), port)
+        .await
+        .unwrap();
+    store.init().await.unwrap();
+    I18N.get_or_init(|| i18n::Catalog::load().unwrap());
+
+    let db_settings = db::Settings::load(store.clone()).await.unwrap();
+    let cursors = cursor::Signer::load(&db_settings).await.unwrap();
+    // test servers listen on loopback, which is refused unless allowed
+    let fetcher = fetch::Fetcher::new(None, None, Some("127.0.0.1".to_string()), 0).unwrap();
+    let (dispatcher, notification_worker) = notify::Dispatcher::new(notify::Registry::default());
+    tokio::spawn(notification_worker);
+    let (prefetch, prefetch_worker) = queue::Queue::new("image prefetch", 1, 1);
+    tokio::spawn(prefetch_worker);
+
+    let app = App {
+        store: store.clone(),
+        db_settings,
+        integrations: integrations::Integrations::default(),
+        fetcher: fetcher.clone(),
+        pipeline: pipeline::Pipeline::builtin(),
+        dispatcher,
+        events: events::Events::new(None, "".to_string()),
+        images: images::Proxy::new(None, fetcher, prefetch),
+        render_cache: render_cache::Cache::default(),
+        scheduler_state: scheduler::State::new(scheduler::Schedule::Interval(3600)),
+        kindle: None,
+        trigger_token: TriggerToken(None),
+        extension_token: ExtensionToken(Some(Arc::from(EXTENSION_TOKEN))),
+        mailgun_key: MailgunKey(Some(Arc::from(MAILGUN_KEY)), Default::default()),
+        cursors,
+        draining: shutdown::Draining::default(),
+        security_headers: security::Headers::new("", "").unwrap(),
+        base_path: "".to_string(),
+    };
+    Scratch {
+        _container: container,
+        app,
+    }
+}
+
+fn add_feed(name: &str, feed_url: &str) -> AddFeed {
+    AddFeed {
+        feed_name: name.to_string(),
+        feed_url: feed_url.to_string(),
+        ..Default::default()
+    }
+}
+
+fn article(feed: &Feed, guid: &str) -> Article {
+    Article::new(
+        format!("Article {}", guid),
+        format!("https://example.com/{}", guid),
+        "".to_string(),
+        "2026-02-02T10:00:00Z".to_string(),
+        false,
+        false,
+    )
+    .in_feed(feed)
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn storage_round_trip() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store;
+
+    let feed = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_feed_by_id(feed.id.clone()).await.unwrap().name,
+        "Scratch"
+    );
+    let duplicate = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        duplicate.downcast_ref(),
+        Some(db::Error::Conflict(_))
+    ));
+
+    // a second ingest of the same entries adds nothing
+    let articles = vec![article(&feed, "1"), article(&feed, "2")];
+    let added = store
+        .add_articles(articles.clone().into_iter())
+        .await
+        .unwrap();
+    assert_eq!(added.len(), 2);
+    let added = store.add_articles(articles.into_iter()).await.unwrap();
+    assert!(added.is_empty());
+    assert_eq!(store.count_unread_articles().await.unwrap(), 2);
+
+    // a toggle from a page that saw an older version does not apply
+    let id = article(&feed, "1").id;
+    let loaded = store.get_article_by_id(id.clone()).await.unwrap();
+    // dates come back as stored, only the pages render them for people
+    assert_eq!(loaded.published, "2026-02-02T10:00:00.000Z");
+    assert_eq!(loaded.read_date, "-1");
+    let read = store
+        .mark_article_read(id.clone(), loaded.updated_at.as_str())
+        .await
+        .unwrap();
+    assert!(read.read);
+    assert!(chrono::DateTime::parse_from_rfc3339(read.read_date.as_str()).is_ok());
+    let stale = store
+        .mark_article_read(id.clone(), loaded.updated_at.as_str())
+        .await
+        .unwrap_err();
+    assert!(matches!(stale.downcast_ref(), Some(db::Error::Stale(_))));
+    assert_eq!(store.count_unread_articles().await.unwrap(), 1);
+
+    // the feeds page counts the articles of each feed
+    let page = store.get_feeds(db::MAX_DATE.to_string()).await.unwrap();
+    let stats = db::FeedStats::from(page.items.first().unwrap());
+    assert_eq!((stats.articles, stats.unread), (2, 1));
+    assert!(stats.last_fetched.is_empty());
+
+    // the unread article was published long before the feed's auto read days and is marked read, not deleted
+    let settings = models::FeedSettings {
+        feed_name: "Scratch".to_string(),
+        site_url: "".to_string(),
+        category: "".to_string(),
+        use_socks: None,
+        auto_read_days: "".to_string(),
+    };
+    store
+        .update_feed_settings(feed.id.clone(), settings, 7)
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .auto_read_articles(Article::rfc3339_timestamp())
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(store.count_unread_articles().await.unwrap(), 0);
+    assert_eq!(store.get_counts().await.unwrap().articles, 2);
+
+    store.delete_feed(feed.id.clone()).await.unwrap();
+    let missing = store.get_feed_by_id(feed.id).await.unwrap_err();
+    assert!(matches!(
+        missing.downcast_ref(),
+        Some(db::Error::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn routes_end_to_end() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    let response = request().path("/healthz").reply(&routes).await;
+    assert_eq!(response.status(), 200);
+
+    let response = request()
+        .method("POST")
+        .path("/feeds")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("feed_name=Scratch&site_url=&feed_url=https%3A%2F%2Fexample.com%2Ffeed.xml")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+
+    // the form is rendered again with the problems
+    let response = request()
+        .method("POST")
+        .path("/feeds")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("feed_name=&site_url=&feed_url=ftp%3A%2F%2Fexample.com")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let response = request()
+        .method("POST")
+        .path("/feeds")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("feed_name=Again&site_url=&feed_url=https%3A%2F%2Fexample.com%2Ffeed.xml")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 409);
+
+    let response = request().path("/feeds/unknown").reply(&routes).await;
+    assert_eq!(response.status(), 404);
+
+    let feeds = store.get_feeds(db::MAX_DATE.to_string()).await.unwrap();
+    let feed: Feed = feeds.items.first().unwrap().into();
+
+    // the settings form is checked like the add form, a rejected one is shown again and nothing is saved
+    let settings = |form: String| {
+        request()
+            .method("POST")
+            .path(format!("/feeds/{}/settings", feed.id).as_str())
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(form)
+            .reply(&routes)
+    };
+    let long = "x".repeat(models::MAX_CATEGORY_CHARS + 1);
+    for form in [
+        "feed_name=&site_url=&category=&auto_read_days=".to_string(),
+        format!(
+            "feed_name=Scratch&site_url=&category={}&auto_read_days=",
+            long
+        ),
+        "feed_name=Scratch&site_url=ftp%3A%2F%2Fexample.com&category=&auto_read_days=".to_string(),
+        "feed_name=Scratch&site_url=&category=&auto_read_days=-1".to_string(),
+        "feed_name=Scratch&site_url=&category=&auto_read_days=9999999".to_string(),
+    ] {
+        let response = settings(form).await;
+        assert_eq!(response.status(), 400);
+        assert!(String::from_utf8_lossy(response.body()).contains("text-error"));
+    }
+    let response =
+        settings("feed_name=Scratch&site_url=&category=News&auto_read_days=0".to_string()).await;
+    assert_eq!(response.status(), 200);
+    let renamed = store.get_feed_by_id(feed.id.clone()).await.unwrap();
+    let response = request()
+        .method("POST")
+        .path("/feeds/unknown/settings")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("feed_name=Scratch&site_url=&category=&auto_read_days=")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 404);
+    assert_eq!(
+        (renamed.name.as_str(), renamed.category.as_str()),
+        ("Scratch", "News")
+    );
+
+    // a default stage is turned off and an optional one on, a stage that always runs cannot be toggled
+    for stage in ["content", "tracking", "content"] {
+        let response = request()
+            .method("POST")
+            .path(format!("/feeds/{}/stages", feed.id).as_str())
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(format!("stage={}", stage))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+    }
+    let toggled = store.get_feed_by_id(feed.id.clone()).await.unwrap();
+    assert_eq!(toggled.stages_on, ["tracking"]);
+    assert!(toggled.stages_off.is_empty());
+    let response = request()
+        .method("POST")
+        .path(format!("/feeds/{}/stages", feed.id).as_str())
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("stage=sanitize")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 404);
+
+    store
+        .add_articles(vec![article(&feed, "1")].into_iter())
+        .await
+        .unwrap();
+    let id = article(&feed, "1").id;
+    let loaded = store.get_article_by_id(id.clone()).await.unwrap();
+
+    let response = request()
+        .method("POST")
+        .path(format!("/articles/{}/toggle_read", id).as_str())
+        .header("version", loaded.updated_at.as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let toggled: Article = serde_json::from_slice(response.body()).unwrap();
+    assert!(toggled.read);
+
+    let response = request()
+        .method("POST")
+        .path(format!("/articles/{}/toggle_read", id).as_str())
+        .header("version", loaded.updated_at.as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 409);
+
+    let response = request()
+        .path("/articles")
+        .header("pagination", db::MAX_DATE)
+        .header("article_filter", "read")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    assert!(String::from_utf8_lossy(response.body()).contains("Article 1"));
+
+    // the article read above is counted on today, the last day of the year
+    let response = request()
+        .path("/api/v1/stats/activity")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let activity: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    let days = activity["days"].as_array().unwrap();
+    assert_eq!(days.len(), 365);
+    assert_eq!(days.last().unwrap()["read"], 1);
+
+    let response = request()
+        .path("/export/history?format=csv&from=2026-01-01")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let csv = String::from_utf8_lossy(response.body()).to_string();
+    assert!(csv.starts_with("read_date,published,feed,"));
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.contains(",Article 1,https://example.com/1,"));
+
+    // a date without a time takes the whole of the last day
+    let response = request()
+        .path(
+            format!(
+                "/export/history?format=csv&to={}",
+                chrono::Utc::now().format("%Y-%m-%d")
+            )
+            .as_str(),
+        )
+        .reply(&routes)
+        .await;
+    assert_eq!(String::from_utf8_lossy(response.body()).lines().count(), 2);
+
+    // a bad range is the caller's mistake and is answered without the details of an error
+    let response = request()
+        .path("/export/history?from=someday")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 400);
+
+    // the theme picker only sends the browser back to a page of this server
+    let theme = |referer: &'static str| {
+        request()
+            .method("POST")
+            .path("/preferences/theme")
+            .header("host", "reader.example.com")
+            .header("referer", referer)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("theme=dark")
+            .reply(&routes)
+    };
+    let response = theme("https://reader.example.com/favorites.html?page=2").await;
+    assert_eq!(response.status(), 303);
+    assert_eq!(response.headers()["location"], "/favorites.html?page=2");
+    for elsewhere in [
+        "https://evil.example.com/",
+        "//evil.example.com/",
+        "javascript:alert(1)",
+    ] {
+        let response = theme(elsewhere).await;
+        assert_eq!(response.headers()["location"], "/");
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn setup_on_first_run() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    let response = request().path("/").reply(&routes).await;
+    assert_eq!(response.status(), 303);
+    assert_eq!(response.headers()["location"], "/setup.html");
+
+    let multipart = |opml: &str| {
+        format!(
+            "--boundary\r\nContent-Disposition: form-data; name=\"opml\"; filename=\"feeds.opml\"\r\n\r\n{}\r\n--boundary\r\nContent-Disposition: form-data; name=\"refresh_seconds\"\r\n\r\n600\r\n--boundary--\r\n",
+            opml
+        )
+    };
+
+    let response = request()
+        .method("POST")
+        .path("/setup")
+        .header("content-type", "multipart/form-data; boundary=boundary")
+        .body(multipart("not opml"))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let opml = r#"<?xml version="1.0"?><opml version="2.0"><head><title>Feeds</title></head><body><outline text="Rust"><outline text="Blog" type="rss" xmlUrl="https://example.com/feed.xml"/></outline></body></opml>"#;
+    let response = request()
+        .method("POST")
+        .path("/setup")
+        .header("content-type", "multipart/form-data; boundary=boundary")
+        .body(multipart(opml))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let feeds = store.get_all_feeds().await.unwrap();
+    assert_eq!(feeds.len(), 1);
+    assert_eq!(feeds[0].category, "Rust");
+
+    let response = request().path("/").reply(&routes).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn import_starred_items() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let feed = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap();
+    store
+        .add_articles(vec![article(&feed, "1")].into_iter())
+        .await
+        .unwrap();
+    let routes = build_app(app);
+
+    // one item of a subscribed feed that is still stored, one of a feed that is gone and one that cannot be kept
+    let export = r#"{"id": "user/1/state/com.google/starred", "items": [
+        {"id": "tag:google.com,2005:reader/item/1", "title": "Article 1", "published": 1262304000,
+         "alternate": [{"href": "https://example.com/1", "type": "text/html"}],
+         "origin": {"streamId": "feed/https://example.com/feed.xml", "title": "Scratch"}},
+        {"id": "tag:google.com,2005:reader/item/2", "title": "Gone", "crawlTimeMsec": "1262304000000",
+         "alternate": [{"href": "https://gone.example.com/post"}], "summary": {"content": "<p>kept</p>"},
+         "origin": {"streamId": "feed/https://gone.example.com/rss", "title": "Gone Blog", "htmlUrl": "https://gone.example.com"}},
+        {"title": "Nothing to link"}
+    ]}"#;
+    let import = || {
+        request()
+            .method("POST")
+            .path("/admin/import/starred")
+            .header("content-type", "application/json")
+            .body(export)
+            .reply(&routes)
+    };
+    let response = import().await;
+    assert_eq!(response.status(), 200);
+    let imported: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(imported["added"], 2);
+    assert_eq!(imported["skipped"].as_array().unwrap().len(), 1);
+
+    let favorites = store
+        .get_favorited_articles(db::MAX_DATE.to_string())
+        .await
+        .unwrap();
+    let favorites: Vec<Article> = favorites.items.iter().map(|r| r.into()).collect();
+    assert_eq!(favorites.len(), 2);
+    let gone = favorites.iter().find(|a| a.feed == "Gone Blog").unwrap();
+    assert!(gone.read);
+    assert_eq!(gone.summary, "kept");
+    assert_eq!(store.get_all_feeds().await.unwrap().len(), 1);
+
+    // a second import only favorites what is already there
+    let response = import().await;
+    let imported: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(imported["added"], 2);
+    assert_eq!(store.get_counts().await.unwrap().articles, 2);
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn smart_folders() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let feed = store
+        .add_feed(AddFeed {
+            category: "Rust".to_string(),
+            ..add_feed("Scratch", "https://example.com/feed.xml")
+        })
+        .await
+        .unwrap();
+    store
+        .add_articles(vec![article(&feed, "1"), article(&feed, "2")].into_iter())
+        .await
+        .unwrap();
+    store
+        .mark_article_read(article(&feed, "2").id, "")
+        .await
+        .unwrap();
+    let routes = build_app(app);
+
+    let response = request()
+        .method("POST")
+        .path("/folders")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("name=Broken&expression=category%3A")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let response = request()
+        .method("POST")
+        .path("/folders")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("name=Rust&expression=category%3A%22rust%22+unread+published+%3E+2026-01-01")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let folders = store.get_smart_folders().await.unwrap();
+    assert_eq!(folders.len(), 1);
+    let id = folders[0].id.clone();
+
+    let response = request()
+        .path(format!("/folders/{}", id).as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let page = String::from_utf8_lossy(response.body()).to_string();
+    assert!(page.contains("Article 1"));
+    assert!(!page.contains("Article 2"));
+
+    // the list htmx swaps in after an action pages through the same folder
+    let response = request()
+        .path("/articles")
+        .header("pagination", db::MAX_DATE)
+        .header("article_filter", format!("folder:{}", id).as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    assert!(String::from_utf8_lossy(response.body()).contains("Article 1"));
+
+    let response = request().path("/sidebar").reply(&routes).await;
+    let sidebar = String::from_utf8_lossy(response.body()).to_string();
+    assert!(sidebar.contains(format!("/folders/{}", id).as_str()));
+
+    let response = request()
+        .method("DELETE")
+        .path(format!("/folders/{}", id).as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let response = request()
+        .path(format!("/folders/{}", id).as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn ai_summaries_for_new_entries() {
+    use rweb::warp::{self, Filter};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store;
+
+    // a stand in for the chat completions endpoint that counts the requests it answers
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counted = requests.clone();
+    let completions = warp::path!("v1" / "chat" / "completions")
+        .and(warp::body::json())
+        .map(move |body: serde_json::Value| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(body["model"], "test-model");
+            warp::reply::json(&serde_json::json!({
+                "choices": [{"message": {"role": "assistant", "content": " Two\nsentences. "}}]
+            }))
+        });
+    let (address, server) = warp::serve(completions).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let summarizer = integrations::Summarizer::new(
+        reqwest::Client::new(),
+        integrations::summarizer::Config {
+            url: format!("http://{}/v1/", address),
+            model: "test-model".to_string(),
+            token: None,
+        },
+        store.clone(),
+    );
+    let mut pipeline = pipeline::Pipeline::builtin();
+    pipeline.register(
+        integrations::summarizer::STAGE,
+        pipeline::Mode::Optional,
+        Arc::new(summarizer),
+    );
+
+    let feed = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap();
+    let mut entry = feed_rs::model::Entry {
+        id: "guid-1".to_string(),
+        ..Default::default()
+    };
+    entry.summary = Some(feed_rs::model::Text {
+        content_type: "text/html".parse().unwrap(),
+        src: None,
+        content: "<p>The whole entry.</p>".to_string(),
+    });
+    let entries = [entry];
+
+    // nothing is asked until the feed turns the stage on
+    let articles = pipeline.run(&feed, &[], &entries).await;
+    assert!(articles[0].ai_summary.is_empty());
+    assert_eq!(requests.load(Ordering::SeqCst), 0);
+
+    store
+        .toggle_feed_stage(
+            feed.id.clone(),
+            integrations::summarizer::STAGE.to_string(),
+            false,
+        )
+        .await
+        .unwrap();
+    let feed = store.get_feed_by_id(feed.id).await.unwrap();
+    let articles = pipeline.run(&feed, &[], &entries).await;
+    assert_eq!(articles[0].ai_summary, "Two sentences.");
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    let id = articles[0].id.clone();
+    store.add_articles(articles.into_iter()).await.unwrap();
+
+    // a later refresh sees the entry stored and keeps the summary it has
+    let again = pipeline.run(&feed, &[], &entries).await;
+    assert!(again[0].ai_summary.is_empty());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    store.add_articles(again.into_iter()).await.unwrap();
+    let stored = store.get_article_by_id(id).await.unwrap();
+    assert_eq!(stored.ai_summary, "Two sentences.");
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn related_articles() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    let feed = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap();
+    let titled = |guid: &str, title: &str| {
+        Article {
+            guid: guid.to_string(),
+            ..Article::new(
+                title.to_string(),
+                format!("https://example.com/{}", guid),
+                "".to_string(),
+                "2026-02-02T10:00:00Z".to_string(),
+                false,
+                false,
+            )
+        }
+        .in_feed(&feed)
+    };
+    let source = titled("1", "Comparing async runtimes in Rust");
+    let articles = vec![
+        source.clone(),
+        titled("2", "Tuning an async Rust service"),
+        titled("3", "Planting tomatoes in spring"),
+    ];
+    store.add_articles(articles.into_iter()).await.unwrap();
+
+    let response = request()
+        .path(format!("/articles/{}", source.id).as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    assert!(String::from_utf8_lossy(response.body()).contains("Comparing async runtimes in Rust"));
+
+    let response = request()
+        .path(format!("/articles/{}/related", source.id).as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let related = String::from_utf8_lossy(response.body()).to_string();
+    assert!(related.contains("Tuning an async Rust service"));
+    assert!(!related.contains("Planting tomatoes"));
+    assert!(!related.contains("Comparing async runtimes"));
+
+    let response = request()
+        .path("/articles/unknown/related")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn browser_extension() {
+    let Scratch { _container, app } = scratch().await;
+    let routes = build_app(app);
+    let bearer = format!("Bearer {}", EXTENSION_TOKEN);
+
+    let response = request().path("/api/v1/unread_count").reply(&routes).await;
+    assert_eq!(response.status(), 401);
+    let response = request()
+        .path("/api/v1/unread_count")
+        .header("authorization", "Bearer wrong")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 401);
+    // the trigger has no token configured here, so any key is refused the same way
+    let response = request()
+        .path("/api/v1/triggers/new_articles")
+        .header("authorization", bearer.as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 401);
+
+    let save = || {
+        request()
+            .method("POST")
+            .path("/api/v1/save")
+            .header("authorization", bearer.as_str())
+            .json(&serde_json::json!({"url": "https://example.com/post", "title": "A post"}))
+            .reply(&routes)
+    };
+    let response = save().await;
+    assert_eq!(response.status(), 200);
+    let first: Article = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(first.title, "A post");
+    assert_eq!(first.feed_id, saved::FEED_ID);
+    let response = save().await;
+    let second: Article = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(second.id, first.id);
+
+    let response = request()
+        .path("/api/v1/unread_count")
+        .header("authorization", bearer.as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(response.body()).unwrap(),
+        serde_json::json!({"unread": 1})
+    );
+
+    let response = request()
+        .path("/api/v1/discover?url=not-a-url")
+        .header("authorization", bearer.as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn saved_pages() {
+    use rweb::warp::{self, Filter};
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    let page = warp::path("post").map(|| {
+        warp::reply::html(
+            r#"<html><head><title>A post</title><meta name="description" content="What it is about"></head><body></body></html>"#,
+        )
+    });
+    let (address, server) = warp::serve(page).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let url = format!("http://{}/post", address);
+
+    let save = |body: String| {
+        request()
+            .method("POST")
+            .path("/articles")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(body)
+            .reply(&routes)
+    };
+    let response = save("url=not-a-url".to_string()).await;
+    assert_eq!(response.status(), 400);
+    assert!(String::from_utf8_lossy(response.body()).contains("not-a-url"));
+
+    let response = save(format!("url={}", url)).await;
+    assert_eq!(response.status(), 303);
+    let location = response.headers()["location"].to_str().unwrap().to_string();
+    let id = location.trim_start_matches("/articles/").to_string();
+    let article = store.get_article_by_id(id.clone()).await.unwrap();
+    assert_eq!(article.title, "A post");
+    assert_eq!(article.summary, "What it is about");
+    assert_eq!(article.feed_id, saved::FEED_ID);
+    assert!(!article.read);
+
+    let response = save(format!("url={}&title=Given+title", url)).await;
+    assert_eq!(response.headers()["location"], location.as_str());
+    let response = request().path(location.as_str()).reply(&routes).await;
+    assert_eq!(response.status(), 200);
+    assert!(String::from_utf8_lossy(response.body()).contains("A post"));
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn newsletters_by_mail() {
+    use hmac::{Hmac, Mac};
+
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    // mailgun signs every post with a token of its own, redeliveries included
+    let timestamp = chrono::Utc::now().timestamp().to_string();
+    let sign = |token: &str| -> String {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(MAILGUN_KEY.as_bytes()).unwrap();
+        mac.update(format!("{}{}", timestamp, token).as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    };
+    let signed = |token: &'static str, signature: &str| {
+        [
+            ("timestamp", timestamp.clone()),
+            ("token", token.to_string()),
+            ("signature", signature.to_string()),
+            ("from", "Weekly Notes <notes@example.com>".to_string()),
+            ("subject", "Issue 1".to_string()),
+            ("Message-Id", "<issue-1@example.com>".to_string()),
+            (
+                "body-plain",
+                "First paragraph\r\n\r\nSecond paragraph".to_string(),
+            ),
+        ]
+    };
+    let fields = signed("token", sign("token").as_str());
+
+    let form = |fields: &[(&str, String)]| {
+        reqwest::Url::parse_with_params("http://localhost", fields)
+            .unwrap()
+            .query()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let forged = form(&[
+        ("signature", "00".to_string()),
+        ("timestamp", "0".to_string()),
+    ]);
+    let response = request()
+        .method("POST")
+        .path("/newsletters/mailgun")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(forged)
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 401);
+
+    let response = request()
+        .method("POST")
+        .path("/newsletters/mailgun")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(form(&fields))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let article: Article = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(article.title, "Issue 1");
+    assert_eq!(article.feed, "Weekly Notes");
+    assert_eq!(article.summary, "First paragraph Second paragraph");
+
+    // the same post sent again is a captured request replayed, its token was already taken
+    let response = request()
+        .method("POST")
+        .path("/newsletters/mailgun")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(form(&fields))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 401);
+
+    // mail with attachments comes as multipart, a redelivered mail is the same article
+    let mut multipart = String::new();
+    for (name, value) in signed("redelivered", sign("redelivered").as_str()) {
+        multipart.push_str(&format!(
+            "--boundary\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            name, value
+        ));
+    }
+    multipart.push_str("--boundary\r\nContent-Disposition: form-data; name=\"attachment-1\"; filename=\"a.pdf\"\r\nContent-Type: application/pdf\r\n\r\n%PDF\r\n--boundary--\r\n");
+    let response = request()
+        .method("POST")
+        .path("/newsletters/mailgun")
+        .header("content-type", "multipart/form-data; boundary=boundary")
+        .body(multipart)
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let redelivered: Article = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(redelivered.id, article.id);
+
+    let response = request()
+        .path(format!("/articles/{}", article.id).as_str())
+        .reply(&routes)
+        .await;
+    let page = String::from_utf8_lossy(response.body()).to_string();
+    assert!(page.contains("<p>First paragraph</p>"));
+    assert!(page.contains("<p>Second paragraph</p>"));
+    assert_eq!(
+        store.get_newsletter_body(article.id).await.unwrap(),
+        "First paragraph\n\nSecond paragraph"
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn json_feeds() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    let feed = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap();
+    let article = Article::new(
+        "A post".to_string(),
+        "https://example.com/post".to_string(),
+        "".to_string(),
+        "2026-02-02T10:00:00Z".to_string(),
+        false,
+        true,
+    )
+    .in_feed(&feed);
+    store
+        .add_articles(std::iter::once(article.clone()))
+        .await
+        .unwrap();
+
+    let response = request()
+        .path(format!("/feeds/{}/articles.json", feed.id).as_str())
+        .header("host", "reader.example.com")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], jsonfeed::CONTENT_TYPE);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["version"], jsonfeed::VERSION);
+    assert_eq!(body["title"], "Scratch");
+    assert_eq!(
+        body["feed_url"],
+        format!("http://reader.example.com/feeds/{}/articles.json", feed.id)
+    );
+    assert_eq!(body["items"][0]["id"], article.id);
+
+    // only http and https are taken from a forwarded proto
+    for (proto, scheme) in [("https", "https"), ("javascript", "http")] {
+        let response = request()
+            .path("/unread.json")
+            .header("host", "reader.example.com")
+            .header("x-forwarded-proto", proto)
+            .reply(&routes)
+            .await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            body["home_page_url"],
+            format!("{}://reader.example.com/", scheme)
+        );
+    }
+    assert_eq!(body["items"][0]["url"], "https://example.com/post");
+    assert_eq!(
+        body["items"][0]["date_published"],
+        "2026-02-02T10:00:00.000Z"
+    );
+
+    for path in ["/unread.json", "/favorites.json"] {
+        let response = request().path(path).reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["items"][0]["id"], article.id, "{}", path);
+    }
+    let response = request().path("/history.json").reply(&routes).await;
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["items"], serde_json::json!([]));
+
+    let response = request()
+        .path("/feeds/unknown/articles.json")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn api_cursors() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    let feed = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap();
+    let articles = (0..db::LIMIT + 2).map(|i| {
+        Article::new(
+            format!("Post {}", i),
+            format!("https://example.com/{}", i),
+            "".to_string(),
+            format!("2026-02-{:02}T10:00:00Z", i + 1),
+            false,
+            false,
+        )
+        .in_feed(&feed)
+    });
+    store.add_articles(articles).await.unwrap();
+
+    let page = |path: String| {
+        let routes = routes.clone();
+        async move {
+            let response = request().path(path.as_str()).reply(&routes).await;
+            let status = response.status();
+            (
+                status,
+                serde_json::from_slice::<serde_json::Value>(response.body()).unwrap_or_default(),
+            )
+        }
+    };
+    let (status, first) = page(format!("/api/v1/articles?filter=feed:{}", feed.id)).await;
+    assert_eq!(status, 200);
+    assert_eq!(first["articles"].as_array().unwrap().len(), db::LIMIT);
+    assert_eq!(first["prev"], serde_json::Value::Null);
+    let next = first["next"].as_str().unwrap().to_string();
+    assert!(!next.contains("2026"));
+
+    // the cursor names its list, the filter can be left out
+    let (status, second) = page(format!("/api/v1/articles?cursor={}", next)).await;
+    assert_eq!(status, 200);
+    assert_eq!(second["articles"].as_array().unwrap().len(), 2);
+    assert_eq!(second["articles"][1]["title"], "Post 0");
+    assert!(second["prev"].is_string());
+
+    let (status, _) = page(format!("/api/v1/articles?filter=favorite&cursor={}", next)).await;
+    assert_eq!(status, 400);
+    let (status, _) = page("/api/v1/articles?cursor=2026-02-03T10:00:00.000Z".to_string()).await;
+    assert_eq!(status, 400);
+
+    let response = request()
+        .path(format!("/feeds/{}/articles.json", feed.id).as_str())
+        .reply(&routes)
+        .await;
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    let next_url = body["next_url"].as_str().unwrap();
+    assert!(next_url.contains("?cursor="));
+    let response = request().path(next_url).reply(&routes).await;
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["items"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn quiet_hours() {
+    use rweb::warp::{self, Filter};
+
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app.clone());
+
+    let rss = r#"<?xml version="1.0"?><rss version="2.0"><channel><title>News</title>
+        <item><title>Overnight</title><link>https://example.com/overnight</link><guid>overnight</guid>
+        <pubDate>Mon, 02 Feb 2026 03:00:00 GMT</pubDate></item></channel></rss>"#;
+    let served = warp::path!("feed.xml")
+        .map(move || warp::reply::with_header(rss, "content-type", "application/rss+xml"));
+    let (address, server) = warp::serve(served).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let feed = store
+        .add_feed(AddFeed {
+            category: "News".to_string(),
+            ..add_feed("News", format!("http://{}/feed.xml", address).as_str())
+        })
+        .await
+        .unwrap();
+
+    let add = |form: &str| {
+        request()
+            .method("POST")
+            .path("/notifications/quiet-hours")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(form)
+            .reply(&routes)
+    };
+    assert_eq!(
+        add("target=category:News&days=someday&starts=22:00&ends=07:00")
+            .await
+            .status(),
+        400
+    );
+    assert_eq!(
+        add("target=feed:unknown&days=daily&starts=22:00&ends=07:00")
+            .await
+            .status(),
+        404
+    );
+    // the same start and end mutes the whole day, so the feed is muted whenever the test runs
+    let response = add("target=category:News&days=daily&starts=0:00&ends=00:00").await;
+    assert_eq!(response.status(), 200);
+    let schedules = store.get_mute_schedules().await.unwrap();
+    assert_eq!(schedules.len(), 1);
+    assert_eq!(schedules[0].starts, "00:00");
+
+    scheduler::refresh(
+        store.clone(),
+        &app.fetcher,
+        &app.pipeline,
+        app.dispatcher.clone(),
+        app.events.clone(),
+        app.images.clone(),
+        feed.clone(),
+    )
+    .await
+    .unwrap();
+
+    // the article came in but stays out of the unread list until the mute ends
+    let listed = store
+        .get_feed_articles(feed.id.clone(), db::MAX_DATE.to_string())
+        .await
+        .unwrap();
+    assert_eq!(listed.items.len(), 1);
+    let id: String = listed.items[0].get(0);
+    let unread = store
+        .get_unread_articles(db::MAX_DATE.to_string())
+        .await
+        .unwrap();
+    assert!(unread.items.is_empty());
+    assert_eq!(store.count_unread_articles().await.unwrap(), 0);
+
+    // deleting the quiet hours lets the article through at once, and it is released for its notifications once
+    let response = request()
+        .method("DELETE")
+        .path(format!("/notifications/quiet-hours/{}", schedules[0].id).as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    assert!(store.get_mute_schedules().await.unwrap().is_empty());
+    assert_eq!(store.count_unread_articles().await.unwrap(), 1);
+    let released = store
+        .release_muted_articles(Article::rfc3339_timestamp())
+        .await
+        .unwrap();
+    assert_eq!(released.len(), 1);
+    assert_eq!(released[0].id, id);
+    assert!(store
+        .release_muted_articles(Article::rfc3339_timestamp())
+        .await
+        .unwrap()
+        .is_empty());
+
+    // quiet hours of a feed go with it
+    let response =
+        add(format!("target=feed:{}&days=daily&starts=22:00&ends=07:00", feed.id).as_str()).await;
+    assert_eq!(response.status(), 200);
+    store.delete_feed(feed.id.clone()).await.unwrap();
+    assert!(store.get_mute_schedules().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn idempotency_keys() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    let feed = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap();
+    let article = article(&feed, "1");
+    store
+        .add_articles(std::iter::once(article.clone()))
+        .await
+        .unwrap();
+    let toggle = format!("/articles/{}/toggle_favorite", article.id);
+
+    let post = |path: String, key: &'static str| {
+        let routes = routes.clone();
+        async move {
+            request()
+                .method("POST")
+                .path(path.as_str())
+                .header("idempotency-key", key)
+                .reply(&routes)
+                .await
+        }
+    };
+
+    // the second click of a double click gets the answer to the first instead of toggling back
+    let first = post(toggle.clone(), "click-1").await;
+    assert_eq!(first.status(), 200);
+    assert!(first.headers().get(idempotency::REPLAYED_HEADER).is_none());
+    let second = post(toggle.clone(), "click-1").await;
+    assert_eq!(second.status(), 200);
+    assert_eq!(second.headers()[idempotency::REPLAYED_HEADER], "true");
+    assert_eq!(second.body(), first.body());
+    assert!(
+        store
+            .get_article_by_id(article.id.clone())
+            .await
+            .unwrap()
+            .favorited
+    );
+
+    // a new key is a new action
+    post(toggle.clone(), "click-2").await;
+    assert!(
+        !store
+            .get_article_by_id(article.id.clone())
+            .await
+            .unwrap()
+            .favorited
+    );
+
+    let reused = post(format!("/articles/{}/toggle_read", article.id), "click-1").await;
+    assert_eq!(reused.status(), 422);
+    assert_eq!(post(toggle.clone(), "two words").await.status(), 400);
+
+    // error pages are answers too, the same missing article is not looked up again
+    let missing = post("/articles/missing/toggle_favorite".to_string(), "click-3").await;
+    assert_eq!(missing.status(), 404);
+    let again = post("/articles/missing/toggle_favorite".to_string(), "click-3").await;
+    assert_eq!(again.status(), 404);
+    assert_eq!(again.headers()[idempotency::REPLAYED_HEADER], "true");
+
+    // the same key sent with another payload to the same path is refused, not answered with the first response
+    let collapse = |category: &'static str| {
+        let routes = routes.clone();
+        async move {
+            request()
+                .method("POST")
+                .path("/preferences/sidebar")
+                .header("idempotency-key", "sidebar-1")
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(format!("category={}", category))
+                .reply(&routes)
+                .await
+        }
+    };
+    assert_eq!(collapse("News").await.status(), 200);
+    let replayed = collapse("News").await;
+    assert_eq!(replayed.headers()[idempotency::REPLAYED_HEADER], "true");
+    assert_eq!(collapse("Sports").await.status(), 422);
+
+    // a key does not let a body past what the route reads, it is refused as too large before it is hashed
+    let large = request()
+        .method("POST")
+        .path("/preferences/sidebar")
+        .header("idempotency-key", "sidebar-2")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(format!("category={}", "x".repeat(4 * 1024 * 1024)))
+        .reply(&routes)
+        .await;
+    assert_eq!(large.status(), 413);
+
+    // a key the first request is still answering is refused until it finishes
+    store
+        .claim_idempotency_key(
+            "in-flight".to_string(),
+            "POST".to_string(),
+            toggle.clone(),
+            idempotency::fingerprint("", b""),
+            "2000-01-01T00:00:00.000Z".to_string(),
+            "2000-01-01T00:00:00.000Z".to_string(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(post(toggle.clone(), "in-flight").await.status(), 409);
+
+    // expired keys are pruned by the scheduler and can be used again
+    assert_eq!(
+        store
+            .prune_idempotency_keys("2999-01-01T00:00:00.000Z".to_string())
+            .await
+            .unwrap(),
+        5
+    );
+    let fresh = post(toggle.clone(), "click-1").await;
+    assert!(fresh.headers().get(idempotency::REPLAYED_HEADER).is_none());
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn trash() {
+    let Scratch { _container, app } = scratch().await;
+    let store = app.store.clone();
+    let routes = build_app(app);
+
+    let feed = store
+        .add_feed(add_feed("Scratch", "https://example.com/feed.xml"))
+        .await
+        .unwrap();
+    let read = Article {
+        read: true,
+        read_date: "2026-02-03T10:00:00.000Z".to_string(),
+        ..article(&feed, "1")
+    };
+    let unread = article(&feed, "2");
+    store
+        .add_articles(vec![read.clone(), unread.clone()].into_iter())
+        .await
+        .unwrap();
+
+    // pruning moves the read article to the trash, the unread one stays
+    assert_eq!(
+        store
+            .prune_articles("2026-03-01T00:00:00.000Z".to_string())
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(store.get_article_by_id(read.id.clone()).await.is_err());
+    assert!(store.get_article_by_id(unread.id.clone()).await.is_ok());
+    let trashed = store.get_trash(10).await.unwrap();
+    assert_eq!(trashed.len(), 1);
+    assert_eq!(trashed[0].article.id, read.id);
+    assert_eq!(store.count_trash().await.unwrap(), 1);
+
+    let page = request().path("/trash.html").reply(&routes).await;
+    assert_eq!(page.status(), 200);
+    assert!(String::from_utf8_lossy(page.body()).contains("Article 1"));
+
+    // restoring puts it back read, with the id it had
+    let restore = format!("/trash/{}/restore", read.id);
+    let restored = request()
+        .method("POST")
+        .path(restore.as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(restored.status(), 200);
+    let article = store.get_article_by_id(read.id.clone()).await.unwrap();
+    assert!(article.read);
+    assert_eq!(article.title, "Article 1");
+    assert_eq!(store.count_trash().await.unwrap(), 0);
+    let again = request()
+        .method("POST")
+        .path(restore.as_str())
+        .reply(&routes)
+        .await;
+    assert_eq!(again.status(), 404);
+
+    // an article trashed before a column was added comes back with the default of that column
+    store
+        .prune_articles("2026-03-01T00:00:00.000Z".to_string())
+        .await
+        .unwrap();
+    store
+        .batch_execute(
+            "UPDATE trashed_articles SET article = article - 'ai_summary' - 'muted_until'",
+        )
+        .await
+        .unwrap();
+    let article = store.restore_article(read.id.clone()).await.unwrap();
+    assert_eq!(article.id, read.id);
+    assert!(article.ai_summary.is_empty());
+
+    // the maintenance job deletes what was trashed before its cutoff for good
+    store
+        .prune_articles("2026-03-01T00:00:00.000Z".to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .empty_trash("2000-01-01T00:00:00.000Z".to_string())
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        store
+            .empty_trash("9999-01-01T00:00:00.000Z".to_string())
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(store.get_trash(10).await.unwrap().is_empty());
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub const DEFAULT_URL: &str = "https://bsky.social";
+
+pub struct Config {
+    pub url: String,
+    pub handle: String,
+    pub app_password: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    access_jwt: String,
+    did: String,
+    handle: String,
+}
+
+#[derive(Deserialize)]
+struct Record {
+    uri: String,
+}
+
+// Bluesky posts to an account with an app password, https://docs.bsky.app/docs/advanced-guides/posts
+pub struct Bluesky {
+    client: Client,
+    config: Config,
+}
+
+impl Bluesky {
+    pub fn new(client: Client, config: Config) -> Self {
+        Bluesky {
+            client,
+            config: Config {
+                url: config.url.trim_end_matches('/').to_string(),
+                ..config
+            },
+        }
+    }
+
+    // post returns the bsky.app url of the new post
+    pub async fn post(&self, text: &str) -> Result<String> {
+        let session: Session = self
+            .client
+            .post(format!(
+                "{}/xrpc/com.atproto.server.createSession",
+                self.config.url
+            ))
+            .json(&json!({
+                "identifier": self.config.handle,
+                "password": self.config.app_password,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let record: Record = self
+            .client
+            .post(format!(
+                "{}/xrpc/com.atproto.repo.createRecord",
+                self.config.url
+            ))
+            .bearer_auth(session.access_jwt.as_str())
+            .json(&json!({
+                "repo": session.did,
+                "collection": "app.bsky.feed.post",
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "text": text,
+                    "facets": link_facets(text),
+                    "createdAt": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                },
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let rkey = record.uri.rsplit('/').next().unwrap_or_default();
+        Ok(format!(
+            "https://bsky.app/profile/{}/post/{}",
+            session.handle, rkey
+        ))
+    }
+}
+
+// link_facets marks the urls in the text, bluesky does not link them otherwise
+fn link_facets(text: &str) -> Vec<Value> {
+    let mut facets = vec![];
+    let mut offset = 0;
+    for word in text.split_inclusive(char::is_whitespace) {
+        let uri = word.trim_end();
+        if uri.starts_with("https://") || uri.starts_with("http://") {
+            facets.push(json!({
+                "index": { "byteStart": offset, "byteEnd": offset + uri.len() },
+                "features": [{ "$type": "app.bsky.richtext.facet#link", "uri": uri }],
+            }));
+        }
+        offset += word.len();
+    }
+    facets
+}
@@ -0,0 +1,106 @@
+use crate::models::Article;
+use anyhow::Result;
+use reqwest::Client;
+use serde_json::json;
+use std::fmt;
+use std::str::FromStr;
+
+pub enum Service {
+    Pinboard,
+    Linkding,
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Service::Pinboard => write!(f, "pinboard"),
+            Service::Linkding => write!(f, "linkding"),
+        }
+    }
+}
+
+impl FromStr for Service {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Service> {
+        match s {
+            "pinboard" => Ok(Service::Pinboard),
+            "linkding" => Ok(Service::Linkding),
+            _ => Err(anyhow::Error::msg(format!("bad bookmark service: {}", s))),
+        }
+    }
+}
+
+impl Service {
+    fn default_url(&self) -> &'static str {
+        match self {
+            Service::Pinboard => "https://api.pinboard.in",
+            Service::Linkding => "",
+        }
+    }
+}
+
+pub struct Config {
+    pub service: Service,
+    pub url: Option<String>,
+    pub token: String,
+}
+
+// Bookmarks creates a bookmark in pinboard or linkding for favorited articles, tagged with the feed name
+pub struct Bookmarks {
+    client: Client,
+    service: Service,
+    url: String,
+    token: String,
+}
+
+impl Bookmarks {
+    pub fn new(client: Client, config: Config) -> Self {
+        let url = config
+            .url
+            .unwrap_or_else(|| config.service.default_url().to_string());
+        Bookmarks {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            service: config.service,
+            token: config.token,
+        }
+    }
+
+    pub async fn add(&self, article: &Article) -> Result<()> {
+        let tag = tag(article.feed.as_str());
+        let request = match self.service {
+            // https://pinboard.in/api/#posts_add
+            Service::Pinboard => self
+                .client
+                .get(format!("{}/v1/posts/add", self.url))
+                .query(&[
+                    ("url", article.link.as_str()),
+                    ("description", article.title.as_str()),
+                    ("tags", tag.as_str()),
+                    ("auth_token", self.token.as_str()),
+                    ("format", "json"),
+                ]),
+            // https://linkding.link/api/#create
+            Service::Linkding => self
+                .client
+                .post(format!("{}/api/bookmarks/", self.url))
+                .header("Authorization", format!("Token {}", self.token))
+                .json(&json!({
+                    "url": article.link,
+                    "title": article.title,
+                    "tag_names": [tag],
+                })),
+        };
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+// tag turns a feed name into a single whitespace free tag
+fn tag(feed: &str) -> String {
+    feed.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("-")
+        .to_lowercase()
+}
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::Deserialize;
+
+pub struct Config {
+    pub url: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    url: String,
+}
+
+// Mastodon posts statuses to an account, https://docs.joinmastodon.org/methods/statuses/#create
+pub struct Mastodon {
+    client: Client,
+    config: Config,
+}
+
+impl Mastodon {
+    pub fn new(client: Client, config: Config) -> Self {
+        Mastodon {
+            client,
+            config: Config {
+                url: config.url.trim_end_matches('/').to_string(),
+                ..config
+            },
+        }
+    }
+
+    // post returns the url of the new status
+    pub async fn post(&self, text: &str) -> Result<String> {
+        let status: Status = self
+            .client
+            .post(format!("{}/api/v1/statuses", self.config.url))
+            .bearer_auth(self.config.token.as_str())
+            .form(&[("status", text)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(status.url)
+    }
+}
@@ -0,0 +1,66 @@
+pub mod bluesky;
+pub mod bookmarks;
+pub mod mastodon;
+pub mod pocket;
+pub mod readwise;
+pub mod summarizer;
+pub mod wallabag;
+
+pub use bluesky::Bluesky;
+pub use bookmarks::Bookmarks;
+pub use mastodon::Mastodon;
+pub use pocket::Pocket;
+pub use readwise::Readwise;
+pub use summarizer::Summarizer;
+pub use wallabag::Wallabag;
+
+use anyhow::Result;
+use std::sync::Arc;
+
+// Integrations holds the clients for the outbound services that have been configured
+#[derive(Clone, Default)]
+pub struct Integrations {
+    pub wallabag: Option<Arc<Wallabag>>,
+    pub pocket: Option<Arc<Pocket>>,
+    pub bookmarks: Option<Arc<Bookmarks>>,
+    pub readwise: Option<Arc<Readwise>>,
+    pub mastodon: Option<Arc<Mastodon>>,
+    pub bluesky: Option<Arc<Bluesky>>,
+}
+
+// Enabled tells the templates which integration actions to render
+#[derive(Clone, Default)]
+pub struct Enabled {
+    pub wallabag: bool,
+    pub share: bool,
+}
+
+impl Integrations {
+    pub fn enabled(&self) -> Enabled {
+        Enabled {
+            wallabag: self.wallabag.is_some(),
+            share: !self.share_services().is_empty(),
+        }
+    }
+
+    // share_services lists the accounts an article can be shared to
+    pub fn share_services(&self) -> Vec<String> {
+        let mut services = vec![];
+        if self.mastodon.is_some() {
+            services.push("mastodon".to_string());
+        }
+        if self.bluesky.is_some() {
+            services.push("bluesky".to_string());
+        }
+        services
+    }
+
+    // share posts the text to the named service, returning the url of the post
+    pub async fn share(&self, service: &str, text: &str) -> Result<String> {
+        match (service, &self.mastodon, &self.bluesky) {
+            ("mastodon", Some(mastodon), _) => mastodon.post(text).await,
+            ("bluesky", _, Some(bluesky)) => bluesky.post(text).await,
+            _ => Err(anyhow::Error::msg(format!("{} is not configured", service))),
+        }
+    }
+}
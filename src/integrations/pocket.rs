@@ -0,0 +1,84 @@
+use crate::models::Article;
+use crate::{db, scheduler};
+use anyhow::Result;
+use futures::stream::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+// most favorites pushed in a single sync
+const SYNC_LIMIT: i64 = 100;
+
+pub struct Config {
+    pub consumer_key: String,
+    pub access_token: String,
+}
+
+#[derive(Deserialize)]
+struct AddResponse {
+    item: Item,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    item_id: String,
+}
+
+// Pocket pushes favorites through the v3 api, https://getpocket.com/developer/docs/v3/add
+pub struct Pocket {
+    client: Client,
+    config: Config,
+}
+
+impl Pocket {
+    pub fn new(client: Client, config: Config) -> Self {
+        Pocket { client, config }
+    }
+
+    // add returns the pocket item id for the article
+    pub async fn add(&self, article: &Article) -> Result<String> {
+        let response: AddResponse = self
+            .client
+            .post("https://getpocket.com/v3/add")
+            .header("X-Accept", "application/json")
+            .json(&json!({
+                "url": article.link,
+                "title": article.title,
+                "consumer_key": self.config.consumer_key,
+                "access_token": self.config.access_token,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.item.item_id)
+    }
+
+    // sync pushes favorites that have no pocket item yet, returning how many were pushed
+    pub async fn sync(&self, store: &db::Storage) -> Result<usize> {
+        let articles = store.get_unsynced_pocket_favorites(SYNC_LIMIT).await?;
+        let mut synced = 0;
+        for article in articles {
+            let item_id = self.add(&article).await?;
+            store
+                .add_pocket_item(article.id, item_id, Article::rfc3339_timestamp())
+                .await?;
+            synced += 1;
+        }
+        Ok(synced)
+    }
+
+    pub async fn run(&self, store: db::Storage, schedule: scheduler::Schedule) {
+        schedule
+            .ticks(true)
+            .for_each(|_| async {
+                match self.sync(&store).await {
+                    Ok(0) => {}
+                    Ok(count) => println!("synced {} favorites to pocket", count),
+                    Err(e) => println!("could not sync favorites to pocket: {}", e),
+                }
+            })
+            .await;
+    }
+}
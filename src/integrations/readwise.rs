@@ -0,0 +1,102 @@
+use crate::models::Article;
+use crate::{db, scheduler};
+use anyhow::Result;
+use futures::stream::StreamExt;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::time;
+
+// most favorites pushed in a single sync
+const SYNC_LIMIT: i64 = 100;
+// times a rate limited save is retried before the sync gives up until the next run
+const RATE_LIMIT_RETRIES: u32 = 3;
+// wait used when a 429 carries no usable Retry-After header
+const DEFAULT_RETRY_AFTER_SECONDS: u64 = 60;
+
+pub struct Config {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+struct SaveResponse {
+    id: String,
+}
+
+// Readwise pushes favorites to reader, https://readwise.io/reader_api
+pub struct Readwise {
+    client: Client,
+    config: Config,
+}
+
+impl Readwise {
+    pub fn new(client: Client, config: Config) -> Self {
+        Readwise { client, config }
+    }
+
+    // save returns the reader document id for the article
+    // article content is not stored, so reader fetches the page from the link itself
+    pub async fn save(&self, article: &Article) -> Result<String> {
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .client
+                .post("https://readwise.io/api/v3/save/")
+                .header("Authorization", format!("Token {}", self.config.token))
+                .json(&json!({
+                    "url": article.link,
+                    "title": article.title,
+                    "author": article.author,
+                    "published_date": article.published,
+                    "tags": [article.feed],
+                    "saved_using": "feedreader",
+                }))
+                .send()
+                .await?;
+
+            if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= RATE_LIMIT_RETRIES {
+                let response: SaveResponse = response.error_for_status()?.json().await?;
+                return Ok(response.id);
+            }
+
+            let wait = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_RETRY_AFTER_SECONDS);
+            println!("readwise rate limited, retrying in {}s", wait);
+            time::sleep(Duration::from_secs(wait)).await;
+            attempt += 1;
+        }
+    }
+
+    // sync pushes favorites that have no reader document yet, returning how many were pushed
+    pub async fn sync(&self, store: &db::Storage) -> Result<usize> {
+        let articles = store.get_unsynced_readwise_favorites(SYNC_LIMIT).await?;
+        let mut synced = 0;
+        for article in articles {
+            let document_id = self.save(&article).await?;
+            store
+                .add_readwise_document(article.id, document_id, Article::rfc3339_timestamp())
+                .await?;
+            synced += 1;
+        }
+        Ok(synced)
+    }
+
+    pub async fn run(&self, store: db::Storage, schedule: scheduler::Schedule) {
+        schedule
+            .ticks(true)
+            .for_each(|_| async {
+                match self.sync(&store).await {
+                    Ok(0) => {}
+                    Ok(count) => println!("synced {} favorites to readwise", count),
+                    Err(e) => println!("could not sync favorites to readwise: {}", e),
+                }
+            })
+            .await;
+    }
+}
@@ -0,0 +1,120 @@
+use crate::db;
+use crate::models::{self, Article};
+use crate::pipeline::{Context, Stage};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+// the stage name feeds turn on, it is registered only when SUMMARIZER_URL is set
+pub const STAGE: &str = "ai-summary";
+// text of the entry sent along with the title, longer entries are cut
+const MAX_INPUT_CHARS: usize = 6000;
+// a summary longer than this is cut, two sentences rarely come close
+const MAX_SUMMARY_CHARS: usize = 600;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const PROMPT: &str = "Summarize the article in two plain sentences, in the language it is written in. Reply with the summary only.";
+
+pub struct Config {
+    pub url: String,
+    pub model: String,
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Completion {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    content: String,
+}
+
+// Summarizer asks an openai compatible chat completions endpoint for a two sentence summary of new entries,
+// https://platform.openai.com/docs/api-reference/chat, ollama and llama.cpp serve the same api locally
+pub struct Summarizer {
+    client: Client,
+    config: Config,
+    store: db::Storage,
+}
+
+impl Summarizer {
+    pub fn new(client: Client, config: Config, store: db::Storage) -> Self {
+        Summarizer {
+            client,
+            config: Config {
+                url: config.url.trim_end_matches('/').to_string(),
+                ..config
+            },
+            store,
+        }
+    }
+
+    pub async fn summarize(&self, title: &str, text: &str) -> Result<String> {
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.config.url))
+            .timeout(REQUEST_TIMEOUT)
+            .json(&json!({
+                "model": self.config.model,
+                "messages": [
+                    {"role": "system", "content": PROMPT},
+                    {"role": "user", "content": format!("{}\n\n{}", title, text)},
+                ],
+            }));
+        if let Some(token) = self.config.token.as_deref() {
+            request = request.bearer_auth(token);
+        }
+        let completion: Completion = request.send().await?.error_for_status()?.json().await?;
+        let summary = completion
+            .choices
+            .into_iter()
+            .next()
+            .map(|c| models::plain_text(c.message.content.as_str()))
+            .unwrap_or_default();
+        Ok(models::truncate(summary, MAX_SUMMARY_CHARS))
+    }
+}
+
+// entries a refresh already stored keep the summary they got then, only new ones cost a request
+#[async_trait]
+impl Stage for Summarizer {
+    async fn process(&self, cx: &Context<'_>, article: &mut Article) -> Result<bool> {
+        let text = cx
+            .entry
+            .content
+            .as_ref()
+            .and_then(|c| c.body.as_deref())
+            .or_else(|| cx.entry.summary.as_ref().map(|s| s.content.as_str()))
+            .map(|html| models::plain_text(models::strip_tags(html).as_str()))
+            .unwrap_or_default();
+        if text.is_empty() {
+            return Ok(true);
+        }
+        let stored = self
+            .store
+            .existing_guids(
+                article.feed_id.clone(),
+                vec![article.guid.clone()],
+                vec![article.link.clone()],
+            )
+            .await?;
+        if !stored.is_empty() {
+            return Ok(true);
+        }
+
+        let text = models::truncate(text, MAX_INPUT_CHARS);
+        article.ai_summary = self
+            .summarize(article.title.as_str(), text.as_str())
+            .await?;
+        Ok(true)
+    }
+}
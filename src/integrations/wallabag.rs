@@ -0,0 +1,94 @@
+use crate::models::Article;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use futures::lock::Mutex;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+pub struct Config {
+    pub url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+struct Token {
+    access_token: String,
+    expires: DateTime<Utc>,
+}
+
+// Wallabag saves articles through the instance api, https://doc.wallabag.org/en/developer/api/oauth.html
+pub struct Wallabag {
+    client: Client,
+    config: Config,
+    token: Mutex<Option<Token>>,
+}
+
+impl Wallabag {
+    pub fn new(client: Client, config: Config) -> Self {
+        Wallabag {
+            client,
+            config: Config {
+                url: config.url.trim_end_matches('/').to_string(),
+                ..config
+            },
+            token: Mutex::new(None),
+        }
+    }
+
+    pub async fn save(&self, article: &Article) -> Result<()> {
+        let access_token = self.access_token().await?;
+        self.client
+            .post(format!("{}/api/entries.json", self.config.url))
+            .bearer_auth(access_token)
+            .json(&json!({
+                "url": article.link,
+                "title": article.title,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    // access_token reuses the current token until shortly before it expires
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(t) = token.as_ref() {
+            if t.expires > Utc::now() {
+                return Ok(t.access_token.clone());
+            }
+        }
+
+        let response: TokenResponse = self
+            .client
+            .post(format!("{}/oauth/v2/token", self.config.url))
+            .form(&[
+                ("grant_type", "password"),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("username", self.config.username.as_str()),
+                ("password", self.config.password.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let access_token = response.access_token.clone();
+        *token = Some(Token {
+            access_token: response.access_token,
+            expires: Utc::now() + Duration::seconds(response.expires_in - 60),
+        });
+        Ok(access_token)
+    }
+}
@@ -0,0 +1,173 @@
+use crate::models::Article;
+use serde::Serialize;
+
+// https://www.jsonfeed.org/version/1.1/
+pub const VERSION: &str = "https://jsonfeed.org/version/1.1";
+pub const CONTENT_TYPE: &str = "application/feed+json";
+
+// Feed is a json feed of a list of articles, urls are absolute when the request said which host it was sent to
+#[derive(Serialize, Debug)]
+pub struct Feed {
+    pub version: &'static str,
+    pub title: String,
+    pub home_page_url: String,
+    pub feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_url: Option<String>,
+    pub items: Vec<Item>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Item {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    // every item needs content, articles only keep their summary so that is what is sent
+    pub content_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    // rfc3339 as the spec asks, the stored time and never the date as the pages render it
+    pub date_published: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<Author>,
+    // the feed the article came from, favorited and read state, so a consumer can slice further
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Author {
+    pub name: String,
+}
+
+impl Item {
+    // origin is put in front of the details page of articles without a link of their own
+    pub fn new(article: &Article, origin: &str) -> Item {
+        let url = match article.link.is_empty() {
+            true => format!("{}{}", origin, article.href()),
+            false => article.link.clone(),
+        };
+        let summary = [article.ai_summary.as_str(), article.summary.as_str()]
+            .into_iter()
+            .find(|summary| !summary.is_empty())
+            .map(str::to_string);
+        let mut tags = vec![article.feed.clone()];
+        if article.favorited {
+            tags.push("favorited".to_string());
+        }
+        if article.read {
+            tags.push("read".to_string());
+        }
+
+        Item {
+            id: article.id.clone(),
+            content_text: summary.clone().unwrap_or_else(|| article.title.clone()),
+            summary,
+            url,
+            title: article.title.clone(),
+            image: (!article.thumbnail.is_empty()).then(|| article.thumbnail.clone()),
+            date_published: article.published.clone(),
+            authors: match article.author.is_empty() {
+                true => vec![],
+                false => vec![Author {
+                    name: article.author.clone(),
+                }],
+            },
+            tags,
+        }
+    }
+}
+
+impl Feed {
+    pub fn new(
+        title: String,
+        home_page_url: String,
+        feed_url: String,
+        next_url: Option<String>,
+        articles: &[Article],
+        origin: &str,
+    ) -> Feed {
+        Feed {
+            version: VERSION,
+            title,
+            home_page_url,
+            feed_url,
+            next_url,
+            items: articles.iter().map(|a| Item::new(a, origin)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn articles_become_items() {
+        let article = Article {
+            id: "abc".to_string(),
+            feed: "Blog".to_string(),
+            summary: "What it is about".to_string(),
+            favorited: true,
+            ..Article::new(
+                "A post".to_string(),
+                "https://example.com/post".to_string(),
+                "Jane".to_string(),
+                "2026-02-02T10:00:00.000Z".to_string(),
+                false,
+                true,
+            )
+        };
+        let newsletter = Article {
+            id: "def".to_string(),
+            feed: "Notes".to_string(),
+            ..Article::new(
+                "Issue 1".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "2026-02-01T10:00:00.000Z".to_string(),
+                true,
+                false,
+            )
+        };
+        let feed = Feed::new(
+            "Favorites".to_string(),
+            "https://reader.example.com/favorites.html".to_string(),
+            "https://reader.example.com/favorites.json".to_string(),
+            None,
+            &[article, newsletter],
+            "https://reader.example.com",
+        );
+        assert_eq!(
+            serde_json::to_value(feed).unwrap(),
+            json!({
+                "version": "https://jsonfeed.org/version/1.1",
+                "title": "Favorites",
+                "home_page_url": "https://reader.example.com/favorites.html",
+                "feed_url": "https://reader.example.com/favorites.json",
+                "items": [
+                    {
+                        "id": "abc",
+                        "url": "https://example.com/post",
+                        "title": "A post",
+                        "content_text": "What it is about",
+                        "summary": "What it is about",
+                        "date_published": "2026-02-02T10:00:00.000Z",
+                        "authors": [{"name": "Jane"}],
+                        "tags": ["Blog", "favorited"],
+                    },
+                    {
+                        "id": "def",
+                        "url": "https://reader.example.com/articles/def",
+                        "title": "Issue 1",
+                        "content_text": "Issue 1",
+                        "date_published": "2026-02-01T10:00:00.000Z",
+                        "tags": ["Notes", "read"],
+                    },
+                ],
+            })
+        );
+    }
+}
@@ -0,0 +1,544 @@
+#![recursion_limit = "256"]
+
+mod assets;
+mod cache_control;
+mod cli;
+pub mod config;
+mod cursor;
+mod dates;
+mod db;
+mod demo;
+mod digest;
+mod discover;
+mod epub;
+mod events;
+mod fetch;
+mod history;
+mod i18n;
+mod idempotency;
+mod images;
+mod import;
+#[cfg(test)]
+mod integration_tests;
+mod integrations;
+mod jsonfeed;
+mod mail;
+mod models;
+mod mqtt;
+mod mute;
+mod newsletters;
+mod notify;
+mod pipeline;
+mod query;
+mod queue;
+mod render_cache;
+mod report;
+mod routes;
+mod saved;
+mod scheduler;
+mod security;
+mod shutdown;
+mod sync;
+
+pub use routes::build_app;
+pub use scheduler::run_scheduler;
+
+use clap::Parser;
+use fluent_bundle::{FluentArgs, FluentValue};
+use futures::future::{self, FutureExt};
+use routes::{ExtensionToken, MailgunKey, TriggerToken};
+use rweb::serve;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
+// set by build.rs
+pub(crate) const GIT_SHA: &str = env!("FEEDREADER_GIT_SHA");
+
+// BASE_PATH is the prefix every route is served under, set once at startup
+pub(crate) static BASE_PATH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// PUBLIC_URL is the origin absolute urls are built with when one is configured, set once at startup
+pub(crate) static PUBLIC_URL: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// base_path is used by the templates so generated urls work when served under a prefix
+pub fn base_path() -> &'static str {
+    BASE_PATH.get().map(|s| s.as_str()).unwrap_or("")
+}
+
+// SETTINGS is the settings table cache, set once at startup so the base layout can read the theme
+pub(crate) static SETTINGS: std::sync::OnceLock<db::Settings> = std::sync::OnceLock::new();
+
+// IMAGES signs the remote images pages show, the content security policy only allows them through the proxy
+pub(crate) static IMAGES: std::sync::OnceLock<images::Proxy> = std::sync::OnceLock::new();
+
+pub fn proxied(src: &str) -> String {
+    match IMAGES.get() {
+        Some(proxy) => proxy.url(src),
+        None => src.to_string(),
+    }
+}
+
+// I18N holds every translation, loaded once at startup
+pub(crate) static I18N: std::sync::OnceLock<i18n::Catalog> = std::sync::OnceLock::new();
+
+pub fn language() -> String {
+    match SETTINGS.get() {
+        Some(settings) => settings.language(),
+        None => i18n::DEFAULT_LANGUAGE.to_string(),
+    }
+}
+
+// tr is the message for key in the chosen language, templates call it for every user facing string
+pub fn tr(key: &str) -> String {
+    match I18N.get() {
+        Some(catalog) => catalog.message(language().as_str(), key, None),
+        None => key.to_string(),
+    }
+}
+
+pub fn tr_with<'a>(key: &str, name: &'a str, value: impl Into<FluentValue<'a>>) -> String {
+    let mut args = FluentArgs::new();
+    args.set(name, value);
+    match I18N.get() {
+        Some(catalog) => catalog.message(language().as_str(), key, Some(&args)),
+        None => key.to_string(),
+    }
+}
+
+// tr_choice is the label of a choice like a theme, messages are named after the setting and its value
+pub fn tr_choice(setting: &str, value: &str) -> String {
+    tr(format!("{}-{}", setting, value).as_str())
+}
+
+// asset is the url of a file under static/, fingerprinted so it can be cached for good
+pub fn asset(name: &str) -> String {
+    format!("{}/static/{}", base_path(), assets::fingerprinted(name))
+}
+
+// theme is the class the base layout puts on the page, system follows the browser preference
+pub fn theme() -> String {
+    match SETTINGS.get() {
+        Some(settings) => settings.theme(),
+        None => db::THEMES[0].to_string(),
+    }
+}
+
+pub fn is_theme(name: &str) -> bool {
+    theme() == name
+}
+
+// custom_css_version changes with the custom stylesheet so browsers fetch it again, empty when none is set
+pub fn custom_css_version() -> String {
+    let css = SETTINGS
+        .get()
+        .map(|settings| settings.custom_css())
+        .unwrap_or_default();
+    if css.trim().is_empty() {
+        return "".to_string();
+    }
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    css.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+// CONFIG is the config file given with --config, set once at startup so a reload reads the same file
+pub(crate) static CONFIG: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+
+// DATES is the timezone and format dates are rendered with, set once at startup
+pub(crate) static DATES: std::sync::OnceLock<dates::Format> = std::sync::OnceLock::new();
+
+// run is the whole server, the binary only starts the runtime and calls it
+pub async fn run() {
+    let cli = cli::Cli::parse();
+    if let Some(path) = cli.config.clone() {
+        CONFIG.set(path).ok();
+    }
+    let settings = match config::Settings::load(CONFIG.get().map(|path| path.as_path())) {
+        Ok(settings) => settings,
+        Err(errors) => exit_with(errors),
+    };
+    let _reporting = report::init(settings.sentry.clone());
+    if let Err(errors) = translations() {
+        exit_with(errors);
+    }
+
+    match cli.command.unwrap_or(cli::Command::Serve) {
+        cli::Command::Serve => {}
+        command => {
+            let (store, fetcher) = match connect(&settings).await {
+                Ok(connected) => connected,
+                Err(errors) => exit_with(errors),
+            };
+            if let Err(e) = cli::run(command, &store, &fetcher).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+
+    let address = settings.address;
+    let shutdown_settings = settings.shutdown;
+    let refresh_on_startup = settings.refresh_on_startup;
+    let app = match App::from_settings(settings).await {
+        Ok(app) => app,
+        Err(errors) => exit_with(errors),
+    };
+    let draining = app.draining.clone();
+    println!(
+        "feedreader {} ({}) listening on {}",
+        VERSION, GIT_SHA, address
+    );
+
+    if cli.seed_demo {
+        demo::seed(&app).await;
+    }
+    let routes = build_app(app.clone());
+
+    // on a shutdown signal readiness fails right away, the server keeps accepting for the delay and then drains
+    let stopping = {
+        let draining = draining.clone();
+        async move {
+            shutdown::signal().await;
+            draining.start();
+            println!(
+                "shutting down, accepting connections for another {}s",
+                shutdown_settings.delay_seconds
+            );
+            tokio::time::sleep(std::time::Duration::from_secs(
+                shutdown_settings.delay_seconds,
+            ))
+            .await;
+        }
+        .shared()
+    };
+
+    let refresh_stream = run_scheduler(app, refresh_on_startup, stopping.clone());
+
+    let (_, server) = serve(routes).bind_with_graceful_shutdown(address, stopping.clone());
+    let drained = future::join(server, refresh_stream);
+    let deadline = stopping.then(|_| {
+        tokio::time::sleep(std::time::Duration::from_secs(
+            shutdown_settings.grace_seconds,
+        ))
+    });
+
+    let finished = future::select(Box::pin(drained), Box::pin(deadline)).await;
+    if let future::Either::Right(_) = finished {
+        println!(
+            "in-flight work did not finish within {}s, exiting anyway",
+            shutdown_settings.grace_seconds
+        );
+    }
+}
+
+// translations loads the catalog the templates use, once even when both run and App::from_settings ask for it
+fn translations() -> Result<(), Vec<String>> {
+    if I18N.get().is_none() {
+        let catalog = i18n::Catalog::load().map_err(|e| vec![e.to_string()])?;
+        I18N.set(catalog).ok();
+    }
+    Ok(())
+}
+
+// connect opens the database, creating or migrating its tables, and builds the client feeds are fetched with
+async fn connect(
+    settings: &config::Settings,
+) -> Result<(db::Storage, fetch::Fetcher), Vec<String>> {
+    let store = match db::connection(
+        settings.postgres.username.as_str(),
+        settings.postgres.password.as_str(),
+        settings.postgres.host.as_str(),
+        settings.postgres.port,
+    )
+    .await
+    {
+        Ok(store) => store,
+        Err(e) => {
+            return Err(vec![format!(
+                "could not connect to postgres at {}:{}: {}",
+                settings.postgres.host, settings.postgres.port, e
+            )])
+        }
+    };
+
+    if let Err(e) = store.init().await {
+        return Err(vec![format!("could not init db: {}", e)]);
+    }
+
+    let fetcher = match fetch::Fetcher::new(
+        settings.fetch.proxy.clone(),
+        settings.fetch.socks_proxy.clone(),
+        settings.fetch.allowed_hosts.clone(),
+        settings.fetch.retries,
+    ) {
+        Ok(fetcher) => fetcher,
+        Err(e) => return Err(vec![format!("could not build the feed client: {}", e)]),
+    };
+    Ok((store, fetcher))
+}
+
+// App is everything the routes and the scheduler share, run builds it from the settings and tests from a scratch database
+#[derive(Clone)]
+pub struct App {
+    pub(crate) store: db::Storage,
+    pub(crate) db_settings: db::Settings,
+    pub(crate) integrations: integrations::Integrations,
+    pub(crate) fetcher: fetch::Fetcher,
+    pub(crate) pipeline: pipeline::Pipeline,
+    pub(crate) dispatcher: notify::Dispatcher,
+    pub(crate) events: events::Events,
+    pub(crate) images: images::Proxy,
+    pub(crate) render_cache: render_cache::Cache,
+    pub(crate) scheduler_state: scheduler::State,
+    pub(crate) kindle: Option<epub::Kindle>,
+    pub(crate) trigger_token: TriggerToken,
+    pub(crate) extension_token: ExtensionToken,
+    pub(crate) mailgun_key: MailgunKey,
+    pub(crate) cursors: cursor::Signer,
+    pub(crate) draining: shutdown::Draining,
+    pub(crate) security_headers: security::Headers,
+    pub(crate) base_path: String,
+}
+
+impl App {
+    // from_settings connects to the database and starts the background work the settings turn on, it is what run
+    // serves and lets another program embed the reader: build_app serves the app and run_scheduler refreshes its feeds
+    pub async fn from_settings(settings: config::Settings) -> Result<App, Vec<String>> {
+        translations()?;
+        let (store, fetcher) = connect(&settings).await?;
+
+        // the globals pages and mails are rendered with are set before any background work starts, a digest or sync
+        // that renders a date first would otherwise fix the default format for good
+        let (prefetch, prefetch_worker) = queue::Queue::new(
+            "image prefetch",
+            images::PREFETCH_QUEUE_SIZE,
+            images::PREFETCH_CONCURRENCY,
+        );
+        tokio::spawn(prefetch_worker);
+        let image_proxy =
+            images::Proxy::new(settings.image_proxy_secret, fetcher.clone(), prefetch);
+        IMAGES.set(image_proxy.clone()).ok();
+        let base_path = settings.base_path.clone();
+        BASE_PATH.set(settings.base_path).ok();
+        if let Some(public_url) = settings.public_url {
+            PUBLIC_URL.set(public_url).ok();
+        }
+        DATES.set(settings.dates).ok();
+
+        let db_settings = match db::Settings::load(store.clone()).await {
+            Ok(db_settings) => db_settings,
+            Err(e) => return Err(vec![format!("could not read settings: {}", e)]),
+        };
+
+        SETTINGS.set(db_settings.clone()).ok();
+        let cursors = match cursor::Signer::load(&db_settings).await {
+            Ok(cursors) => cursors,
+            Err(e) => return Err(vec![format!("could not read the cursor key: {}", e)]),
+        };
+
+        // an interval saved from the settings page wins over the configured schedule
+        let schedule = match db_settings.refresh_seconds() {
+            Some(seconds) => scheduler::Schedule::Interval(seconds),
+            None => settings.schedule,
+        };
+        let refresh_on_startup = settings.refresh_on_startup;
+
+        let paused = db_settings
+            .get(scheduler::PAUSED_SETTING)
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(false);
+
+        let scheduler_state = scheduler::State::new(schedule.clone());
+        scheduler_state.set_paused(paused).await;
+        scheduler_state
+            .set_next_run(schedule.first_run(refresh_on_startup))
+            .await;
+
+        let mailer = match settings.smtp.map(mail::Mailer::new) {
+            Some(Ok(mailer)) => Some(mailer),
+            Some(Err(e)) => return Err(vec![format!("could not build the smtp client: {}", e)]),
+            None => None,
+        };
+
+        // integrations and notifiers share one client that goes through the feed proxy
+        let outbound = match fetch::outbound(settings.fetch.proxy.as_deref()) {
+            Ok(outbound) => outbound,
+            Err(e) => return Err(vec![format!("could not build the outbound client: {}", e)]),
+        };
+
+        if let (Some(mailer), Some(digest)) = (mailer.clone(), settings.digest) {
+            if let Some(digest_schedule) = digest.schedule {
+                tokio::spawn(digest::run(
+                    mailer,
+                    store.clone(),
+                    digest_schedule,
+                    digest.to,
+                ));
+            }
+        }
+
+        let (kindle, kindle_schedule) = match (mailer.clone(), settings.kindle) {
+            (Some(mailer), Some(kindle)) => (
+                Some(epub::Kindle {
+                    mailer,
+                    to: kindle.to,
+                }),
+                kindle.schedule,
+            ),
+            _ => (None, None),
+        };
+
+        if let (Some(kindle), Some(kindle_schedule)) = (kindle.clone(), kindle_schedule) {
+            let kindle_store = store.clone();
+            tokio::spawn(async move { kindle.run(kindle_store, kindle_schedule).await });
+        }
+
+        let registry = notifiers(outbound.clone(), mailer);
+
+        let render_cache = render_cache::Cache::default();
+        let events =
+            events::Events::new(settings.mqtt.map(mqtt::Publisher::new), settings.mqtt_topic);
+
+        let (dispatcher, notification_worker) = notify::Dispatcher::new(registry);
+        tokio::spawn(notification_worker);
+
+        let wallabag = settings
+            .wallabag
+            .map(|config| Arc::new(integrations::Wallabag::new(outbound.clone(), config)));
+
+        let pocket = settings
+            .pocket
+            .map(|config| Arc::new(integrations::Pocket::new(outbound.clone(), config)));
+
+        if let Some(pocket) = pocket.clone() {
+            let pocket_sync_seconds = settings.pocket_sync_seconds;
+            let pocket_store = store.clone();
+            tokio::spawn(async move {
+                pocket
+                    .run(
+                        pocket_store,
+                        scheduler::Schedule::Interval(pocket_sync_seconds),
+                    )
+                    .await
+            });
+        }
+
+        let bookmarks = settings
+            .bookmarks
+            .map(|config| Arc::new(integrations::Bookmarks::new(outbound.clone(), config)));
+
+        let readwise = settings
+            .readwise
+            .map(|config| Arc::new(integrations::Readwise::new(outbound.clone(), config)));
+
+        if let Some(readwise) = readwise.clone() {
+            let readwise_sync_seconds = settings.readwise_sync_seconds;
+            let readwise_store = store.clone();
+            tokio::spawn(async move {
+                readwise
+                    .run(
+                        readwise_store,
+                        scheduler::Schedule::Interval(readwise_sync_seconds),
+                    )
+                    .await
+            });
+        }
+
+        let mastodon = settings
+            .mastodon
+            .map(|config| Arc::new(integrations::Mastodon::new(outbound.clone(), config)));
+
+        let bluesky = settings
+            .bluesky
+            .map(|config| Arc::new(integrations::Bluesky::new(outbound.clone(), config)));
+
+        let mut pipeline = pipeline::Pipeline::builtin();
+        if let Some(config) = settings.summarizer {
+            let summarizer = integrations::Summarizer::new(outbound.clone(), config, store.clone());
+            pipeline.register(
+                integrations::summarizer::STAGE,
+                pipeline::Mode::Optional,
+                Arc::new(summarizer),
+            );
+        }
+
+        let trigger_token = TriggerToken(settings.trigger_token.map(Arc::from));
+        let extension_token = ExtensionToken(settings.extension_token.map(Arc::from));
+        let mailgun_key = MailgunKey(
+            settings.mailgun_signing_key.map(Arc::from),
+            Default::default(),
+        );
+        let security_headers = settings.security;
+
+        let integrations = integrations::Integrations {
+            wallabag,
+            pocket,
+            bookmarks,
+            readwise,
+            mastodon,
+            bluesky,
+        };
+
+        Ok(App {
+            store: store.clone(),
+            db_settings: db_settings.clone(),
+            integrations,
+            fetcher: fetcher.clone(),
+            pipeline,
+            dispatcher: dispatcher.clone(),
+            events: events.clone(),
+            images: image_proxy.clone(),
+            render_cache,
+            scheduler_state: scheduler_state.clone(),
+            kindle,
+            trigger_token,
+            extension_token,
+            mailgun_key,
+            cursors,
+            draining: shutdown::Draining::default(),
+            security_headers,
+            base_path,
+        })
+    }
+}
+
+// notifiers is rebuilt on reload so a changed smtp setting reaches the email notifier, notify_client is fetch::outbound
+pub(crate) fn notifiers(
+    notify_client: reqwest::Client,
+    mailer: Option<mail::Mailer>,
+) -> notify::Registry {
+    let mut registry = notify::Registry::default();
+    registry.register("ntfy", Arc::new(notify::Ntfy::new(notify_client.clone())));
+    registry.register(
+        "gotify",
+        Arc::new(notify::Gotify::new(notify_client.clone())),
+    );
+    registry.register(
+        "webhook",
+        Arc::new(notify::Webhook::new(notify_client.clone())),
+    );
+    registry.register(
+        "telegram",
+        Arc::new(notify::Telegram::new(notify_client.clone())),
+    );
+    registry.register(
+        "matrix",
+        Arc::new(notify::Matrix::new(notify_client.clone())),
+    );
+    if let Some(mailer) = mailer {
+        registry.register("email", Arc::new(notify::Email::new(mailer)));
+    }
+    registry
+}
+
+// exit_with prints every startup problem and exits nonzero instead of panicking with a backtrace
+fn exit_with(errors: Vec<String>) -> ! {
+    eprintln!("feedreader could not start:");
+    for e in errors {
+        eprintln!("  - {}", e);
+    }
+    std::process::exit(1)
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use lettre::message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn new(config: Config) -> Result<Mailer> {
+        let mut builder =
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(config.host.as_str())?
+                .port(config.port);
+        if let (Some(username), Some(password)) = (config.username, config.password) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Mailer {
+            transport: builder.build(),
+            from: config.from.parse()?,
+        })
+    }
+
+    pub async fn send_html(&self, to: &str, subject: String, body: String) -> Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .header(ContentType::TEXT_HTML)
+            .body(body)?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+
+    pub async fn send_attachment(
+        &self,
+        to: &str,
+        subject: String,
+        body: String,
+        filename: String,
+        content_type: &str,
+        attachment: Vec<u8>,
+    ) -> Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .multipart(
+                MultiPart::mixed()
+                    .singlepart(SinglePart::plain(body))
+                    .singlepart(
+                        Attachment::new(filename)
+                            .body(attachment, ContentType::parse(content_type)?),
+                    ),
+            )?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
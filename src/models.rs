@@ -0,0 +1,838 @@
+use crate::{dates, db, tr, DATES};
+use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+// limits on what the add feed form accepts
+pub(crate) const MAX_FEED_NAME_CHARS: usize = 200;
+pub(crate) const MAX_CATEGORY_CHARS: usize = 100;
+pub(crate) const MAX_URL_BYTES: usize = 2048;
+// the most days the feed settings form accepts for auto read, the feeds table refuses more
+pub(crate) const MAX_AUTO_READ_DAYS: i32 = 3650;
+// limits on what ingest stores of an entry, longer text is cut with an ellipsis
+pub(crate) const MAX_TITLE_CHARS: usize = 300;
+pub(crate) const MAX_AUTHOR_CHARS: usize = 100;
+pub(crate) const MAX_GUID_BYTES: usize = 512;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Feed {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) site_url: String,
+    pub(crate) feed_url: String,
+    pub(crate) date_added: String,
+    pub(crate) last_updated: String,
+    pub(crate) use_socks: bool,
+    pub(crate) last_error: String,
+    pub(crate) category: String,
+    // days after which unread articles are marked read, 0 never does
+    pub(crate) auto_read_days: i32,
+    // the pipeline stages the feed turned on or off, the others run as registered
+    #[serde(default)]
+    pub(crate) stages_on: Vec<String>,
+    #[serde(default)]
+    pub(crate) stages_off: Vec<String>,
+}
+
+impl Feed {
+    pub fn new(
+        name: String,
+        site_url: String,
+        feed_url: String,
+        use_socks: bool,
+        category: String,
+    ) -> Self {
+        // the id is not derived from the url, so a feed that moves keeps its articles and history
+        Feed {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            site_url,
+            feed_url,
+            date_added: Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .to_string(),
+            last_updated: "-1".to_string(),
+            use_socks,
+            last_error: "".to_string(),
+            category,
+            auto_read_days: 0,
+            stages_on: vec![],
+            stages_off: vec![],
+        }
+    }
+}
+
+impl From<&tokio_postgres::Row> for Feed {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        Feed {
+            id: row.get(0),
+            name: row.get(1),
+            site_url: row.get(2),
+            feed_url: row.get(3),
+            date_added: row.get(4),
+            last_updated: row.get(5),
+            use_socks: row.get(6),
+            last_error: row.get(7),
+            category: row.get(9),
+            auto_read_days: row.get(10),
+            stages_on: row.get(11),
+            stages_off: row.get(12),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FetchLog {
+    pub(crate) feed_id: String,
+    pub(crate) fetched_at: String,
+    pub(crate) duration_ms: i64,
+    pub(crate) bytes: i64,
+    pub(crate) error: String,
+    pub(crate) warning: String,
+}
+
+impl From<&tokio_postgres::Row> for FetchLog {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        FetchLog {
+            feed_id: row.get(0),
+            fetched_at: row.get(1),
+            duration_ms: row.get(2),
+            bytes: row.get(3),
+            error: row.get(4),
+            warning: row.get(5),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FetchLatency {
+    pub(crate) samples: i64,
+    pub(crate) p50_ms: i64,
+    pub(crate) p95_ms: i64,
+    pub(crate) p99_ms: i64,
+    pub(crate) avg_bytes: i64,
+}
+
+impl From<&tokio_postgres::Row> for FetchLatency {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        FetchLatency {
+            samples: row.get(0),
+            p50_ms: row.get::<_, f64>(1).round() as i64,
+            p95_ms: row.get::<_, f64>(2).round() as i64,
+            p99_ms: row.get::<_, f64>(3).round() as i64,
+            avg_bytes: row.get::<_, f64>(4).round() as i64,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct NotificationRule {
+    pub(crate) id: String,
+    pub(crate) feed_id: String,
+    pub(crate) keyword: String,
+    pub(crate) service: String,
+    pub(crate) url: String,
+    pub(crate) token: String,
+    pub(crate) action: String,
+}
+
+impl NotificationRule {
+    pub fn new(
+        feed_id: String,
+        keyword: String,
+        service: String,
+        url: String,
+        token: String,
+        action: String,
+    ) -> Self {
+        NotificationRule {
+            id: general_purpose::URL_SAFE.encode(format!(
+                "{}|{}|{}|{}|{}",
+                action, service, url, feed_id, keyword
+            )),
+            feed_id,
+            keyword,
+            service,
+            url,
+            token,
+            action,
+        }
+    }
+}
+
+impl From<&tokio_postgres::Row> for NotificationRule {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        NotificationRule {
+            id: row.get(0),
+            feed_id: row.get(1),
+            keyword: row.get(2),
+            service: row.get(3),
+            url: row.get(4),
+            token: row.get(5),
+            action: row.get(6),
+        }
+    }
+}
+
+// SmartFolder lists the articles its filter expression matches, it shows up in the sidebar like a category
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SmartFolder {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) expression: String,
+}
+
+impl From<&tokio_postgres::Row> for SmartFolder {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        SmartFolder {
+            id: row.get(0),
+            name: row.get(1),
+            expression: row.get(2),
+        }
+    }
+}
+
+// IdempotentResponse is what was answered to a request with an idempotency key, status is 0 while that request runs
+#[derive(Clone, Debug)]
+pub struct IdempotentResponse {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) fingerprint: String,
+    pub(crate) status: i32,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl From<&tokio_postgres::Row> for IdempotentResponse {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        IdempotentResponse {
+            method: row.get(0),
+            path: row.get(1),
+            fingerprint: row.get(2),
+            status: row.get(3),
+            headers: serde_json::from_str(row.get(4)).unwrap_or_default(),
+            body: row.get(5),
+        }
+    }
+}
+
+// MuteSchedule keeps a feed, or every feed of a category, out of the unread list and notifications in its window
+// days is one of mute::DAYS and the window runs from starts to ends in the configured timezone
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MuteSchedule {
+    pub(crate) id: String,
+    pub(crate) feed_id: String,
+    pub(crate) category: String,
+    pub(crate) days: String,
+    pub(crate) starts: String,
+    pub(crate) ends: String,
+}
+
+impl From<&tokio_postgres::Row> for MuteSchedule {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        MuteSchedule {
+            id: row.get(0),
+            feed_id: row.get(1),
+            category: row.get(2),
+            days: row.get(3),
+            starts: row.get(4),
+            ends: row.get(5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct AddFeed {
+    pub(crate) feed_name: String,
+    pub(crate) site_url: String,
+    pub(crate) feed_url: String,
+    pub(crate) use_socks: Option<String>,
+    // feeds without a category are listed under uncategorized in the sidebar
+    #[serde(default)]
+    pub(crate) category: String,
+}
+
+// SavePage is a page to keep as an article, the title of the page is used when none is given
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct SavePage {
+    pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) title: String,
+}
+
+impl SavePage {
+    pub(crate) fn validate(&mut self) -> Vec<FieldError> {
+        self.url = self.url.trim().to_string();
+        self.title = plain_text(self.title.as_str());
+
+        let message = match self.url.is_empty() {
+            true => Err("invalid-page-url-empty"),
+            false => check_url(self.url.as_str()),
+        };
+        match message {
+            Err(message) => vec![FieldError {
+                field: "url",
+                message,
+            }],
+            Ok(()) => vec![],
+        }
+    }
+}
+
+// FieldError is a rejected form field, message is the key of the text explaining why
+#[derive(Debug)]
+pub(crate) struct FieldError {
+    pub(crate) field: &'static str,
+    pub(crate) message: &'static str,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, tr(self.message))
+    }
+}
+
+impl AddFeed {
+    // validate trims the fields and returns what is wrong with them, nothing is stored unless the list is empty
+    pub(crate) fn validate(&mut self) -> Vec<FieldError> {
+        self.feed_name = plain_text(self.feed_name.as_str());
+        self.category = plain_text(self.category.as_str());
+        for value in [&mut self.site_url, &mut self.feed_url] {
+            *value = value.trim().to_string();
+        }
+
+        let mut errors = vec![];
+        let mut reject = |field, message| errors.push(FieldError { field, message });
+        match self.feed_name.chars().count() {
+            0 => reject("feed_name", "invalid-feed-name-empty"),
+            n if n > MAX_FEED_NAME_CHARS => reject("feed_name", "invalid-feed-name-long"),
+            _ => (),
+        }
+        if self.feed_url.is_empty() {
+            reject("feed_url", "invalid-url-empty");
+        } else if let Err(message) = check_url(self.feed_url.as_str()) {
+            reject("feed_url", message);
+        }
+        // the site url is optional, the feed page links to it when it is set
+        if !self.site_url.is_empty() {
+            if let Err(message) = check_url(self.site_url.as_str()) {
+                reject("site_url", message);
+            }
+        }
+        if self.category.chars().count() > MAX_CATEGORY_CHARS {
+            reject("category", "invalid-category-long");
+        }
+        errors
+    }
+}
+
+// check_url only lets through absolute http and https urls, anything else would end up in a link or be fetched
+pub(crate) fn check_url(url: &str) -> Result<(), &'static str> {
+    if url.len() > MAX_URL_BYTES {
+        return Err("invalid-url-long");
+    }
+    match reqwest::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => Ok(()),
+        _ => Err("invalid-url"),
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Article {
+    pub(crate) id: String,
+    pub(crate) feed: String,
+    pub(crate) title: String,
+    pub(crate) link: String,
+    pub(crate) author: String,
+    pub(crate) published: String,
+    pub(crate) read: bool,
+    pub(crate) favorited: bool,
+    pub(crate) read_date: String,
+    pub(crate) wallabag_saved: String,
+    pub(crate) summary: String,
+    pub(crate) thumbnail: String,
+    // the entry had no readable date, published is when it was fetched
+    #[serde(default)]
+    pub(crate) published_estimated: bool,
+    // articles are identified by their feed and the entry guid, the link when the entry has none
+    #[serde(default)]
+    pub(crate) feed_id: String,
+    #[serde(default)]
+    pub(crate) guid: String,
+    // the version mark actions check, so a toggle from a stale page does not undo another client's
+    #[serde(default)]
+    pub(crate) updated_at: String,
+    // written by the ai-summary stage for feeds that turned it on
+    #[serde(default)]
+    pub(crate) ai_summary: String,
+    #[serde(skip)]
+    pub(crate) day: String,
+}
+
+impl Article {
+    pub fn new(
+        title: String,
+        link: String,
+        author: String,
+        published: String,
+        read: bool,
+        favorited: bool,
+    ) -> Self {
+        // every date is stored in one format since lists sort on the text
+        let (published, published_estimated) = match dates::parse_published(published.as_str()) {
+            Some(dt) => (dates::stored(dt), false),
+            None => (Article::rfc3339_timestamp(), true),
+        };
+        Article {
+            id: "".to_string(),
+            feed: "".to_string(),
+            title,
+            guid: link.clone(),
+            link,
+            author,
+            published,
+            published_estimated,
+            read,
+            favorited,
+            read_date: "-1".to_string(),
+            wallabag_saved: "-1".to_string(),
+            summary: "".to_string(),
+            thumbnail: "".to_string(),
+            feed_id: "".to_string(),
+            updated_at: "".to_string(),
+            ai_summary: "".to_string(),
+            day: "".to_string(),
+        }
+    }
+
+    // in_feed assigns the article to its feed, the id is derived from the feed and guid
+    // so the same link syndicated by two feeds is two articles
+    pub fn in_feed(self, feed: &Feed) -> Self {
+        let digest = Sha256::new()
+            .chain_update(feed.id.as_bytes())
+            .chain_update([0])
+            .chain_update(self.guid.as_bytes())
+            .finalize();
+        Article {
+            id: general_purpose::URL_SAFE_NO_PAD.encode(digest),
+            feed: feed.name.clone(),
+            feed_id: feed.id.clone(),
+            ..self
+        }
+    }
+
+    // href is where the title leads, articles without a page of their own like newsletters open their details page
+    pub(crate) fn href(&self) -> String {
+        match self.link.is_empty() {
+            true => format!("{}/articles/{}", crate::base_path(), self.id),
+            false => self.link.clone(),
+        }
+    }
+
+    pub fn rfc3339_timestamp() -> String {
+        Utc::now()
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+            .to_string()
+    }
+
+    pub fn rfc3339_timestamp_to_day(timestamp: &str) -> String {
+        match DateTime::parse_from_rfc3339(timestamp) {
+            Ok(dt) => DATES.get_or_init(dates::Format::default).day(dt),
+            Err(_) => "".to_string(),
+        }
+    }
+
+    pub fn rfc3339_timestamp_to_human(timestamp: &str) -> String {
+        match DateTime::parse_from_rfc3339(timestamp) {
+            Ok(dt) => DATES.get_or_init(dates::Format::default).render(dt),
+            Err(_) => timestamp.to_string(),
+        }
+    }
+
+    // published_on is the publish date in the configured timezone and format, as the pages show it
+    pub fn published_on(&self) -> String {
+        Article::rfc3339_timestamp_to_human(self.published.as_str())
+    }
+
+    // read_on is when the article was read as the pages show it, -1 for an unread one
+    pub fn read_on(&self) -> String {
+        Article::rfc3339_timestamp_to_human(self.read_date.as_str())
+    }
+}
+
+impl From<&tokio_postgres::Row> for Article {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        // dates are kept as stored, the pages render them with published_on and read_on
+        let published: String = row.get(5);
+        Article {
+            id: row.get(0),
+            feed: row.get(1),
+            title: row.get(2),
+            link: row.get(3),
+            author: row.get(4),
+            day: Article::rfc3339_timestamp_to_day(published.as_str()),
+            published,
+            read: row.get(6),
+            favorited: row.get(7),
+            read_date: row.get(8),
+            wallabag_saved: row.get(9),
+            summary: row.get(11),
+            thumbnail: row.get(12),
+            published_estimated: row.get(14),
+            feed_id: row.get(15),
+            guid: row.get(16),
+            updated_at: row.get(13),
+            ai_summary: row.get(17),
+        }
+    }
+}
+
+// TrashedArticle is a pruned article waiting in the trash, trashed_at is rendered like the dates of articles
+pub struct TrashedArticle {
+    pub(crate) article: Article,
+    pub(crate) trashed_at: String,
+}
+
+impl From<&tokio_postgres::Row> for TrashedArticle {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        TrashedArticle {
+            article: row.into(),
+            // trashed_at is selected after every article column
+            trashed_at: Article::rfc3339_timestamp_to_human(row.get(row.len() - 1)),
+        }
+    }
+}
+
+// SearchResult is a matching article with its title as html, matched terms wrapped in mark tags
+pub struct SearchResult {
+    pub(crate) article: Article,
+    pub(crate) headline: String,
+}
+
+impl From<&tokio_postgres::Row> for SearchResult {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        SearchResult {
+            article: row.into(),
+            // the headline is selected after every article column
+            headline: highlight(row.get(row.len() - 1)),
+        }
+    }
+}
+
+// highlight escapes the headline while turning the markers ts_headline left into tags, in one pass over the title
+pub(crate) fn highlight(headline: &str) -> String {
+    let mut html = String::with_capacity(headline.len() + 16);
+    for c in headline.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#x27;"),
+            db::HIGHLIGHT_START => html.push_str("<mark>"),
+            db::HIGHLIGHT_STOP => html.push_str("</mark>"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+// NewArticle is an article with the time it was first stored
+pub struct NewArticle {
+    pub(crate) article: Article,
+    pub(crate) added: String,
+}
+
+impl From<&tokio_postgres::Row> for NewArticle {
+    fn from(row: &tokio_postgres::Row) -> Self {
+        NewArticle {
+            article: row.into(),
+            added: row.get(10),
+        }
+    }
+}
+
+// web_link keeps only web links, a javascript: or data: link would run when the title is clicked
+// a link past the limit is dropped rather than cut, a cut link would lead somewhere else
+pub(crate) fn web_link(href: &str) -> String {
+    match reqwest::Url::parse(href) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && href.len() <= MAX_URL_BYTES => {
+            href.to_string()
+        }
+        _ => "".to_string(),
+    }
+}
+
+// stored_guid is the guid as it is indexed, one past the limit is kept as its digest
+pub(crate) fn stored_guid(guid: &str) -> String {
+    match guid.len() > MAX_GUID_BYTES {
+        true => format!("sha256:{:x}", Sha256::digest(guid.as_bytes())),
+        false => guid.to_string(),
+    }
+}
+
+// truncate cuts text past max characters and marks the cut with an ellipsis
+pub(crate) fn truncate(text: String, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text,
+    }
+}
+
+// plain_text keeps feed provided text on one line, control characters would break the layout and the search highlight markers
+// whitespace runs are collapsed the way a browser would render them
+pub(crate) fn plain_text(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// summarize reduces entry html to a short line of plain text for article cards
+pub(crate) fn summarize(html: &str) -> String {
+    const MAX_CHARS: usize = 280;
+
+    truncate(strip_tags(html), MAX_CHARS)
+}
+
+// strip_tags reduces html to its text, a < that does not open a tag is kept as written
+pub(crate) fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    let mut chars = html.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '<' if !in_tag
+                && matches!(chars.peek(), Some(n) if n.is_ascii_alphabetic() || *n == '/' || *n == '!') =>
+            {
+                in_tag = true
+            }
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    plain_text(text.as_str())
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AddNotificationRule {
+    pub(crate) feed_id: String,
+    pub(crate) keyword: String,
+    pub(crate) action: String,
+    pub(crate) service: String,
+    pub(crate) url: String,
+    pub(crate) token: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct AddSmartFolder {
+    pub(crate) name: String,
+    pub(crate) expression: String,
+}
+
+// AddMuteSchedule is the quiet hours form, target is feed:<id> or category:<name>
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct AddMuteSchedule {
+    pub(crate) target: String,
+    pub(crate) days: String,
+    pub(crate) starts: String,
+    pub(crate) ends: String,
+}
+
+// FeedSettings are the options the feed page can change after a feed is added
+#[derive(Serialize, Deserialize)]
+pub(crate) struct FeedSettings {
+    pub(crate) feed_name: String,
+    pub(crate) site_url: String,
+    #[serde(default)]
+    pub(crate) category: String,
+    pub(crate) use_socks: Option<String>,
+    #[serde(default)]
+    pub(crate) auto_read_days: String,
+}
+
+impl FeedSettings {
+    // validate trims the fields like AddFeed does and returns what is wrong with them, nothing is saved unless the list is empty
+    pub(crate) fn validate(&mut self) -> Vec<FieldError> {
+        self.feed_name = plain_text(self.feed_name.as_str());
+        self.category = plain_text(self.category.as_str());
+        self.site_url = self.site_url.trim().to_string();
+
+        let mut errors = vec![];
+        let mut reject = |field, message| errors.push(FieldError { field, message });
+        match self.feed_name.chars().count() {
+            0 => reject("feed_name", "invalid-feed-name-empty"),
+            n if n > MAX_FEED_NAME_CHARS => reject("feed_name", "invalid-feed-name-long"),
+            _ => (),
+        }
+        if !self.site_url.is_empty() {
+            if let Err(message) = check_url(self.site_url.as_str()) {
+                reject("site_url", message);
+            }
+        }
+        if self.category.chars().count() > MAX_CATEGORY_CHARS {
+            reject("category", "invalid-category-long");
+        }
+        if self.auto_read_days().is_none() {
+            reject("auto_read_days", "invalid-auto-read-days");
+        }
+        errors
+    }
+
+    // auto_read_days is the number of days entered, empty is 0 and anything but a whole number of days up to
+    // MAX_AUTO_READ_DAYS is None, more would push the cutoff the scheduler computes out of the range of a timestamp
+    pub(crate) fn auto_read_days(&self) -> Option<i32> {
+        match self.auto_read_days.trim() {
+            "" => Some(0),
+            value => value
+                .parse::<i32>()
+                .ok()
+                .filter(|days| (0..=MAX_AUTO_READ_DAYS).contains(days)),
+        }
+    }
+}
+
+// FeedStage turns one pipeline stage of a feed off or on, whichever it is not
+#[derive(Serialize, Deserialize)]
+pub(crate) struct FeedStage {
+    pub(crate) stage: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::{self, Source};
+    use crate::pipeline::Pipeline;
+    use async_trait::async_trait;
+    use std::path::PathBuf;
+
+    // FEEDREADER_UPDATE_GOLDEN=1 rewrites the golden files from what the pipeline produces now
+    const UPDATE_GOLDEN: &str = "FEEDREADER_UPDATE_GOLDEN";
+
+    // each fixture is served with a content type, the golden file next to it holds the articles it becomes
+    const CASES: [(&str, &str); 11] = [
+        ("rss2.xml", "application/rss+xml"),
+        ("rss1.rdf", "application/rdf+xml"),
+        ("atom.xml", "application/atom+xml; charset=utf-8"),
+        ("feed.json", "application/feed+json"),
+        ("missing_dates.xml", "application/rss+xml"),
+        ("missing_guids.xml", "application/rss+xml"),
+        ("relative_links.xml", "application/atom+xml"),
+        ("latin1.xml", "text/xml"),
+        ("windows1252.xml", "text/xml; charset=windows-1252"),
+        ("utf16.xml", "application/xml"),
+        ("html_page.html", "text/html"),
+    ];
+
+    fn fixtures() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+    }
+
+    // Fixture serves a file from tests/fixtures in place of the network
+    struct Fixture {
+        content_type: &'static str,
+    }
+
+    #[async_trait]
+    impl Source for Fixture {
+        async fn fetch(&self, url: &str, _use_socks: bool) -> Result<fetch::Fetched> {
+            let name = url.rsplit('/').next().unwrap_or_default();
+            Ok(fetch::Fetched {
+                url: url.to_string(),
+                status: 200,
+                headers: vec![],
+                content_type: Some(self.content_type.to_string()),
+                body: fetch::Body::Memory(std::fs::read(fixtures().join(name))?),
+            })
+        }
+    }
+
+    // golden runs the fetch and parse stages of a refresh and converts the entries like ingest does
+    // estimated dates are the time of the run, so only the fact they were estimated is written
+    async fn golden(name: &str, content_type: &'static str) -> String {
+        let feed = Feed::new(
+            "Fixture".to_string(),
+            "".to_string(),
+            format!("https://fixtures.invalid/feeds/{}", name),
+            false,
+            "".to_string(),
+        );
+        let source = Fixture { content_type };
+        let parsed = match source.fetch(feed.feed_url.as_str(), false).await {
+            Ok(fetched) => source.parse(fetched).await,
+            Err(e) => Err(e),
+        };
+        let (parsed_feed, warning) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => return format!("error: {}\n", e),
+        };
+
+        let mut out = String::new();
+        if let Some(warning) = warning {
+            out.push_str(format!("warning: {}\n", warning).as_str());
+        }
+        let articles = Pipeline::builtin()
+            .run(&feed, &[], &parsed_feed.entries)
+            .await;
+        for a in articles {
+            let published = match a.published_estimated {
+                true => "estimated".to_string(),
+                false => a.published,
+            };
+            out.push_str(
+                format!(
+                    "\ntitle: {}\nlink: {}\nguid: {}\nauthor: {}\npublished: {}\nsummary: {}\nthumbnail: {}\n",
+                    a.title, a.link, a.guid, a.author, published, a.summary, a.thumbnail
+                )
+                .as_str(),
+            );
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn fixtures_match_golden_files() {
+        let update = std::env::var(UPDATE_GOLDEN).is_ok();
+        let mut mismatched = vec![];
+        for (name, content_type) in CASES {
+            let got = golden(name, content_type).await;
+            let path = fixtures().join(format!("{}.golden", name));
+            if update {
+                std::fs::write(&path, got.as_str()).unwrap();
+                continue;
+            }
+            let want = std::fs::read_to_string(&path).unwrap_or_default();
+            if got != want {
+                mismatched.push(format!("{}:\n--- want\n{}--- got\n{}", name, want, got));
+            }
+        }
+        assert!(
+            mismatched.is_empty(),
+            "run with {}=1 to accept the new output\n{}",
+            UPDATE_GOLDEN,
+            mismatched.join("\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_content_type_still_parses() {
+        let got = golden("rss2.xml", "text/html").await;
+        assert!(got.starts_with("warning: served as text/html instead of a feed type\n"));
+        assert!(got.contains("title: Second post\n"));
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time;
+
+const CONNECT_TIMEOUT_SECONDS: u64 = 10;
+const KEEP_ALIVE_SECONDS: u16 = 60;
+
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub client_id: String,
+}
+
+pub struct Publish {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub retain: bool,
+}
+
+// Publisher sends qos 0 messages to an mqtt 3.1.1 broker, connecting for each batch since publishes are infrequent
+pub struct Publisher {
+    config: Config,
+}
+
+impl Publisher {
+    pub fn new(config: Config) -> Self {
+        Publisher { config }
+    }
+
+    pub async fn publish(&self, messages: Vec<Publish>) -> Result<()> {
+        let mut stream = time::timeout(
+            Duration::from_secs(CONNECT_TIMEOUT_SECONDS),
+            TcpStream::connect((self.config.host.as_str(), self.config.port)),
+        )
+        .await??;
+
+        stream.write_all(&self.connect_packet()).await?;
+
+        let mut connack = [0u8; 4];
+        time::timeout(
+            Duration::from_secs(CONNECT_TIMEOUT_SECONDS),
+            stream.read_exact(&mut connack),
+        )
+        .await??;
+        if connack[0] != 0x20 || connack[3] != 0 {
+            return Err(anyhow::Error::msg(format!(
+                "mqtt broker refused connection with code {}",
+                connack[3]
+            )));
+        }
+
+        for message in messages {
+            stream.write_all(&publish_packet(&message)).await?;
+        }
+
+        // disconnect
+        stream.write_all(&[0xe0, 0x00]).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+
+    fn connect_packet(&self) -> Vec<u8> {
+        // clean session
+        let mut flags = 0x02;
+        let mut body = vec![];
+        write_string(&mut body, "MQTT");
+        // protocol level 4 is mqtt 3.1.1
+        body.push(4);
+
+        let mut payload = vec![];
+        write_string(&mut payload, self.config.client_id.as_str());
+        // mqtt 3.1.1 only allows a password after a user name, config refuses one without the other
+        if let Some(username) = &self.config.username {
+            flags |= 0x80;
+            write_string(&mut payload, username);
+            if let Some(password) = &self.config.password {
+                flags |= 0x40;
+                write_string(&mut payload, password);
+            }
+        }
+
+        body.push(flags);
+        body.extend_from_slice(&KEEP_ALIVE_SECONDS.to_be_bytes());
+        body.extend(payload);
+        packet(0x10, body)
+    }
+}
+
+fn publish_packet(message: &Publish) -> Vec<u8> {
+    let mut body = vec![];
+    write_string(&mut body, message.topic.as_str());
+    body.extend_from_slice(&message.payload);
+    packet(0x30 | message.retain as u8, body)
+}
+
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut packet = vec![header];
+
+    // remaining length is a variable length int, seven bits per byte
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+
+    packet.extend(body);
+    packet
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publisher(username: Option<&str>, password: Option<&str>) -> Publisher {
+        Publisher::new(Config {
+            host: "broker".to_string(),
+            port: 1883,
+            username: username.map(str::to_string),
+            password: password.map(str::to_string),
+            client_id: "reader".to_string(),
+        })
+    }
+
+    #[test]
+    fn connect_packet_sends_credentials_only_with_a_user_name() {
+        let header = |flags: u8| {
+            let mut body = vec![0, 4, b'M', b'Q', b'T', b'T', 4, flags, 0, 60];
+            body.extend_from_slice(&[0, 6, b'r', b'e', b'a', b'd', b'e', b'r']);
+            body
+        };
+
+        let mut body = header(0xc2);
+        body.extend_from_slice(&[0, 3, b'b', b'o', b'b', 0, 2, b'p', b'w']);
+        let mut want = vec![0x10, body.len() as u8];
+        want.extend(body);
+        assert_eq!(publisher(Some("bob"), Some("pw")).connect_packet(), want);
+
+        let body = header(0x02);
+        let mut want = vec![0x10, body.len() as u8];
+        want.extend(body);
+        assert_eq!(publisher(None, None).connect_packet(), want);
+        assert_eq!(publisher(None, Some("pw")).connect_packet(), want);
+    }
+
+    #[test]
+    fn publish_packet_encodes_the_remaining_length() {
+        let retained = Publish {
+            topic: "t".to_string(),
+            payload: b"hi".to_vec(),
+            retain: true,
+        };
+        assert_eq!(
+            publish_packet(&retained),
+            vec![0x31, 5, 0, 1, b't', b'h', b'i']
+        );
+
+        // 3 bytes of topic and 197 of payload take two bytes of length, 200 is 0x48 with the continuation bit and 1
+        let large = Publish {
+            topic: "t".to_string(),
+            payload: vec![0; 197],
+            retain: false,
+        };
+        let packet = publish_packet(&large);
+        assert_eq!(packet[..3], [0x30, 0xc8, 0x01]);
+        assert_eq!(packet.len(), 3 + 200);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::models::{Feed, MuteSchedule};
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+// the days a schedule can be on, a schedule is either every day, a group of days or a single weekday
+pub const DAYS: [&str; 10] = [
+    "daily", "weekdays", "weekend", "mon", "tue", "wed", "thu", "fri", "sat", "sun",
+];
+// windows a mute can run into before it is cut off, a week of back to back all day windows is the longest there is
+const MAX_CHAINED_WINDOWS: usize = 8;
+
+// weekdays are the days of a days key, None for a key that is not one of DAYS
+pub fn weekdays(days: &str) -> Option<Vec<Weekday>> {
+    use Weekday::*;
+    let weekdays = match days {
+        "daily" => vec![Mon, Tue, Wed, Thu, Fri, Sat, Sun],
+        "weekdays" => vec![Mon, Tue, Wed, Thu, Fri],
+        "weekend" => vec![Sat, Sun],
+        day => vec![day
+            .parse::<Weekday>()
+            .ok()
+            .filter(|_| DAYS.contains(&day))?],
+    };
+    Some(weekdays)
+}
+
+// time reads the hours and minutes a window starts or ends at, as a time input sends them
+pub fn time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+// Window is a schedule with its days and times read, it starts on each of its days and ends the same day
+// or the next one when the end is not after the start, a window that ends when it starts lasts the whole day
+struct Window {
+    days: Vec<Weekday>,
+    starts: NaiveTime,
+    ends: NaiveTime,
+}
+
+impl Window {
+    fn new(schedule: &MuteSchedule) -> Option<Window> {
+        Some(Window {
+            days: weekdays(schedule.days.as_str())?,
+            starts: time(schedule.starts.as_str())?,
+            ends: time(schedule.ends.as_str())?,
+        })
+    }
+
+    // until is when the window that at falls in ends, windows overnight started the day before
+    fn until(&self, at: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = at.timezone();
+        let today = at.date_naive();
+        [today, today - Duration::days(1)]
+            .into_iter()
+            .filter(|day| self.days.contains(&day.weekday()))
+            .filter_map(|day| {
+                let end_day = match self.ends > self.starts {
+                    true => day,
+                    false => day + Duration::days(1),
+                };
+                Some((local(tz, day, self.starts)?, local(tz, end_day, self.ends)?))
+            })
+            .find(|(starts, ends)| *starts <= at && at < *ends)
+            .map(|(_, ends)| ends)
+    }
+}
+
+// local is a wall clock time in the timezone, a time skipped when clocks go forward is taken an hour later
+fn local(tz: Tz, day: NaiveDate, time: NaiveTime) -> Option<DateTime<Tz>> {
+    let naive = NaiveDateTime::new(day, time);
+    tz.from_local_datetime(&naive).earliest().or_else(|| {
+        tz.from_local_datetime(&(naive + Duration::hours(1)))
+            .earliest()
+    })
+}
+
+// applies is whether a schedule is for the feed, by its id or its category
+pub fn applies(schedule: &MuteSchedule, feed: &Feed) -> bool {
+    match schedule.feed_id.is_empty() {
+        false => schedule.feed_id == feed.id,
+        true => !schedule.category.is_empty() && schedule.category == feed.category,
+    }
+}
+
+// muted_until is when the schedules of a feed stop muting it, None when none of them mutes it now
+// windows that meet, like the two days of a weekend, are followed to the end of the last one
+pub fn muted_until(
+    schedules: &[MuteSchedule],
+    feed: &Feed,
+    now: DateTime<Utc>,
+    tz: Tz,
+) -> Option<DateTime<Utc>> {
+    let windows: Vec<Window> = schedules
+        .iter()
+        .filter(|s| applies(s, feed))
+        .filter_map(Window::new)
+        .collect();
+    let mut until = now.with_timezone(&tz);
+    for _ in 0..MAX_CHAINED_WINDOWS {
+        match windows.iter().filter_map(|w| w.until(until)).max() {
+            Some(ends) if ends > until => until = ends,
+            _ => break,
+        }
+    }
+    let until = until.with_timezone(&Utc);
+    (until > now).then_some(until)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(
+        feed_id: &str,
+        category: &str,
+        days: &str,
+        starts: &str,
+        ends: &str,
+    ) -> MuteSchedule {
+        MuteSchedule {
+            id: "".to_string(),
+            feed_id: feed_id.to_string(),
+            category: category.to_string(),
+            days: days.to_string(),
+            starts: starts.to_string(),
+            ends: ends.to_string(),
+        }
+    }
+
+    fn at(tz: Tz, value: &str) -> DateTime<Utc> {
+        tz.from_local_datetime(&NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap())
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn schedules_mute_until_their_window_ends() {
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let feed = Feed {
+            id: "feed-1".to_string(),
+            category: "News".to_string(),
+            ..Feed::new(
+                "Blog".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+                "".to_string(),
+            )
+        };
+        let quiet_nights = [schedule("feed-1", "", "daily", "22:00", "07:00")];
+        // 2026-02-06 is a friday
+        assert_eq!(
+            muted_until(&quiet_nights, &feed, at(tz, "2026-02-06 23:30"), tz),
+            Some(at(tz, "2026-02-07 07:00"))
+        );
+        assert_eq!(
+            muted_until(&quiet_nights, &feed, at(tz, "2026-02-07 06:59"), tz),
+            Some(at(tz, "2026-02-07 07:00"))
+        );
+        assert_eq!(
+            muted_until(&quiet_nights, &feed, at(tz, "2026-02-07 07:00"), tz),
+            None
+        );
+
+        // a weekend of all day windows runs from friday night into monday morning
+        let weekends = [
+            schedule("", "News", "weekend", "00:00", "00:00"),
+            schedule("", "News", "fri", "18:00", "00:00"),
+            schedule("", "News", "mon", "00:00", "08:00"),
+        ];
+        assert_eq!(
+            muted_until(&weekends, &feed, at(tz, "2026-02-06 19:00"), tz),
+            Some(at(tz, "2026-02-09 08:00"))
+        );
+        assert_eq!(
+            muted_until(&weekends, &feed, at(tz, "2026-02-06 17:00"), tz),
+            None
+        );
+
+        let others = [
+            schedule("feed-2", "", "daily", "00:00", "00:00"),
+            schedule("", "Sports", "daily", "00:00", "00:00"),
+            schedule("feed-1", "", "someday", "00:00", "00:00"),
+        ];
+        assert_eq!(
+            muted_until(&others, &feed, at(tz, "2026-02-06 12:00"), tz),
+            None
+        );
+
+        assert_eq!(weekdays("sat"), Some(vec![Weekday::Sat]));
+        assert_eq!(weekdays("saturday"), None);
+        assert_eq!(time("7:05"), NaiveTime::from_hms_opt(7, 5, 0));
+        assert_eq!(time("25:00"), None);
+    }
+}
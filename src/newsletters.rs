@@ -0,0 +1,338 @@
+use crate::db;
+use crate::discover::{attribute, tags};
+use crate::models::{self, Article, Feed};
+use crate::saved;
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// a webhook older than this is refused, mailgun signs the time it sent it so a captured request can't be replayed later
+const MAX_AGE_SECONDS: i64 = 15 * 60;
+// text kept of a newsletter, the rest of a very long one is cut
+const MAX_BODY_CHARS: usize = 100_000;
+// tags that end a paragraph when an html only mail is turned into text
+const BLOCK_TAGS: [&str; 12] = [
+    "p",
+    "br",
+    "div",
+    "li",
+    "tr",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+];
+// link text of the copy of a newsletter on the web, the article links there when the mail has one
+const WEB_VERSION: [&str; 6] = [
+    "view in browser",
+    "view in your browser",
+    "view online",
+    "view this email",
+    "read online",
+    "web version",
+];
+
+// Mail is a newsletter as it was received, text and html are the two parts and either can be empty
+#[derive(Default, Debug)]
+pub struct Mail {
+    pub from: String,
+    pub subject: String,
+    pub message_id: String,
+    pub text: String,
+    pub html: String,
+    pub received: String,
+}
+
+impl Mail {
+    // mailgun posts the parsed mail as fields, https://documentation.mailgun.com/docs/mailgun/user-manual/receive-forward-store/
+    pub fn from_mailgun(fields: &HashMap<String, String>) -> Mail {
+        let field = |name: &str| fields.get(name).cloned().unwrap_or_default();
+        let received = field("timestamp")
+            .parse::<i64>()
+            .ok()
+            .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_default();
+        Mail {
+            from: field("from"),
+            subject: field("subject"),
+            message_id: field("Message-Id"),
+            text: field("body-plain"),
+            html: field("body-html"),
+            received,
+        }
+    }
+}
+
+// verify checks the signature mailgun adds to a webhook, the hex hmac of timestamp and token under the signing key
+pub fn verify(key: &str, timestamp: &str, token: &str, signature: &str, now: i64) -> bool {
+    let fresh = timestamp
+        .parse::<i64>()
+        .map(|sent| fresh(sent, now))
+        .unwrap_or(false);
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(token.as_bytes());
+    let signature = match hex(signature) {
+        Some(signature) => signature,
+        None => return false,
+    };
+    fresh && mac.verify_slice(signature.as_slice()).is_ok()
+}
+
+// fresh is whether a webhook sent at sent is recent enough to take at now, any i64 a request carries is compared safely
+fn fresh(sent: i64, now: i64) -> bool {
+    now.abs_diff(sent) <= MAX_AGE_SECONDS.unsigned_abs()
+}
+
+// Seen remembers the tokens of the webhooks taken while their timestamp is fresh, mailgun makes a token for every post
+// so one that comes again is a captured request replayed, once the timestamp is too old verify refuses it anyway
+#[derive(Clone, Default)]
+pub struct Seen(Arc<Mutex<HashMap<String, i64>>>);
+
+impl Seen {
+    // first records the token of a verified webhook and tells whether it was not taken before
+    pub fn first(&self, timestamp: &str, token: &str, now: i64) -> bool {
+        let sent = timestamp.parse::<i64>().unwrap_or_default();
+        let mut seen = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, sent| fresh(*sent, now));
+        seen.insert(token.to_string(), sent).is_none()
+    }
+}
+
+fn hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// sender splits a from header into the name and the address, a bare address is its own name
+fn sender(from: &str) -> (String, String) {
+    match (from.rfind('<'), from.rfind('>')) {
+        (Some(start), Some(end)) if start < end => {
+            let address = from[start + 1..end].trim().to_lowercase();
+            let name = models::plain_text(from[..start].trim().trim_matches('"'));
+            match name.is_empty() {
+                true => (address.clone(), address),
+                false => (name, address),
+            }
+        }
+        _ => {
+            let address = from.trim().to_lowercase();
+            (address.clone(), address)
+        }
+    }
+}
+
+// feed is the pseudo-feed of everything one address sends, like saved pages it is never saved or refreshed
+pub fn feed(from: &str) -> Feed {
+    let (name, address) = sender(from);
+    Feed {
+        id: format!("newsletter-{:x}", Sha256::digest(address.as_bytes())),
+        ..Feed::new(
+            models::truncate(name, models::MAX_FEED_NAME_CHARS),
+            "".to_string(),
+            "".to_string(),
+            false,
+            "".to_string(),
+        )
+    }
+}
+
+// receive stores a newsletter as an unread article with its text, the message id is its guid so a redelivered mail adds nothing
+pub async fn receive(store: &db::Storage, mail: Mail) -> Result<Article> {
+    let feed = feed(mail.from.as_str());
+    let body = body(&mail);
+    let guid = match mail.message_id.trim() {
+        "" => format!(
+            "{:x}",
+            Sha256::digest(format!("{}\n{}\n{}", mail.from, mail.subject, body).as_bytes())
+        ),
+        message_id => message_id.to_string(),
+    };
+    let title = match models::plain_text(mail.subject.as_str()) {
+        subject if subject.is_empty() => feed.name.clone(),
+        subject => models::truncate(subject, models::MAX_TITLE_CHARS),
+    };
+    let article = Article {
+        guid,
+        summary: models::summarize(body.as_str()),
+        ..Article::new(
+            title,
+            web_version(mail.html.as_str()),
+            feed.name.clone(),
+            mail.received,
+            false,
+            false,
+        )
+    }
+    .in_feed(&feed);
+
+    match store.get_article_by_id(article.id.clone()).await {
+        Ok(stored) => return Ok(stored),
+        Err(e) if matches!(e.downcast_ref(), Some(db::Error::NotFound(_))) => {}
+        Err(e) => return Err(e),
+    }
+    let id = article.id.clone();
+    store.add_articles(std::iter::once(article)).await?;
+    store.add_newsletter_body(id.clone(), body).await?;
+    store.get_article_by_id(id).await
+}
+
+// body is the text of a mail as paragraphs separated by a blank line, taken from the html part when there is no text part
+pub fn body(mail: &Mail) -> String {
+    let paragraphs: Vec<String> = match mail.text.trim().is_empty() {
+        false => mail
+            .text
+            .replace("\r\n", "\n")
+            .split("\n\n")
+            .map(models::plain_text)
+            .filter(|p| !p.is_empty())
+            .collect(),
+        true => html_paragraphs(mail.html.as_str()),
+    };
+    models::truncate(paragraphs.join("\n\n"), MAX_BODY_CHARS)
+}
+
+fn html_paragraphs(html: &str) -> Vec<String> {
+    let html = saved::visible(html);
+    let lower = html.to_ascii_lowercase();
+    let mut paragraphs = vec![];
+    let mut start = 0;
+    let mut at = 0;
+    while let Some(open) = lower[at..].find('<') {
+        let open = at + open;
+        at = open + 1;
+        let name: String = lower[at..]
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        if BLOCK_TAGS.contains(&name.as_str()) {
+            paragraphs.push(models::strip_tags(&html[start..open]));
+            start = open;
+        }
+    }
+    paragraphs.push(models::strip_tags(&html[start..]));
+    paragraphs.retain(|p| !p.is_empty());
+    paragraphs
+}
+
+// web_version is the link to the newsletter on the web, found by the text of the link
+fn web_version(html: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    WEB_VERSION
+        .iter()
+        .filter_map(|text| lower.find(text))
+        .filter_map(|at| lower[..at].rfind("<a"))
+        .filter_map(|start| tags(&html[start..], "a").into_iter().next())
+        .map(|anchor| models::web_link(attribute(&anchor, "href")))
+        .find(|link| !link.is_empty())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replayed_tokens_are_refused_while_fresh() {
+        let seen = Seen::default();
+        assert!(seen.first("1700000000", "token", 1_700_000_060));
+        assert!(!seen.first("1700000000", "token", 1_700_000_120));
+        assert!(seen.first("1700000000", "other", 1_700_000_120));
+        // by the time the first one is forgotten verify refuses its timestamp
+        assert!(seen.first("1700001000", "later", 1_700_001_000));
+        assert!(seen.first("1700000000", "token", 1_700_001_000));
+        assert!(!fresh(1_700_000_000, 1_700_001_000));
+        assert!(!fresh(i64::MIN, i64::MAX));
+    }
+
+    #[test]
+    fn verifies_mailgun_signatures() {
+        let (key, timestamp, token) = ("key-1", "1700000000", "token");
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(format!("{}{}", timestamp, token).as_bytes());
+        let signature: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        assert!(verify(
+            key,
+            timestamp,
+            token,
+            signature.as_str(),
+            1_700_000_060
+        ));
+        assert!(!verify(
+            "key-2",
+            timestamp,
+            token,
+            signature.as_str(),
+            1_700_000_060
+        ));
+        assert!(!verify(
+            key,
+            timestamp,
+            "other",
+            signature.as_str(),
+            1_700_000_060
+        ));
+        assert!(!verify(
+            key,
+            timestamp,
+            token,
+            signature.as_str(),
+            1_700_009_000
+        ));
+        assert!(!verify(key, timestamp, token, "not hex", 1_700_000_060));
+    }
+
+    #[test]
+    fn newsletters_become_text() {
+        assert_eq!(
+            sender("\"Weekly Notes\" <Notes@Example.com>"),
+            ("Weekly Notes".to_string(), "notes@example.com".to_string())
+        );
+        assert_eq!(
+            feed("Notes@example.com").id,
+            feed("Other <notes@example.com>").id
+        );
+
+        let text = Mail {
+            text: "Hello\r\nreader,\r\n\r\n\r\nsecond  paragraph\r\n".to_string(),
+            html: "<p>ignored</p>".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(body(&text), "Hello reader,\n\nsecond paragraph");
+
+        let html = Mail {
+            html: r#"<html><head><style>p { margin: 0 }</style></head><body>
+                <a href="https://example.com/issue/1">View in browser</a>
+                <h1>Issue 1</h1><p>First <b>point</b></p><br><p>Second</p></body></html>"#
+                .to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            body(&html),
+            "View in browser\n\nIssue 1\n\nFirst point\n\nSecond"
+        );
+        assert_eq!(
+            web_version(html.html.as_str()),
+            "https://example.com/issue/1"
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use super::{Message, Notifier, Target};
+use crate::mail;
+use anyhow::Result;
+use askama::Template;
+use async_trait::async_trait;
+
+#[derive(Template)]
+#[template(path = "notification_email.html")]
+struct NotificationEmailTemplate<'a> {
+    message: &'a Message,
+}
+
+// Email sends one message per article, the target url is the recipient address
+pub struct Email {
+    mailer: mail::Mailer,
+}
+
+impl Email {
+    pub fn new(mailer: mail::Mailer) -> Self {
+        Email { mailer }
+    }
+}
+
+#[async_trait]
+impl Notifier for Email {
+    async fn send(&self, target: &Target, message: &Message) -> Result<()> {
+        let body = NotificationEmailTemplate { message }.render()?;
+        self.mailer
+            .send_html(
+                target.url.as_str(),
+                format!("{}: {}", message.feed, message.title),
+                body,
+            )
+            .await
+    }
+}
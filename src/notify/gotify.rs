@@ -0,0 +1,36 @@
+use super::{Message, Notifier, Target};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+
+// Gotify posts to a server's message api with an application token, https://gotify.net/api-docs#/message/createMessage
+pub struct Gotify {
+    client: Client,
+}
+
+impl Gotify {
+    pub fn new(client: Client) -> Self {
+        Gotify { client }
+    }
+}
+
+#[async_trait]
+impl Notifier for Gotify {
+    async fn send(&self, target: &Target, message: &Message) -> Result<()> {
+        self.client
+            .post(format!("{}/message", target.url.trim_end_matches('/')))
+            .header("X-Gotify-Key", target.token.as_str())
+            .json(&json!({
+                "title": message.feed,
+                "message": message.title,
+                "extras": {
+                    "client::notification": { "click": { "url": message.link } }
+                }
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use super::{Message, Notifier, Target};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Client, Url};
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Matrix sends to a room as the access token's user
+// the target url is the homeserver followed by the room id, e.g. https://matrix.example.org/!abc123:example.org
+pub struct Matrix {
+    client: Client,
+    transactions: AtomicU64,
+}
+
+impl Matrix {
+    pub fn new(client: Client) -> Self {
+        Matrix {
+            client,
+            transactions: AtomicU64::new(0),
+        }
+    }
+
+    // transaction_id only has to be unique per access token, https://spec.matrix.org/v1.9/client-server-api/#transaction-identifiers
+    fn transaction_id(&self) -> String {
+        format!(
+            "feedreader-{}-{}",
+            Utc::now().timestamp_millis(),
+            self.transactions.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+#[async_trait]
+impl Notifier for Matrix {
+    async fn send(&self, target: &Target, message: &Message) -> Result<()> {
+        let (homeserver, room) = match target.url.rsplit_once('/') {
+            Some((homeserver, room)) if !room.is_empty() => (homeserver, room),
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "matrix target {} has no room id",
+                    target.url
+                )))
+            }
+        };
+
+        let mut url = Url::parse(homeserver)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::Error::msg(format!("bad matrix homeserver: {}", homeserver)))?
+            .pop_if_empty()
+            .extend(&[
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                room,
+                "send",
+                "m.room.message",
+            ])
+            .push(self.transaction_id().as_str());
+
+        self.client
+            .put(url)
+            .bearer_auth(target.token.as_str())
+            .json(&json!({
+                "msgtype": "m.text",
+                "body": format!("**{}**: [{}]({})", message.feed, message.title, message.link),
+                "format": "org.matrix.custom.html",
+                "formatted_body": format!(
+                    "<strong>{}</strong>: <a href=\"{}\">{}</a>",
+                    escape(message.feed.as_str()),
+                    escape(message.link.as_str()),
+                    escape(message.title.as_str())
+                ),
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
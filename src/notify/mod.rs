@@ -0,0 +1,228 @@
+mod email;
+mod gotify;
+mod matrix;
+mod ntfy;
+mod telegram;
+mod webhook;
+
+pub use email::Email;
+pub use gotify::Gotify;
+pub use matrix::Matrix;
+pub use ntfy::Ntfy;
+pub use telegram::Telegram;
+pub use webhook::Webhook;
+
+use crate::models::{Article, Feed, NotificationRule};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time;
+
+const QUEUE_SIZE: usize = 1024;
+
+// a rule either notifies its target or acts on the matching articles as they are stored
+pub const NOTIFY: &str = "notify";
+pub const READ: &str = "read";
+pub const FAVORITE: &str = "favorite";
+pub const SKIP: &str = "skip";
+pub const ACTIONS: [&str; 4] = [NOTIFY, READ, FAVORITE, SKIP];
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF_MILLIS: u64 = 1000;
+
+// Target is where a rule delivers to, its meaning depends on the notifier (topic url, chat id, email address)
+pub struct Target {
+    pub url: String,
+    pub token: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Message {
+    pub feed: String,
+    pub title: String,
+    pub link: String,
+    pub published: String,
+}
+
+impl Message {
+    pub fn new(feed: &Feed, article: &Article) -> Self {
+        Message {
+            feed: feed.name.clone(),
+            title: article.title.clone(),
+            link: article.link.clone(),
+            published: article.published.clone(),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, target: &Target, message: &Message) -> Result<()>;
+}
+
+impl NotificationRule {
+    // an empty feed or keyword matches everything
+    pub fn matches(&self, feed: &Feed, article: &Article) -> bool {
+        let feed_matches = self.feed_id.is_empty() || self.feed_id == feed.id;
+        let keyword_matches = self.keyword.is_empty()
+            || article
+                .title
+                .to_lowercase()
+                .contains(self.keyword.to_lowercase().as_str());
+        feed_matches && keyword_matches
+    }
+
+    fn is_notify(&self) -> bool {
+        self.action == NOTIFY
+    }
+
+    fn target(&self) -> Target {
+        Target {
+            url: self.url.clone(),
+            token: self.token.clone(),
+        }
+    }
+}
+
+// apply runs the read, favorite and skip rules on a fetched article before it is stored, false when it is skipped
+pub fn apply(rules: &[NotificationRule], feed: &Feed, article: &mut Article) -> bool {
+    let actions: Vec<&str> = rules
+        .iter()
+        .filter(|r| r.matches(feed, article))
+        .map(|r| r.action.as_str())
+        .collect();
+    for action in actions {
+        match action {
+            READ if !article.read => {
+                article.read = true;
+                article.read_date = Article::rfc3339_timestamp();
+            }
+            FAVORITE => article.favorited = true,
+            SKIP => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+#[derive(Default)]
+pub struct Registry {
+    notifiers: HashMap<String, Arc<dyn Notifier>>,
+}
+
+impl Registry {
+    pub fn register(&mut self, service: &str, notifier: Arc<dyn Notifier>) {
+        self.notifiers.insert(service.to_string(), notifier);
+    }
+
+    pub fn services(&self) -> Vec<String> {
+        let mut services: Vec<String> = self.notifiers.keys().cloned().collect();
+        services.sort();
+        services
+    }
+}
+
+struct Notification {
+    service: String,
+    target: Target,
+    message: Message,
+}
+
+// Registry is swapped whole on reload, queued notifications are delivered with whichever registry is current
+type Shared = Arc<RwLock<Arc<Registry>>>;
+
+#[derive(Clone)]
+pub struct Dispatcher {
+    sender: mpsc::Sender<Notification>,
+    registry: Shared,
+}
+
+impl Dispatcher {
+    // new returns the dispatcher and the worker that drains its queue, the worker has to be spawned by the caller
+    pub fn new(registry: Registry) -> (Dispatcher, impl std::future::Future<Output = ()>) {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        let registry = Arc::new(RwLock::new(Arc::new(registry)));
+        let dispatcher = Dispatcher {
+            sender,
+            registry: registry.clone(),
+        };
+        (dispatcher, deliver(registry, receiver))
+    }
+
+    pub fn supports(&self, service: &str) -> bool {
+        current(&self.registry).notifiers.contains_key(service)
+    }
+
+    pub fn services(&self) -> Vec<String> {
+        current(&self.registry).services()
+    }
+
+    pub fn replace(&self, registry: Registry) {
+        *self.registry.write().unwrap() = Arc::new(registry);
+    }
+
+    // dispatch queues a notification for every rule matching each article without waiting for delivery
+    pub fn dispatch(&self, rules: &[NotificationRule], feed: &Feed, articles: &[Article]) {
+        for article in articles {
+            for rule in rules
+                .iter()
+                .filter(|r| r.is_notify() && r.matches(feed, article))
+            {
+                let notification = Notification {
+                    service: rule.service.clone(),
+                    target: rule.target(),
+                    message: Message::new(feed, article),
+                };
+
+                if let Err(e) = self.sender.try_send(notification) {
+                    println!("dropping notification for {}: {}", rule.url, e);
+                }
+            }
+        }
+    }
+}
+
+fn current(registry: &Shared) -> Arc<Registry> {
+    registry.read().unwrap().clone()
+}
+
+async fn deliver(registry: Shared, mut receiver: mpsc::Receiver<Notification>) {
+    while let Some(notification) = receiver.recv().await {
+        let notifier = match current(&registry)
+            .notifiers
+            .get(notification.service.as_str())
+        {
+            Some(notifier) => notifier.clone(),
+            None => {
+                println!(
+                    "no notifier registered for service {}",
+                    notification.service
+                );
+                continue;
+            }
+        };
+
+        let mut attempt = 0;
+        while let Err(e) = notifier
+            .send(&notification.target, &notification.message)
+            .await
+        {
+            attempt += 1;
+            if attempt >= MAX_ATTEMPTS {
+                println!(
+                    "could not send {} notification to {}: {}",
+                    notification.service, notification.target.url, e
+                );
+                break;
+            }
+
+            time::sleep(Duration::from_millis(
+                RETRY_BACKOFF_MILLIS * 2u64.pow(attempt - 1),
+            ))
+            .await;
+        }
+    }
+}
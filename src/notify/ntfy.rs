@@ -0,0 +1,33 @@
+use super::{Message, Notifier, Target};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+
+// Ntfy publishes to a topic url, https://docs.ntfy.sh/publish/
+pub struct Ntfy {
+    client: Client,
+}
+
+impl Ntfy {
+    pub fn new(client: Client) -> Self {
+        Ntfy { client }
+    }
+}
+
+#[async_trait]
+impl Notifier for Ntfy {
+    async fn send(&self, target: &Target, message: &Message) -> Result<()> {
+        let mut request = self
+            .client
+            .post(target.url.as_str())
+            .header("Title", message.feed.as_str())
+            .header("Click", message.link.as_str())
+            .body(message.title.clone());
+        if !target.token.is_empty() {
+            request = request.bearer_auth(target.token.as_str());
+        }
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
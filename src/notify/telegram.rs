@@ -0,0 +1,35 @@
+use super::{Message, Notifier, Target};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+
+// Telegram sends through the bot api, the target url is the chat id and the token is the bot token
+pub struct Telegram {
+    client: Client,
+}
+
+impl Telegram {
+    pub fn new(client: Client) -> Self {
+        Telegram { client }
+    }
+}
+
+#[async_trait]
+impl Notifier for Telegram {
+    async fn send(&self, target: &Target, message: &Message) -> Result<()> {
+        self.client
+            .post(format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                target.token
+            ))
+            .json(&json!({
+                "chat_id": target.url,
+                "text": format!("{}: {}\n{}", message.feed, message.title, message.link),
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
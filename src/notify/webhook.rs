@@ -0,0 +1,28 @@
+use super::{Message, Notifier, Target};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+
+// Webhook posts the message as json to any url, with the token as a bearer token when set
+pub struct Webhook {
+    client: Client,
+}
+
+impl Webhook {
+    pub fn new(client: Client) -> Self {
+        Webhook { client }
+    }
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    async fn send(&self, target: &Target, message: &Message) -> Result<()> {
+        let mut request = self.client.post(target.url.as_str()).json(message);
+        if !target.token.is_empty() {
+            request = request.bearer_auth(target.token.as_str());
+        }
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+use crate::models::{self, Article, Feed, NotificationRule};
+use crate::{notify, report};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::SecondsFormat;
+use feed_rs::model::Entry;
+use std::sync::Arc;
+
+// the builtin stages, in the order they run
+pub const SANITIZE: &str = "sanitize";
+pub const TRACKING: &str = "tracking";
+pub const CONTENT: &str = "content";
+pub const THUMBNAIL: &str = "thumbnail";
+pub const RULES: &str = "rules";
+
+// Context is what a stage sees besides the article, the entry it was made from, its feed and the rules of every feed
+pub struct Context<'a> {
+    pub entry: &'a Entry,
+    pub feed: &'a Feed,
+    pub rules: &'a [NotificationRule],
+}
+
+// Stage is one step of turning an entry into an article, returning false drops the entry
+// a stage that fails should leave the article as it found it, the error is reported and the next stage runs
+#[async_trait]
+pub trait Stage: Send + Sync {
+    async fn process(&self, cx: &Context<'_>, article: &mut Article) -> Result<bool>;
+}
+
+// Mode is whether a feed can turn a stage off or on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    // runs for every feed
+    Always,
+    // runs unless the feed turns it off
+    Default,
+    // runs only for feeds that turn it on
+    Optional,
+}
+
+#[derive(Clone)]
+struct Registered {
+    name: &'static str,
+    mode: Mode,
+    stage: Arc<dyn Stage>,
+}
+
+impl Registered {
+    fn runs(&self, feed: &Feed) -> bool {
+        let name = self.name.to_string();
+        match self.mode {
+            Mode::Always => true,
+            Mode::Default => !feed.stages_off.contains(&name),
+            Mode::Optional => feed.stages_on.contains(&name),
+        }
+    }
+}
+
+// Setting is a stage a feed can turn off or on, as the feed page lists it
+pub struct Setting {
+    pub(crate) name: &'static str,
+    pub(crate) on: bool,
+}
+
+// Pipeline is how ingest turns entries into articles, its stages run in the order they were registered
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    stages: Vec<Registered>,
+}
+
+impl Pipeline {
+    pub fn builtin() -> Pipeline {
+        let mut pipeline = Pipeline::default();
+        pipeline.register(SANITIZE, Mode::Always, Arc::new(Sanitize));
+        pipeline.register(TRACKING, Mode::Optional, Arc::new(Tracking));
+        pipeline.register(CONTENT, Mode::Default, Arc::new(Content));
+        pipeline.register(THUMBNAIL, Mode::Default, Arc::new(Thumbnail));
+        pipeline.register(RULES, Mode::Always, Arc::new(Rules));
+        pipeline
+    }
+
+    pub fn register(&mut self, name: &'static str, mode: Mode, stage: Arc<dyn Stage>) {
+        self.stages.push(Registered { name, mode, stage });
+    }
+
+    pub fn mode(&self, name: &str) -> Option<Mode> {
+        self.stages.iter().find(|s| s.name == name).map(|s| s.mode)
+    }
+
+    // settings lists the stages the feed can turn off or on, with whether they run for it
+    pub fn settings(&self, feed: &Feed) -> Vec<Setting> {
+        self.stages
+            .iter()
+            .filter(|s| s.mode != Mode::Always)
+            .map(|s| Setting {
+                name: s.name,
+                on: s.runs(feed),
+            })
+            .collect()
+    }
+
+    // run turns entries of the feed into articles, leaving out the ones a stage dropped
+    pub async fn run(
+        &self,
+        feed: &Feed,
+        rules: &[NotificationRule],
+        entries: &[Entry],
+    ) -> Vec<Article> {
+        let stages: Vec<&Registered> = self.stages.iter().filter(|s| s.runs(feed)).collect();
+        let mut articles = Vec::with_capacity(entries.len());
+        'entries: for entry in entries {
+            let cx = Context { entry, feed, rules };
+            let mut article = convert(entry).in_feed(feed);
+            for s in stages.iter() {
+                match s.stage.process(&cx, &mut article).await {
+                    Ok(true) => {}
+                    Ok(false) => continue 'entries,
+                    Err(e) => {
+                        println!("stage {} failed on {}: {}", s.name, article.guid, e);
+                        report::feed_error(&e, feed);
+                    }
+                }
+            }
+            articles.push(article);
+        }
+        articles
+    }
+}
+
+// convert takes the fields of the entry as they are, the stages clean them up and fill in the rest
+// feed_rs ids that were not in the feed are cleared while parsing, those entries keep the link as guid
+fn convert(entry: &Entry) -> Article {
+    let title = entry
+        .title
+        .as_ref()
+        .map(|text| text.content.clone())
+        .unwrap_or_default();
+    let href = entry
+        .links
+        .first()
+        .map(|l| l.href.as_str())
+        .unwrap_or_default();
+    let author = entry
+        .authors
+        .first()
+        .map(|p| p.name.clone())
+        .unwrap_or_default();
+    let published = entry
+        .published
+        .or(entry.updated)
+        .map(|ts| ts.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default();
+    let guid = match entry.id.is_empty() {
+        true => href,
+        false => entry.id.as_str(),
+    };
+    Article {
+        guid: models::stored_guid(guid),
+        ..Article::new(title, href.to_string(), author, published, false, false)
+    }
+}
+
+// Sanitize makes the entry safe to show, titles are rendered as text and a link has to lead to a web page
+struct Sanitize;
+
+#[async_trait]
+impl Stage for Sanitize {
+    async fn process(&self, _: &Context<'_>, article: &mut Article) -> Result<bool> {
+        // some feeds mark up their titles, the tags are dropped
+        article.title = models::truncate(
+            models::strip_tags(article.title.as_str()),
+            models::MAX_TITLE_CHARS,
+        );
+        article.link = models::web_link(article.link.as_str());
+        article.author = models::truncate(
+            models::plain_text(article.author.as_str()),
+            models::MAX_AUTHOR_CHARS,
+        );
+        Ok(true)
+    }
+}
+
+// Tracking drops the utm_ parameters campaigns add to links, it is off by default since a few sites need them
+struct Tracking;
+
+#[async_trait]
+impl Stage for Tracking {
+    async fn process(&self, _: &Context<'_>, article: &mut Article) -> Result<bool> {
+        let mut url = match reqwest::Url::parse(article.link.as_str()) {
+            Ok(url) => url,
+            Err(_) => return Ok(true),
+        };
+        let kept: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(key, _)| !key.starts_with("utm_"))
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        if kept.len() == url.query_pairs().count() {
+            return Ok(true);
+        }
+        match kept.is_empty() {
+            true => url.set_query(None),
+            false => {
+                url.query_pairs_mut().clear().extend_pairs(kept);
+            }
+        }
+        article.link = url.to_string();
+        Ok(true)
+    }
+}
+
+// Content keeps a short plain text summary, from the summary of the entry or else its content
+struct Content;
+
+#[async_trait]
+impl Stage for Content {
+    async fn process(&self, cx: &Context<'_>, article: &mut Article) -> Result<bool> {
+        article.summary = cx
+            .entry
+            .summary
+            .as_ref()
+            .map(|text| text.content.as_str())
+            .or_else(|| cx.entry.content.as_ref().and_then(|c| c.body.as_deref()))
+            .map(models::summarize)
+            .unwrap_or_default();
+        Ok(true)
+    }
+}
+
+// Thumbnail picks media rss thumbnails first, then the first image attached to the entry
+struct Thumbnail;
+
+#[async_trait]
+impl Stage for Thumbnail {
+    async fn process(&self, cx: &Context<'_>, article: &mut Article) -> Result<bool> {
+        let media = &cx.entry.media;
+        article.thumbnail = media
+            .iter()
+            .flat_map(|m| m.thumbnails.iter().map(|t| t.image.uri.clone()))
+            .chain(media.iter().flat_map(|m| {
+                m.content
+                    .iter()
+                    .filter(|c| c.content_type.as_ref().map(|t| t.type_() == "image") == Some(true))
+                    .filter_map(|c| c.url.as_ref().map(|u| u.to_string()))
+            }))
+            .next()
+            .unwrap_or_default();
+        Ok(true)
+    }
+}
+
+// Rules runs the read, favorite and skip rules, it comes last so they match the title as it is stored
+struct Rules;
+
+#[async_trait]
+impl Stage for Rules {
+    async fn process(&self, cx: &Context<'_>, article: &mut Article) -> Result<bool> {
+        Ok(notify::apply(cx.rules, cx.feed, article))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    fn feed() -> Feed {
+        Feed::new(
+            "Fixture".to_string(),
+            "".to_string(),
+            "https://fixtures.invalid/feed".to_string(),
+            false,
+            "".to_string(),
+        )
+    }
+
+    fn entry(id: &str, link: &str, title: &str) -> Entry {
+        let mut e = Entry {
+            id: id.to_string(),
+            ..Default::default()
+        };
+        if !link.is_empty() {
+            e.links.push(feed_rs::model::Link {
+                href: link.to_string(),
+                rel: None,
+                media_type: None,
+                href_lang: None,
+                title: None,
+                length: None,
+            });
+        }
+        e.title = Some(feed_rs::model::Text {
+            content_type: "text/plain".parse().unwrap(),
+            src: None,
+            content: title.to_string(),
+        });
+        e.summary = e.title.clone();
+        e
+    }
+
+    #[tokio::test]
+    async fn entries_become_articles() {
+        let feed = feed();
+        let long_guid = "g".repeat(models::MAX_GUID_BYTES + 1);
+        let long_title = "t".repeat(models::MAX_TITLE_CHARS + 10);
+
+        // (entry, title, link, guid)
+        let cases = [
+            (
+                entry("guid-1", "https://example.com/1", "Plain"),
+                "Plain".to_string(),
+                "https://example.com/1",
+                "guid-1".to_string(),
+            ),
+            (
+                entry("", "https://example.com/2", "No guid"),
+                "No guid".to_string(),
+                "https://example.com/2",
+                "https://example.com/2".to_string(),
+            ),
+            (
+                entry("guid-3", "javascript:alert(1)", "Script <b>link</b>"),
+                "Script link".to_string(),
+                "",
+                "guid-3".to_string(),
+            ),
+            (
+                entry(
+                    long_guid.as_str(),
+                    "https://example.com/4",
+                    long_title.as_str(),
+                ),
+                format!("{}…", "t".repeat(models::MAX_TITLE_CHARS)),
+                "https://example.com/4",
+                format!("sha256:{:x}", Sha256::digest(long_guid.as_bytes())),
+            ),
+        ];
+
+        for (e, title, link, guid) in cases {
+            let articles = Pipeline::builtin().run(&feed, &[], &[e]).await;
+            let a = &articles[0];
+            assert_eq!(a.title, title);
+            assert_eq!(a.link, link);
+            assert_eq!(a.guid, guid);
+            assert_eq!(a.feed_id, feed.id);
+            assert!(a.published_estimated);
+        }
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl Stage for Failing {
+        async fn process(&self, _: &Context<'_>, _: &mut Article) -> Result<bool> {
+            Err(anyhow::Error::msg("stage failed"))
+        }
+    }
+
+    struct DropAll;
+
+    #[async_trait]
+    impl Stage for DropAll {
+        async fn process(&self, _: &Context<'_>, _: &mut Article) -> Result<bool> {
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn feeds_choose_their_stages() {
+        let mut pipeline = Pipeline::builtin();
+        pipeline.register("failing", Mode::Default, Arc::new(Failing));
+        pipeline.register("drop", Mode::Optional, Arc::new(DropAll));
+        let entries = [entry(
+            "guid-1",
+            "https://example.com/1?id=7&utm_source=rss",
+            "Plain",
+        )];
+
+        // a failing stage is skipped over, the optional ones are off until the feed turns them on
+        let mut feed = feed();
+        let articles = pipeline.run(&feed, &[], &entries).await;
+        assert_eq!(articles.len(), 1);
+        assert_eq!(articles[0].summary, "Plain");
+        assert_eq!(
+            articles[0].link,
+            "https://example.com/1?id=7&utm_source=rss"
+        );
+
+        feed.stages_on = vec![TRACKING.to_string()];
+        let articles = pipeline.run(&feed, &[], &entries).await;
+        assert_eq!(articles[0].link, "https://example.com/1?id=7");
+
+        feed.stages_off = vec![CONTENT.to_string()];
+        let articles = pipeline.run(&feed, &[], &entries).await;
+        assert_eq!(articles[0].summary, "");
+        assert_eq!(articles[0].title, "Plain");
+
+        feed.stages_on.push("drop".to_string());
+        assert!(pipeline.run(&feed, &[], &entries).await.is_empty());
+
+        let settings: Vec<(&str, bool)> = pipeline
+            .settings(&feed)
+            .iter()
+            .map(|s| (s.name, s.on))
+            .collect();
+        assert_eq!(
+            settings,
+            [
+                (TRACKING, true),
+                (CONTENT, false),
+                (THUMBNAIL, true),
+                ("failing", true),
+                ("drop", true)
+            ]
+        );
+    }
+}
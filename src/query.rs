@@ -0,0 +1,397 @@
+use crate::dates;
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+
+// longest expression accepted, it also bounds how deep parentheses can nest
+pub const MAX_EXPRESSION_CHARS: usize = 500;
+
+// Expr is a parsed filter expression, terms are joined with AND, OR and NOT and grouped with parentheses
+// adjacent terms without an operator are joined with AND
+//
+//   unread, read, favorited                   the state of the article
+//   feed:"name", feed:~"part"                 the feed name is the value, or contains it, case is ignored
+//   category:, title:, author:                the same for the category of the feed, the title and the author
+//   published > 7d, published < 2024-01-01    newer or older than an age in h, d or w, or than a day in utc
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Unread,
+    Read,
+    Favorited,
+    Text {
+        field: Field,
+        contains: bool,
+        value: String,
+    },
+    Published {
+        newer: bool,
+        since: Since,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Field {
+    Feed,
+    Category,
+    Title,
+    Author,
+}
+
+// Since is what published is compared with, an age is taken from when the query runs
+#[derive(Debug, PartialEq)]
+pub enum Since {
+    Ago(Duration),
+    Day(NaiveDate),
+}
+
+// Sql is a where clause over the articles table, its placeholders are numbered from the first one it was written with
+pub struct Sql {
+    pub clause: String,
+    pub params: Vec<String>,
+}
+
+pub fn parse(expression: &str) -> Result<Expr> {
+    if expression.chars().count() > MAX_EXPRESSION_CHARS {
+        return Err(anyhow::Error::msg(format!(
+            "expression is longer than {} characters",
+            MAX_EXPRESSION_CHARS
+        )));
+    }
+    let mut parser = Parser {
+        tokens: tokens(expression)?,
+        at: 0,
+    };
+    if parser.tokens.is_empty() {
+        return Err(anyhow::Error::msg("expression is empty"));
+    }
+    let expr = parser.or()?;
+    match parser.next() {
+        None => Ok(expr),
+        Some(token) => Err(unexpected(Some(token))),
+    }
+}
+
+impl Expr {
+    pub fn sql(&self, first: usize, now: DateTime<Utc>) -> Sql {
+        let mut params = vec![];
+        let clause = self.write(first, now, &mut params);
+        Sql { clause, params }
+    }
+
+    fn write(&self, first: usize, now: DateTime<Utc>, params: &mut Vec<String>) -> String {
+        match self {
+            Expr::And(left, right) => {
+                let left = left.write(first, now, params);
+                format!("({} AND {})", left, right.write(first, now, params))
+            }
+            Expr::Or(left, right) => {
+                let left = left.write(first, now, params);
+                format!("({} OR {})", left, right.write(first, now, params))
+            }
+            Expr::Not(expr) => format!("NOT ({})", expr.write(first, now, params)),
+            Expr::Unread => "read = false".to_string(),
+            Expr::Read => "read = true".to_string(),
+            Expr::Favorited => "favorited = true".to_string(),
+            Expr::Text {
+                field,
+                contains,
+                value,
+            } => {
+                let value = bind(params, first, value.clone());
+                let compare = |column: &str| match contains {
+                    true => format!("strpos(lower({}), lower({})) > 0", column, value),
+                    false => format!("lower({}) = lower({})", column, value),
+                };
+                match field {
+                    Field::Feed => compare("feed"),
+                    Field::Title => compare("title"),
+                    Field::Author => compare("author"),
+                    Field::Category => format!(
+                        "feed_id IN (SELECT f.id FROM feeds f WHERE {})",
+                        compare("f.category")
+                    ),
+                }
+            }
+            Expr::Published { newer, since } => {
+                let cutoff = match since {
+                    Since::Ago(age) => now - *age,
+                    Since::Day(day) => Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap()),
+                };
+                let cutoff = bind(params, first, dates::stored(cutoff));
+                match newer {
+                    true => format!("published > {}", cutoff),
+                    false => format!("published < {}", cutoff),
+                }
+            }
+        }
+    }
+}
+
+// bind adds a value and returns its placeholder
+fn bind(params: &mut Vec<String>, first: usize, value: String) -> String {
+    params.push(value);
+    format!("${}", first + params.len() - 1)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Open,
+    Close,
+    Colon,
+    Tilde,
+    Greater,
+    Less,
+    Word(String),
+    Quoted(String),
+}
+
+fn tokens(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ':' => tokens.push(Token::Colon),
+            '~' => tokens.push(Token::Tilde),
+            '>' => tokens.push(Token::Greater),
+            '<' => tokens.push(Token::Less),
+            // a backslash keeps the character after it, so a value can hold a quote
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(anyhow::Error::msg("a quote is not closed")),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            c if is_word(c) => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| is_word(*c)) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+            c => return Err(anyhow::Error::msg(format!("unexpected {:?}", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+fn unexpected(token: Option<Token>) -> anyhow::Error {
+    match token {
+        Some(Token::Word(word)) | Some(Token::Quoted(word)) => {
+            anyhow::Error::msg(format!("unexpected {:?}", word))
+        }
+        Some(token) => anyhow::Error::msg(format!("unexpected {:?}", token)),
+        None => anyhow::Error::msg("expression ends too early"),
+    }
+}
+
+// Parser reads tokens by precedence, OR binds loosest, then AND, then NOT
+struct Parser {
+    tokens: Vec<Token>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        token
+    }
+
+    // keyword matches operators in any case, and and AND are the same
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut left = self.and()?;
+        while self.keyword("or") {
+            self.at += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut left = self.not()?;
+        loop {
+            if self.keyword("and") {
+                self.at += 1;
+            } else if matches!(self.peek(), None | Some(Token::Close)) || self.keyword("or") {
+                return Ok(left);
+            }
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        match self.keyword("not") {
+            true => {
+                self.at += 1;
+                Ok(Expr::Not(Box::new(self.not()?)))
+            }
+            false => self.term(),
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let word = match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                return match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    token => Err(unexpected(token)),
+                };
+            }
+            Some(Token::Word(word)) => word,
+            token => return Err(unexpected(token)),
+        };
+        match word.to_lowercase().as_str() {
+            "unread" => Ok(Expr::Unread),
+            "read" => Ok(Expr::Read),
+            "favorited" | "favorite" | "starred" => Ok(Expr::Favorited),
+            "feed" => self.text(Field::Feed),
+            "category" => self.text(Field::Category),
+            "title" => self.text(Field::Title),
+            "author" => self.text(Field::Author),
+            "published" => self.published(),
+            _ => Err(anyhow::Error::msg(format!("unknown term {:?}", word))),
+        }
+    }
+
+    fn text(&mut self, field: Field) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Colon) => {}
+            token => return Err(unexpected(token)),
+        }
+        let contains = self.peek() == Some(&Token::Tilde);
+        if contains {
+            self.at += 1;
+        }
+        match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => Ok(Expr::Text {
+                field,
+                contains,
+                value,
+            }),
+            token => Err(unexpected(token)),
+        }
+    }
+
+    fn published(&mut self) -> Result<Expr> {
+        let newer = match self.next() {
+            Some(Token::Greater) => true,
+            Some(Token::Less) => false,
+            token => return Err(unexpected(token)),
+        };
+        let value = match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => value,
+            token => return Err(unexpected(token)),
+        };
+        if let Ok(day) = NaiveDate::parse_from_str(value.as_str(), "%Y-%m-%d") {
+            return Ok(Expr::Published {
+                newer,
+                since: Since::Day(day),
+            });
+        }
+
+        let bad = || anyhow::Error::msg(format!("{:?} is not an age like 7d or a day", value));
+        let unit = value.chars().last().ok_or_else(bad)?;
+        let count: i64 = value[..value.len() - unit.len_utf8()]
+            .parse::<u16>()
+            .map_err(|_| bad())?
+            .into();
+        let age = match unit.to_ascii_lowercase() {
+            'h' => Duration::hours(count),
+            'd' => Duration::days(count),
+            'w' => Duration::weeks(count),
+            _ => return Err(bad()),
+        };
+        Ok(Expr::Published {
+            newer,
+            since: Since::Ago(age),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_by_precedence() {
+        let expr =
+            parse(r#"feed:~"rust" AND unread published > 7d OR NOT (favorited or read)"#).unwrap();
+        let rust = Expr::Text {
+            field: Field::Feed,
+            contains: true,
+            value: "rust".to_string(),
+        };
+        let recent = Expr::Published {
+            newer: true,
+            since: Since::Ago(Duration::days(7)),
+        };
+        let expected = Expr::Or(
+            Box::new(Expr::And(
+                Box::new(Expr::And(Box::new(rust), Box::new(Expr::Unread))),
+                Box::new(recent),
+            )),
+            Box::new(Expr::Not(Box::new(Expr::Or(
+                Box::new(Expr::Favorited),
+                Box::new(Expr::Read),
+            )))),
+        );
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn writes_placeholders_in_order() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 8, 12, 0, 0).unwrap();
+        let sql = parse(r#"category:"News \"daily\"" and published < 1w and title:~rust"#)
+            .unwrap()
+            .sql(2, now);
+        assert_eq!(
+            sql.clause,
+            "((feed_id IN (SELECT f.id FROM feeds f WHERE lower(f.category) = lower($2)) AND published < $3) AND strpos(lower(title), lower($4)) > 0)"
+        );
+        assert_eq!(
+            sql.params,
+            ["News \"daily\"", "2024-01-01T12:00:00.000Z", "rust"]
+        );
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_read() {
+        for expression in [
+            "",
+            "unread AND",
+            "(unread",
+            "feed \"rust\"",
+            "published > soon",
+            "published = 7d",
+            "tag:rust",
+            "title:\"open",
+            "unread; DROP TABLE articles",
+        ] {
+            assert!(parse(expression).is_err(), "{:?} parsed", expression);
+        }
+    }
+}
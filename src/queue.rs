@@ -0,0 +1,48 @@
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use std::future::Future;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+
+type Task = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+// Queue runs deferred work in the background with its own concurrency limit, pushing never waits on the work
+// a full queue drops new tasks, so it only suits work that can be skipped like warming caches
+#[derive(Clone)]
+pub struct Queue {
+    name: &'static str,
+    sender: mpsc::Sender<Task>,
+}
+
+impl Queue {
+    // new returns the queue and the worker that runs its tasks, the worker has to be spawned by the caller
+    pub fn new(
+        name: &'static str,
+        size: usize,
+        concurrency: usize,
+    ) -> (Queue, impl Future<Output = ()>) {
+        let (sender, receiver) = mpsc::channel(size);
+        (Queue { name, sender }, work(name, receiver, concurrency))
+    }
+
+    pub fn push<F>(&self, task: F)
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        if let Err(e) = self.sender.try_send(Box::pin(task)) {
+            println!("dropping {} task: {}", self.name, e);
+        }
+    }
+}
+
+async fn work(name: &'static str, receiver: mpsc::Receiver<Task>, concurrency: usize) {
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|task| (task, receiver))
+    })
+    .for_each_concurrent(concurrency, |task| async move {
+        if let Err(e) = task.await {
+            println!("{} task failed: {}", name, e);
+        }
+    })
+    .await
+}
@@ -0,0 +1,84 @@
+use rweb::hyper::body::Bytes;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// pages are rendered again after this even when nothing changed, relative dates would go stale otherwise
+const TTL: Duration = Duration::from_secs(60);
+// most pages kept, the oldest is evicted first
+const CAPACITY: usize = 256;
+
+#[derive(Serialize, Clone, Default)]
+pub struct Stats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+struct Entry {
+    generation: u64,
+    rendered: Instant,
+    html: Bytes,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    stats: Stats,
+}
+
+// Cache keeps rendered article lists so devices polling the same view share one render
+// an entry only counts while it was rendered at the current storage generation
+#[derive(Clone, Default)]
+pub struct Cache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Cache {
+    pub fn get(&self, key: &str, generation: u64) -> Option<Bytes> {
+        let mut inner = self.inner.lock().unwrap();
+        let html = inner
+            .entries
+            .get(key)
+            .filter(|e| e.generation == generation && e.rendered.elapsed() < TTL)
+            .map(|e| e.html.clone());
+        match html {
+            Some(_) => inner.stats.hits += 1,
+            None => inner.stats.misses += 1,
+        }
+        html
+    }
+
+    pub fn insert(&self, key: String, generation: u64, html: Bytes) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.len() >= CAPACITY && !inner.entries.contains_key(&key) {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.rendered)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+                inner.stats.evictions += 1;
+            }
+        }
+        inner.entries.insert(
+            key,
+            Entry {
+                generation,
+                rendered: Instant::now(),
+                html,
+            },
+        );
+    }
+
+    pub fn stats(&self) -> Stats {
+        let inner = self.inner.lock().unwrap();
+        Stats {
+            entries: inner.entries.len(),
+            ..inner.stats.clone()
+        }
+    }
+}
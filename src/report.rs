@@ -0,0 +1,30 @@
+use crate::models::Feed;
+use sentry::types::Dsn;
+use sentry::ClientInitGuard;
+
+// init reports panics and captured errors to sentry when a dsn is configured, events queued when the guard drops are flushed
+pub fn init(dsn: Option<Dsn>) -> Option<ClientInitGuard> {
+    let dsn = dsn?;
+    Some(sentry::init(sentry::ClientOptions {
+        dsn: Some(dsn),
+        release: sentry::release_name!(),
+        ..Default::default()
+    }))
+}
+
+// error is a no-op unless init bound a client
+pub fn error(e: &anyhow::Error) {
+    sentry::capture_error(e.as_ref() as &dyn std::error::Error);
+}
+
+// feed_error tags the event with the feed so recurring failures of one feed group together
+pub fn feed_error(e: &anyhow::Error, f: &Feed) {
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("feed_id", &f.id);
+            scope.set_tag("feed_url", &f.feed_url);
+            scope.set_fingerprint(Some(&["{{ default }}", f.feed_url.as_str()]));
+        },
+        || error(e),
+    );
+}
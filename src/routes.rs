@@ -0,0 +1,3416 @@
+use crate::models::*;
+use crate::scheduler::refresh;
+use crate::{
+    asset, assets, base_path, cache_control, config, cursor, dates, db, discover, epub, events,
+    fetch, idempotency, images, import, integrations, jsonfeed, mail, mute, newsletters, notifiers,
+    notify, pipeline, query, render_cache, report, saved, scheduler, shutdown, sync, tr, tr_choice,
+    tr_with, App, CONFIG, DATES, GIT_SHA, I18N, PUBLIC_URL, SETTINGS, VERSION,
+};
+use anyhow::Result;
+use askama::Template;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{future, stream, StreamExt, TryStreamExt};
+use rweb::hyper::body::{Buf, Bytes};
+use rweb::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr, sync::Arc, vec};
+
+const DEFAULT_TRIGGER_LIMIT: i64 = 50;
+// largest setup form accepted, an opml export of a few thousand feeds fits well within it
+const MAX_SETUP_BYTES: u64 = 4 * 1024 * 1024;
+// largest mail accepted from mailgun, attachments count towards it though they are dropped
+const MAX_MAIL_BYTES: u64 = 32 * 1024 * 1024;
+// largest starred items export accepted, they hold the text of every item and years of them add up
+const MAX_STARRED_BYTES: u64 = 64 * 1024 * 1024;
+// articles listed under related at the bottom of an article page
+const RELATED_LIMIT: i64 = 5;
+// pruned articles listed in the trash, newest first
+const TRASH_LIMIT: i64 = 100;
+
+fn density() -> String {
+    match SETTINGS.get() {
+        Some(settings) => settings.density(),
+        None => db::DENSITIES[0].to_string(),
+    }
+}
+
+fn group_by_day() -> bool {
+    SETTINGS
+        .get()
+        .map(|settings| settings.group_by_day())
+        .unwrap_or(false)
+}
+
+// Day is a run of articles published on the same day, the label is empty when grouping is off
+struct Day {
+    label: String,
+    articles: Vec<Article>,
+}
+
+// days groups consecutive articles, the queries already return them newest first
+fn days(articles: Vec<Article>) -> Vec<Day> {
+    if !group_by_day() {
+        return match articles.is_empty() {
+            true => vec![],
+            false => vec![Day {
+                label: "".to_string(),
+                articles,
+            }],
+        };
+    }
+
+    let mut days: Vec<Day> = vec![];
+    for mut article in articles {
+        match days.last_mut() {
+            Some(day) if day.label == article.day => day.articles.push(article),
+            _ => days.push(Day {
+                label: std::mem::take(&mut article.day),
+                articles: vec![article],
+            }),
+        }
+    }
+    days
+}
+
+// Category is a folder of feeds in the sidebar, the name is empty for feeds without one
+struct Category {
+    name: String,
+    unread: i64,
+    collapsed: bool,
+    feeds: Vec<db::SidebarFeed>,
+}
+
+impl Category {
+    fn label(&self) -> String {
+        match self.name.is_empty() {
+            true => tr("sidebar-uncategorized"),
+            false => self.name.clone(),
+        }
+    }
+}
+
+// SidebarFolder is a smart folder with the number of unread articles it matches
+struct SidebarFolder {
+    id: String,
+    name: String,
+    unread: i64,
+}
+
+// categories groups the feeds, the query already orders them by category
+fn categories(feeds: Vec<db::SidebarFeed>, collapsed: &[String]) -> Vec<Category> {
+    let mut categories: Vec<Category> = vec![];
+    for feed in feeds {
+        match categories.last_mut() {
+            Some(category) if category.name == feed.category => {
+                category.unread += feed.unread;
+                category.feeds.push(feed);
+            }
+            _ => categories.push(Category {
+                name: feed.category.clone(),
+                unread: feed.unread,
+                collapsed: collapsed.contains(&feed.category),
+                feeds: vec![feed],
+            }),
+        }
+    }
+    // uncategorized feeds sort first, list them last instead
+    categories.sort_by_key(|category| category.name.is_empty());
+    categories
+}
+
+// with_counts runs a page's own query next to the counts every page shows in its navigation
+async fn with_counts<T>(
+    store: &db::Storage,
+    query: impl std::future::Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<(db::Counts, T)> {
+    futures::try_join!(store.get_counts(), query)
+}
+
+// cached serves a rendered view from the render cache, the key names the view and its cursor
+// the generation is read before any query runs, a change made mid render leaves the entry stale
+async fn cached<T: Template>(
+    cache: &render_cache::Cache,
+    store: &db::Storage,
+    key: String,
+    view: impl std::future::Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<warp::reply::Html<Bytes>> {
+    let generation = store.generation();
+    if let Some(html) = cache.get(key.as_str(), generation) {
+        return Ok(warp::reply::html(html));
+    }
+
+    // the cache and the response share the rendered page instead of copying it
+    let html = Bytes::from(view.await?.render()?);
+    cache.insert(key, generation, html.clone());
+    Ok(warp::reply::html(html))
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct AppError(anyhow::Error);
+impl rweb::reject::Reject for AppError {}
+
+fn reject_anyhow(err: anyhow::Error) -> Rejection {
+    // missing and duplicate rows are the caller's mistake, only failures are worth a report
+    if err.downcast_ref::<db::Error>().is_none() {
+        report::error(&err);
+    }
+    warp::reject::custom(AppError(err))
+}
+
+// recover renders rejections as an error page instead of warp's plain text, errors from handlers are reported when they are rejected
+// rweb checks the method before the path, so any request that matches no route also carries method not allowed from the
+// routes of other methods, a GET like that is answered as not found since browsers only navigate with GET
+async fn recover(
+    method: http::Method,
+    result: Result<warp::reply::Response, Rejection>,
+) -> Result<warp::reply::Response, std::convert::Infallible> {
+    let rejection = match result {
+        Ok(response) => return Ok(response),
+        Err(rejection) => rejection,
+    };
+    if let Some(idempotency::Settled::Replay(stored)) = rejection.find() {
+        return Ok(idempotency::replay(stored));
+    }
+
+    let not_found = (
+        http::StatusCode::NOT_FOUND,
+        tr("error-not-found"),
+        "".to_string(),
+    );
+    let (status, message, detail) = if let Some(AppError(e)) = rejection.find::<AppError>() {
+        match e.downcast_ref::<db::Error>() {
+            Some(db::Error::NotFound(_)) => (
+                http::StatusCode::NOT_FOUND,
+                tr("error-not-found"),
+                e.to_string(),
+            ),
+            Some(db::Error::Conflict(_)) => (
+                http::StatusCode::CONFLICT,
+                tr("error-conflict"),
+                e.to_string(),
+            ),
+            Some(db::Error::Stale(_)) => {
+                (http::StatusCode::CONFLICT, tr("error-stale"), e.to_string())
+            }
+            // a failure can carry connection strings or sql, it is reported but kept off the page
+            None => (
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                tr("error-internal"),
+                "".to_string(),
+            ),
+        }
+    } else if rejection.find::<Unauthorized>().is_some() {
+        (
+            http::StatusCode::UNAUTHORIZED,
+            tr("error-unauthorized"),
+            "".to_string(),
+        )
+    } else if let Some(settled) = rejection.find::<idempotency::Settled>() {
+        match settled {
+            idempotency::Settled::InFlight => (
+                http::StatusCode::CONFLICT,
+                tr("error-idempotency-in-flight"),
+                "".to_string(),
+            ),
+            idempotency::Settled::Reused => (
+                http::StatusCode::UNPROCESSABLE_ENTITY,
+                tr("error-idempotency-reused"),
+                "".to_string(),
+            ),
+            idempotency::Settled::TooLarge => (
+                http::StatusCode::PAYLOAD_TOO_LARGE,
+                tr("error-bad-request"),
+                "Request payload is too large".to_string(),
+            ),
+            _ => (
+                http::StatusCode::BAD_REQUEST,
+                tr("error-bad-request"),
+                "".to_string(),
+            ),
+        }
+    } else if rejection.find::<BadActionError>().is_some() {
+        (
+            http::StatusCode::BAD_REQUEST,
+            tr("error-bad-request"),
+            "".to_string(),
+        )
+    } else if let Some((status, e)) = request_error(&rejection) {
+        (status, tr("error-bad-request"), e)
+    } else if rejection.find::<warp::cors::CorsForbidden>().is_some() {
+        (
+            http::StatusCode::FORBIDDEN,
+            tr("error-forbidden"),
+            "".to_string(),
+        )
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        match method {
+            http::Method::GET | http::Method::HEAD => not_found,
+            _ => (
+                http::StatusCode::METHOD_NOT_ALLOWED,
+                tr("error-method-not-allowed"),
+                "".to_string(),
+            ),
+        }
+    } else {
+        not_found
+    };
+
+    let page = ErrorTemplate {
+        counts: db::Counts::default(),
+        status: status.as_u16(),
+        message,
+        detail,
+    };
+    let html = page.render().unwrap_or_else(|_| status.to_string());
+    Ok(warp::reply::with_status(warp::reply::html(html), status).into_response())
+}
+
+// request_error finds the rejections that come from extracting the request, a missing header or a form that does not parse
+fn request_error(rejection: &Rejection) -> Option<(http::StatusCode, String)> {
+    use warp::reject::{
+        InvalidHeader, InvalidQuery, LengthRequired, MissingHeader, PayloadTooLarge,
+        UnsupportedMediaType,
+    };
+    let bad_request = |e: &dyn std::fmt::Display| (http::StatusCode::BAD_REQUEST, e.to_string());
+    rejection
+        .find::<warp::body::BodyDeserializeError>()
+        .map(|e| bad_request(e))
+        .or_else(|| rejection.find::<MissingHeader>().map(|e| bad_request(e)))
+        .or_else(|| rejection.find::<InvalidHeader>().map(|e| bad_request(e)))
+        .or_else(|| rejection.find::<InvalidQuery>().map(|e| bad_request(e)))
+        .or_else(|| {
+            rejection
+                .find::<PayloadTooLarge>()
+                .map(|e| (http::StatusCode::PAYLOAD_TOO_LARGE, e.to_string()))
+        })
+        .or_else(|| {
+            rejection
+                .find::<UnsupportedMediaType>()
+                .map(|e| (http::StatusCode::UNSUPPORTED_MEDIA_TYPE, e.to_string()))
+        })
+        .or_else(|| {
+            rejection
+                .find::<LengthRequired>()
+                .map(|e| (http::StatusCode::LENGTH_REQUIRED, e.to_string()))
+        })
+}
+
+#[derive(Deserialize, Serialize)]
+struct Healthz {
+    up: bool,
+    version: &'static str,
+    git_sha: &'static str,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Version {
+    version: &'static str,
+    git_sha: &'static str,
+    profile: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "feeds.html")]
+struct FeedsTemplate {
+    counts: db::Counts,
+    cursor: db::Cursor,
+    feeds: Vec<(Feed, db::FeedStats)>,
+    empty: Empty,
+}
+
+#[derive(Template)]
+#[template(path = "feed_list.html")]
+struct FeedListTemplate {
+    cursor: db::Cursor,
+    feeds: Vec<(Feed, db::FeedStats)>,
+    empty: Empty,
+}
+
+#[derive(Template)]
+#[template(path = "feed.html")]
+struct FeedTemplate {
+    counts: db::Counts,
+    feed: Feed,
+    latency: FetchLatency,
+    fetch_log: Vec<FetchLog>,
+    rules: Vec<NotificationRule>,
+    stages: Vec<pipeline::Setting>,
+    saved: bool,
+    empty: Empty,
+    // a rejected settings form is shown again with what was entered and a message under each rejected field
+    errors: Vec<FieldError>,
+}
+
+impl FeedTemplate {
+    fn error(&self, field: &str) -> String {
+        self.errors
+            .iter()
+            .find(|e| e.field == field)
+            .map(|e| tr(e.message))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "settings.html")]
+struct SettingsTemplate {
+    counts: db::Counts,
+    refresh_seconds: String,
+    retention_days: i64,
+    page_size: usize,
+    theme: String,
+    themes: Vec<String>,
+    density: String,
+    densities: Vec<String>,
+    group_by_day: bool,
+    language: String,
+    // each language with its name in that language
+    languages: Vec<(String, String)>,
+    custom_css: String,
+    saved: bool,
+}
+
+#[derive(Template)]
+#[template(path = "offline.html")]
+struct OfflineTemplate {
+    counts: db::Counts,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    counts: db::Counts,
+    status: u16,
+    message: String,
+    detail: String,
+}
+
+#[derive(Template)]
+#[template(path = "sw.js", escape = "none")]
+struct ServiceWorkerTemplate {
+    version: String,
+    precache: String,
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    name: &'static str,
+    short_name: &'static str,
+    start_url: String,
+    scope: String,
+    display: &'static str,
+    background_color: &'static str,
+    theme_color: &'static str,
+    icons: Vec<ManifestIcon>,
+}
+
+#[derive(Serialize)]
+struct ManifestIcon {
+    src: String,
+    sizes: &'static str,
+    #[serde(rename = "type")]
+    content_type: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "notifications.html")]
+struct NotificationsTemplate {
+    counts: db::Counts,
+    services: Vec<String>,
+    feeds: Vec<Feed>,
+    rules: Vec<NotificationRule>,
+    categories: Vec<String>,
+    schedules: Vec<MuteSchedule>,
+}
+
+#[derive(Template)]
+#[template(path = "notification_list.html")]
+struct NotificationListTemplate {
+    rules: Vec<NotificationRule>,
+}
+
+#[derive(Template)]
+#[template(path = "mute_list.html")]
+struct MuteListTemplate {
+    feeds: Vec<Feed>,
+    schedules: Vec<MuteSchedule>,
+}
+
+#[derive(Template)]
+#[template(path = "trash.html")]
+struct TrashTemplate {
+    counts: db::Counts,
+    trashed: Vec<TrashedArticle>,
+    total: usize,
+}
+
+#[derive(Template)]
+#[template(path = "trash_list.html")]
+struct TrashListTemplate {
+    trashed: Vec<TrashedArticle>,
+    total: usize,
+}
+
+#[derive(Template)]
+#[template(path = "sidebar.html")]
+struct SidebarTemplate {
+    folders: Vec<SidebarFolder>,
+    categories: Vec<Category>,
+}
+
+#[derive(Template)]
+#[template(path = "article_page.html")]
+struct ArticlePageTemplate {
+    counts: db::Counts,
+    article: Article,
+    // the paragraphs of a newsletter received by mail, empty for articles of feeds
+    body: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "related.html")]
+struct RelatedTemplate {
+    articles: Vec<Article>,
+}
+
+#[derive(Template)]
+#[template(path = "folders.html")]
+struct FoldersTemplate {
+    counts: db::Counts,
+    folders: Vec<SmartFolder>,
+    form: AddSmartFolder,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "folder_list.html")]
+struct FolderListTemplate {
+    folders: Vec<SmartFolder>,
+}
+
+#[derive(Template)]
+#[template(path = "add_feed.html")]
+struct AddFeedTemplate {
+    counts: db::Counts,
+    form: AddFeed,
+    // the form saving a single page sits under the feed form, errors name the field of either
+    page: SavePage,
+    errors: Vec<FieldError>,
+}
+
+impl AddFeedTemplate {
+    fn error(&self, field: &str) -> String {
+        self.errors
+            .iter()
+            .find(|e| e.field == field)
+            .map(|e| tr(e.message))
+            .unwrap_or_default()
+    }
+}
+
+// SetupTemplate is the first run page, once submitted it shows what the import did instead of the form
+#[derive(Template)]
+#[template(path = "setup.html")]
+struct SetupTemplate {
+    counts: db::Counts,
+    refresh_seconds: String,
+    imported: Option<import::Imported>,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "share.html")]
+struct ShareTemplate {
+    article_id: String,
+    services: Vec<String>,
+    text: String,
+    posted: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "article_list.html")]
+struct ArticleListTemplate {
+    cursor: db::Cursor,
+    days: Vec<Day>,
+    density: String,
+    integrations: integrations::Enabled,
+    empty: Empty,
+}
+
+// ArticleRowsTemplate is the next page of articles without the list around it, it ends with either a loader for the page after or an end marker
+#[derive(Template)]
+#[template(path = "article_rows.html")]
+struct ArticleRowsTemplate {
+    cursor: db::Cursor,
+    days: Vec<Day>,
+    density: String,
+    integrations: integrations::Enabled,
+    next: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    counts: db::Counts,
+    query: String,
+    results: Vec<SearchResult>,
+    prev: Option<String>,
+    next: Option<String>,
+}
+
+#[derive(Template, Default)]
+#[template(path = "articles.html")]
+struct ArticleBaseTemplate {
+    counts: db::Counts,
+    article_filter: String,
+    title: String,
+    cursor: db::Cursor,
+    days: Vec<Day>,
+    density: String,
+    integrations: integrations::Enabled,
+    empty: Empty,
+}
+
+// Empty says why a listing has nothing to show, the templates render guidance for it instead of a blank page
+#[derive(Default)]
+enum Empty {
+    // the listing has items
+    #[default]
+    No,
+    // nothing is subscribed yet, as on the first run
+    NoFeeds,
+    // feeds are subscribed but none of them has brought in an article yet
+    NoArticles,
+    // there are articles but the filter matches none of them
+    NoMatches(db::Filter),
+}
+
+impl Empty {
+    fn of(counts: &db::Counts, items: usize, filter: db::Filter) -> Empty {
+        match (items, counts.feeds, counts.articles) {
+            (0, 0, _) => Empty::NoFeeds,
+            (0, _, 0) => Empty::NoArticles,
+            (0, _, _) => Empty::NoMatches(filter),
+            _ => Empty::No,
+        }
+    }
+
+    fn feeds<T>(feeds: &[T]) -> Empty {
+        match feeds.is_empty() {
+            true => Empty::NoFeeds,
+            false => Empty::No,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !matches!(self, Empty::No)
+    }
+
+    fn needs_feed(&self) -> bool {
+        matches!(self, Empty::NoFeeds)
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Empty::No => "".to_string(),
+            Empty::NoFeeds => tr("empty-no-feeds"),
+            Empty::NoArticles => tr("empty-no-articles"),
+            Empty::NoMatches(db::Filter::Folder(_)) => tr("empty-folder"),
+            Empty::NoMatches(db::Filter::Feed(_)) => tr("empty-feed"),
+            Empty::NoMatches(filter) => tr_choice("empty", filter.to_string().as_str()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BadActionError();
+impl rweb::reject::Reject for BadActionError {}
+
+// Unauthorized is a request to a token protected endpoint without the token
+#[derive(Debug)]
+struct Unauthorized;
+impl rweb::reject::Reject for Unauthorized {}
+
+// ExportArticles selects articles by a comma separated list of ids, an empty selection means all unread
+#[derive(Serialize, Deserialize)]
+struct ExportArticles {
+    ids: Option<String>,
+}
+
+impl ExportArticles {
+    fn ids(&self) -> Vec<String> {
+        self.ids
+            .clone()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| id.to_string())
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ImageQuery {
+    url: String,
+    sig: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SearchQuery {
+    q: Option<String>,
+    pagination: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MoreQuery {
+    filter: String,
+    pagination: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SyncQuery {
+    since: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TriggerQuery {
+    since: Option<String>,
+    limit: Option<i64>,
+}
+
+// TriggerToken authenticates polling triggers, the endpoint is disabled without one
+#[derive(Clone)]
+pub(crate) struct TriggerToken(pub(crate) Option<Arc<str>>);
+
+#[derive(Serialize, Deserialize)]
+struct TriggerMeta {
+    id: String,
+    timestamp: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TriggerItem {
+    id: String,
+    feed: String,
+    title: String,
+    link: String,
+    author: String,
+    published: String,
+    added: String,
+    meta: TriggerMeta,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TriggerResponse {
+    data: Vec<TriggerItem>,
+}
+
+#[derive(Deserialize)]
+struct JsonFeedQuery {
+    // the signed cursor of the page, the newest articles when it is missing
+    cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArticlesQuery {
+    // unread when neither the filter nor a cursor is given, a cursor names the filter it was made for
+    filter: Option<String>,
+    cursor: Option<String>,
+}
+
+// ArticlesPage is a page of the json api, next and prev are opaque cursors to pass back as they are
+#[derive(Serialize)]
+struct ArticlesPage {
+    articles: Vec<Article>,
+    next: Option<String>,
+    prev: Option<String>,
+}
+
+// MailgunKey verifies newsletters posted by a mailgun route, the endpoint is disabled without one
+// the tokens of the webhooks it took are kept so the same post is not taken twice
+#[derive(Clone)]
+pub(crate) struct MailgunKey(pub(crate) Option<Arc<str>>, pub(crate) newsletters::Seen);
+
+// ExtensionToken authenticates the browser extension endpoints, they are disabled without one
+#[derive(Clone)]
+pub(crate) struct ExtensionToken(pub(crate) Option<Arc<str>>);
+
+#[derive(Deserialize)]
+struct DiscoverQuery {
+    url: String,
+}
+
+// Discovery tells the extension whether the page it is on is subscribed and which feeds it links to
+// a page that could not be fetched carries the error, whether it is subscribed is still known
+#[derive(Serialize)]
+struct Discovery {
+    url: String,
+    // the subscribed feed whose site or feed url is the page
+    feed_id: Option<String>,
+    feeds: Vec<discover::Found>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UnreadCount {
+    unread: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ShareArticle {
+    service: String,
+    text: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveSettings {
+    refresh_seconds: String,
+    retention_days: String,
+    page_size: String,
+    theme: String,
+    density: String,
+    language: String,
+    // unchecked boxes are left out of the form
+    group_by_day: Option<String>,
+    #[serde(default)]
+    custom_css: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ThemePreference {
+    theme: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CategoryPreference {
+    category: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct DebugEntry {
+    title: String,
+    link: String,
+    published: String,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct DebugReport {
+    url: String,
+    status: Option<u16>,
+    headers: Vec<(String, String)>,
+    entry_count: usize,
+    new_entries: Vec<DebugEntry>,
+    warnings: Vec<String>,
+    error: Option<String>,
+}
+
+// build_app puts every route together behind the base path, with the error page and the headers every response gets
+pub fn build_app(
+    app: App,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone {
+    let cors = warp::cors()
+        .allow_any_origin()
+        .allow_headers(vec![
+            "Authorization",
+            "Content-Type",
+            "Idempotency-Key",
+            "User-Agent",
+            "Sec-Fetch-Mode",
+            "Referer",
+            "Origin",
+            "Access-Control-Request-Method",
+            "Access-Control-Request-Headers",
+            "article_filter",
+            "pagination",
+            "version",
+        ])
+        .allow_methods(vec!["GET", "HEAD", "POST", "DELETE"]);
+
+    // the routes are boxed in groups, one long chain of or() nests deep enough to overflow the stack in debug builds
+    let status = healthz()
+        .or(readyz(app.draining.clone()))
+        .or(version())
+        .or(static_assets())
+        .or(manifest())
+        .or(service_worker())
+        .or(offline())
+        .or(custom_css(app.db_settings.clone()))
+        .boxed();
+
+    let articles = index(
+        app.store.clone(),
+        app.db_settings.clone(),
+        app.integrations.clone(),
+        app.render_cache.clone(),
+    )
+    .or(search(app.store.clone()))
+    .or(proxy_image(app.images.clone()))
+    .or(favorites(
+        app.store.clone(),
+        app.integrations.clone(),
+        app.render_cache.clone(),
+    ))
+    .or(history(app.store.clone(), app.integrations.clone()))
+    .or(trash(app.store.clone()))
+    .or(restore_article(app.store.clone()))
+    .or(more_articles(
+        app.store.clone(),
+        app.integrations.clone(),
+        app.render_cache.clone(),
+    ))
+    .or(next_unread(app.store.clone()))
+    .or(related_articles(app.store.clone()))
+    .or(article_page(app.store.clone()))
+    .or(sync_changes(app.store.clone()))
+    .or(list_articles(app.store.clone(), app.cursors.clone()))
+    .or(read_activity(app.store.clone()))
+    .or(toggle_read(app.store.clone()))
+    .or(toggle_favorite(app.store.clone()))
+    .or(get_articles(app.store.clone(), app.integrations.clone()))
+    .or(save_page(app.store.clone(), app.fetcher.clone()))
+    .or(mark_article_read(
+        app.store.clone(),
+        app.integrations.clone(),
+    ))
+    .or(mark_article_favorite(
+        app.store.clone(),
+        app.integrations.clone(),
+    ))
+    .or(unread_json(app.store.clone(), app.cursors.clone()))
+    .or(favorites_json(app.store.clone(), app.cursors.clone()))
+    .or(history_json(app.store.clone(), app.cursors.clone()))
+    .or(folder_json(app.store.clone(), app.cursors.clone()))
+    .or(feed_json(app.store.clone(), app.cursors.clone()))
+    .boxed();
+
+    let sharing = save_article_to_wallabag(app.store.clone(), app.integrations.clone())
+        .or(share_form(app.store.clone(), app.integrations.clone()))
+        .or(share_article(app.integrations.clone()))
+        .or(sync_pocket(app.store.clone(), app.integrations.clone()))
+        .or(sync_readwise(app.store.clone(), app.integrations.clone()))
+        .or(export_unread_epub(app.store.clone()))
+        .or(export_epub(app.store.clone()))
+        .or(export_history(app.store.clone()))
+        .or(send_to_kindle(app.store.clone(), app.kindle.clone()))
+        .boxed();
+
+    let feeds = create_feed(app.store.clone())
+        .or(feeds(app.store.clone()))
+        .or(delete_feed(app.store.clone()))
+        .or(add_feed(app.store.clone()))
+        .or(refresh_feed(app.clone()))
+        .or(recent_events(app.store.clone(), app.events.clone()))
+        .or(render_cache_stats(app.render_cache.clone()))
+        .or(new_articles_trigger(
+            app.store.clone(),
+            app.trigger_token.clone(),
+        ))
+        .or(debug_feed(
+            app.store.clone(),
+            app.fetcher.clone(),
+            app.pipeline.clone(),
+        ))
+        .or(feed(app.store.clone(), app.pipeline.clone()))
+        .or(save_feed_settings(app.store.clone(), app.pipeline.clone()))
+        .or(toggle_feed_stage(app.store.clone(), app.pipeline.clone()))
+        .boxed();
+
+    let extension = discover_feeds(
+        app.extension_token.clone(),
+        app.store.clone(),
+        app.fetcher.clone(),
+    )
+    .or(quick_save(
+        app.extension_token.clone(),
+        app.store.clone(),
+        app.fetcher.clone(),
+    ))
+    .or(unread_count(app.extension_token.clone(), app.store.clone()))
+    .or(receive_newsletter(
+        app.mailgun_key.clone(),
+        app.store.clone(),
+    ))
+    .boxed();
+
+    let admin = notifications(app.store.clone(), app.dispatcher.clone())
+        .or(create_notification_rule(
+            app.store.clone(),
+            app.dispatcher.clone(),
+        ))
+        .or(delete_notification_rule(app.store.clone()))
+        .or(create_mute_schedule(
+            app.store.clone(),
+            app.dispatcher.clone(),
+        ))
+        .or(delete_mute_schedule(app.store.clone()))
+        .or(scheduler_status(app.scheduler_state.clone()))
+        .or(toggle_scheduler_paused(
+            app.db_settings.clone(),
+            app.scheduler_state.clone(),
+        ))
+        .or(setup_page(app.store.clone(), app.scheduler_state.clone()))
+        .or(finish_setup(
+            app.store.clone(),
+            app.db_settings.clone(),
+            app.scheduler_state.clone(),
+        ))
+        .or(settings_page(
+            app.store.clone(),
+            app.db_settings.clone(),
+            app.scheduler_state.clone(),
+        ))
+        .or(save_settings(
+            app.store.clone(),
+            app.db_settings.clone(),
+            app.scheduler_state.clone(),
+        ))
+        .or(import_starred(app.store.clone()))
+        .or(set_theme(app.db_settings.clone()))
+        .or(sidebar(app.store.clone(), app.db_settings.clone()))
+        .or(toggle_category(app.store.clone(), app.db_settings.clone()))
+        .or(reload(
+            app.scheduler_state.clone(),
+            app.dispatcher.clone(),
+            app.db_settings.clone(),
+        ))
+        .boxed();
+
+    let folders = folders(app.store.clone())
+        .or(create_folder(app.store.clone()))
+        .or(delete_folder(app.store.clone()))
+        .or(folder(app.store.clone(), app.integrations.clone()))
+        .boxed();
+
+    let prefix = app
+        .base_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .fold(warp::any().boxed(), |prefix, segment| {
+            prefix.and(warp::path(segment.to_string())).boxed()
+        });
+    let security_headers = app.security_headers;
+    let store = app.store.clone();
+    let routes = prefix
+        .and(
+            status
+                .or(articles)
+                .or(sharing)
+                .or(feeds)
+                .or(admin)
+                .or(folders)
+                .or(extension)
+                .with(cors),
+        )
+        .map(|reply| Ok(Reply::into_response(reply)))
+        .or_else(|rejection| future::ok::<_, Rejection>((Err(rejection),)));
+    // a request answered from its idempotency key never reaches the routes, the routes themselves do not reject
+    let routes = idempotency::claim(app.store.clone())
+        .and(routes)
+        .or_else(|rejection| future::ok::<_, Rejection>((None, Err(rejection))));
+    // the method is taken before the routes run, the error page needs it to tell a wrong path from a wrong method
+    // the answer, error pages included, is kept for the idempotency key the request claimed
+    let answered = warp::method()
+        .and(routes)
+        .and_then(move |method, key, result| {
+            let store = store.clone();
+            async move {
+                let response = recover(method, result).await?;
+                Ok::<_, std::convert::Infallible>(idempotency::record(&store, key, response).await)
+            }
+        })
+        .map(move |reply| cache_control::apply(security_headers.apply(reply)));
+    idempotency::fingerprinted(answered, body_limit)
+}
+
+// body_limit is the largest body the route at path reads, every route but these takes a form smaller than the setup form
+fn body_limit(path: &str) -> u64 {
+    match path.strip_prefix(base_path()).unwrap_or(path) {
+        "/admin/import/starred" => MAX_STARRED_BYTES,
+        "/newsletters/mailgun" => MAX_MAIL_BYTES,
+        _ => MAX_SETUP_BYTES,
+    }
+}
+
+#[get("/healthz")]
+fn healthz() -> Json<Healthz> {
+    Healthz {
+        up: true,
+        version: VERSION,
+        git_sha: GIT_SHA,
+    }
+    .into()
+}
+
+#[derive(Deserialize, Serialize)]
+struct Readyz {
+    ready: bool,
+}
+
+// readyz fails while draining so kubernetes stops sending traffic before the server stops accepting it
+#[get("/readyz")]
+fn readyz(#[data] draining: shutdown::Draining) -> warp::reply::WithStatus<Json<Readyz>> {
+    let (ready, status) = match draining.is_draining() {
+        true => (false, http::StatusCode::SERVICE_UNAVAILABLE),
+        false => (true, http::StatusCode::OK),
+    };
+    warp::reply::with_status(Readyz { ready }.into(), status)
+}
+
+#[get("/version")]
+fn version() -> Json<Version> {
+    Version {
+        version: VERSION,
+        git_sha: GIT_SHA,
+        profile: env!("FEEDREADER_PROFILE"),
+    }
+    .into()
+}
+
+// static_assets serves the files embedded from static/, the macro routes only match a single segment so the tail is taken by hand
+fn static_assets() -> impl Filter<Extract = (http::Response<Vec<u8>>,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path("static"))
+        .and(warp::path::tail())
+        .and_then(|tail: warp::path::Tail| async move {
+            let asset = match assets::get(tail.as_str()) {
+                Some(asset) => asset,
+                None => return Err(warp::reject::not_found()),
+            };
+            let cache_control = match asset.immutable {
+                true => "public, max-age=31536000, immutable",
+                false => "no-cache",
+            };
+            http::Response::builder()
+                .header("Content-Type", asset.content_type)
+                .header("Cache-Control", cache_control)
+                .body(asset.body)
+                .map_err(|e| reject_anyhow(e.into()))
+        })
+}
+
+// manifest makes the reader installable, urls are absolute so they keep working under a base path
+#[get("/manifest.webmanifest")]
+async fn manifest() -> Result<http::Response<Vec<u8>>, Rejection> {
+    let base = base_path();
+    let icon = |size: &'static str| ManifestIcon {
+        src: asset(format!("icon-{}.png", size.split('x').next().unwrap_or(size)).as_str()),
+        sizes: size,
+        content_type: "image/png",
+    };
+    let manifest = Manifest {
+        name: "Feedreader",
+        short_name: "Feedreader",
+        start_url: format!("{}/", base),
+        scope: format!("{}/", base),
+        display: "standalone",
+        background_color: "#ffffff",
+        theme_color: "#f26522",
+        icons: vec![icon("192x192"), icon("512x512")],
+    };
+
+    let body = serde_json::to_vec(&manifest).map_err(|e| reject_anyhow(e.into()))?;
+    http::Response::builder()
+        .header("Content-Type", "application/manifest+json")
+        .body(body)
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+// custom_css is served as its own stylesheet rather than inlined, so nothing in it is ever parsed as html
+#[get("/custom.css")]
+async fn custom_css(#[data] settings: db::Settings) -> Result<http::Response<Vec<u8>>, Rejection> {
+    http::Response::builder()
+        .header("Content-Type", "text/css; charset=utf-8")
+        .header("Cache-Control", "no-cache")
+        .body(settings.custom_css().into_bytes())
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+// service_worker is served from the base path rather than /static so its scope covers every page
+#[get("/sw.js")]
+async fn service_worker() -> Result<http::Response<Vec<u8>>, Rejection> {
+    let base = base_path();
+    let mut precache: Vec<String> = assets::names()
+        .iter()
+        .map(|name| format!("{}/static/{}", base, name))
+        .collect();
+    precache.push(format!("{}/offline.html", base));
+
+    let body = ServiceWorkerTemplate {
+        version: format!("{}-{}", VERSION, GIT_SHA),
+        precache: serde_json::to_string(&precache).map_err(|e| reject_anyhow(e.into()))?,
+    }
+    .render()
+    .map_err(|e| reject_anyhow(e.into()))?;
+    http::Response::builder()
+        .header("Content-Type", "text/javascript; charset=utf-8")
+        .header("Cache-Control", "no-cache")
+        .body(body.into_bytes())
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+#[get("/offline.html")]
+fn offline() -> OfflineTemplate {
+    // the page is precached, counts would be stale by the time it is shown
+    OfflineTemplate {
+        counts: db::Counts::default(),
+    }
+}
+
+// render_cache_stats reports how many pages the render cache holds and how often it was hit, missed and evicted
+#[get("/api/render_cache")]
+async fn render_cache_stats(
+    #[data] cache: render_cache::Cache,
+) -> Result<Json<render_cache::Stats>, Rejection> {
+    Ok(cache.stats().into())
+}
+
+#[derive(Deserialize, Serialize)]
+struct EventsResponse {
+    unread: i64,
+    events: Vec<events::Event>,
+}
+
+// unread is the number home dashboards usually want, events are the most recent refreshes that found new articles
+#[get("/api/events")]
+async fn recent_events(
+    #[data] store: db::Storage,
+    #[data] events: events::Events,
+) -> Result<Json<EventsResponse>, Rejection> {
+    let unread = store.count_unread_articles().await.map_err(reject_anyhow)?;
+    Ok(EventsResponse {
+        unread,
+        events: events.recent().await,
+    }
+    .into())
+}
+
+// ACTIVITY_DAYS is how far back the activity endpoint goes, a year like a contribution graph
+const ACTIVITY_DAYS: i64 = 365;
+
+#[derive(Deserialize, Serialize)]
+struct Activity {
+    timezone: String,
+    days: Vec<ActivityDay>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ActivityDay {
+    date: String,
+    read: i64,
+}
+
+// read_activity is heatmap data, every day of the last year oldest first with the number of articles read on it
+// days are calendar days in the configured timezone and days without reads are included with a count of zero
+#[get("/api/v1/stats/activity")]
+async fn read_activity(#[data] store: db::Storage) -> Result<Json<Activity>, Rejection> {
+    let timezone = DATES.get_or_init(dates::Format::default).timezone();
+    let today = Utc::now().with_timezone(&timezone).date_naive();
+    let first = today - chrono::Duration::days(ACTIVITY_DAYS - 1);
+    // a day more than the range is read so the first local day is complete whatever the offset
+    let since = dates::stored(Utc::now() - chrono::Duration::days(ACTIVITY_DAYS + 1));
+
+    let read: HashMap<String, i64> = store
+        .get_read_activity(since, timezone.name().to_string())
+        .await
+        .map_err(reject_anyhow)?
+        .into_iter()
+        .collect();
+    let days = first
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .map(|day| {
+            let date = day.format("%Y-%m-%d").to_string();
+            ActivityDay {
+                read: read.get(&date).copied().unwrap_or(0),
+                date,
+            }
+        })
+        .collect();
+
+    Ok(Activity {
+        timezone: timezone.name().to_string(),
+        days,
+    }
+    .into())
+}
+
+fn trigger_query() -> impl Filter<Extract = (TriggerQuery,), Error = Rejection> + Clone {
+    warp::query::<TriggerQuery>()
+}
+
+// trigger_key accepts the token as a bearer token or the header ifttt sends
+fn trigger_key() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::header::optional::<String>("ifttt-service-key"))
+        .map(
+            |authorization: Option<String>, service_key: Option<String>| {
+                authorization
+                    .and_then(|value| value.strip_prefix("Bearer ").map(|token| token.to_string()))
+                    .or(service_key)
+            },
+        )
+}
+
+// mailgun_fields reads a mailgun post, a form unless the mail has attachments, then it is multipart and the attachments are skipped
+fn mailgun_fields() -> impl Filter<Extract = (HashMap<String, String>,), Error = Rejection> + Clone
+{
+    let multipart = warp::header::<String>("content-type")
+        .and_then(|content_type: String| async move {
+            match content_type
+                .to_ascii_lowercase()
+                .starts_with("multipart/form-data")
+            {
+                true => Ok(()),
+                false => Err(warp::reject()),
+            }
+        })
+        .untuple_one()
+        .and(warp::multipart::form().max_length(MAX_MAIL_BYTES))
+        .and_then(|form: warp::multipart::FormData| async move {
+            form.try_filter(|part| future::ready(part.filename().is_none()))
+                .and_then(|part| async move {
+                    let name = part.name().to_string();
+                    let value = part
+                        .stream()
+                        .try_fold(vec![], |mut value, chunk| async move {
+                            value.extend_from_slice(chunk.chunk());
+                            Ok(value)
+                        })
+                        .await?;
+                    Ok((name, String::from_utf8_lossy(&value).into_owned()))
+                })
+                .try_collect::<HashMap<String, String>>()
+                .await
+                .map_err(|e| reject_anyhow(e.into()))
+        });
+    let form = warp::body::content_length_limit(MAX_MAIL_BYTES).and(warp::body::form());
+    multipart.or(form).unify()
+}
+
+// receive_newsletter turns a mail a mailgun route forwards into an article under the pseudo-feed of its sender
+#[post("/newsletters/mailgun")]
+async fn receive_newsletter(
+    #[filter = "mailgun_fields"] fields: HashMap<String, String>,
+    #[data] key: MailgunKey,
+    #[data] store: db::Storage,
+) -> Result<Json<Article>, Rejection> {
+    let field = |name: &str| fields.get(name).map(String::as_str).unwrap_or_default();
+    let now = Utc::now().timestamp();
+    let verified = key.0.as_deref().is_some_and(|secret| {
+        newsletters::verify(
+            secret,
+            field("timestamp"),
+            field("token"),
+            field("signature"),
+            now,
+        ) && key.1.first(field("timestamp"), field("token"), now)
+    });
+    if !verified {
+        return Err(warp::reject::custom(Unauthorized));
+    }
+    let article = newsletters::receive(&store, newsletters::Mail::from_mailgun(&fields))
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(article.into())
+}
+
+fn discover_query() -> impl Filter<Extract = (DiscoverQuery,), Error = Rejection> + Clone {
+    warp::query::<DiscoverQuery>()
+}
+
+fn bearer_token() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization").map(|authorization: Option<String>| {
+        authorization.and_then(|value| value.strip_prefix("Bearer ").map(|token| token.to_string()))
+    })
+}
+
+fn authorize(token: &ExtensionToken, key: Option<String>) -> Result<(), Rejection> {
+    match (token.0.as_deref(), key) {
+        (Some(token), Some(key)) if same_secret(token, key.as_str()) => Ok(()),
+        _ => Err(warp::reject::custom(Unauthorized)),
+    }
+}
+
+// same_secret compares a configured token with the one a request sent in constant time, so how long it takes
+// does not tell how much of a guess was right. hmac's verify does the comparison
+fn same_secret(token: &str, sent: &str) -> bool {
+    use hmac::{Hmac, Mac};
+    let mac = |value: &str| {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(token.as_bytes())
+            .expect("hmac accepts keys of any length");
+        mac.update(value.as_bytes());
+        mac
+    };
+    mac(sent)
+        .verify_slice(&mac(token).finalize().into_bytes())
+        .is_ok()
+}
+
+// discover_feeds fetches the page the extension is on for the feeds it links to, a page that is a feed is returned itself
+#[get("/api/v1/discover")]
+async fn discover_feeds(
+    #[filter = "discover_query"] query: DiscoverQuery,
+    #[filter = "bearer_token"] key: Option<String>,
+    #[data] token: ExtensionToken,
+    #[data] store: db::Storage,
+    #[data] fetcher: fetch::Fetcher,
+) -> Result<Json<Discovery>, Rejection> {
+    authorize(&token, key)?;
+    if check_url(query.url.as_str()).is_err() {
+        return Err(warp::reject::custom(BadActionError()));
+    }
+
+    let feeds = store.get_all_feeds().await.map_err(reject_anyhow)?;
+    let same =
+        |a: &str, b: &str| !a.is_empty() && a.trim_end_matches('/') == b.trim_end_matches('/');
+    let subscribed = |url: &str| {
+        feeds
+            .iter()
+            .find(|f| same(f.feed_url.as_str(), url) || same(f.site_url.as_str(), url))
+            .map(|f| f.id.clone())
+    };
+    let mut discovery = Discovery {
+        feed_id: subscribed(query.url.as_str()),
+        url: query.url,
+        feeds: vec![],
+        error: None,
+    };
+
+    let found = match fetcher.send(discovery.url.as_str(), false).await {
+        Ok(fetched) => discover::feeds(fetched).await,
+        Err(e) => Err(e),
+    };
+    match found {
+        Ok(found) => {
+            discovery.feeds = found
+                .into_iter()
+                .map(|f| discover::Found {
+                    feed_id: subscribed(f.url.as_str()),
+                    ..f
+                })
+                .collect()
+        }
+        Err(e) => discovery.error = Some(e.to_string()),
+    }
+    Ok(discovery.into())
+}
+
+// quick_save keeps the page the extension is on as an unread article under saved pages
+#[post("/api/v1/save")]
+async fn quick_save(
+    #[json] mut page: SavePage,
+    #[filter = "bearer_token"] key: Option<String>,
+    #[data] token: ExtensionToken,
+    #[data] store: db::Storage,
+    #[data] fetcher: fetch::Fetcher,
+) -> Result<Json<Article>, Rejection> {
+    authorize(&token, key)?;
+    if !page.validate().is_empty() {
+        return Err(warp::reject::custom(BadActionError()));
+    }
+    let article = saved::save(&store, &fetcher, page.url.as_str(), page.title.as_str())
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(article.into())
+}
+
+// unread_count is the number the extension shows on its badge
+#[get("/api/v1/unread_count")]
+async fn unread_count(
+    #[filter = "bearer_token"] key: Option<String>,
+    #[data] token: ExtensionToken,
+    #[data] store: db::Storage,
+) -> Result<Json<UnreadCount>, Rejection> {
+    authorize(&token, key)?;
+    let unread = store.count_unread_articles().await.map_err(reject_anyhow)?;
+    Ok(UnreadCount { unread }.into())
+}
+
+// new_articles_trigger follows the polling trigger conventions of ifttt and zapier, newest first with a stable id per item
+#[get("/api/v1/triggers/new_articles")]
+async fn new_articles_trigger(
+    #[filter = "trigger_query"] query: TriggerQuery,
+    #[filter = "trigger_key"] key: Option<String>,
+    #[data] store: db::Storage,
+    #[data] token: TriggerToken,
+) -> Result<Json<TriggerResponse>, Rejection> {
+    match (token.0, key) {
+        (Some(token), Some(key)) if same_secret(&token, key.as_str()) => {}
+        _ => return Err(warp::reject::custom(Unauthorized)),
+    }
+
+    let since = match query.since {
+        Some(since) => DateTime::parse_from_rfc3339(since.as_str())
+            .map_err(|_| warp::reject::custom(BadActionError()))?
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Millis, true),
+        None => "".to_string(),
+    };
+
+    // a limit outside what a page holds is clamped to it rather than refused, trigger services pick their own
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_TRIGGER_LIMIT)
+        .clamp(1, db::MAX_LIMIT as i64);
+    let articles = store
+        .get_new_articles(since, limit)
+        .await
+        .map_err(reject_anyhow)?;
+
+    let data = articles
+        .into_iter()
+        .map(|new| TriggerItem {
+            meta: TriggerMeta {
+                id: new.article.id.clone(),
+                timestamp: DateTime::parse_from_rfc3339(new.added.as_str())
+                    .map(|added| added.timestamp())
+                    .unwrap_or_default(),
+            },
+            id: new.article.id,
+            feed: new.article.feed,
+            title: new.article.title,
+            link: new.article.link,
+            author: new.article.author,
+            published: new.article.published,
+            added: new.added,
+        })
+        .collect();
+
+    Ok(TriggerResponse { data }.into())
+}
+
+#[get("/admin/scheduler")]
+async fn scheduler_status(
+    #[data] state: scheduler::State,
+) -> Result<Json<scheduler::Status>, Rejection> {
+    Ok(state.status().await.into())
+}
+
+#[post("/admin/scheduler/pause")]
+async fn toggle_scheduler_paused(
+    #[data] settings: db::Settings,
+    #[data] state: scheduler::State,
+) -> Result<Json<scheduler::Status>, Rejection> {
+    let paused = !state.paused().await;
+    settings
+        .set(scheduler::PAUSED_SETTING, paused.to_string())
+        .await
+        .map_err(reject_anyhow)?;
+    state.set_paused(paused).await;
+
+    Ok(state.status().await.into())
+}
+
+async fn settings_template(
+    store: &db::Storage,
+    settings: &db::Settings,
+    state: &scheduler::State,
+    saved: bool,
+) -> anyhow::Result<SettingsTemplate> {
+    Ok(SettingsTemplate {
+        counts: store.get_counts().await?,
+        refresh_seconds: refresh_seconds(state).await,
+        retention_days: settings.retention_days(),
+        page_size: settings.page_size(),
+        theme: settings.theme(),
+        themes: db::THEMES.iter().map(|t| t.to_string()).collect(),
+        density: settings.density(),
+        densities: db::DENSITIES.iter().map(|d| d.to_string()).collect(),
+        group_by_day: settings.group_by_day(),
+        language: settings.language(),
+        languages: I18N
+            .get()
+            .map(|catalog| {
+                catalog
+                    .languages()
+                    .into_iter()
+                    .map(|l| {
+                        let name = catalog.message(l.as_str(), "language-name", None);
+                        (l, name)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        custom_css: settings.custom_css(),
+        saved,
+    })
+}
+
+#[get("/settings.html")]
+async fn settings_page(
+    #[data] store: db::Storage,
+    #[data] settings: db::Settings,
+    #[data] state: scheduler::State,
+) -> Result<SettingsTemplate, Rejection> {
+    settings_template(&store, &settings, &state, false)
+        .await
+        .map_err(reject_anyhow)
+}
+
+// save_settings validates every field before writing any of them
+#[post("/settings")]
+async fn save_settings(
+    #[form] form: SaveSettings,
+    #[data] store: db::Storage,
+    #[data] settings: db::Settings,
+    #[data] state: scheduler::State,
+) -> Result<SettingsTemplate, Rejection> {
+    let bad = || warp::reject::custom(BadActionError());
+
+    let refresh_seconds = match form.refresh_seconds.trim() {
+        "" => None,
+        value => match value.parse::<u64>() {
+            Ok(seconds) if seconds > 0 => Some(seconds),
+            _ => return Err(bad()),
+        },
+    };
+    let retention_days = match form.retention_days.trim().parse::<i64>() {
+        Ok(days) if days >= 0 => days,
+        _ => return Err(bad()),
+    };
+    let page_size = match form.page_size.trim().parse::<usize>() {
+        Ok(size) if (1..=db::MAX_LIMIT).contains(&size) => size,
+        _ => return Err(bad()),
+    };
+    if !db::THEMES.contains(&form.theme.as_str()) {
+        return Err(bad());
+    }
+    if !db::DENSITIES.contains(&form.density.as_str()) {
+        return Err(bad());
+    }
+    if !I18N
+        .get()
+        .map(|catalog| catalog.contains(form.language.as_str()))
+        .unwrap_or(false)
+    {
+        return Err(bad());
+    }
+    if form.custom_css.len() > db::MAX_CUSTOM_CSS {
+        return Err(warp::reject::custom(BadActionError()));
+    }
+
+    if let Some(seconds) = refresh_seconds {
+        settings
+            .set(db::REFRESH_SECONDS, seconds.to_string())
+            .await
+            .map_err(reject_anyhow)?;
+        state
+            .set_schedule(scheduler::Schedule::Interval(seconds))
+            .await;
+    }
+    settings
+        .set(db::RETENTION_DAYS, retention_days.to_string())
+        .await
+        .map_err(reject_anyhow)?;
+    settings
+        .set(db::PAGE_SIZE, page_size.to_string())
+        .await
+        .map_err(reject_anyhow)?;
+    settings
+        .set(db::THEME, form.theme)
+        .await
+        .map_err(reject_anyhow)?;
+    settings
+        .set(db::LANGUAGE, form.language)
+        .await
+        .map_err(reject_anyhow)?;
+    settings
+        .set(db::DENSITY, form.density)
+        .await
+        .map_err(reject_anyhow)?;
+    settings
+        .set(db::GROUP_BY_DAY, form.group_by_day.is_some().to_string())
+        .await
+        .map_err(reject_anyhow)?;
+    settings
+        .set(db::CUSTOM_CSS, form.custom_css)
+        .await
+        .map_err(reject_anyhow)?;
+
+    settings_template(&store, &settings, &state, true)
+        .await
+        .map_err(reject_anyhow)
+}
+
+fn referer() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("referer")
+}
+
+fn host() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("host")
+}
+
+// back_to is the path of a referer on this server under the base path, where a form can send the browser back to
+// anything else, another site or a page outside the prefix, is None so a forged referer cannot redirect elsewhere
+fn back_to(referer: &str, host: Option<&str>) -> Option<String> {
+    let base = reqwest::Url::parse("http://relative.invalid").ok()?;
+    let url = match reqwest::Url::parse(referer) {
+        Ok(url) => {
+            let origin = match url.port() {
+                Some(port) => format!("{}:{}", url.host_str()?, port),
+                None => url.host_str()?.to_string(),
+            };
+            if !matches!(url.scheme(), "http" | "https") || Some(origin.as_str()) != host {
+                return None;
+            }
+            url
+        }
+        Err(_) if referer.starts_with('/') && !referer.starts_with("//") => {
+            base.join(referer).ok()?
+        }
+        Err(_) => return None,
+    };
+
+    let path = url.path();
+    let prefix = base_path();
+    if !path.starts_with(prefix) || !path[prefix.len()..].starts_with('/') {
+        return None;
+    }
+    Some(match url.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    })
+}
+
+// set_theme sends the browser back to the page the picker was submitted from
+#[post("/preferences/theme")]
+async fn set_theme(
+    #[form] preference: ThemePreference,
+    #[data] settings: db::Settings,
+    #[filter = "referer"] referer: Option<String>,
+    #[filter = "host"] host: Option<String>,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    if !db::THEMES.contains(&preference.theme.as_str()) {
+        return Err(warp::reject::custom(BadActionError()));
+    }
+
+    settings
+        .set(db::THEME, preference.theme)
+        .await
+        .map_err(reject_anyhow)?;
+
+    http::Response::builder()
+        .status(http::StatusCode::SEE_OTHER)
+        .header(
+            "Location",
+            referer
+                .and_then(|referer| back_to(referer.as_str(), host.as_deref()))
+                .unwrap_or_else(|| format!("{}/", base_path())),
+        )
+        .body(vec![])
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+async fn sidebar_template(
+    store: &db::Storage,
+    settings: &db::Settings,
+) -> anyhow::Result<SidebarTemplate> {
+    let feeds = store.get_sidebar_feeds().await?;
+    let mut folders = vec![];
+    for folder in store.get_smart_folders().await? {
+        let unread = store
+            .count_folder_unread(&query::parse(folder.expression.as_str())?)
+            .await?;
+        folders.push(SidebarFolder {
+            id: folder.id,
+            name: folder.name,
+            unread,
+        });
+    }
+    Ok(SidebarTemplate {
+        folders,
+        categories: categories(feeds, settings.collapsed_categories().as_slice()),
+    })
+}
+
+#[get("/sidebar")]
+async fn sidebar(
+    #[data] store: db::Storage,
+    #[data] settings: db::Settings,
+) -> Result<SidebarTemplate, Rejection> {
+    sidebar_template(&store, &settings)
+        .await
+        .map_err(reject_anyhow)
+}
+
+// toggle_category flips whether a category is collapsed and renders the sidebar again
+#[post("/preferences/sidebar")]
+async fn toggle_category(
+    #[form] preference: CategoryPreference,
+    #[data] store: db::Storage,
+    #[data] settings: db::Settings,
+) -> Result<SidebarTemplate, Rejection> {
+    let mut collapsed = settings.collapsed_categories();
+    match collapsed.iter().position(|c| *c == preference.category) {
+        Some(i) => {
+            collapsed.remove(i);
+        }
+        None => collapsed.push(preference.category),
+    }
+
+    let value = serde_json::to_string(&collapsed).map_err(|e| reject_anyhow(e.into()))?;
+    settings
+        .set(db::COLLAPSED_CATEGORIES, value)
+        .await
+        .map_err(reject_anyhow)?;
+
+    sidebar_template(&store, &settings)
+        .await
+        .map_err(reject_anyhow)
+}
+
+#[derive(Deserialize, Serialize)]
+struct ReloadResult {
+    reloaded: bool,
+    errors: Vec<String>,
+}
+
+// reload re-reads the config file, only the refresh schedule and notification targets change without a restart
+// an interval saved from the settings page is kept over the configured schedule
+#[post("/admin/reload")]
+async fn reload(
+    #[data] state: scheduler::State,
+    #[data] dispatcher: notify::Dispatcher,
+    #[data] db_settings: db::Settings,
+) -> Result<Json<ReloadResult>, Rejection> {
+    let settings = match config::Settings::load(CONFIG.get().map(|path| path.as_path())) {
+        Ok(settings) => settings,
+        Err(errors) => {
+            return Ok(ReloadResult {
+                reloaded: false,
+                errors,
+            }
+            .into())
+        }
+    };
+
+    let mailer = match settings.smtp.map(mail::Mailer::new) {
+        Some(Ok(mailer)) => Some(mailer),
+        Some(Err(e)) => {
+            return Ok(ReloadResult {
+                reloaded: false,
+                errors: vec![format!("could not build the smtp client: {}", e)],
+            }
+            .into())
+        }
+        None => None,
+    };
+
+    let outbound = match fetch::outbound(settings.fetch.proxy.as_deref()) {
+        Ok(outbound) => outbound,
+        Err(e) => {
+            return Ok(ReloadResult {
+                reloaded: false,
+                errors: vec![format!("could not build the outbound client: {}", e)],
+            }
+            .into())
+        }
+    };
+
+    dispatcher.replace(notifiers(outbound, mailer));
+    if db_settings.refresh_seconds().is_none() {
+        state.set_schedule(settings.schedule).await;
+    }
+    println!("reloaded configuration");
+
+    Ok(ReloadResult {
+        reloaded: true,
+        errors: vec![],
+    }
+    .into())
+}
+
+#[derive(Deserialize, Serialize)]
+struct SyncResult {
+    synced: usize,
+}
+
+#[get("/articles/{article_id}/share")]
+async fn share_form(
+    article_id: String,
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+) -> Result<ShareTemplate, Rejection> {
+    let article = store
+        .get_article_by_id(article_id)
+        .await
+        .map_err(reject_anyhow)?;
+
+    Ok(ShareTemplate {
+        article_id: article.id,
+        services: integrations.share_services(),
+        text: format!("{}\n\n{}", article.title, article.link),
+        posted: None,
+    })
+}
+
+#[post("/articles/{article_id}/share")]
+async fn share_article(
+    article_id: String,
+    #[form] share: ShareArticle,
+    #[data] integrations: integrations::Integrations,
+) -> Result<ShareTemplate, Rejection> {
+    let url = integrations
+        .share(share.service.as_str(), share.text.as_str())
+        .await
+        .map_err(reject_anyhow)?;
+
+    Ok(ShareTemplate {
+        article_id,
+        services: integrations.share_services(),
+        text: share.text,
+        posted: Some(url),
+    })
+}
+
+#[post("/admin/pocket/sync")]
+async fn sync_pocket(
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+) -> Result<Json<SyncResult>, Rejection> {
+    let pocket = match integrations.pocket {
+        Some(pocket) => pocket,
+        None => {
+            return Err(reject_anyhow(anyhow::Error::msg(
+                "pocket is not configured",
+            )))
+        }
+    };
+
+    let synced = pocket.sync(&store).await.map_err(reject_anyhow)?;
+    Ok(SyncResult { synced }.into())
+}
+
+#[post("/admin/readwise/sync")]
+async fn sync_readwise(
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+) -> Result<Json<SyncResult>, Rejection> {
+    let readwise = match integrations.readwise {
+        Some(readwise) => readwise,
+        None => {
+            return Err(reject_anyhow(anyhow::Error::msg(
+                "readwise is not configured",
+            )))
+        }
+    };
+
+    let synced = readwise.sync(&store).await.map_err(reject_anyhow)?;
+    Ok(SyncResult { synced }.into())
+}
+
+fn starred_body() -> impl Filter<Extract = (Bytes,), Error = Rejection> + Clone {
+    warp::body::content_length_limit(MAX_STARRED_BYTES).and(warp::body::bytes())
+}
+
+// import_starred takes the json of a google reader takeout or the old reader starred export as the body
+#[post("/admin/import/starred")]
+async fn import_starred(
+    #[filter = "starred_body"] body: Bytes,
+    #[data] store: db::Storage,
+) -> Result<Json<import::Imported>, Rejection> {
+    let document = String::from_utf8_lossy(&body);
+    let imported = import::starred(&store, &document)
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(imported.into())
+}
+
+fn epub_response(articles: Vec<Article>) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let title = epub::title();
+    let book = epub::bundle(title.as_str(), articles).map_err(reject_anyhow)?;
+    http::Response::builder()
+        .header("Content-Type", epub::CONTENT_TYPE)
+        .header(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{}.epub\"",
+                title.replace(' ', "-").to_lowercase()
+            ),
+        )
+        .body(book)
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+#[get("/export/unread.epub")]
+async fn export_unread_epub(
+    #[data] store: db::Storage,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let articles = store
+        .get_digest_articles(epub::EPUB_LIMIT)
+        .await
+        .map_err(reject_anyhow)?;
+    epub_response(articles)
+}
+
+#[post("/export/epub")]
+async fn export_epub(
+    #[form] selection: ExportArticles,
+    #[data] store: db::Storage,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let ids = selection.ids();
+    let articles = match ids.is_empty() {
+        true => store.get_digest_articles(epub::EPUB_LIMIT).await,
+        false => store.get_articles_by_ids(ids).await,
+    }
+    .map_err(reject_anyhow)?;
+    epub_response(articles)
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryQuery {
+    // from is inclusive and to exclusive, both take a date or an rfc3339 time
+    from: Option<String>,
+    to: Option<String>,
+    format: Option<String>,
+}
+
+fn history_query() -> impl Filter<Extract = (HistoryQuery,), Error = Rejection> + Clone {
+    warp::query::<HistoryQuery>()
+}
+
+// export_history streams the read articles in the range newest first, jsonl unless csv is asked for
+// each chunk is queried once the one before it was sent, so a long history is never held in memory
+#[get("/export/history")]
+async fn export_history(
+    #[filter = "history_query"] query: HistoryQuery,
+    #[data] store: db::Storage,
+) -> Result<http::Response<rweb::hyper::Body>, Rejection> {
+    let format = match query.format {
+        Some(format) => crate::history::Format::from_str(format.as_str())
+            .map_err(|_| warp::reject::custom(BadActionError()))?,
+        None => crate::history::Format::default(),
+    };
+    // a date without a time is a day in the configured timezone, the way the activity stats count days, and to is
+    // exclusive so a date there is read as the start of the day after and the range takes the whole of it
+    let timezone = DATES.get_or_init(dates::Format::default).timezone();
+    let bound = |value: Option<String>, default: &str, end: bool| match value {
+        Some(value) => match chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+            Ok(day) => match end {
+                true => day.succ_opt(),
+                false => Some(day),
+            }
+            .and_then(|day| dates::day_start(day, timezone))
+            .map(dates::stored)
+            .ok_or_else(|| warp::reject::custom(BadActionError())),
+            Err(_) => match dates::parse_published(value.as_str()) {
+                Some(dt) => Ok(dates::stored(dt)),
+                None => Err(warp::reject::custom(BadActionError())),
+            },
+        },
+        None => Ok(default.to_string()),
+    };
+    let from = bound(query.from, "", false)?;
+    let to = bound(query.to, db::MAX_DATE, true)?;
+
+    let chunks = stream::unfold(Some((to, "".to_string())), move |cursor| {
+        let store = store.clone();
+        let from = from.clone();
+        async move {
+            let (before, before_id) = cursor?;
+            let entries = match store
+                .get_read_history(from, before, before_id, crate::history::CHUNK_SIZE)
+                .await
+            {
+                Ok(entries) => entries,
+                Err(e) => {
+                    report::error(&e);
+                    return Some((Err(e), None));
+                }
+            };
+            let next = match entries.len() as i64 == crate::history::CHUNK_SIZE {
+                true => entries.last().map(|e| (e.read_date.clone(), e.id.clone())),
+                false => None,
+            };
+            Some((format.write(entries.as_slice()).map(Bytes::from), next))
+        }
+    });
+    let header = Bytes::from_static(format.header().as_bytes());
+    let body = stream::once(future::ok::<_, anyhow::Error>(header)).chain(chunks);
+
+    http::Response::builder()
+        .header("Content-Type", format.content_type())
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"history.{}\"", format.extension()),
+        )
+        .body(rweb::hyper::Body::wrap_stream(body))
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+#[derive(Deserialize, Serialize)]
+struct KindleResult {
+    sent: usize,
+}
+
+#[post("/export/kindle")]
+async fn send_to_kindle(
+    #[form] selection: ExportArticles,
+    #[data] store: db::Storage,
+    #[data] kindle: Option<epub::Kindle>,
+) -> Result<Json<KindleResult>, Rejection> {
+    let kindle = match kindle {
+        Some(kindle) => kindle,
+        None => {
+            return Err(reject_anyhow(anyhow::Error::msg(
+                "send to kindle is not configured",
+            )))
+        }
+    };
+
+    let ids = selection.ids();
+    let articles = match ids.is_empty() {
+        true => vec![],
+        false => store
+            .get_articles_by_ids(ids)
+            .await
+            .map_err(reject_anyhow)?,
+    };
+    let sent = kindle.send(&store, articles).await.map_err(reject_anyhow)?;
+    Ok(KindleResult { sent }.into())
+}
+
+// index opens on the setup page until it was finished or skipped, an instance that already has feeds never sees it
+#[get("/")]
+async fn index(
+    #[data] store: db::Storage,
+    #[data] settings: db::Settings,
+    #[data] integrations: integrations::Integrations,
+    #[data] cache: render_cache::Cache,
+) -> Result<warp::reply::Response, Rejection> {
+    if !settings.setup_complete() && store.get_counts().await.map_err(reject_anyhow)?.feeds == 0 {
+        return http::Response::builder()
+            .status(http::StatusCode::SEE_OTHER)
+            .header("Location", format!("{}/setup.html", base_path()))
+            .body(vec![])
+            .map(Reply::into_response)
+            .map_err(|e| reject_anyhow(e.into()));
+    }
+
+    cached(&cache, &store, "unread".to_string(), async {
+        let (counts, page) =
+            with_counts(&store, store.get_unread_articles(db::MAX_DATE.to_string())).await?;
+        Ok(ArticleBaseTemplate {
+            empty: Empty::of(&counts, page.items.len(), db::Filter::Unread),
+            counts,
+            title: tr("page-unread"),
+            article_filter: db::Filter::Unread.to_string(),
+            cursor: page.cursor,
+            days: days(page.items.iter().map(|r| r.into()).collect()),
+            density: density(),
+            integrations: integrations.enabled(),
+        })
+    })
+    .await
+    .map(Reply::into_response)
+    .map_err(reject_anyhow)
+}
+
+fn image_query() -> impl Filter<Extract = (ImageQuery,), Error = Rejection> + Clone {
+    warp::query::<ImageQuery>()
+}
+
+// proxy_image only serves urls signed by this instance so it cannot be used as an open proxy
+#[get("/proxy/image")]
+async fn proxy_image(
+    #[filter = "image_query"] query: ImageQuery,
+    #[data] proxy: images::Proxy,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    if !proxy.verify(query.url.as_str(), query.sig.as_str()) {
+        return http::Response::builder()
+            .status(http::StatusCode::FORBIDDEN)
+            .body(vec![])
+            .map_err(|e| reject_anyhow(e.into()));
+    }
+
+    let image = proxy.get(query.url.as_str()).await.map_err(reject_anyhow)?;
+
+    // svg can carry script, the policy keeps it inert when the image is opened directly
+    http::Response::builder()
+        .header("Content-Type", image.content_type)
+        .header("Cache-Control", "public, max-age=604800, immutable")
+        .header("X-Content-Type-Options", "nosniff")
+        .header("Content-Security-Policy", "default-src 'none'; sandbox")
+        .body(image.body.as_ref().clone())
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+fn search_query() -> impl Filter<Extract = (SearchQuery,), Error = Rejection> + Clone {
+    warp::query::<SearchQuery>()
+}
+
+// article_page shows one article with the ones related to it, the path has one segment after /articles
+// so it does not take the requests of /articles/more or the actions on an article
+#[get("/articles/{id}")]
+async fn article_page(
+    id: String,
+    #[data] store: db::Storage,
+) -> Result<ArticlePageTemplate, Rejection> {
+    let (counts, article, body) = futures::try_join!(
+        store.get_counts(),
+        store.get_article_by_id(id.clone()),
+        store.get_newsletter_body(id)
+    )
+    .map_err(reject_anyhow)?;
+    Ok(ArticlePageTemplate {
+        counts,
+        article,
+        body: body
+            .split("\n\n")
+            .map(str::to_string)
+            .filter(|p| !p.is_empty())
+            .collect(),
+    })
+}
+
+#[get("/articles/{id}/related")]
+async fn related_articles(
+    id: String,
+    #[data] store: db::Storage,
+) -> Result<RelatedTemplate, Rejection> {
+    store
+        .get_article_by_id(id.clone())
+        .await
+        .map_err(reject_anyhow)?;
+    let articles = store
+        .get_related_articles(id, RELATED_LIMIT)
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(RelatedTemplate { articles })
+}
+
+#[get("/search")]
+async fn search(
+    #[filter = "search_query"] params: SearchQuery,
+    #[data] store: db::Storage,
+) -> Result<SearchTemplate, Rejection> {
+    let query = params.q.unwrap_or_default().trim().to_string();
+    if query.is_empty() {
+        return Ok(SearchTemplate {
+            counts: store.get_counts().await.map_err(reject_anyhow)?,
+            query,
+            results: vec![],
+            prev: None,
+            next: None,
+        });
+    }
+
+    let pagination = params
+        .pagination
+        .unwrap_or_else(|| db::MAX_DATE.to_string());
+    let (counts, page) = with_counts(&store, store.search_articles(query.clone(), pagination))
+        .await
+        .map_err(reject_anyhow)?;
+
+    // page links carry the query along with the cursor so they work without htmx
+    let href = |pagination: &str| {
+        let mut url = reqwest::Url::parse("http://feedreader/search").unwrap();
+        url.query_pairs_mut()
+            .append_pair("q", query.as_str())
+            .append_pair("pagination", pagination);
+        format!("{}/search?{}", base_path(), url.query().unwrap_or_default())
+    };
+
+    Ok(SearchTemplate {
+        counts,
+        prev: page
+            .cursor
+            .has_prev
+            .then(|| href(page.cursor.prev.as_str())),
+        next: page
+            .cursor
+            .has_next
+            .then(|| href(page.cursor.next.as_str())),
+        results: page.items.iter().map(|r| r.into()).collect(),
+        query,
+    })
+}
+
+#[get("/favorites.html")]
+async fn favorites(
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+    #[data] cache: render_cache::Cache,
+) -> Result<warp::reply::Html<Bytes>, Rejection> {
+    cached(&cache, &store, "favorites".to_string(), async {
+        let (counts, page) = with_counts(
+            &store,
+            store.get_favorited_articles(db::MAX_DATE.to_string()),
+        )
+        .await?;
+        Ok(ArticleBaseTemplate {
+            empty: Empty::of(&counts, page.items.len(), db::Filter::Favorite),
+            counts,
+            cursor: page.cursor,
+            title: tr("page-favorites"),
+            article_filter: db::Filter::Favorite.to_string(),
+            days: days(page.items.iter().map(|r| r.into()).collect()),
+            density: density(),
+            integrations: integrations.enabled(),
+        })
+    })
+    .await
+    .map_err(reject_anyhow)
+}
+
+#[get("/history.html")]
+async fn history(
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+) -> Result<ArticleBaseTemplate, Rejection> {
+    let (counts, page) = with_counts(&store, store.get_read_articles(db::MAX_DATE.to_string()))
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(ArticleBaseTemplate {
+        empty: Empty::of(&counts, page.items.len(), db::Filter::Read),
+        counts,
+        cursor: page.cursor,
+        title: tr("page-history"),
+        article_filter: db::Filter::Read.to_string(),
+        days: days(page.items.iter().map(|r| r.into()).collect()),
+        density: density(),
+        integrations: integrations.enabled(),
+    })
+}
+
+// trash_list is the most recently pruned articles with how many there are in all
+async fn trash_list(store: &db::Storage) -> anyhow::Result<TrashListTemplate> {
+    Ok(TrashListTemplate {
+        trashed: store.get_trash(TRASH_LIMIT).await?,
+        total: store.count_trash().await? as usize,
+    })
+}
+
+#[get("/trash.html")]
+async fn trash(#[data] store: db::Storage) -> Result<TrashTemplate, Rejection> {
+    let counts = store.get_counts().await.map_err(reject_anyhow)?;
+    let list = trash_list(&store).await.map_err(reject_anyhow)?;
+    Ok(TrashTemplate {
+        counts,
+        trashed: list.trashed,
+        total: list.total,
+    })
+}
+
+// restore_article puts a pruned article back as it was when it was pruned, read and with its id, and shows the trash again
+#[post("/trash/{id}/restore")]
+async fn restore_article(
+    id: String,
+    #[data] store: db::Storage,
+) -> Result<TrashListTemplate, Rejection> {
+    store.restore_article(id).await.map_err(reject_anyhow)?;
+    trash_list(&store).await.map_err(reject_anyhow)
+}
+
+#[get("/folders.html")]
+async fn folders(#[data] store: db::Storage) -> Result<FoldersTemplate, Rejection> {
+    Ok(FoldersTemplate {
+        counts: store.get_counts().await.map_err(reject_anyhow)?,
+        folders: store.get_smart_folders().await.map_err(reject_anyhow)?,
+        form: AddSmartFolder::default(),
+        error: "".to_string(),
+    })
+}
+
+// create_folder saves a folder once its expression parses, otherwise the form is shown again with what is wrong
+#[post("/folders")]
+async fn create_folder(
+    #[form] mut form: AddSmartFolder,
+    #[data] store: db::Storage,
+) -> Result<warp::reply::Response, Rejection> {
+    form.name = plain_text(form.name.as_str());
+    form.expression = form.expression.trim().to_string();
+    let problem = match query::parse(form.expression.as_str()) {
+        _ if form.name.is_empty() => Some(tr("folder-name-required")),
+        Ok(_) => None,
+        Err(e) => Some(tr_with("folder-expression-invalid", "error", e.to_string())),
+    };
+
+    if let Some(error) = problem {
+        let page = FoldersTemplate {
+            counts: store.get_counts().await.map_err(reject_anyhow)?,
+            folders: store.get_smart_folders().await.map_err(reject_anyhow)?,
+            form,
+            error,
+        };
+        return Ok(warp::reply::with_status(page, http::StatusCode::BAD_REQUEST).into_response());
+    }
+
+    store.add_smart_folder(form).await.map_err(reject_anyhow)?;
+    Ok(FoldersTemplate {
+        counts: store.get_counts().await.map_err(reject_anyhow)?,
+        folders: store.get_smart_folders().await.map_err(reject_anyhow)?,
+        form: AddSmartFolder::default(),
+        error: "".to_string(),
+    }
+    .into_response())
+}
+
+#[delete("/folders/{id}")]
+async fn delete_folder(
+    id: String,
+    #[data] store: db::Storage,
+) -> Result<FolderListTemplate, Rejection> {
+    store.delete_smart_folder(id).await.map_err(reject_anyhow)?;
+    Ok(FolderListTemplate {
+        folders: store.get_smart_folders().await.map_err(reject_anyhow)?,
+    })
+}
+
+fn json_feed_query() -> impl Filter<Extract = (JsonFeedQuery,), Error = Rejection> + Clone {
+    warp::query::<JsonFeedQuery>()
+}
+
+// origin is the configured public url, or else the scheme and host the request was sent to, json feeds need absolute urls
+// a forwarded proto other than http or https is not a scheme a feed reader could follow and is taken as http
+fn origin() -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("host")
+        .and(warp::header::optional::<String>("x-forwarded-proto"))
+        .map(|host: Option<String>, proto: Option<String>| {
+            if let Some(public_url) = PUBLIC_URL.get() {
+                return public_url.clone();
+            }
+            let proto = match proto.as_deref().map(str::trim) {
+                Some("https") => "https",
+                _ => "http",
+            };
+            match host {
+                Some(host) => format!("{}://{}", proto, host),
+                None => "".to_string(),
+            }
+        })
+}
+
+// JsonFeedView is a list served as a json feed, page is where the same list is shown in the reader
+struct JsonFeedView {
+    filter: db::Filter,
+    title: String,
+    page: String,
+    feed: String,
+}
+
+// json_feed serves a page of a list as a json feed, next_url continues from the last article like the more loader does
+async fn json_feed(
+    store: &db::Storage,
+    cursors: &cursor::Signer,
+    view: JsonFeedView,
+    origin: String,
+    query: JsonFeedQuery,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let pagination = match query.cursor {
+        Some(token) => cursors
+            .resume(token.as_str(), &view.filter)
+            .map_err(|_| warp::reject::custom(BadActionError()))?,
+        None => db::MAX_DATE.to_string(),
+    };
+    let page = store
+        .clone()
+        .filter(view.filter.clone(), pagination)
+        .await
+        .map_err(reject_anyhow)?;
+    let articles: Vec<Article> = page.items.iter().map(|r| r.into()).collect();
+
+    let feed_url = format!("{}{}{}", origin, base_path(), view.feed);
+    let (next, _) = cursors.cursors(&view.filter, &page.cursor);
+    let next_url = next.map(|token| {
+        let mut url = reqwest::Url::parse("http://feedreader/").unwrap();
+        url.query_pairs_mut().append_pair("cursor", token.as_str());
+        format!("{}?{}", feed_url, url.query().unwrap_or_default())
+    });
+    let feed = jsonfeed::Feed::new(
+        view.title,
+        format!("{}{}{}", origin, base_path(), view.page),
+        feed_url,
+        next_url,
+        &articles,
+        origin.as_str(),
+    );
+    http::Response::builder()
+        .header("Content-Type", jsonfeed::CONTENT_TYPE)
+        .body(serde_json::to_vec(&feed).map_err(|e| reject_anyhow(e.into()))?)
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+fn articles_query() -> impl Filter<Extract = (ArticlesQuery,), Error = Rejection> + Clone {
+    warp::query::<ArticlesQuery>()
+}
+
+// list_articles pages through a list for api clients, the cursors are signed so clients can't build or alter them
+#[get("/api/v1/articles")]
+async fn list_articles(
+    #[filter = "articles_query"] query: ArticlesQuery,
+    #[data] store: db::Storage,
+    #[data] cursors: cursor::Signer,
+) -> Result<Json<ArticlesPage>, Rejection> {
+    let bad = || warp::reject::custom(BadActionError());
+    let position = match query.cursor.as_deref() {
+        Some(token) => Some(cursors.verify(token).map_err(|_| bad())?),
+        None => None,
+    };
+    let filter = match (query.filter.as_deref(), &position) {
+        (Some(filter), _) => filter,
+        (None, Some(position)) => position.filter.as_str(),
+        (None, None) => "unread",
+    };
+    let filter = db::Filter::from_str(filter).map_err(|_| bad())?;
+    let pagination = match query.cursor.as_deref() {
+        Some(token) => cursors.resume(token, &filter).map_err(|_| bad())?,
+        None => db::MAX_DATE.to_string(),
+    };
+
+    let page = store
+        .clone()
+        .filter(filter.clone(), pagination)
+        .await
+        .map_err(reject_anyhow)?;
+    let (next, prev) = cursors.cursors(&filter, &page.cursor);
+    Ok(ArticlesPage {
+        articles: page.items.iter().map(|r| r.into()).collect(),
+        next,
+        prev,
+    }
+    .into())
+}
+
+#[get("/unread.json")]
+async fn unread_json(
+    #[filter = "json_feed_query"] query: JsonFeedQuery,
+    #[filter = "origin"] origin: String,
+    #[data] store: db::Storage,
+    #[data] cursors: cursor::Signer,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let view = JsonFeedView {
+        filter: db::Filter::Unread,
+        title: tr("page-unread"),
+        page: "/".to_string(),
+        feed: "/unread.json".to_string(),
+    };
+    json_feed(&store, &cursors, view, origin, query).await
+}
+
+#[get("/favorites.json")]
+async fn favorites_json(
+    #[filter = "json_feed_query"] query: JsonFeedQuery,
+    #[filter = "origin"] origin: String,
+    #[data] store: db::Storage,
+    #[data] cursors: cursor::Signer,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let view = JsonFeedView {
+        filter: db::Filter::Favorite,
+        title: tr("page-favorites"),
+        page: "/favorites.html".to_string(),
+        feed: "/favorites.json".to_string(),
+    };
+    json_feed(&store, &cursors, view, origin, query).await
+}
+
+#[get("/history.json")]
+async fn history_json(
+    #[filter = "json_feed_query"] query: JsonFeedQuery,
+    #[filter = "origin"] origin: String,
+    #[data] store: db::Storage,
+    #[data] cursors: cursor::Signer,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let view = JsonFeedView {
+        filter: db::Filter::Read,
+        title: tr("page-history"),
+        page: "/history.html".to_string(),
+        feed: "/history.json".to_string(),
+    };
+    json_feed(&store, &cursors, view, origin, query).await
+}
+
+#[get("/folders/{id}/articles.json")]
+async fn folder_json(
+    id: String,
+    #[filter = "json_feed_query"] query: JsonFeedQuery,
+    #[filter = "origin"] origin: String,
+    #[data] store: db::Storage,
+    #[data] cursors: cursor::Signer,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let folder = store
+        .get_smart_folder(id.clone())
+        .await
+        .map_err(reject_anyhow)?;
+    let view = JsonFeedView {
+        title: folder.name,
+        page: format!("/folders/{}", id),
+        feed: format!("/folders/{}/articles.json", id),
+        filter: db::Filter::Folder(id),
+    };
+    json_feed(&store, &cursors, view, origin, query).await
+}
+
+#[get("/feeds/{id}/articles.json")]
+async fn feed_json(
+    id: String,
+    #[filter = "json_feed_query"] query: JsonFeedQuery,
+    #[filter = "origin"] origin: String,
+    #[data] store: db::Storage,
+    #[data] cursors: cursor::Signer,
+) -> Result<http::Response<Vec<u8>>, Rejection> {
+    let feed = store
+        .get_feed_by_id(id.clone())
+        .await
+        .map_err(reject_anyhow)?;
+    let view = JsonFeedView {
+        title: feed.name,
+        page: format!("/feeds/{}", id),
+        feed: format!("/feeds/{}/articles.json", id),
+        filter: db::Filter::Feed(id),
+    };
+    json_feed(&store, &cursors, view, origin, query).await
+}
+
+#[get("/folders/{id}")]
+async fn folder(
+    id: String,
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+) -> Result<ArticleBaseTemplate, Rejection> {
+    let folder = store
+        .get_smart_folder(id.clone())
+        .await
+        .map_err(reject_anyhow)?;
+    let filter = db::Filter::Folder(id);
+    let (counts, page) = with_counts(
+        &store,
+        store
+            .clone()
+            .filter(filter.clone(), db::MAX_DATE.to_string()),
+    )
+    .await
+    .map_err(reject_anyhow)?;
+    Ok(ArticleBaseTemplate {
+        empty: Empty::of(&counts, page.items.len(), filter.clone()),
+        counts,
+        cursor: page.cursor,
+        title: folder.name,
+        article_filter: filter.to_string(),
+        days: days(page.items.iter().map(|r| r.into()).collect()),
+        density: density(),
+        integrations: integrations.enabled(),
+    })
+}
+
+#[get("/feeds.html")]
+async fn feeds(#[data] db: db::Storage) -> Result<FeedsTemplate, Rejection> {
+    feeds_template(&db).await.map_err(reject_anyhow)
+}
+
+async fn feeds_template(store: &db::Storage) -> anyhow::Result<FeedsTemplate> {
+    let (counts, page) = with_counts(store, store.get_feeds(db::MAX_DATE.to_string())).await?;
+    let feeds = feed_rows(&page);
+    Ok(FeedsTemplate {
+        counts,
+        cursor: page.cursor,
+        empty: Empty::feeds(&feeds),
+        feeds,
+    })
+}
+
+// feed_rows pairs each feed of a page with its stats
+fn feed_rows(page: &db::Page) -> Vec<(Feed, db::FeedStats)> {
+    page.items.iter().map(|r| (r.into(), r.into())).collect()
+}
+
+async fn feed_template(
+    store: &db::Storage,
+    pipeline: &pipeline::Pipeline,
+    id: String,
+    saved: bool,
+) -> anyhow::Result<FeedTemplate> {
+    let (counts, feed, latency, fetch_log, rules, articles) = futures::try_join!(
+        store.get_counts(),
+        store.get_feed_by_id(id.clone()),
+        store.get_fetch_latency(id.clone()),
+        store.get_fetch_log(id.clone(), 10),
+        store.get_feed_notification_rules(id.clone()),
+        store.count_feed_articles(id),
+    )?;
+
+    Ok(FeedTemplate {
+        stages: pipeline.settings(&feed),
+        counts,
+        feed,
+        latency,
+        fetch_log,
+        rules,
+        saved,
+        empty: match articles {
+            0 => Empty::NoArticles,
+            _ => Empty::No,
+        },
+        errors: vec![],
+    })
+}
+
+#[get("/feeds/{id}")]
+async fn feed(
+    id: String,
+    #[data] store: db::Storage,
+    #[data] pipeline: pipeline::Pipeline,
+) -> Result<FeedTemplate, Rejection> {
+    feed_template(&store, &pipeline, id, false)
+        .await
+        .map_err(reject_anyhow)
+}
+
+#[post("/feeds/{id}/settings")]
+async fn save_feed_settings(
+    id: String,
+    #[form] mut form: FeedSettings,
+    #[data] store: db::Storage,
+    #[data] pipeline: pipeline::Pipeline,
+) -> Result<warp::reply::Response, Rejection> {
+    let errors = form.validate();
+    if !errors.is_empty() {
+        let mut page = feed_template(&store, &pipeline, id, false)
+            .await
+            .map_err(reject_anyhow)?;
+        page.feed.name = form.feed_name;
+        page.feed.site_url = form.site_url;
+        page.feed.category = form.category;
+        page.feed.use_socks = form.use_socks.is_some();
+        page.errors = errors;
+        return Ok(warp::reply::with_status(page, http::StatusCode::BAD_REQUEST).into_response());
+    }
+
+    let auto_read_days = form.auto_read_days().unwrap_or_default();
+    store
+        .update_feed_settings(id.clone(), form, auto_read_days)
+        .await
+        .map_err(reject_anyhow)?;
+    let page = feed_template(&store, &pipeline, id, true)
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(page.into_response())
+}
+
+// toggle_feed_stage turns a default stage off for the feed or an optional one on, and back
+#[post("/feeds/{id}/stages")]
+async fn toggle_feed_stage(
+    id: String,
+    #[form] form: FeedStage,
+    #[data] store: db::Storage,
+    #[data] pipeline: pipeline::Pipeline,
+) -> Result<FeedTemplate, Rejection> {
+    let off = match pipeline.mode(form.stage.as_str()) {
+        Some(pipeline::Mode::Default) => true,
+        Some(pipeline::Mode::Optional) => false,
+        Some(pipeline::Mode::Always) | None => {
+            return Err(reject_anyhow(db::Error::NotFound("stage").into()))
+        }
+    };
+    store
+        .toggle_feed_stage(id.clone(), form.stage, off)
+        .await
+        .map_err(reject_anyhow)?;
+    feed_template(&store, &pipeline, id, true)
+        .await
+        .map_err(reject_anyhow)
+}
+
+#[get("/add_feed.html")]
+async fn add_feed(#[data] store: db::Storage) -> Result<AddFeedTemplate, Rejection> {
+    let counts = store.get_counts().await.map_err(reject_anyhow)?;
+    Ok(AddFeedTemplate {
+        counts,
+        form: AddFeed::default(),
+        page: SavePage::default(),
+        errors: vec![],
+    })
+}
+
+async fn refresh_seconds(state: &scheduler::State) -> String {
+    // a cron schedule has no interval to show, the field stays empty until one is saved
+    match state.schedule().await {
+        scheduler::Schedule::Interval(seconds) => seconds.to_string(),
+        scheduler::Schedule::Cron(_) => "".to_string(),
+    }
+}
+
+#[get("/setup.html")]
+async fn setup_page(
+    #[data] store: db::Storage,
+    #[data] state: scheduler::State,
+) -> Result<SetupTemplate, Rejection> {
+    Ok(SetupTemplate {
+        counts: store.get_counts().await.map_err(reject_anyhow)?,
+        refresh_seconds: refresh_seconds(&state).await,
+        imported: None,
+        error: "".to_string(),
+    })
+}
+
+fn setup_form() -> impl Filter<Extract = (warp::multipart::FormData,), Error = Rejection> + Clone {
+    warp::multipart::form().max_length(MAX_SETUP_BYTES)
+}
+
+// fields reads every part of a multipart form as text, an uploaded file is read like any other field
+async fn fields(form: warp::multipart::FormData) -> anyhow::Result<HashMap<String, String>> {
+    let parts: Vec<warp::multipart::Part> = form.try_collect().await?;
+    let mut fields = HashMap::new();
+    for part in parts {
+        let name = part.name().to_string();
+        let value = part
+            .stream()
+            .try_fold(vec![], |mut value, data| async move {
+                value.extend_from_slice(data.chunk());
+                Ok(value)
+            })
+            .await?;
+        fields.insert(name, String::from_utf8_lossy(value.as_slice()).into_owned());
+    }
+    Ok(fields)
+}
+
+// finish_setup imports the uploaded opml and saves the refresh interval, a form with neither skips the setup
+// a document that does not parse is shown on the form again, nothing is saved until it does
+#[post("/setup")]
+async fn finish_setup(
+    #[filter = "setup_form"] form: warp::multipart::FormData,
+    #[data] store: db::Storage,
+    #[data] settings: db::Settings,
+    #[data] state: scheduler::State,
+) -> Result<warp::reply::Response, Rejection> {
+    let fields = fields(form).await.map_err(reject_anyhow)?;
+    let field = |name: &str| fields.get(name).map(|v| v.trim()).unwrap_or_default();
+
+    let refresh = match field(db::REFRESH_SECONDS) {
+        "" => None,
+        value => match value.parse::<u64>() {
+            Ok(seconds) if seconds > 0 => Some(seconds),
+            _ => return Err(warp::reject::custom(BadActionError())),
+        },
+    };
+
+    let imported = match field("opml") {
+        "" => None,
+        document => match import::opml(&store, document).await {
+            Ok(imported) => Some(imported),
+            Err(e) => {
+                let page = SetupTemplate {
+                    counts: store.get_counts().await.map_err(reject_anyhow)?,
+                    refresh_seconds: field(db::REFRESH_SECONDS).to_string(),
+                    imported: None,
+                    error: tr_with("setup-opml-invalid", "error", e.to_string()),
+                };
+                return Ok(
+                    warp::reply::with_status(page, http::StatusCode::BAD_REQUEST).into_response(),
+                );
+            }
+        },
+    };
+
+    if let Some(seconds) = refresh {
+        settings
+            .set(db::REFRESH_SECONDS, seconds.to_string())
+            .await
+            .map_err(reject_anyhow)?;
+        state
+            .set_schedule(scheduler::Schedule::Interval(seconds))
+            .await;
+    }
+    settings
+        .set(db::SETUP_COMPLETE, true.to_string())
+        .await
+        .map_err(reject_anyhow)?;
+
+    Ok(SetupTemplate {
+        counts: store.get_counts().await.map_err(reject_anyhow)?,
+        refresh_seconds: refresh_seconds(&state).await,
+        imported: Some(imported.unwrap_or_default()),
+        error: "".to_string(),
+    }
+    .into_response())
+}
+
+#[post("/feeds")]
+async fn create_feed(
+    #[form] mut feed: AddFeed,
+    #[data] store: db::Storage,
+) -> Result<warp::reply::Response, Rejection> {
+    // an invalid form is shown again with what was entered and a message under each rejected field
+    let errors = feed.validate();
+    if !errors.is_empty() {
+        let counts = store.get_counts().await.map_err(reject_anyhow)?;
+        let page = AddFeedTemplate {
+            counts,
+            form: feed,
+            page: SavePage::default(),
+            errors,
+        };
+        return Ok(warp::reply::with_status(page, http::StatusCode::BAD_REQUEST).into_response());
+    }
+
+    store.add_feed(feed).await.map_err(reject_anyhow)?;
+    let page = feeds_template(&store).await.map_err(reject_anyhow)?;
+    Ok(page.into_response())
+}
+
+#[delete("/feeds/{id}")]
+async fn delete_feed(
+    #[data] store: db::Storage,
+    id: String,
+    #[header = "pagination"] pagination: String,
+) -> Result<FeedListTemplate, Rejection> {
+    store.delete_feed(id).await.map_err(reject_anyhow)?;
+    let page = store.get_feeds(pagination).await.map_err(reject_anyhow)?;
+
+    let feeds = feed_rows(&page);
+    Ok(FeedListTemplate {
+        cursor: page.cursor,
+        empty: Empty::feeds(&feeds),
+        feeds,
+    })
+}
+
+#[post("/feeds/{id}/refresh")]
+async fn refresh_feed(
+    id: String,
+    #[data] app: App,
+    #[header = "pagination"] pagination: String,
+) -> Result<FeedListTemplate, Rejection> {
+    let store = app.store;
+    let f = store
+        .get_feed_by_id(id.clone())
+        .await
+        .map_err(reject_anyhow)?;
+
+    refresh(
+        store.clone(),
+        &app.fetcher,
+        &app.pipeline,
+        app.dispatcher,
+        app.events,
+        app.images,
+        f,
+    )
+    .await
+    .map_err(reject_anyhow)?;
+
+    let page = store.get_feeds(pagination).await.map_err(reject_anyhow)?;
+
+    let feeds = feed_rows(&page);
+    Ok(FeedListTemplate {
+        cursor: page.cursor,
+        empty: Empty::feeds(&feeds),
+        feeds,
+    })
+}
+
+async fn notifications_template(
+    store: &db::Storage,
+    dispatcher: &notify::Dispatcher,
+) -> anyhow::Result<NotificationsTemplate> {
+    let (counts, feeds, rules, schedules) = futures::try_join!(
+        store.get_counts(),
+        store.get_all_feeds(),
+        store.get_notification_rules(),
+        store.get_mute_schedules(),
+    )?;
+    let mut categories: Vec<String> = feeds
+        .iter()
+        .map(|f| f.category.clone())
+        .filter(|c| !c.is_empty())
+        .collect();
+    categories.sort();
+    categories.dedup();
+    Ok(NotificationsTemplate {
+        counts,
+        services: dispatcher.services(),
+        feeds,
+        rules,
+        categories,
+        schedules,
+    })
+}
+
+#[get("/notifications.html")]
+async fn notifications(
+    #[data] store: db::Storage,
+    #[data] dispatcher: notify::Dispatcher,
+) -> Result<NotificationsTemplate, Rejection> {
+    notifications_template(&store, &dispatcher)
+        .await
+        .map_err(reject_anyhow)
+}
+
+#[post("/notifications")]
+async fn create_notification_rule(
+    #[form] rule: AddNotificationRule,
+    #[data] store: db::Storage,
+    #[data] dispatcher: notify::Dispatcher,
+) -> Result<NotificationsTemplate, Rejection> {
+    if !notify::ACTIONS.contains(&rule.action.as_str()) {
+        return Err(warp::reject::custom(BadActionError()));
+    }
+
+    // only notify rules deliver anywhere, the other actions apply to the article itself
+    let rule = match rule.action.as_str() {
+        notify::NOTIFY => {
+            if !dispatcher.supports(rule.service.as_str()) {
+                return Err(warp::reject::custom(BadActionError()));
+            }
+            rule
+        }
+        _ => AddNotificationRule {
+            service: "".to_string(),
+            url: "".to_string(),
+            token: "".to_string(),
+            ..rule
+        },
+    };
+
+    store
+        .add_notification_rule(rule)
+        .await
+        .map_err(reject_anyhow)?;
+
+    notifications_template(&store, &dispatcher)
+        .await
+        .map_err(reject_anyhow)
+}
+
+#[delete("/notifications/{id}")]
+async fn delete_notification_rule(
+    id: String,
+    #[data] store: db::Storage,
+) -> Result<NotificationListTemplate, Rejection> {
+    store
+        .delete_notification_rule(id)
+        .await
+        .map_err(reject_anyhow)?;
+    let rules = store
+        .get_notification_rules()
+        .await
+        .map_err(reject_anyhow)?;
+
+    Ok(NotificationListTemplate { rules })
+}
+
+// create_mute_schedule adds quiet hours for a feed or a category, times are normalized so 7:05 is listed as 07:05
+#[post("/notifications/quiet-hours")]
+async fn create_mute_schedule(
+    #[form] schedule: AddMuteSchedule,
+    #[data] store: db::Storage,
+    #[data] dispatcher: notify::Dispatcher,
+) -> Result<NotificationsTemplate, Rejection> {
+    let bad = || warp::reject::custom(BadActionError());
+    let (feed_id, category) = match schedule.target.split_once(':') {
+        Some(("feed", id)) if !id.is_empty() => {
+            let f = store
+                .get_feed_by_id(id.to_string())
+                .await
+                .map_err(reject_anyhow)?;
+            (f.id, "".to_string())
+        }
+        Some(("category", name)) if !name.trim().is_empty() => {
+            ("".to_string(), name.trim().to_string())
+        }
+        _ => return Err(bad()),
+    };
+    if mute::weekdays(schedule.days.as_str()).is_none() {
+        return Err(bad());
+    }
+    let starts = mute::time(schedule.starts.as_str()).ok_or_else(bad)?;
+    let ends = mute::time(schedule.ends.as_str()).ok_or_else(bad)?;
+
+    store
+        .add_mute_schedule(
+            feed_id,
+            category,
+            schedule.days,
+            starts.format("%H:%M").to_string(),
+            ends.format("%H:%M").to_string(),
+        )
+        .await
+        .map_err(reject_anyhow)?;
+    scheduler::remute(&store).await.map_err(reject_anyhow)?;
+
+    notifications_template(&store, &dispatcher)
+        .await
+        .map_err(reject_anyhow)
+}
+
+#[delete("/notifications/quiet-hours/{id}")]
+async fn delete_mute_schedule(
+    id: String,
+    #[data] store: db::Storage,
+) -> Result<MuteListTemplate, Rejection> {
+    store
+        .delete_mute_schedule(id)
+        .await
+        .map_err(reject_anyhow)?;
+    scheduler::remute(&store).await.map_err(reject_anyhow)?;
+    let (feeds, schedules) = futures::try_join!(store.get_all_feeds(), store.get_mute_schedules())
+        .map_err(reject_anyhow)?;
+
+    Ok(MuteListTemplate { feeds, schedules })
+}
+
+#[post("/feeds/{id}/debug")]
+async fn debug_feed(
+    id: String,
+    #[data] store: db::Storage,
+    #[data] fetcher: fetch::Fetcher,
+    #[data] pipeline: pipeline::Pipeline,
+) -> Result<Json<DebugReport>, Rejection> {
+    let f = store.get_feed_by_id(id).await.map_err(reject_anyhow)?;
+    let mut report = DebugReport {
+        url: f.feed_url.clone(),
+        ..Default::default()
+    };
+
+    // the report carries fetch and parse failures instead of rejecting, that is the point of the endpoint
+    let fetched = match fetcher.send(f.feed_url.as_str(), f.use_socks).await {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return Ok(report.into());
+        }
+    };
+
+    report.url = fetched.url.clone();
+    report.status = Some(fetched.status);
+    report.headers = fetched.headers.clone();
+    if !(200..=299).contains(&fetched.status) {
+        report
+            .warnings
+            .push(format!("HTTP status {}", fetched.status));
+    }
+    if let Some(warning) = fetched.content_warning() {
+        report.warnings.push(warning);
+    }
+
+    let content = match fetched.feed_content() {
+        Ok(content) => content,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return Ok(report.into());
+        }
+    };
+
+    let parsed_feed = match fetch::parse(content, report.url.clone()).await {
+        Ok(parsed_feed) => parsed_feed,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return Ok(report.into());
+        }
+    };
+
+    // rules are left out, the report counts the entries a refresh would see
+    let articles = pipeline.run(&f, &[], &parsed_feed.entries).await;
+    report.entry_count = articles.len();
+
+    for (i, a) in articles.iter().enumerate() {
+        if a.link.is_empty() {
+            report.warnings.push(format!("entry {} has no link", i));
+        }
+        if a.title.is_empty() {
+            report.warnings.push(format!("entry {} has no title", i));
+        }
+        if a.published_estimated {
+            report
+                .warnings
+                .push(format!("entry {} has no published or updated date that could be read, its date was estimated", i));
+        }
+    }
+
+    let existing = store
+        .existing_guids(
+            f.id.clone(),
+            articles.iter().map(|a| a.guid.clone()).collect(),
+            articles.iter().map(|a| a.link.clone()).collect(),
+        )
+        .await
+        .map_err(reject_anyhow)?;
+
+    report.new_entries = articles
+        .into_iter()
+        .filter(|a| !existing.contains(&a.guid))
+        .map(|a| DebugEntry {
+            title: a.title,
+            link: a.link,
+            published: a.published,
+        })
+        .collect();
+
+    Ok(report.into())
+}
+
+fn article_version() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("version")
+}
+
+// marked ignores a stale version, the list rendered afterwards already shows what the other client left
+fn marked(result: anyhow::Result<Article>) -> Result<Option<Article>, Rejection> {
+    match result {
+        Ok(article) => Ok(Some(article)),
+        Err(e) if matches!(e.downcast_ref(), Some(db::Error::Stale(_))) => Ok(None),
+        Err(e) => Err(reject_anyhow(e)),
+    }
+}
+
+#[post("/articles/{article_id}/read")]
+async fn mark_article_read(
+    article_id: String,
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+    #[header = "pagination"] pagination: String,
+    #[header = "article_filter"] article_filter: String,
+    #[filter = "article_version"] version: Option<String>,
+) -> Result<ArticleListTemplate, Rejection> {
+    marked(
+        store
+            .mark_article_read(article_id, version.unwrap_or_default().as_str())
+            .await,
+    )?;
+
+    let filter = db::Filter::from_str(article_filter.as_str()).map_err(reject_anyhow)?;
+    article_list(&store, &integrations, filter, pagination)
+        .await
+        .map_err(reject_anyhow)
+}
+
+#[post("/articles/{article_id}/favorite")]
+async fn mark_article_favorite(
+    article_id: String,
+    #[header = "pagination"] pagination: String,
+    #[header = "article_filter"] article_filter: String,
+    #[filter = "article_version"] version: Option<String>,
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+) -> Result<ArticleListTemplate, Rejection> {
+    let article = marked(
+        store
+            .mark_article_favorite(article_id, version.unwrap_or_default().as_str())
+            .await,
+    )?;
+
+    // a failed bookmark should not undo the favorite
+    if let (Some(bookmarks), Some(article)) = (integrations.bookmarks.clone(), article) {
+        if article.favorited {
+            if let Err(e) = bookmarks.add(&article).await {
+                println!("could not bookmark {}: {}", article.link, e);
+            }
+        }
+    }
+
+    let filter = db::Filter::from_str(article_filter.as_str()).map_err(reject_anyhow)?;
+    article_list(&store, &integrations, filter, pagination)
+        .await
+        .map_err(reject_anyhow)
+}
+
+#[post("/articles/{article_id}/wallabag")]
+async fn save_article_to_wallabag(
+    article_id: String,
+    #[header = "pagination"] pagination: String,
+    #[header = "article_filter"] article_filter: String,
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+) -> Result<ArticleListTemplate, Rejection> {
+    let wallabag = match integrations.wallabag.clone() {
+        Some(wallabag) => wallabag,
+        None => {
+            return Err(reject_anyhow(anyhow::Error::msg(
+                "wallabag is not configured",
+            )))
+        }
+    };
+
+    let article = store
+        .get_article_by_id(article_id)
+        .await
+        .map_err(reject_anyhow)?;
+    wallabag.save(&article).await.map_err(reject_anyhow)?;
+    store
+        .mark_article_wallabag_saved(Article::rfc3339_timestamp(), article.id)
+        .await
+        .map_err(reject_anyhow)?;
+
+    let filter = db::Filter::from_str(article_filter.as_str()).map_err(reject_anyhow)?;
+    article_list(&store, &integrations, filter, pagination)
+        .await
+        .map_err(reject_anyhow)
+}
+
+// save_page fetches a page that did not come from a feed and keeps it as an unread article under saved pages
+#[post("/articles")]
+async fn save_page(
+    #[form] mut page: SavePage,
+    #[data] store: db::Storage,
+    #[data] fetcher: fetch::Fetcher,
+) -> Result<warp::reply::Response, Rejection> {
+    let errors = page.validate();
+    if !errors.is_empty() {
+        let counts = store.get_counts().await.map_err(reject_anyhow)?;
+        let page = AddFeedTemplate {
+            counts,
+            form: AddFeed::default(),
+            page,
+            errors,
+        };
+        return Ok(warp::reply::with_status(page, http::StatusCode::BAD_REQUEST).into_response());
+    }
+
+    let article = saved::save(&store, &fetcher, page.url.as_str(), page.title.as_str())
+        .await
+        .map_err(reject_anyhow)?;
+    http::Response::builder()
+        .status(http::StatusCode::SEE_OTHER)
+        .header(
+            "Location",
+            format!("{}/articles/{}", base_path(), article.id),
+        )
+        .body(vec![])
+        .map(Reply::into_response)
+        .map_err(|e| reject_anyhow(e.into()))
+}
+
+#[get("/articles")]
+async fn get_articles(
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+    #[header = "pagination"] pagination: String,
+    #[header = "article_filter"] article_filter: String,
+) -> Result<ArticleListTemplate, Rejection> {
+    let filter = db::Filter::from_str(article_filter.as_str()).map_err(reject_anyhow)?;
+    article_list(&store, &integrations, filter, pagination)
+        .await
+        .map_err(reject_anyhow)
+}
+
+// article_list is the list htmx swaps in after an action or a page change
+async fn article_list(
+    store: &db::Storage,
+    integrations: &integrations::Integrations,
+    filter: db::Filter,
+    pagination: String,
+) -> anyhow::Result<ArticleListTemplate> {
+    let (counts, page) =
+        with_counts(store, store.clone().filter(filter.clone(), pagination)).await?;
+    Ok(ArticleListTemplate {
+        empty: Empty::of(&counts, page.items.len(), filter),
+        cursor: page.cursor,
+        days: days(page.items.iter().map(|r| r.into()).collect()),
+        density: density(),
+        integrations: integrations.enabled(),
+    })
+}
+
+fn more_query() -> impl Filter<Extract = (MoreQuery,), Error = Rejection> + Clone {
+    warp::query::<MoreQuery>()
+}
+
+// more_articles is for infinite scrolling, the filter and cursor are query parameters so the loader can chain itself
+#[get("/articles/more")]
+async fn more_articles(
+    #[filter = "more_query"] query: MoreQuery,
+    #[data] store: db::Storage,
+    #[data] integrations: integrations::Integrations,
+    #[data] cache: render_cache::Cache,
+) -> Result<warp::reply::Html<Bytes>, Rejection> {
+    let filter = db::Filter::from_str(query.filter.as_str()).map_err(reject_anyhow)?;
+    let pagination = query.pagination.unwrap_or_else(|| db::MAX_DATE.to_string());
+    let key = format!("more {} {}", filter, pagination);
+    cached(&cache, &store, key, async {
+        let page = store.clone().filter(filter, pagination).await?;
+
+        let next = page.cursor.has_next.then(|| {
+            let mut url = reqwest::Url::parse("http://feedreader/articles/more").unwrap();
+            url.query_pairs_mut()
+                .append_pair("filter", query.filter.as_str())
+                .append_pair("pagination", page.cursor.next.as_str());
+            format!(
+                "{}/articles/more?{}",
+                base_path(),
+                url.query().unwrap_or_default()
+            )
+        });
+
+        Ok(ArticleRowsTemplate {
+            cursor: page.cursor,
+            days: days(page.items.iter().map(|r| r.into()).collect()),
+            density: density(),
+            integrations: integrations.enabled(),
+            next,
+        })
+    })
+    .await
+    .map_err(reject_anyhow)
+}
+
+#[derive(Serialize)]
+struct NextUnread {
+    article: Option<Article>,
+    unread: i64,
+}
+
+// next_unread and the toggles answer with json so a j/k style client can drive the reader without rendering pages
+#[post("/articles/next_unread")]
+async fn next_unread(#[data] store: db::Storage) -> Result<Json<NextUnread>, Rejection> {
+    let article = store.next_unread_article().await.map_err(reject_anyhow)?;
+    let unread = store.count_unread_articles().await.map_err(reject_anyhow)?;
+    Ok(NextUnread { article, unread }.into())
+}
+
+// the toggles answer with the row as their update left it, a stale version is a conflict the client resolves
+#[post("/articles/{article_id}/toggle_read")]
+async fn toggle_read(
+    article_id: String,
+    #[data] store: db::Storage,
+    #[filter = "article_version"] version: Option<String>,
+) -> Result<Json<Article>, Rejection> {
+    let article = store
+        .mark_article_read(article_id, version.unwrap_or_default().as_str())
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(article.into())
+}
+
+#[post("/articles/{article_id}/toggle_favorite")]
+async fn toggle_favorite(
+    article_id: String,
+    #[data] store: db::Storage,
+    #[filter = "article_version"] version: Option<String>,
+) -> Result<Json<Article>, Rejection> {
+    let article = store
+        .mark_article_favorite(article_id, version.unwrap_or_default().as_str())
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(article.into())
+}
+
+fn sync_query() -> impl Filter<Extract = (SyncQuery,), Error = Rejection> + Clone {
+    warp::query::<SyncQuery>()
+}
+
+// sync_changes is incremental sync for offline clients, without since everything is returned
+#[get("/api/v1/sync")]
+async fn sync_changes(
+    #[filter = "sync_query"] query: SyncQuery,
+    #[data] store: db::Storage,
+) -> Result<Json<sync::Changes>, Rejection> {
+    let since = match query.since {
+        Some(since) => Some(
+            DateTime::parse_from_rfc3339(since.as_str())
+                .map_err(|_| warp::reject::custom(BadActionError()))?
+                .with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+        ),
+        None => None,
+    };
+    let checkpoint = (Utc::now() - chrono::Duration::seconds(sync::OVERLAP_SECONDS))
+        .to_rfc3339_opts(SecondsFormat::Millis, true);
+
+    let changes = store
+        .get_changes(since, checkpoint)
+        .await
+        .map_err(reject_anyhow)?;
+    Ok(changes.into())
+}
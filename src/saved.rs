@@ -0,0 +1,214 @@
+use crate::db;
+use crate::discover::{self, attribute, tags, unescape};
+use crate::fetch;
+use crate::models::{self, Article, Feed};
+use anyhow::Result;
+use reqwest::Url;
+use std::ops::Range;
+
+// saved pages are stored under a feed that is never saved, like imported items of unsubscribed feeds, so it is never refreshed
+pub const FEED_ID: &str = "saved-pages";
+pub const FEED_NAME: &str = "Saved pages";
+// elements whose text is not part of what the page says
+const HIDDEN_ELEMENTS: [&str; 6] = ["head", "script", "style", "noscript", "template", "svg"];
+
+pub fn feed() -> Feed {
+    Feed {
+        id: FEED_ID.to_string(),
+        ..Feed::new(
+            FEED_NAME.to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            "".to_string(),
+        )
+    }
+}
+
+// Page is what could be read from a saved page, fields that were not found are empty
+#[derive(Default, Debug, PartialEq)]
+pub struct Page {
+    pub title: String,
+    pub summary: String,
+    pub thumbnail: String,
+}
+
+// save stores a link the caller checked as an unread article, the url is its guid so saving it again returns the article saved first
+// the page is fetched for its title, summary and image, one that cannot be fetched is saved with the title given
+pub async fn save(
+    store: &db::Storage,
+    fetcher: &fetch::Fetcher,
+    url: &str,
+    title: &str,
+) -> Result<Article> {
+    let mut article = Article::new(
+        "".to_string(),
+        url.to_string(),
+        "".to_string(),
+        Article::rfc3339_timestamp(),
+        false,
+        false,
+    )
+    .in_feed(&feed());
+
+    match store.get_article_by_id(article.id.clone()).await {
+        Ok(saved) => return Ok(saved),
+        Err(e) if matches!(e.downcast_ref(), Some(db::Error::NotFound(_))) => {}
+        Err(e) => return Err(e),
+    }
+
+    let page = match fetch_page(fetcher, url).await {
+        Ok(page) => page,
+        Err(e) => {
+            println!("could not read saved page {}: {}", url, e);
+            Page::default()
+        }
+    };
+    // a title given when saving wins over the one of the page, the url is the last resort
+    article.title = [title, page.title.as_str(), url]
+        .into_iter()
+        .map(models::plain_text)
+        .find(|title| !title.is_empty())
+        .map(|title| models::truncate(title, models::MAX_TITLE_CHARS))
+        .unwrap_or_default();
+    article.summary = page.summary;
+    article.thumbnail = page.thumbnail;
+
+    let id = article.id.clone();
+    store.add_articles(std::iter::once(article)).await?;
+    store.get_article_by_id(id).await
+}
+
+async fn fetch_page(fetcher: &fetch::Fetcher, url: &str) -> Result<Page> {
+    let fetched = fetcher.get(url, false).await?;
+    let base = Url::parse(fetched.url.as_str())?;
+    if !discover::is_html(fetched.content_type.as_deref().unwrap_or_default()) {
+        return Ok(Page::default());
+    }
+    let html = discover::page(fetched.feed_content()?).await?;
+    Ok(extract(html.as_str(), &base))
+}
+
+// extract reads the title, summary and image of a page, the ones it declares for sharing come first
+pub fn extract(html: &str, base: &Url) -> Page {
+    let metas = tags(html, "meta");
+    let meta = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| {
+                metas
+                    .iter()
+                    .find(|m| {
+                        attribute(m, "property").eq_ignore_ascii_case(name)
+                            || attribute(m, "name").eq_ignore_ascii_case(name)
+                    })
+                    .map(|m| models::plain_text(unescape(attribute(m, "content")).as_str()))
+                    .filter(|content| !content.is_empty())
+            })
+            .unwrap_or_default()
+    };
+
+    let mut title = meta(&["og:title", "twitter:title"]);
+    if title.is_empty() {
+        title = element(html, "title")
+            .map(|(_, inner, _)| models::strip_tags(&html[inner]))
+            .unwrap_or_default();
+    }
+    let mut summary =
+        models::summarize(meta(&["og:description", "description", "twitter:description"]).as_str());
+    if summary.is_empty() {
+        let text = ["article", "main", "body"]
+            .into_iter()
+            .find_map(|name| element(html, name))
+            .map(|(_, inner, _)| visible(&html[inner]))
+            .unwrap_or_default();
+        summary = models::summarize(text.as_str());
+    }
+    let image = meta(&["og:image", "twitter:image"]);
+    let thumbnail = match base.join(image.as_str()) {
+        Ok(image_url) if !image.is_empty() => models::web_link(image_url.as_str()),
+        _ => "".to_string(),
+    };
+
+    Page {
+        title,
+        summary,
+        thumbnail,
+    }
+}
+
+// element finds the first element with the name, it returns where it starts, the range of its inner html and where it ends
+// an element that is never closed runs to the end of the page
+fn element(html: &str, name: &str) -> Option<(usize, Range<usize>, usize)> {
+    let lower = html.to_ascii_lowercase();
+    let open = format!("<{}", name);
+    let close = format!("</{}", name);
+    let mut at = 0;
+    while let Some(found) = lower[at..].find(open.as_str()) {
+        let start = at + found;
+        at = start + open.len();
+        if !lower[at..].starts_with(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/') {
+            continue;
+        }
+        let inner_start = at + lower[at..].find('>')? + 1;
+        let (inner_end, end) = match lower[inner_start..].find(close.as_str()) {
+            Some(found) => {
+                let inner_end = inner_start + found;
+                let end = lower[inner_end..]
+                    .find('>')
+                    .map(|found| inner_end + found + 1)
+                    .unwrap_or(html.len());
+                (inner_end, end)
+            }
+            None => (html.len(), html.len()),
+        };
+        return Some((start, inner_start..inner_end, end));
+    }
+    None
+}
+
+// visible drops the elements a browser would not show, their text would end up in the summary
+pub(crate) fn visible(html: &str) -> String {
+    let mut html = html.to_string();
+    for name in HIDDEN_ELEMENTS {
+        while let Some((start, _, end)) = element(html.as_str(), name) {
+            html.replace_range(start..end, " ");
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_what_a_page_says_about_itself() {
+        let base = Url::parse("https://example.com/posts/one").unwrap();
+        let shared = r#"<html><head><title>Site | One</title>
+            <meta property="og:title" content="One &amp; only">
+            <meta name="description" content="  The first
+                post ">
+            <meta property="og:image" content="/images/one.png">
+            </head><body><p>Body text</p></body></html>"#;
+        assert_eq!(
+            extract(shared, &base),
+            Page {
+                title: "One & only".to_string(),
+                summary: "The first post".to_string(),
+                thumbnail: "https://example.com/images/one.png".to_string(),
+            }
+        );
+
+        let plain = r#"<html><head><TITLE>Plain <b>page</b></TITLE><style>p { color: red }</style></head>
+            <body><nav>Menu</nav><article><script>track()</script><p>What the page says.</p></article></body></html>"#;
+        assert_eq!(
+            extract(plain, &base),
+            Page {
+                title: "Plain page".to_string(),
+                summary: "What the page says.".to_string(),
+                thumbnail: "".to_string(),
+            }
+        );
+    }
+}
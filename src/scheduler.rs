@@ -0,0 +1,438 @@
+use crate::models::{Article, Feed, FetchLog};
+use crate::{
+    dates, db, events, fetch, idempotency, images, mute, notify, pipeline, report, App, DATES,
+};
+use anyhow::Result;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use futures::future::{self, Future};
+use futures::lock::Mutex;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Notify;
+use tokio::time;
+use tokio_stream::wrappers::IntervalStream;
+
+// settings key the paused flag is persisted under
+pub const PAUSED_SETTING: &str = "scheduler_paused";
+// entries converted and inserted per statement while ingesting a feed
+const INGEST_CHUNK_SIZE: usize = 250;
+
+#[derive(Clone)]
+pub enum Schedule {
+    Interval(u64),
+    Cron(Box<cron::Schedule>),
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Schedule> {
+        // the cron crate expects a leading seconds field, accept the usual five field form as well
+        let expression = match s.split_whitespace().count() {
+            5 => format!("0 {}", s),
+            _ => s.to_string(),
+        };
+
+        match cron::Schedule::from_str(expression.as_str()) {
+            Ok(schedule) => Ok(Schedule::Cron(Box::new(schedule))),
+            Err(e) => Err(anyhow::Error::msg(format!(
+                "bad cron expression {}: {}",
+                s, e
+            ))),
+        }
+    }
+}
+
+impl Schedule {
+    // first_run is when the first tick of the stream returned by ticks will fire
+    pub fn first_run(&self, refresh_on_startup: bool) -> Option<DateTime<Utc>> {
+        match refresh_on_startup {
+            true => Some(Utc::now()),
+            false => self.next_run(Utc::now()),
+        }
+    }
+
+    pub fn next_run(&self, last_started: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Interval(seconds) => Some(last_started + Duration::seconds(*seconds as i64)),
+            Schedule::Cron(schedule) => schedule.upcoming(Utc).next(),
+        }
+    }
+
+    // ticks yields once per scheduled run, plus once immediately when refresh_on_startup is set
+    pub fn ticks(&self, refresh_on_startup: bool) -> BoxStream<'static, ()> {
+        let scheduled = match self {
+            Schedule::Interval(seconds) => {
+                let period = time::Duration::from_secs(*seconds);
+                IntervalStream::new(time::interval_at(time::Instant::now() + period, period))
+                    .map(|_| ())
+                    .boxed()
+            }
+            Schedule::Cron(schedule) => stream::unfold(schedule.clone(), |schedule| async move {
+                let next = schedule.upcoming(Utc).next()?;
+                let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                time::sleep(wait).await;
+                Some(((), schedule))
+            })
+            .boxed(),
+        };
+
+        match refresh_on_startup {
+            true => stream::once(future::ready(())).chain(scheduled).boxed(),
+            false => scheduled,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct Status {
+    pub paused: bool,
+    pub last_run: Option<String>,
+    pub last_duration_ms: Option<i64>,
+    pub feeds_refreshed: usize,
+    pub failures: usize,
+    pub next_run: Option<String>,
+}
+
+// Run is the outcome of a single pass of the refresh loop
+pub struct Run {
+    pub started: DateTime<Utc>,
+    pub feeds_refreshed: usize,
+    pub failures: usize,
+}
+
+impl Run {
+    pub fn start() -> Self {
+        Run {
+            started: Utc::now(),
+            feeds_refreshed: 0,
+            failures: 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct State {
+    status: Arc<Mutex<Status>>,
+    schedule: Arc<Mutex<Schedule>>,
+    changed: Arc<Notify>,
+}
+
+impl State {
+    pub fn new(schedule: Schedule) -> Self {
+        State {
+            status: Arc::new(Mutex::new(Status::default())),
+            schedule: Arc::new(Mutex::new(schedule)),
+            changed: Arc::new(Notify::new()),
+        }
+    }
+
+    pub async fn status(&self) -> Status {
+        self.status.lock().await.clone()
+    }
+
+    pub async fn paused(&self) -> bool {
+        self.status.lock().await.paused
+    }
+
+    pub async fn set_paused(&self, paused: bool) {
+        self.status.lock().await.paused = paused;
+    }
+
+    pub async fn set_next_run(&self, next_run: Option<DateTime<Utc>>) {
+        self.status.lock().await.next_run = next_run.map(timestamp);
+    }
+
+    pub async fn schedule(&self) -> Schedule {
+        self.schedule.lock().await.clone()
+    }
+
+    // set_schedule wakes the stream returned by ticks so the next run is computed from the new schedule
+    pub async fn set_schedule(&self, schedule: Schedule) {
+        *self.schedule.lock().await = schedule;
+        self.changed.notify_waiters();
+    }
+
+    // ticks is Schedule::ticks for a schedule that can be replaced while it runs, it keeps next_run in the status current
+    pub fn ticks(&self, refresh_on_startup: bool) -> BoxStream<'static, ()> {
+        let scheduled = stream::unfold((self.clone(), Utc::now()), |(state, last)| async move {
+            loop {
+                let changed = state.changed.clone();
+                let notified = changed.notified();
+                let next = state.schedule().await.next_run(last)?;
+                state.set_next_run(Some(next)).await;
+
+                let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                tokio::select! {
+                    _ = time::sleep(wait) => return Some(((), (state, next))),
+                    _ = notified => continue,
+                }
+            }
+        })
+        .boxed();
+
+        match refresh_on_startup {
+            true => stream::once(future::ready(())).chain(scheduled).boxed(),
+            false => scheduled,
+        }
+    }
+
+    pub async fn record(&self, run: Run) {
+        let finished = Utc::now();
+        let mut status = self.status.lock().await;
+        status.last_run = Some(timestamp(run.started));
+        status.last_duration_ms = Some((finished - run.started).num_milliseconds());
+        status.feeds_refreshed = run.feeds_refreshed;
+        status.failures = run.failures;
+    }
+}
+
+fn timestamp(dt: DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// run_scheduler refreshes every feed on each tick until stop resolves, then sends the notifications held back by mutes that ended,
+// marks articles of feeds with auto read days read, moves read articles past the retention to the trash, empties the trash of
+// articles pruned a week ago and prunes expired idempotency keys
+pub async fn run_scheduler(app: App, refresh_on_startup: bool, stop: impl Future<Output = ()>) {
+    let state = app.scheduler_state.clone();
+    state
+        .ticks(refresh_on_startup)
+        .take_until(stop)
+        .for_each(|_| async {
+            if state.paused().await {
+                return;
+            }
+
+            let mut run = Run::start();
+            let mut has_next = true;
+            let mut pagination = db::MAX_DATE.to_string();
+            while has_next {
+                let page = match app.store.get_feeds(pagination.clone()).await {
+                    Ok(p) => p,
+                    Err(e) => {
+                        println!("could not list feeds: {}", e);
+                        report::error(&e);
+                        has_next = false;
+                        continue;
+                    }
+                };
+
+                has_next = page.cursor.has_next;
+                pagination = page.cursor.next;
+
+                let feeds: Vec<Feed> = page.items.iter().map(|r| r.into()).collect();
+                for f in feeds.iter() {
+                    match refresh(
+                        app.store.clone(),
+                        &app.fetcher,
+                        &app.pipeline,
+                        app.dispatcher.clone(),
+                        app.events.clone(),
+                        app.images.clone(),
+                        f.to_owned(),
+                    )
+                    .await
+                    {
+                        Ok(_) => run.feeds_refreshed += 1,
+                        Err(e) => {
+                            run.failures += 1;
+                            println!("error updating feed {}: {}", f.feed_url, e);
+                            report::feed_error(&e, f);
+                        }
+                    }
+                }
+            }
+
+            if let Err(e) = release_muted(&app.store, &app.dispatcher, &app.events).await {
+                println!("could not release muted articles: {}", e);
+                report::error(&e);
+            }
+
+            if let Err(e) = app
+                .store
+                .auto_read_articles(Article::rfc3339_timestamp())
+                .await
+            {
+                println!("could not mark old articles read: {}", e);
+                report::error(&e);
+            }
+
+            let retention_days = app.db_settings.retention_days();
+            if retention_days > 0 {
+                let before = (Utc::now() - Duration::days(retention_days))
+                    .to_rfc3339_opts(SecondsFormat::Millis, true);
+                if let Err(e) = app.store.prune_articles(before).await {
+                    println!("could not prune read articles: {}", e);
+                    report::error(&e);
+                }
+            }
+
+            let trashed = dates::stored(Utc::now() - Duration::days(db::TRASH_DAYS));
+            if let Err(e) = app.store.empty_trash(trashed).await {
+                println!("could not empty the trash: {}", e);
+                report::error(&e);
+            }
+
+            let expired = dates::stored(Utc::now() - Duration::hours(idempotency::TTL_HOURS));
+            if let Err(e) = app.store.prune_idempotency_keys(expired).await {
+                println!("could not prune idempotency keys: {}", e);
+                report::error(&e);
+            }
+
+            state.record(run).await;
+        })
+        .await
+}
+
+// refresh fetches, parses and stores a feed and logs how the fetch went, the source supplies the first two stages
+pub(crate) async fn refresh(
+    store: db::Storage,
+    source: &impl fetch::Source,
+    pipeline: &pipeline::Pipeline,
+    dispatcher: notify::Dispatcher,
+    events: events::Events,
+    images: images::Proxy,
+    f: Feed,
+) -> Result<()> {
+    let fetched_at = Article::rfc3339_timestamp();
+    let started = Instant::now();
+    let result = ingest(
+        store.clone(),
+        source,
+        pipeline,
+        dispatcher,
+        events,
+        images,
+        f.clone(),
+    )
+    .await;
+
+    let mut log = FetchLog {
+        feed_id: f.id.clone(),
+        fetched_at,
+        duration_ms: started.elapsed().as_millis() as i64,
+        bytes: 0,
+        error: "".to_string(),
+        warning: "".to_string(),
+    };
+
+    match result {
+        Ok((bytes, warning)) => {
+            log.bytes = bytes as i64;
+            log.warning = warning.unwrap_or_default();
+            store.add_fetch_log(log).await?;
+            Ok(())
+        }
+        Err(e) => {
+            log.error = e.to_string();
+            store.add_fetch_log(log).await?;
+            store.update_feed_error(e.to_string(), f.id).await?;
+            Err(e)
+        }
+    }
+}
+
+// ingest returns the size in bytes of the fetched feed and a warning about how it was served
+async fn ingest(
+    store: db::Storage,
+    source: &impl fetch::Source,
+    pipeline: &pipeline::Pipeline,
+    dispatcher: notify::Dispatcher,
+    events: events::Events,
+    images: images::Proxy,
+    f: Feed,
+) -> Result<(usize, Option<String>)> {
+    let fetched = source.fetch(f.feed_url.as_str(), f.use_socks).await?;
+    let bytes = fetched.body.len();
+    let (parsed_feed, warning) = source.parse(fetched).await?;
+    let rules = store.get_notification_rules().await?;
+    let timezone = DATES.get_or_init(dates::Format::default).timezone();
+    let muted_until =
+        mute::muted_until(&store.get_mute_schedules().await?, &f, Utc::now(), timezone)
+            .map(dates::stored);
+
+    // entries go through the pipeline a chunk at a time and are dropped once stored, only the new titles are kept for the event
+    let mut added_titles = vec![];
+    for entries in parsed_feed.entries.chunks(INGEST_CHUNK_SIZE) {
+        let articles = pipeline.run(&f, &rules, entries).await;
+        let added = store
+            .add_muted_articles(
+                articles.into_iter(),
+                muted_until.clone().unwrap_or_default(),
+            )
+            .await?;
+        // articles of a muted feed are notified about by release_muted once the mute ends
+        if muted_until.is_none() {
+            dispatcher.dispatch(&rules, &f, &added);
+        }
+        for a in added.iter().filter(|a| !a.thumbnail.is_empty()) {
+            images.prefetch(a.thumbnail.clone());
+        }
+        added_titles.extend(added.into_iter().map(|a| a.title));
+    }
+
+    store
+        .update_feed_last_updated(Article::rfc3339_timestamp(), f.id.clone())
+        .await?;
+
+    // like notifications the event for articles of a muted feed waits for release_muted
+    if !added_titles.is_empty() && muted_until.is_none() {
+        events.record(&store, &f, added_titles).await;
+    }
+
+    Ok((bytes, warning))
+}
+
+// remute applies changed quiet hours to the articles they already hold back, articles of a feed that is no longer muted
+// are let through at once and release_muted sends their notifications on the next tick
+pub(crate) async fn remute(store: &db::Storage) -> Result<()> {
+    let now = Utc::now();
+    let schedules = store.get_mute_schedules().await?;
+    let timezone = DATES.get_or_init(dates::Format::default).timezone();
+    for f in store.get_muted_feeds(dates::stored(now)).await? {
+        // a second back, so the unread list that compares with a later now lists them right away
+        let until = mute::muted_until(&schedules, &f, now, timezone)
+            .unwrap_or_else(|| now - Duration::seconds(1));
+        store
+            .remute_articles(f.id, dates::stored(until), dates::stored(now))
+            .await?;
+    }
+    Ok(())
+}
+
+// release_muted sends the notifications and the new articles event of articles whose mute ended, articles read in the
+// meantime are left out
+async fn release_muted(
+    store: &db::Storage,
+    dispatcher: &notify::Dispatcher,
+    events: &events::Events,
+) -> Result<()> {
+    let released = store
+        .release_muted_articles(Article::rfc3339_timestamp())
+        .await?;
+    if released.is_empty() {
+        return Ok(());
+    }
+
+    let rules = store.get_notification_rules().await?;
+    let mut by_feed: HashMap<String, Vec<Article>> = HashMap::new();
+    for a in released.into_iter().filter(|a| !a.read) {
+        by_feed.entry(a.feed_id.clone()).or_default().push(a);
+    }
+    for (feed_id, articles) in by_feed {
+        match store.get_feed_by_id(feed_id).await {
+            Ok(f) => {
+                dispatcher.dispatch(&rules, &f, &articles);
+                let titles = articles.into_iter().map(|a| a.title).collect();
+                events.record(store, &f, titles).await;
+            }
+            Err(e) if matches!(e.downcast_ref(), Some(db::Error::NotFound(_))) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use rweb::http::header::{
+    HeaderValue, CONTENT_SECURITY_POLICY, CONTENT_TYPE, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS,
+    X_FRAME_OPTIONS,
+};
+use rweb::reply::{Reply, Response};
+
+// the stylesheet and htmx are loaded from unpkg unless configured otherwise
+pub const DEFAULT_STATIC_SOURCES: &str = "https://unpkg.com";
+
+// Headers are added to every html page, the policy is built once at startup
+#[derive(Clone)]
+pub struct Headers {
+    policy: HeaderValue,
+}
+
+impl Headers {
+    // image_sources and static_sources are space separated csp sources on top of the instance itself
+    pub fn new(image_sources: &str, static_sources: &str) -> anyhow::Result<Headers> {
+        // htmx injects its indicator styles inline
+        let policy = format!(
+            "default-src 'self'; script-src 'self' {static_sources}; style-src 'self' 'unsafe-inline' {static_sources}; img-src 'self' data: {image_sources}; object-src 'none'; base-uri 'self'; form-action 'self'; frame-ancestors 'none'"
+        );
+        Ok(Headers {
+            policy: HeaderValue::from_str(policy.as_str())?,
+        })
+    }
+
+    pub fn apply(&self, reply: impl Reply) -> Response {
+        let mut response = reply.into_response();
+        let headers = response.headers_mut();
+        headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+
+        let is_html = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.starts_with("text/html"))
+            .unwrap_or(false);
+        if is_html {
+            headers.insert(CONTENT_SECURITY_POLICY, self.policy.clone());
+            headers.insert(REFERRER_POLICY, HeaderValue::from_static("same-origin"));
+            headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+        }
+        response
+    }
+}
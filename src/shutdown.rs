@@ -0,0 +1,75 @@
+use futures::future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Draining is set once a shutdown signal arrives so readiness fails while in-flight work finishes
+#[derive(Clone, Default)]
+pub struct Draining(Arc<AtomicBool>);
+
+impl Draining {
+    pub fn start(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// signal resolves on the first shutdown request, ctrl-c everywhere plus the platform specific signals
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            println!("could not listen for ctrl-c: {}", e);
+            future::pending::<()>().await;
+        }
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = platform() => {},
+    }
+}
+
+// docker and kubernetes stop containers with SIGTERM
+#[cfg(unix)]
+async fn platform() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match (signal(SignalKind::terminate()), signal(SignalKind::quit())) {
+        (Ok(mut terminate), Ok(mut quit)) => {
+            tokio::select! {
+                _ = terminate.recv() => {},
+                _ = quit.recv() => {},
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            println!("could not listen for unix signals: {}", e);
+            future::pending::<()>().await;
+        }
+    }
+}
+
+// closing the console window or a service stop sends ctrl-close, ctrl-break is the other console interrupt
+#[cfg(windows)]
+async fn platform() {
+    use tokio::signal::windows::{ctrl_break, ctrl_close};
+
+    match (ctrl_break(), ctrl_close()) {
+        (Ok(mut ctrl_break), Ok(mut ctrl_close)) => {
+            tokio::select! {
+                _ = ctrl_break.recv() => {},
+                _ = ctrl_close.recv() => {},
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            println!("could not listen for console signals: {}", e);
+            future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+async fn platform() {
+    future::pending::<()>().await;
+}
@@ -0,0 +1,98 @@
+use serde::Serialize;
+use tokio_postgres::Row;
+
+// clients send back the checkpoint they were given, it trails the sync by this much so rows
+// written by transactions still open when the changes were read are picked up next time
+pub const OVERLAP_SECONDS: i64 = 5;
+
+// Article is an article as stored, timestamps stay rfc3339 so clients can render them themselves
+#[derive(Serialize)]
+pub struct Article {
+    pub id: String,
+    pub feed: String,
+    pub title: String,
+    pub link: String,
+    pub author: String,
+    pub published: String,
+    pub read: bool,
+    pub favorited: bool,
+    pub read_date: String,
+    pub summary: String,
+    pub thumbnail: String,
+    pub updated_at: String,
+}
+
+impl From<&Row> for Article {
+    fn from(row: &Row) -> Self {
+        Article {
+            id: row.get(0),
+            feed: row.get(1),
+            title: row.get(2),
+            link: row.get(3),
+            author: row.get(4),
+            published: row.get(5),
+            read: row.get(6),
+            favorited: row.get(7),
+            read_date: row.get(8),
+            summary: row.get(11),
+            thumbnail: row.get(12),
+            updated_at: row.get(13),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Feed {
+    pub id: String,
+    pub name: String,
+    pub site_url: String,
+    pub feed_url: String,
+    pub date_added: String,
+    pub last_updated: String,
+    pub last_error: String,
+    pub category: String,
+    pub updated_at: String,
+}
+
+impl From<&Row> for Feed {
+    fn from(row: &Row) -> Self {
+        Feed {
+            id: row.get(0),
+            name: row.get(1),
+            site_url: row.get(2),
+            feed_url: row.get(3),
+            date_added: row.get(4),
+            last_updated: row.get(5),
+            last_error: row.get(7),
+            category: row.get(9),
+            updated_at: row.get(8),
+        }
+    }
+}
+
+// Tombstone is left behind by a delete, kind is article or feed
+#[derive(Serialize)]
+pub struct Tombstone {
+    pub kind: String,
+    pub id: String,
+    pub deleted_at: String,
+}
+
+impl From<&Row> for Tombstone {
+    fn from(row: &Row) -> Self {
+        Tombstone {
+            kind: row.get(0),
+            id: row.get(1),
+            deleted_at: row.get(2),
+        }
+    }
+}
+
+// Changes are everything written since the client's checkpoint, upserting by id is safe since the windows overlap
+#[derive(Serialize)]
+pub struct Changes {
+    pub checkpoint: String,
+    pub articles: Vec<Article>,
+    pub feeds: Vec<Feed>,
+    pub deleted: Vec<Tombstone>,
+}
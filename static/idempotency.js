@@ -0,0 +1,46 @@
+// gives htmx POST and DELETE requests an idempotency key, so a double click or a retried request is only handled once
+// the key belongs to the element that sent the request and to its verb, path and parameters, so two controls posting to
+// the same path never share one. it is dropped a moment after any answer, a request that got none keeps its key so
+// trying again replays or finishes the first one
+(function () {
+    const KEEP_MS = 1500;
+    const keys = new WeakMap();
+    const newKey = () =>
+        window.crypto && crypto.randomUUID
+            ? crypto.randomUUID()
+            : Date.now().toString(36) + Math.random().toString(36).slice(2);
+    const requestOf = (verb, path, parameters) =>
+        verb.toUpperCase() + " " + path + " " + JSON.stringify(parameters || {});
+
+    document.addEventListener("htmx:configRequest", (event) => {
+        const verb = event.detail.verb.toUpperCase();
+        if (verb !== "POST" && verb !== "DELETE") {
+            return;
+        }
+        if (!keys.has(event.detail.elt)) {
+            keys.set(event.detail.elt, new Map());
+        }
+        const sent = keys.get(event.detail.elt);
+        const request = requestOf(verb, event.detail.path, event.detail.parameters);
+        if (!sent.has(request)) {
+            sent.set(request, newKey());
+        }
+        event.detail.headers["Idempotency-Key"] = sent.get(request);
+    });
+
+    document.addEventListener("htmx:afterRequest", (event) => {
+        const config = event.detail.requestConfig;
+        const sent = keys.get(event.detail.elt);
+        const status = event.detail.xhr ? event.detail.xhr.status : 0;
+        if (!config || !sent || status === 0) {
+            return;
+        }
+        const request = requestOf(config.verb, config.path, config.parameters);
+        const key = sent.get(request);
+        setTimeout(() => {
+            if (sent.get(request) === key) {
+                sent.delete(request);
+            }
+        }, KEEP_MS);
+    });
+})();
@@ -0,0 +1,10 @@
+// registers the service worker, the script tag carries its url and scope since the base path is configurable
+(function () {
+    const script = document.currentScript;
+    if (!("serviceWorker" in navigator) || !script) {
+        return;
+    }
+    navigator.serviceWorker
+        .register(script.dataset.serviceWorker, { scope: script.dataset.scope })
+        .catch((err) => console.warn("service worker registration failed", err));
+})();
@@ -0,0 +1,33 @@
+// generated by feedreader, the cache name changes with every build so old assets are dropped
+const CACHE = "feedreader-{{ version }}";
+const OFFLINE = "{{ crate::base_path() }}/offline.html";
+const PRECACHE = {{ precache }};
+
+self.addEventListener("install", (event) => {
+    event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(PRECACHE)).then(() => self.skipWaiting()));
+});
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(
+        caches.keys()
+            .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+            .then(() => self.clients.claim())
+    );
+});
+
+self.addEventListener("fetch", (event) => {
+    const request = event.request;
+    if (request.method !== "GET") {
+        return;
+    }
+
+    // pages always come from the network so unread counts stay current, the shell is shown when it is unreachable
+    if (request.mode === "navigate") {
+        event.respondWith(fetch(request).catch(() => caches.match(OFFLINE)));
+        return;
+    }
+
+    if (PRECACHE.includes(new URL(request.url).pathname)) {
+        event.respondWith(caches.match(request).then((cached) => cached || fetch(request)));
+    }
+});